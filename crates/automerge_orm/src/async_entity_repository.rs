@@ -0,0 +1,128 @@
+use std::{borrow::Borrow, collections::BTreeMap, marker::PhantomData, sync::Arc};
+
+use autosurgeon::Hydrate;
+use futures::future::{BoxFuture, FutureExt};
+use tokio::task;
+
+use crate::{
+    document::Document,
+    impls::{find_all_keys_under, find_all_under, find_under, list_all_under},
+    EntityManager, Error, Key, Mapped, Result,
+};
+
+/// A repository where instances of an entity can be retrieved without blocking
+/// the async runtime.
+///
+/// This is the asynchronous counterpart of [`EntityRepository`]. See
+/// [`DefaultAsyncEntityRepository`] for the trade-offs between the two.
+///
+/// [`EntityRepository`]: crate::EntityRepository
+pub trait AsyncEntityRepository<T> {
+    /// Finds an object by its key / identifier.
+    fn find(&self, id: Key<T>) -> BoxFuture<'static, Result<Option<T>>>;
+
+    /// Finds all objects in the repository.
+    fn find_all(&self) -> BoxFuture<'static, Result<BTreeMap<String, T>>>;
+
+    /// Lists all objects in the repository, ordered by key.
+    fn list(&self) -> BoxFuture<'static, Result<Vec<T>>>;
+
+    /// Finds the keys of all objects in the repository, ordered by key.
+    fn keys(&self) -> BoxFuture<'static, Result<Vec<Key<T>>>>;
+}
+
+/// A default implementation for [`AsyncEntityRepository`].
+///
+/// Each operation reads the Automerge document on the blocking thread pool of
+/// the Tokio runtime, using the same functions as
+/// [`DefaultEntityRepository`]. This keeps the async runtime responsive while
+/// a read lock is held on the document and large objects are hydrated, at the
+/// cost of a thread handoff per operation. Prefer [`DefaultEntityRepository`]
+/// for cheap reads, or outside of an async context.
+///
+/// The operations must be awaited within the context of a Tokio runtime.
+///
+/// Like [`DefaultEntityRepository`], the repository holds on to the
+/// [`EntityManager`] through `M`, which is an `Arc<EntityManager>` by default,
+/// but can be any type which borrows an `EntityManager`. Only the document is
+/// handed to the blocking thread pool, so `M` need not be [`Send`].
+///
+/// [`DefaultEntityRepository`]: crate::DefaultEntityRepository
+#[derive(Clone, Debug)]
+pub struct DefaultAsyncEntityRepository<T, M = Arc<EntityManager>> {
+    entity_manager: M,
+    phantom: PhantomData<fn(T) -> T>,
+}
+
+impl<T, M> AsyncEntityRepository<T> for DefaultAsyncEntityRepository<T, M>
+where
+    T: Mapped + Hydrate + Send + 'static,
+    M: Borrow<EntityManager>,
+{
+    fn find(&self, id: Key<T>) -> BoxFuture<'static, Result<Option<T>>> {
+        self.spawn_blocking(move |doc, root| doc.with_doc(|doc| find_under(doc, root, id)))
+    }
+
+    fn find_all(&self) -> BoxFuture<'static, Result<BTreeMap<String, T>>> {
+        self.spawn_blocking(|doc, root| doc.with_doc(|doc| find_all_under(doc, root)))
+    }
+
+    fn list(&self) -> BoxFuture<'static, Result<Vec<T>>> {
+        self.spawn_blocking(|doc, root| doc.with_doc(|doc| list_all_under(doc, root)))
+    }
+
+    fn keys(&self) -> BoxFuture<'static, Result<Vec<Key<T>>>> {
+        self.spawn_blocking(|doc, root| doc.with_doc(|doc| find_all_keys_under(doc, root)))
+    }
+}
+
+impl<T> DefaultAsyncEntityRepository<T> {
+    /// Creates a new `DefaultAsyncEntityRepository` which uses the
+    /// [`EntityManager`].
+    pub fn new(entity_manager: Arc<EntityManager>) -> Self {
+        Self::new_with(entity_manager)
+    }
+}
+
+impl<T, M> DefaultAsyncEntityRepository<T, M>
+where
+    M: Borrow<EntityManager>,
+{
+    /// Creates a new `DefaultAsyncEntityRepository` which uses the
+    /// [`EntityManager`] borrowed from `entity_manager`.
+    ///
+    /// Unlike [`new`], this does not require the `EntityManager` to be wrapped
+    /// in an [`Arc`].
+    ///
+    /// [`new`]: DefaultAsyncEntityRepository::new
+    pub fn new_with(entity_manager: M) -> Self {
+        Self {
+            entity_manager,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Runs the provided function `f` with the Automerge document and the key
+    /// of the map under which its tables are stored on the blocking thread
+    /// pool.
+    ///
+    /// Panics in `f` are propagated to the caller.
+    fn spawn_blocking<F, O>(&self, f: F) -> BoxFuture<'static, Result<O>>
+    where
+        F: FnOnce(&Document, Option<&str>) -> Result<O> + Send + 'static,
+        O: Send + 'static,
+    {
+        let entity_manager: &EntityManager = self.entity_manager.borrow();
+        let doc = entity_manager.document().clone();
+        let root = entity_manager.root().map(str::to_owned);
+        task::spawn_blocking(move || f(&doc, root.as_deref()))
+            .map(|result| match result {
+                Ok(result) => result,
+                Err(e) => match e.try_into_panic() {
+                    Ok(panic) => std::panic::resume_unwind(panic),
+                    Err(e) => Err(Error::custom(e)),
+                },
+            })
+            .boxed()
+    }
+}
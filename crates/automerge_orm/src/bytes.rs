@@ -0,0 +1,44 @@
+//! Stores a `Vec<u8>` field as an Automerge bytes scalar, rather than as a
+//! list.
+//!
+//! By default, `autosurgeon` reconciles a [`Vec<u8>`] field as a list of
+//! integers, which takes an operation per byte and merges concurrent changes
+//! byte by byte. Annotating the field with
+//! `#[autosurgeon(with = "automerge_orm::bytes")]` stores it as a single bytes
+//! scalar instead, which is replaced as a whole:
+//!
+//! ```
+//! use automerge_orm::Entity;
+//! use autosurgeon::{Hydrate, Reconcile};
+//! use uuid::Uuid;
+//!
+//! #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+//! struct Book {
+//!     #[key]
+//!     id: Uuid,
+//!     #[autosurgeon(with = "automerge_orm::bytes")]
+//!     thumbnail: Vec<u8>,
+//! }
+//! ```
+
+use automerge::ObjId;
+use autosurgeon::{Hydrate, HydrateError, Prop, ReadDoc, Reconciler};
+
+/// The contents of a bytes scalar.
+struct Bytes(Vec<u8>);
+
+impl Hydrate for Bytes {
+    fn hydrate_bytes(bytes: &[u8]) -> Result<Self, HydrateError> {
+        Ok(Self(bytes.to_vec()))
+    }
+}
+
+/// Hydrates a [`Vec<u8>`] from a bytes scalar.
+pub fn hydrate<D: ReadDoc>(doc: &D, obj: &ObjId, prop: Prop<'_>) -> Result<Vec<u8>, HydrateError> {
+    Bytes::hydrate(doc, obj, prop).map(|Bytes(bytes)| bytes)
+}
+
+/// Reconciles the contents of a [`Vec<u8>`] as a bytes scalar.
+pub fn reconcile<R: Reconciler>(bytes: &[u8], mut reconciler: R) -> Result<(), R::Error> {
+    reconciler.bytes(bytes)
+}
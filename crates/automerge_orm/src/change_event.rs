@@ -0,0 +1,78 @@
+use std::collections::BTreeSet;
+
+use automerge::{Automerge, ChangeHash, Patch, PatchAction, Prop};
+
+use crate::Mapped;
+
+/// An event which is emitted when the Automerge document changes.
+///
+/// This `struct` is yielded by the stream returned from the [`changes`] method
+/// on [`EntityManager`]. See its documentation for more.
+///
+/// [`changes`]: crate::EntityManager::changes
+/// [`EntityManager`]: crate::EntityManager
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ChangeEvent {
+    heads: Vec<ChangeHash>,
+    tables: BTreeSet<String>,
+}
+
+impl ChangeEvent {
+    /// Returns the heads of the document after the change.
+    pub fn heads(&self) -> &[ChangeHash] {
+        &self.heads
+    }
+
+    /// Returns the names of the tables which were changed.
+    pub fn tables(&self) -> &BTreeSet<String> {
+        &self.tables
+    }
+
+    /// Returns whether the table of the entity type `T` was changed.
+    pub fn contains_table<T>(&self) -> bool
+    where
+        T: Mapped,
+    {
+        self.tables.contains(<T as Mapped>::table_name())
+    }
+
+    /// Computes the event for the changes made to the document since `before`,
+    /// or returns `None` if the document has not changed.
+    ///
+    /// Tables are looked up under `root`, as configured for the entity
+    /// manager.
+    pub(crate) fn since(
+        doc: &Automerge,
+        root: Option<&str>,
+        before: &[ChangeHash],
+    ) -> Option<Self> {
+        let heads = doc.get_heads();
+        if heads == before {
+            return None;
+        }
+        let tables = doc
+            .diff(before, &heads)
+            .into_iter()
+            .filter_map(|patch| changed_table(patch, root))
+            .collect();
+
+        Some(Self { heads, tables })
+    }
+}
+
+/// Returns the name of the table under `root` which was changed by the
+/// patch, if any.
+fn changed_table(patch: Patch, root: Option<&str>) -> Option<String> {
+    let mut path = patch.path.into_iter().map(|(_, prop)| prop);
+    if let Some(root) = root {
+        match path.next() {
+            Some(Prop::Map(key)) if key == root => {},
+            _ => return None,
+        }
+    }
+    match (path.next(), patch.action) {
+        (Some(Prop::Map(table_name)), _) => Some(table_name),
+        (None, PatchAction::PutMap { key, .. } | PatchAction::DeleteMap { key }) => Some(key),
+        _ => None,
+    }
+}
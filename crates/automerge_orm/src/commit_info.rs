@@ -0,0 +1,60 @@
+use automerge::{ActorId, Change, ChangeHash};
+
+/// Information about a change which was committed to the Automerge document.
+///
+/// This `struct` is created by the [`history`] method on [`EntityManager`].
+/// See its documentation for more.
+///
+/// [`history`]: crate::EntityManager::history
+/// [`EntityManager`]: crate::EntityManager
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CommitInfo {
+    hash: ChangeHash,
+    message: Option<String>,
+    time: i64,
+    actor: ActorId,
+}
+
+impl CommitInfo {
+    /// Returns the hash of the change.
+    pub fn hash(&self) -> ChangeHash {
+        self.hash
+    }
+
+    /// Returns the commit message of the change, if any.
+    ///
+    /// Changes committed by a [`Transaction`] have the message
+    /// `"automerge_orm::Transaction::commit"`.
+    ///
+    /// [`Transaction`]: crate::Transaction
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+
+    /// Returns the time of the change.
+    ///
+    /// Changes committed by a [`Transaction`] record the number of seconds
+    /// since the Unix epoch. Changes committed by other means may use a
+    /// different unit, or `0` if no time was recorded.
+    ///
+    /// [`Transaction`]: crate::Transaction
+    pub fn time(&self) -> i64 {
+        self.time
+    }
+
+    /// Returns the id of the actor which made the change.
+    pub fn actor(&self) -> &ActorId {
+        &self.actor
+    }
+}
+
+impl From<&Change> for CommitInfo {
+    fn from(change: &Change) -> Self {
+        Self {
+            hash: change.hash(),
+            message: change.message().cloned(),
+            time: change.timestamp(),
+            actor: change.actor_id().clone(),
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use automerge::{
+    transaction::{Transactable, Transaction as AutomergeTransaction},
+    Automerge, ObjId, ObjType, Prop, ReadDoc, Value,
+};
+
+use crate::{impls::get_table_under, Key, Mapped, Result};
+
+/// The policy which decides the value of a property which has been set
+/// concurrently to conflicting values, e.g. on different peers.
+///
+/// This `enum` is used by the [`with_conflict_policy`] method on
+/// [`DefaultEntityRepository`]. See its documentation for more.
+///
+/// [`with_conflict_policy`]: crate::DefaultEntityRepository::with_conflict_policy
+/// [`DefaultEntityRepository`]: crate::DefaultEntityRepository
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ConflictPolicy {
+    /// Uses the value which Automerge picks as the winner of the conflict.
+    ///
+    /// The winner is the same on all peers which have received the same
+    /// changes, but it depends on the actor ids of the conflicting changes,
+    /// which are usually random.
+    #[default]
+    Automerge,
+    /// Uses the greatest of the conflicting values, compared by their textual
+    /// representation, e.g. `"b"` over `"a"`.
+    ///
+    /// Numbers are compared as text as well, rather than numerically, e.g.
+    /// `9` over `10`.
+    ///
+    /// The winner depends only on the conflicting values, so entities are
+    /// hydrated identically regardless of the actor ids of the changes, e.g.
+    /// in snapshot tests. Resolving conflicts requires copying the entities
+    /// which are read, i.e. the whole table for reads of all entities, which
+    /// makes reads considerably slower.
+    Greatest,
+}
+
+/// Copies the table of the entity type `T` stored under `root` into the root
+/// of a new document, resolving conflicting values according to `Greatest`.
+pub(crate) fn resolve_table<T>(doc: &Automerge, root: Option<&str>) -> Result<Automerge>
+where
+    T: Mapped,
+{
+    let mut resolved = Automerge::new();
+    if let Some(table_id) = get_table_under::<_, T>(doc, root)? {
+        let mut tx = resolved.transaction();
+        let target = tx.put_object(automerge::ROOT, <T as Mapped>::table_name(), ObjType::Map)?;
+        copy_object(doc, &table_id, &mut tx, &target)?;
+        tx.commit();
+    }
+
+    Ok(resolved)
+}
+
+/// Copies the entity with the key `id` from the table of the entity type `T`
+/// stored under `root` into the table in the root of a new document, resolving
+/// conflicting values according to `Greatest`.
+pub(crate) fn resolve_entity<T>(
+    doc: &Automerge,
+    root: Option<&str>,
+    id: Key<T>,
+) -> Result<Automerge>
+where
+    T: Mapped,
+{
+    let mut resolved = Automerge::new();
+    let Some(table_id) = get_table_under::<_, T>(doc, root)? else {
+        return Ok(resolved);
+    };
+    let key = id.to_prop();
+    if let Some((value, obj_id)) = greatest(doc, doc.get_all(&table_id, &*key)?)? {
+        let mut tx = resolved.transaction();
+        let target = tx.put_object(automerge::ROOT, <T as Mapped>::table_name(), ObjType::Map)?;
+        copy_value(doc, &obj_id, value, &mut tx, &target, Prop::Map(key))?;
+        tx.commit();
+    }
+
+    Ok(resolved)
+}
+
+/// Copies the properties of the object `source` into the object `target`.
+fn copy_object(
+    doc: &Automerge,
+    source: &ObjId,
+    tx: &mut AutomergeTransaction<'_>,
+    target: &ObjId,
+) -> Result<()> {
+    match doc.object_type(source)? {
+        ObjType::Map | ObjType::Table => {
+            for key in doc.keys(source) {
+                if let Some((value, obj_id)) = greatest(doc, doc.get_all(source, &*key)?)? {
+                    copy_value(doc, &obj_id, value, tx, target, Prop::Map(key))?;
+                }
+            }
+        },
+        ObjType::List => {
+            for index in 0..doc.length(source) {
+                if let Some((value, obj_id)) = greatest(doc, doc.get_all(source, index)?)? {
+                    copy_value(doc, &obj_id, value, tx, target, Prop::Seq(index))?;
+                }
+            }
+        },
+        ObjType::Text => {
+            tx.splice_text(target, 0, 0, &doc.text(source)?)?;
+        },
+    }
+
+    Ok(())
+}
+
+/// Copies a value into the property `prop` of the object `target`.
+fn copy_value(
+    doc: &Automerge,
+    source: &ObjId,
+    value: Value<'_>,
+    tx: &mut AutomergeTransaction<'_>,
+    target: &ObjId,
+    prop: Prop,
+) -> Result<()> {
+    match (value, prop) {
+        (Value::Scalar(value), Prop::Map(key)) => tx.put(target, key, value.into_owned())?,
+        (Value::Scalar(value), Prop::Seq(index)) => tx.insert(target, index, value.into_owned())?,
+        (Value::Object(obj_type), prop) => {
+            let obj_id = match prop {
+                Prop::Map(key) => tx.put_object(target, key, obj_type)?,
+                Prop::Seq(index) => tx.insert_object(target, index, obj_type)?,
+            };
+            copy_object(doc, source, tx, &obj_id)?;
+        },
+    }
+
+    Ok(())
+}
+
+/// Returns the greatest of the conflicting values of a property, by their
+/// textual representation.
+fn greatest<'a>(
+    doc: &Automerge,
+    values: Vec<(Value<'a>, ObjId)>,
+) -> Result<Option<(Value<'a>, ObjId)>> {
+    if values.len() <= 1 {
+        return Ok(values.into_iter().next());
+    }
+    let mut described = Vec::with_capacity(values.len());
+    for (value, obj_id) in values {
+        described.push((describe(doc, &value, &obj_id)?, value, obj_id));
+    }
+
+    Ok(described
+        .into_iter()
+        .max_by(|(a, ..), (b, ..)| a.cmp(b))
+        .map(|(_, value, obj_id)| (value, obj_id)))
+}
+
+/// Returns the textual representation of a value, in which conflicts of nested
+/// objects are resolved as well.
+fn describe(doc: &Automerge, value: &Value<'_>, obj_id: &ObjId) -> Result<String> {
+    let obj_type = match value {
+        Value::Scalar(value) => return Ok(value.to_string()),
+        Value::Object(obj_type) => obj_type,
+    };
+    let repr = match obj_type {
+        ObjType::Map | ObjType::Table => {
+            let mut entries = Vec::new();
+            for key in doc.keys(obj_id) {
+                if let Some((value, nested_id)) = greatest(doc, doc.get_all(obj_id, &*key)?)? {
+                    entries.push(format!("{key:?}: {}", describe(doc, &value, &nested_id)?));
+                }
+            }
+            format!("{{{}}}", entries.join(", "))
+        },
+        ObjType::List => {
+            let mut elements = Vec::new();
+            for index in 0..doc.length(obj_id) {
+                if let Some((value, nested_id)) = greatest(doc, doc.get_all(obj_id, index)?)? {
+                    elements.push(describe(doc, &value, &nested_id)?);
+                }
+            }
+            format!("[{}]", elements.join(", "))
+        },
+        ObjType::Text => format!("{:?}", doc.text(obj_id)?),
+    };
+
+    Ok(repr)
+}
@@ -0,0 +1,117 @@
+//! Stores an integer field as an Automerge counter.
+//!
+//! Annotating a field with `#[automerge_orm(counter)]` and
+//! `#[autosurgeon(with = "automerge_orm::counter")]` stores it as a counter,
+//! which can be incremented with [`Transaction::increment`], while the field
+//! keeps its integer type. Concurrent increments to the counter are summed,
+//! rather than one of the values winning:
+//!
+//! ```
+//! use automerge_orm::{Entity, EntityManager, Keyed};
+//! use autosurgeon::{Hydrate, Reconcile};
+//! use uuid::Uuid;
+//!
+//! #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+//! struct Book {
+//!     #[key]
+//!     id: Uuid,
+//!     #[automerge_orm(counter)]
+//!     #[autosurgeon(with = "automerge_orm::counter")]
+//!     likes: i64,
+//! }
+//!
+//! let entity_manager = EntityManager::ephemeral();
+//! let book = Book {
+//!     id: Uuid::new_v4(),
+//!     likes: 0,
+//! };
+//! entity_manager.transact(|tx| tx.insert(&book))?;
+//! entity_manager.transact(|tx| tx.increment(book.id(), "likes", 2))?;
+//! let found = entity_manager.find_related(book.id())?.unwrap();
+//! assert_eq!(found.likes, 2);
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! Writing the entity through a [`Transaction`], e.g. with
+//! [`Transaction::update`], changes the counter by the difference between the
+//! value of the field and the current value of the counter, rather than
+//! replacing it. Writing an entity whose counter field is unchanged, e.g.
+//! after updating another field, therefore leaves the counter untouched, and
+//! increments made concurrently to the write are kept. An entity which was
+//! read before the counter was incremented, e.g. before merging the changes
+//! of another peer, changes the counter back to the value which was read, so
+//! entities should be read and written in the same transaction.
+//!
+//! Counters are only written by the transaction for fields of an entity which
+//! are annotated with `#[automerge_orm(counter)]`, as returned by
+//! [`Mapped::counter_fields`]. The adapter must not be used on fields of
+//! nested structs, which are not written by the transaction.
+//!
+//! [`Transaction::increment`]: crate::Transaction::increment
+//! [`Transaction`]: crate::Transaction
+//! [`Transaction::update`]: crate::Transaction::update
+//! [`Mapped::counter_fields`]: crate::Mapped::counter_fields
+
+use std::cell::Cell;
+
+use automerge::ObjId;
+use autosurgeon::{
+    reconcile::CounterReconciler, Counter, Hydrate, HydrateError, Prop, ReadDoc, Reconciler,
+};
+
+thread_local! {
+    /// Whether counters are written by the transaction which writes the
+    /// entity on this thread, rather than being reconciled.
+    static DEFERRED: Cell<bool> = Cell::new(false);
+}
+
+/// Hydrates an integer from the current value of a counter.
+pub fn hydrate<D, T>(doc: &D, obj: &ObjId, prop: Prop<'_>) -> Result<T, HydrateError>
+where
+    D: ReadDoc,
+    T: TryFrom<i64>,
+{
+    let value = Counter::hydrate(doc, obj, prop)?.value();
+    T::try_from(value).map_err(|_| {
+        HydrateError::unexpected(
+            "a counter which fits into the type of the field",
+            format!("a counter with the value {value}"),
+        )
+    })
+}
+
+/// Reconciles an integer as a counter holding its value.
+///
+/// Nothing is reconciled while an entity is written by a transaction, which
+/// writes the counter itself.
+pub fn reconcile<R, T>(value: &T, mut reconciler: R) -> Result<(), R::Error>
+where
+    R: Reconciler,
+    T: Copy + Into<i64>,
+{
+    if DEFERRED.with(Cell::get) {
+        return Ok(());
+    }
+    let mut counter = reconciler.counter()?;
+    counter.set((*value).into())
+}
+
+/// Runs the provided function `f` with reconciling counters deferred to the
+/// transaction which writes the entity on the current thread.
+///
+/// The previous state is restored afterwards, even if `f` panics.
+pub(crate) fn deferred<F, O>(f: F) -> O
+where
+    F: FnOnce() -> O,
+{
+    struct Restore(bool);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            DEFERRED.with(|deferred| deferred.set(self.0));
+        }
+    }
+
+    let _restore = Restore(DEFERRED.with(|deferred| deferred.replace(true)));
+    f()
+}
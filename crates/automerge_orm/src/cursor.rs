@@ -0,0 +1,69 @@
+use automerge::{Automerge, AutomergeError, Cursor, ObjId, ObjType, ReadDoc, Value};
+
+use crate::{impls::get_raw_under, Key, Mapped, Result};
+
+/// Returns a cursor which points to the element at `index` in the `field`
+/// list of an entity in the Automerge document.
+///
+/// Returns `None` if the entity or the field does not exist.
+pub(crate) fn get_cursor<T>(
+    doc: &Automerge,
+    root: Option<&str>,
+    id: Key<T>,
+    field: &str,
+    index: usize,
+) -> Result<Option<Cursor>>
+where
+    T: Mapped,
+{
+    let Some(list_id) = get_list(doc, root, id, field)? else {
+        return Ok(None);
+    };
+
+    Ok(Some(doc.get_cursor(&list_id, index, None)?))
+}
+
+/// Resolves a cursor which points to an element in the `field` list of an
+/// entity in the Automerge document to the current index of the element.
+///
+/// Returns `None` if the entity or the field does not exist.
+pub(crate) fn resolve_cursor<T>(
+    doc: &Automerge,
+    root: Option<&str>,
+    id: Key<T>,
+    field: &str,
+    cursor: &Cursor,
+) -> Result<Option<usize>>
+where
+    T: Mapped,
+{
+    let Some(list_id) = get_list(doc, root, id, field)? else {
+        return Ok(None);
+    };
+
+    Ok(Some(doc.get_cursor_position(&list_id, cursor, None)?))
+}
+
+/// Returns the Automerge object id of the `field` list of an entity, or an
+/// error if the field is neither a list nor a text object.
+fn get_list<T>(
+    doc: &Automerge,
+    root: Option<&str>,
+    id: Key<T>,
+    field: &str,
+) -> Result<Option<ObjId>>
+where
+    T: Mapped,
+{
+    let Some((value, list_id)) = get_raw_under(doc, root, id, field)? else {
+        return Ok(None);
+    };
+    let Value::Object(ObjType::List | ObjType::Text) = value else {
+        Err(AutomergeError::InvalidValueType {
+            expected: "a list or a text object".to_owned(),
+            unexpected: format!("{value}"),
+        })?
+    };
+
+    Ok(Some(list_id))
+}
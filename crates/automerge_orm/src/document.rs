@@ -0,0 +1,156 @@
+use std::{
+    sync::{Arc, PoisonError, RwLock, TryLockError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use automerge::Automerge;
+use automerge_repo::DocHandle;
+
+#[cfg(feature = "crypto")]
+use crate::{encrypted, EncryptionKey};
+
+/// The Automerge document which is managed by an [`EntityManager`].
+///
+/// [`EntityManager`]: crate::EntityManager
+#[derive(Clone, Debug)]
+pub(crate) struct Document {
+    storage: Storage,
+    /// The key which fields are encrypted with while the document is accessed.
+    #[cfg(feature = "crypto")]
+    encryption_key: Option<Arc<EncryptionKey>>,
+}
+
+/// Where an Automerge [`Document`] is stored.
+#[derive(Clone, Debug)]
+enum Storage {
+    /// A document which is managed by a repo.
+    Handle(DocHandle),
+    /// A standalone, in-memory document which is not managed by any repo.
+    Standalone(Arc<RwLock<Automerge>>),
+}
+
+impl Document {
+    /// Creates a new `Document` for a document which is managed by a repo.
+    pub(crate) fn from_handle(handle: DocHandle) -> Self {
+        Self::new(Storage::Handle(handle))
+    }
+
+    /// Creates a new `Document` for a standalone, in-memory document.
+    pub(crate) fn standalone(doc: Automerge) -> Self {
+        Self::new(Storage::Standalone(Arc::new(RwLock::new(doc))))
+    }
+
+    fn new(storage: Storage) -> Self {
+        Self {
+            storage,
+            #[cfg(feature = "crypto")]
+            encryption_key: None,
+        }
+    }
+
+    /// Returns the handle of the document, or `None` if the document is a
+    /// standalone document which is not managed by a repo.
+    pub(crate) fn handle(&self) -> Option<&DocHandle> {
+        match &self.storage {
+            Storage::Handle(handle) => Some(handle),
+            Storage::Standalone(_) => None,
+        }
+    }
+
+    /// Forks the document into a new standalone document, which keeps the
+    /// encryption key of this document.
+    pub(crate) fn fork(&self) -> Self {
+        Self {
+            storage: Storage::Standalone(Arc::new(RwLock::new(self.with_doc(|doc| doc.fork())))),
+            #[cfg(feature = "crypto")]
+            encryption_key: self.encryption_key.clone(),
+        }
+    }
+
+    /// Sets the key which fields are encrypted with while the document is
+    /// accessed.
+    #[cfg(feature = "crypto")]
+    pub(crate) fn set_encryption_key(&mut self, key: EncryptionKey) {
+        self.encryption_key = Some(Arc::new(key));
+    }
+
+    /// Runs the provided function `f` with a read lock held on the document.
+    pub(crate) fn with_doc<F, O>(&self, f: F) -> O
+    where
+        F: FnOnce(&Automerge) -> O,
+    {
+        self.with_encryption_key(|| match &self.storage {
+            Storage::Handle(handle) => handle.with_doc(f),
+            Storage::Standalone(doc) => f(&doc.read().unwrap_or_else(PoisonError::into_inner)),
+        })
+    }
+
+    /// Runs the provided function `f` with a write lock held on the document.
+    pub(crate) fn with_doc_mut<F, O>(&self, f: F) -> O
+    where
+        F: FnOnce(&mut Automerge) -> O,
+    {
+        self.with_encryption_key(|| match &self.storage {
+            Storage::Handle(handle) => handle.with_doc_mut(f),
+            Storage::Standalone(doc) => f(&mut doc.write().unwrap_or_else(PoisonError::into_inner)),
+        })
+    }
+
+    /// Runs the provided function `f` with a write lock held on the document,
+    /// unless the write lock cannot be acquired within `timeout`.
+    ///
+    /// Acquiring the lock is retried with exponential backoff. Returns `None`
+    /// if the timeout has elapsed. A document which is managed by a repo only
+    /// offers blocking access, so acquiring its lock never times out.
+    pub(crate) fn with_doc_mut_timeout<F, O>(&self, timeout: Duration, f: F) -> Option<O>
+    where
+        F: FnOnce(&mut Automerge) -> O,
+    {
+        let Storage::Standalone(doc) = &self.storage else {
+            return Some(self.with_doc_mut(f));
+        };
+        let deadline = Instant::now() + timeout;
+        let mut backoff = Duration::from_micros(50);
+        loop {
+            match doc.try_write() {
+                Ok(mut doc) => return Some(self.with_encryption_key(|| f(&mut doc))),
+                Err(TryLockError::Poisoned(e)) => {
+                    return Some(self.with_encryption_key(|| f(&mut e.into_inner())));
+                },
+                Err(TryLockError::WouldBlock) => {},
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return None;
+            }
+            thread::sleep(backoff.min(deadline - now));
+            backoff = (backoff * 2).min(Duration::from_millis(10));
+        }
+    }
+
+    /// Runs the provided function `f` with the encryption key of the document,
+    /// if any, made available to [`encrypted`] fields on the current thread.
+    ///
+    /// [`encrypted`]: crate::encrypted
+    fn with_encryption_key<F, O>(&self, f: F) -> O
+    where
+        F: FnOnce() -> O,
+    {
+        #[cfg(feature = "crypto")]
+        let f = || encrypted::with_key(self.encryption_key.as_ref(), f);
+        f()
+    }
+
+    /// Waits until the document has changed.
+    ///
+    /// Returns `None` if changes to the document can no longer be observed.
+    /// Changes to a standalone document are never observed.
+    #[cfg(feature = "stream")]
+    pub(crate) async fn changed(&self) -> Option<()> {
+        match &self.storage {
+            Storage::Handle(handle) => handle.changed().await.ok(),
+            Storage::Standalone(_) => None,
+        }
+    }
+}
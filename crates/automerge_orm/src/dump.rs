@@ -0,0 +1,104 @@
+//! Rendering of Automerge documents as readable trees, for debugging.
+
+use std::fmt::Write;
+
+use automerge::{AutomergeError, ObjId, ObjType, ScalarValue, Value};
+use autosurgeon::ReadDoc;
+use uuid::Uuid;
+
+use crate::Result;
+
+/// The number of spaces by which each level of the tree is indented.
+const INDENT: usize = 2;
+
+/// Renders the Automerge object as a readable tree, indented by `depth`
+/// levels.
+///
+/// Map entries are rendered as `key: value`, and list items as `- value`.
+/// Nested objects are rendered on the following lines, indented by one more
+/// level.
+pub(crate) fn dump_object<D>(doc: &D, obj: &ObjId, depth: usize) -> Result<String>
+where
+    D: ReadDoc,
+{
+    let mut out = String::new();
+    let indent = depth * INDENT;
+    match doc.object_type(obj) {
+        Some(ObjType::Map | ObjType::Table) => {
+            for (key, value, id) in doc.map_range(obj, ..) {
+                write_entry(&mut out, doc, &format!("{key}:"), value, &id, depth)?;
+            }
+        },
+        Some(ObjType::List) => {
+            for (_, value, id) in doc.list_range(obj, ..) {
+                write_entry(&mut out, doc, "-", value, &id, depth)?;
+            }
+        },
+        Some(ObjType::Text) => {
+            let text = doc.text(obj)?;
+            writeln!(out, "{:indent$}{text:?} (text)", "").unwrap();
+        },
+        None => Err(AutomergeError::NotAnObject)?,
+    }
+
+    Ok(out)
+}
+
+/// Renders a map entry or list item, which is identified by `id` if it is an
+/// object, prefixed by `label`.
+fn write_entry<D>(
+    out: &mut String,
+    doc: &D,
+    label: &str,
+    value: Value<'_>,
+    id: &ObjId,
+    depth: usize,
+) -> Result<()>
+where
+    D: ReadDoc,
+{
+    let indent = depth * INDENT;
+    match value {
+        Value::Scalar(scalar) => {
+            writeln!(out, "{:indent$}{label} {}", "", dump_scalar(&scalar)).unwrap();
+        },
+        Value::Object(ObjType::Text) => {
+            let text = doc.text(id)?;
+            writeln!(out, "{:indent$}{label} {text:?} (text)", "").unwrap();
+        },
+        Value::Object(obj_type) => {
+            if doc.length(id) == 0 {
+                let empty = if obj_type == ObjType::List {
+                    "[]"
+                } else {
+                    "{}"
+                };
+                writeln!(out, "{:indent$}{label} {empty}", "").unwrap();
+            } else {
+                writeln!(out, "{:indent$}{label}", "").unwrap();
+                out.push_str(&dump_object(doc, id, depth + 1)?);
+            }
+        },
+    }
+
+    Ok(())
+}
+
+/// Renders an Automerge scalar value.
+fn dump_scalar(scalar: &ScalarValue) -> String {
+    match scalar {
+        ScalarValue::Bytes(bytes) => match Uuid::from_slice(bytes) {
+            Ok(uuid) => uuid.to_string(),
+            Err(_) => format!("{bytes:?} (bytes)"),
+        },
+        ScalarValue::Str(s) => format!("{:?}", s.as_str()),
+        ScalarValue::Int(n) => n.to_string(),
+        ScalarValue::Uint(n) => n.to_string(),
+        ScalarValue::F64(n) => n.to_string(),
+        ScalarValue::Counter(counter) => format!("{} (counter)", i64::from(counter)),
+        ScalarValue::Timestamp(millis) => format!("{millis} (timestamp)"),
+        ScalarValue::Boolean(b) => b.to_string(),
+        ScalarValue::Unknown { type_code, .. } => format!("<unknown type {type_code}>"),
+        ScalarValue::Null => "null".to_owned(),
+    }
+}
@@ -0,0 +1,40 @@
+use autosurgeon::Reconcile;
+
+use crate::{Entity, Keyed, Mapped, Result, Transaction};
+
+/// An object-safe view of an entity, so that instances of different entity
+/// types can be stored together, e.g. in a `Vec<Box<dyn DynEntity>>`.
+///
+/// [`Mapped`] and [`Keyed`] cannot be used as trait objects, so this trait is
+/// implemented for every entity which can be inserted with
+/// [`Transaction::insert`]. Use [`Transaction::insert_dyn`] to insert a
+/// `dyn DynEntity`.
+pub trait DynEntity {
+    /// Returns the name of the table which the entity is mapped to, as
+    /// returned by [`Mapped::table_name`].
+    fn table_name(&self) -> &'static str;
+
+    /// Returns the property in the table which the entity is stored under, as
+    /// returned by [`Mapped::key_prop`].
+    fn key_string(&self) -> String;
+
+    /// Inserts the entity using [`Transaction::insert`].
+    fn insert_into(&self, tx: &mut Transaction<'_>) -> Result<()>;
+}
+
+impl<T> DynEntity for T
+where
+    T: Entity + Keyed<Entity = T> + Reconcile,
+{
+    fn table_name(&self) -> &'static str {
+        <T as Mapped>::table_name()
+    }
+
+    fn key_string(&self) -> String {
+        <T as Mapped>::key_prop(self.id().into())
+    }
+
+    fn insert_into(&self, tx: &mut Transaction<'_>) -> Result<()> {
+        tx.insert(self)
+    }
+}
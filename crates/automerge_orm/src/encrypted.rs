@@ -0,0 +1,205 @@
+//! Encrypts a `String` field with the encryption key of the
+//! [`EntityManager`], so that it is stored as opaque bytes.
+//!
+//! Annotating a field with `#[automerge_orm(encrypt)]` and
+//! `#[autosurgeon(with = "automerge_orm::encrypted")]` encrypts it with
+//! AES-256-GCM when it is written, and decrypts it when it is read, using the
+//! key configured by [`EntityManager::with_encryption_key`]. The field is
+//! stored as a bytes scalar holding a random nonce followed by the ciphertext,
+//! so other replicas which lack the key, e.g. a sync server, cannot read it:
+//!
+//! ```
+//! use automerge_orm::{Entity, EntityManager, EncryptionKey, Keyed};
+//! use autosurgeon::{Hydrate, Reconcile};
+//! use uuid::Uuid;
+//!
+//! #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+//! struct User {
+//!     #[key]
+//!     id: Uuid,
+//!     #[automerge_orm(encrypt)]
+//!     #[autosurgeon(with = "automerge_orm::encrypted")]
+//!     email: String,
+//! }
+//!
+//! let key = EncryptionKey::new([7; 32]);
+//! let entity_manager = EntityManager::ephemeral().with_encryption_key(key);
+//!
+//! let user = User {
+//!     id: Uuid::new_v4(),
+//!     email: "alice@example.com".to_owned(),
+//! };
+//! entity_manager.transact(|tx| tx.insert(&user))?;
+//! let found = entity_manager.find_related(user.id())?.unwrap();
+//! assert_eq!(found.email, "alice@example.com");
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! Only `String` fields can be encrypted. Other values can be encrypted by
+//! storing them in a `String` field, e.g. serialized as JSON.
+//!
+//! The key is only available while the `EntityManager`, or a repository
+//! using it, accesses the document. Reading or writing an entity with
+//! encrypted fields by other means, e.g. through the functions of the
+//! [`impls`] module, or with an `EntityManager` which has no key, fails with
+//! an [`Error::EncryptionKeyMissing`]. Reading it with a different key fails
+//! as the field cannot be decrypted.
+//!
+//! Each write encrypts the field with a new nonce, so writing an unchanged
+//! entity changes the stored ciphertext, and concurrent writes to the field
+//! are resolved as a whole rather than merged. Fields are encrypted
+//! individually, so the names of the fields and the keys of the entities are
+//! still stored in plain text.
+//!
+//! [`EntityManager`]: crate::EntityManager
+//! [`EntityManager::with_encryption_key`]: crate::EntityManager::with_encryption_key
+//! [`impls`]: crate::impls
+//! [`Error::EncryptionKeyMissing`]: crate::Error::EncryptionKeyMissing
+
+use std::{cell::RefCell, fmt, sync::Arc};
+
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng},
+    Aes256Gcm, Nonce,
+};
+use automerge::{AutomergeError, ObjId};
+use autosurgeon::{Hydrate, HydrateError, Prop, ReadDoc, Reconciler};
+
+use crate::{Error, Mapped};
+
+/// The length of the nonce which precedes the ciphertext, in bytes.
+const NONCE_LEN: usize = 12;
+
+thread_local! {
+    /// The encryption key of the document which is accessed on this thread.
+    static CURRENT_KEY: RefCell<Option<Arc<EncryptionKey>>> = RefCell::new(None);
+}
+
+/// A 256-bit key which fields are encrypted with.
+///
+/// This `struct` is used by the [`with_encryption_key`] method on
+/// [`EntityManager`]. See the [module documentation](self) for more.
+///
+/// [`with_encryption_key`]: crate::EntityManager::with_encryption_key
+/// [`EntityManager`]: crate::EntityManager
+pub struct EncryptionKey(Aes256Gcm);
+
+impl EncryptionKey {
+    /// Creates a new `EncryptionKey` from 32 bytes of key material.
+    pub fn new(key: [u8; 32]) -> Self {
+        Self(Aes256Gcm::new(&key.into()))
+    }
+}
+
+impl fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EncryptionKey").finish_non_exhaustive()
+    }
+}
+
+/// The contents of a bytes scalar.
+struct Ciphertext(Vec<u8>);
+
+impl Hydrate for Ciphertext {
+    fn hydrate_bytes(bytes: &[u8]) -> Result<Self, HydrateError> {
+        Ok(Self(bytes.to_vec()))
+    }
+}
+
+/// Hydrates a [`String`] by decrypting a bytes scalar.
+pub fn hydrate<D: ReadDoc>(doc: &D, obj: &ObjId, prop: Prop<'_>) -> Result<String, HydrateError> {
+    let Ciphertext(bytes) = Ciphertext::hydrate(doc, obj, prop)?;
+    let expected = "bytes encrypted with the encryption key of the entity manager";
+    let undecryptable = || {
+        HydrateError::unexpected(
+            expected,
+            format!("{} bytes which cannot be decrypted", bytes.len()),
+        )
+    };
+    if bytes.len() < NONCE_LEN {
+        return Err(undecryptable());
+    }
+    let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+    let plaintext = current_key(|key| {
+        let Some(EncryptionKey(cipher)) = key else {
+            return Err(HydrateError::unexpected(
+                expected,
+                "encrypted bytes without an encryption key configured".to_owned(),
+            ));
+        };
+        cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| undecryptable())
+    })?;
+
+    String::from_utf8(plaintext).map_err(|_| undecryptable())
+}
+
+/// Reconciles a [`String`] as a bytes scalar holding its encrypted contents.
+pub fn reconcile<R: Reconciler>(value: &str, mut reconciler: R) -> Result<(), R::Error> {
+    let encrypted = current_key(|key| {
+        let EncryptionKey(cipher) = key?;
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = cipher.encrypt(&nonce, value.as_bytes()).ok()?;
+        Some([nonce.as_slice(), ciphertext.as_slice()].concat())
+    });
+    let Some(encrypted) = encrypted else {
+        Err(AutomergeError::InvalidValueType {
+            expected: "a value which can be encrypted with the encryption key of the entity \
+                manager"
+                .to_owned(),
+            unexpected: "a value without an encryption key configured".to_owned(),
+        })?
+    };
+
+    reconciler.bytes(encrypted)
+}
+
+/// Returns an [`Error::EncryptionKeyMissing`] if the entity type `T` has
+/// encrypted fields, but no encryption key is configured for the document
+/// which is accessed on the current thread.
+///
+/// [`Error::EncryptionKeyMissing`]: crate::Error::EncryptionKeyMissing
+pub(crate) fn check_key<T>() -> crate::Result<()>
+where
+    T: Mapped,
+{
+    if <T as Mapped>::encrypted_fields().is_empty() || current_key(|key| key.is_some()) {
+        return Ok(());
+    }
+
+    Err(Error::EncryptionKeyMissing {
+        table_name: <T as Mapped>::table_name().to_owned(),
+    })
+}
+
+/// Runs the provided function `f` with `key` as the encryption key of the
+/// document which is accessed on the current thread.
+///
+/// The previous key is restored afterwards, even if `f` panics.
+pub(crate) fn with_key<F, O>(key: Option<&Arc<EncryptionKey>>, f: F) -> O
+where
+    F: FnOnce() -> O,
+{
+    struct Restore(Option<Arc<EncryptionKey>>);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            let previous = self.0.take();
+            CURRENT_KEY.with(|key| *key.borrow_mut() = previous);
+        }
+    }
+
+    let previous = CURRENT_KEY.with(|current| current.replace(key.cloned()));
+    let _restore = Restore(previous);
+    f()
+}
+
+/// Runs the provided function `f` with the encryption key of the document
+/// which is accessed on the current thread, if any.
+fn current_key<F, O>(f: F) -> O
+where
+    F: FnOnce(Option<&EncryptionKey>) -> O,
+{
+    CURRENT_KEY.with(|key| f(key.borrow().as_deref()))
+}
@@ -1,24 +1,248 @@
-use std::sync::Arc;
+use std::{
+    fmt,
+    ops::ControlFlow,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
+use automerge::{ActorId, Automerge, AutomergeError, ChangeHash, ObjId, ReadDoc};
 use automerge_repo::DocHandle;
+use autosurgeon::Hydrate;
+#[cfg(feature = "stream")]
+use futures::stream::{self, Stream};
 
-use crate::{Error, Result, Transaction};
+#[cfg(feature = "stream")]
+use crate::ChangeEvent;
+#[cfg(feature = "crypto")]
+use crate::EncryptionKey;
+use crate::{
+    document::Document,
+    dump,
+    impls::{find_under, get_table_under},
+    migration, CommitInfo, Error, Key, Mapped, Metrics, MetricsEvent, Migration, Reader, Result,
+    Savepoint, TableStatus, TransactError, Transaction, TransactionOptions,
+};
 
 /// The central access point to ORM functionality.
-#[derive(Debug)]
+///
+/// Clones of an `EntityManager` operate on the same document. For a document
+/// which is managed by a repo, each clone holds its own clone of the
+/// [`DocHandle`], which observes changes to the document independently of the
+/// handles held by other clones.
+#[derive(Clone)]
 pub struct EntityManager {
-    doc: DocHandle,
+    doc: Document,
+    root: Option<String>,
+    metrics: Option<Arc<dyn Metrics>>,
+}
+
+impl fmt::Debug for EntityManager {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EntityManager")
+            .field("doc", &self.doc)
+            .field("root", &self.root)
+            .finish_non_exhaustive()
+    }
 }
 
 impl EntityManager {
     /// Creates a new `EntityManager` for an Automerge document.
     pub fn new(doc: DocHandle) -> Self {
-        Self { doc }
+        Self {
+            doc: Document::from_handle(doc),
+            root: None,
+            metrics: None,
+        }
+    }
+
+    /// Creates a new `EntityManager` for an Automerge document, which stores
+    /// its tables in the map under `root` in the root of the document, rather
+    /// than in the root of the document itself.
+    ///
+    /// This allows the Automerge ORM to share a document with other data,
+    /// e.g. of other subsystems. The map is created along with the first
+    /// table. The schema version stored by [`migrate`] is stored in the map as
+    /// well, so that each `EntityManager` sharing a document migrates its
+    /// tables independently.
+    ///
+    /// Tables under `root` can be accessed without an `EntityManager` through
+    /// the `*_under` functions of the [`impls`] module, e.g.
+    /// [`find_under`].
+    ///
+    /// [`migrate`]: EntityManager::migrate
+    /// [`impls`]: crate::impls
+    /// [`find_under`]: crate::impls::find_under
+    pub fn with_root(doc: DocHandle, root: impl Into<String>) -> Self {
+        Self {
+            doc: Document::from_handle(doc),
+            root: Some(root.into()),
+            metrics: None,
+        }
+    }
+
+    /// Creates a new `EntityManager` for an Automerge document, and sets the
+    /// actor id of the document to `actor`.
+    ///
+    /// Changes made through the `EntityManager` are attributed to `actor`.
+    /// Using a fixed actor id makes change hashes reproducible, e.g. for
+    /// snapshot testing. Two peers must never share the same actor id, so do
+    /// not use a fixed actor id for documents which are edited concurrently.
+    pub fn with_actor(doc: DocHandle, actor: ActorId) -> Self {
+        doc.with_doc_mut(|doc| doc.set_actor(actor));
+
+        Self::new(doc)
+    }
+
+    /// Creates a new `EntityManager` for a standalone Automerge document,
+    /// which is not managed by a repo.
+    ///
+    /// The document is kept in memory, and is neither persisted nor synced
+    /// with remote peers.
+    pub fn from_doc(doc: Automerge) -> Self {
+        Self {
+            doc: Document::standalone(doc),
+            root: None,
+            metrics: None,
+        }
+    }
+
+    /// Creates a new `EntityManager` for a standalone Automerge document,
+    /// which stores its tables in the map under `root`, as with
+    /// [`with_root`].
+    ///
+    /// [`with_root`]: EntityManager::with_root
+    pub fn from_doc_with_root(doc: Automerge, root: impl Into<String>) -> Self {
+        Self {
+            doc: Document::standalone(doc),
+            root: Some(root.into()),
+            metrics: None,
+        }
+    }
+
+    /// Creates a new `EntityManager` for an empty, standalone Automerge
+    /// document.
+    ///
+    /// This is a shorthand for [`from_doc`] with a new document, e.g. for tests
+    /// which do not need a repo. Use [`from_doc_with_root`] with a new
+    /// document to store the tables under a root.
+    ///
+    /// [`from_doc`]: EntityManager::from_doc
+    /// [`from_doc_with_root`]: EntityManager::from_doc_with_root
+    pub fn ephemeral() -> Self {
+        Self::from_doc(Automerge::new())
+    }
+
+    /// Configures the `EntityManager` to encrypt and decrypt fields which are
+    /// annotated with `#[automerge_orm(encrypt)]` and
+    /// `#[autosurgeon(with = "automerge_orm::encrypted")]` with `key`.
+    ///
+    /// Clones of the `EntityManager` share the key. See [`encrypted`] for more.
+    ///
+    /// [`encrypted`]: crate::encrypted
+    #[cfg(feature = "crypto")]
+    pub fn with_encryption_key(mut self, key: EncryptionKey) -> Self {
+        self.doc.set_encryption_key(key);
+        self
+    }
+
+    /// Configures the `EntityManager` to record metrics of its operations,
+    /// e.g. the time spent in [`transact`], with the provided sink.
+    ///
+    /// Without a sink, no metrics are recorded.
+    ///
+    /// [`transact`]: EntityManager::transact
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// use automerge_orm::{Entity, EntityManager, MetricsEvent};
+    /// use autosurgeon::{Hydrate, Reconcile};
+    /// use uuid::Uuid;
+    ///
+    /// #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    /// struct Book {
+    ///     #[key]
+    ///     id: Uuid,
+    /// }
+    ///
+    /// let commits = Arc::new(Mutex::new(Vec::new()));
+    /// let entity_manager = EntityManager::ephemeral().with_metrics({
+    ///     let commits = Arc::clone(&commits);
+    ///     move |event: &MetricsEvent| {
+    ///         if let MetricsEvent::Commit { ops, .. } = event {
+    ///             commits.lock().unwrap().push(*ops);
+    ///         }
+    ///     }
+    /// });
+    ///
+    /// entity_manager.transact(|tx| tx.insert(&Book { id: Uuid::new_v4() }))?;
+    /// assert_eq!(commits.lock().unwrap().len(), 1);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn with_metrics(mut self, metrics: impl Metrics + 'static) -> Self {
+        self.metrics = Some(Arc::new(metrics));
+        self
     }
 
     /// Performs a transaction, running the provided function `f` within the
     /// context of the [`Transaction`], and returns its result.
     ///
+    /// The result of the function `f` must be owned, and cannot borrow from
+    /// the transaction or from entities read within it. The transaction
+    /// borrows the document while the write lock is held, and the lock is
+    /// released when `f` returns, so borrowed data would outlive the lock.
+    /// Entities read within the transaction are hydrated into owned values,
+    /// so they can be returned as is. To return data which borrows from them,
+    /// e.g. a single field, clone it on the way out.
+    ///
+    /// If the function `f` panics, the panic is propagated to the caller. The
+    /// changes made within the transaction are rolled back as the panic
+    /// unwinds, in the same way as if `f` had returned an error, and the write
+    /// lock is released. A standalone document, e.g. one created by
+    /// [`from_doc`], ignores the poisoning of its lock by the panic, so
+    /// subsequent transactions succeed as usual.
+    ///
+    /// [`from_doc`]: EntityManager::from_doc
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use automerge_orm::{Entity, EntityManager};
+    /// use autosurgeon::{Hydrate, Reconcile};
+    /// use uuid::Uuid;
+    ///
+    /// #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    /// struct Book {
+    ///     #[key]
+    ///     id: Uuid,
+    ///     title: String,
+    /// }
+    ///
+    /// let entity_manager = EntityManager::ephemeral();
+    ///
+    /// let longest_title = entity_manager.transact(|tx| {
+    ///     for title in ["Spirited Away", "Your Name"] {
+    ///         tx.insert(&Book {
+    ///             id: Uuid::new_v4(),
+    ///             title: title.to_owned(),
+    ///         })?;
+    ///     }
+    ///     let books = tx.find_all::<Book>()?;
+    ///     // `books` is dropped at the end of the transaction, so the title is
+    ///     // cloned rather than borrowed.
+    ///     let longest_title = books
+    ///         .values()
+    ///         .map(|book| &book.title)
+    ///         .max_by_key(|title| title.len())
+    ///         .cloned();
+    ///     automerge_orm::Result::Ok(longest_title)
+    /// })?;
+    /// assert_eq!(longest_title.as_deref(), Some("Spirited Away"));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    ///
     /// # Performance
     ///
     /// Within the scope of the function `f`, a write lock is held on the
@@ -28,24 +252,580 @@ impl EntityManager {
         F: FnOnce(&mut Transaction<'_>) -> std::result::Result<O, E>,
         E: std::error::Error + Send + Sync + 'static,
     {
+        self.transact_with_options(TransactionOptions::default(), f)
+    }
+
+    /// Performs a transaction like [`transact`], but commits its changes using
+    /// the provided [`TransactionOptions`], e.g. with a custom commit message
+    /// or a fixed timestamp.
+    ///
+    /// [`transact`]: EntityManager::transact
+    ///
+    /// # Performance
+    ///
+    /// Within the scope of the function `f`, a write lock is held on the
+    /// document. Do not perform expensive operations within the function `f`.
+    pub fn transact_with_options<F, O, E>(&self, options: TransactionOptions, f: F) -> Result<O>
+    where
+        F: FnOnce(&mut Transaction<'_>) -> std::result::Result<O, E>,
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        self.try_transact_with_options(options, f)
+            .map_err(Error::from)
+    }
+
+    /// Performs a transaction like [`transact`], but fails with
+    /// [`Error::Timeout`] if the write lock on the document cannot be acquired
+    /// within `timeout`.
+    ///
+    /// Acquiring the write lock is retried with exponential backoff until the
+    /// timeout has elapsed. The timeout only bounds the time spent waiting for
+    /// the lock, not the time spent running the function `f`.
+    ///
+    /// A [`DocHandle`] only offers blocking access to its document, so for a
+    /// document which is managed by a repo, this waits for the write lock
+    /// indefinitely like [`transact`]. The timeout only applies to standalone
+    /// documents, e.g. as created by [`from_doc`].
+    ///
+    /// [`transact`]: EntityManager::transact
+    /// [`from_doc`]: EntityManager::from_doc
+    ///
+    /// # Performance
+    ///
+    /// Within the scope of the function `f`, a write lock is held on the
+    /// document. Do not perform expensive operations within the function `f`.
+    pub fn transact_timeout<F, O, E>(&self, timeout: Duration, f: F) -> Result<O>
+    where
+        F: FnOnce(&mut Transaction<'_>) -> std::result::Result<O, E>,
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        let started = Instant::now();
+        let result = self
+            .doc
+            .with_doc_mut_timeout(timeout, |doc| {
+                run_transaction(doc, self, started, TransactionOptions::default(), f)
+            })
+            .ok_or(Error::Timeout(timeout))?;
+
+        result.map_err(Error::from)
+    }
+
+    /// Performs a transaction like [`transact`], but preserves the concrete
+    /// type of the error which aborted the transaction.
+    ///
+    /// If the function `f` returns an error, the transaction is rolled back
+    /// and the error is returned as [`TransactError::Aborted`]. Errors in the
+    /// Automerge ORM, e.g. when committing the transaction, are returned as
+    /// [`TransactError::Orm`].
+    ///
+    /// [`transact`]: EntityManager::transact
+    ///
+    /// # Performance
+    ///
+    /// Within the scope of the function `f`, a write lock is held on the
+    /// document. Do not perform expensive operations within the function `f`.
+    pub fn try_transact<F, O, E>(&self, f: F) -> std::result::Result<O, TransactError<E>>
+    where
+        F: FnOnce(&mut Transaction<'_>) -> std::result::Result<O, E>,
+    {
+        self.try_transact_with_options(TransactionOptions::default(), f)
+    }
+
+    /// Performs a transaction like [`transact`], but allows the function `f`
+    /// to roll back the transaction without failing.
+    ///
+    /// If the function `f` returns [`ControlFlow::Continue`], the changes are
+    /// committed. If it returns [`ControlFlow::Break`], e.g. because there is
+    /// nothing to do, the changes are rolled back. Either way, the value is
+    /// returned as is, so that an intentional rollback is not mistaken for an
+    /// error.
+    ///
+    /// [`transact`]: EntityManager::transact
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::ops::ControlFlow;
+    ///
+    /// use automerge_orm::{Entity, EntityManager};
+    /// use autosurgeon::{Hydrate, Reconcile};
+    /// use uuid::Uuid;
+    ///
+    /// #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    /// struct Book {
+    ///     #[key]
+    ///     id: Uuid,
+    /// }
+    ///
+    /// let entity_manager = EntityManager::ephemeral();
+    ///
+    /// let flow = entity_manager.transact_controlled(|tx| {
+    ///     tx.insert(&Book { id: Uuid::new_v4() })?;
+    ///     if tx.find_all::<Book>()?.len() < 2 {
+    ///         return Ok(ControlFlow::Break("not enough books"));
+    ///     }
+    ///     automerge_orm::Result::Ok(ControlFlow::Continue(()))
+    /// })?;
+    /// assert_eq!(flow, ControlFlow::Break("not enough books"));
+    /// assert!(entity_manager.heads().is_empty());
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    ///
+    /// # Performance
+    ///
+    /// Within the scope of the function `f`, a write lock is held on the
+    /// document. Do not perform expensive operations within the function `f`.
+    pub fn transact_controlled<F, B, C, E>(&self, f: F) -> Result<ControlFlow<B, C>>
+    where
+        F: FnOnce(&mut Transaction<'_>) -> std::result::Result<ControlFlow<B, C>, E>,
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        /// The reason why the function `f` rolled back the transaction.
+        enum Rollback<B, E> {
+            Break(B),
+            Aborted(E),
+        }
+
+        let result = self.try_transact(|tx| match f(tx) {
+            Ok(ControlFlow::Continue(value)) => Ok(value),
+            Ok(ControlFlow::Break(value)) => Err(Rollback::Break(value)),
+            Err(err) => Err(Rollback::Aborted(err)),
+        });
+        match result {
+            Ok(value) => Ok(ControlFlow::Continue(value)),
+            Err(TransactError::Aborted(Rollback::Break(value))) => Ok(ControlFlow::Break(value)),
+            Err(TransactError::Aborted(Rollback::Aborted(err))) => {
+                Err(TransactError::Aborted(err).into())
+            },
+            Err(TransactError::Orm(err)) => Err(err),
+        }
+    }
+
+    fn try_transact_with_options<F, O, E>(
+        &self,
+        options: TransactionOptions,
+        f: F,
+    ) -> std::result::Result<O, TransactError<E>>
+    where
+        F: FnOnce(&mut Transaction<'_>) -> std::result::Result<O, E>,
+    {
+        let started = Instant::now();
+        self.doc
+            .with_doc_mut(|doc| run_transaction(doc, self, started, options, f))
+    }
+
+    /// Runs the pending [`Migration`]s on the Automerge document in the order of
+    /// their versions, and returns the resulting schema version.
+    ///
+    /// The schema version is stored in the root of the document, or in the map
+    /// under the root configured by [`with_root`]. A migration is pending if
+    /// its version is greater than the stored schema version. Each migration
+    /// is run within its own transaction, which also stores its version as the
+    /// new schema version, so running the same migrations again is a no-op. If
+    /// a migration fails, the migrations before it remain applied.
+    ///
+    /// Migrations which are run concurrently on multiple peers are merged like
+    /// any other changes, so a migration should only be run by a single peer,
+    /// or be safe to run more than once.
+    ///
+    /// [`with_root`]: EntityManager::with_root
+    ///
+    /// # Performance
+    ///
+    /// Within the scope of each migration, a write lock is held on the
+    /// document.
+    pub fn migrate(&self, migrations: &[&dyn Migration]) -> Result<u32> {
+        let mut migrations = migrations.to_vec();
+        migrations.sort_by_key(|migration| migration.version());
+        for migration in migrations {
+            self.transact(|tx| {
+                if migration.version() <= tx.schema_version()? {
+                    return Ok(());
+                }
+                migration.up(tx)?;
+                tx.set_schema_version(migration.version())
+            })?;
+        }
+
+        self.doc
+            .with_doc(|doc| migration::schema_version(doc, self.root()))
+    }
+
+    /// Finds an object by its key / identifier.
+    ///
+    /// As the key carries the type of the object it identifies, this can be
+    /// used to resolve relationships between objects, e.g. the object
+    /// referenced by a foreign key field of another object.
+    pub fn find_related<R>(&self, key: Key<R>) -> Result<Option<R>>
+    where
+        R: Mapped + Hydrate,
+    {
+        self.doc.with_doc(|doc| find_under(doc, self.root(), key))
+    }
+
+    /// Reads objects of any type within the scope of the function `f`, under
+    /// a single read lock.
+    ///
+    /// This is the read-only counterpart of [`transact`], e.g. for loading the
+    /// objects of different types which make up a view, without acquiring the
+    /// read lock once per repository call.
+    ///
+    /// [`transact`]: EntityManager::transact
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use automerge_orm::{Entity, EntityManager, Keyed};
+    /// use autosurgeon::{Hydrate, Reconcile};
+    /// use uuid::Uuid;
+    ///
+    /// #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    /// struct Author {
+    ///     #[key]
+    ///     id: Uuid,
+    /// }
+    ///
+    /// #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    /// struct Book {
+    ///     #[key]
+    ///     id: Uuid,
+    ///     author_id: Uuid,
+    /// }
+    ///
+    /// let entity_manager = EntityManager::ephemeral();
+    ///
+    /// let author = Author { id: Uuid::new_v4() };
+    /// entity_manager.transact(|tx| {
+    ///     tx.insert(&author)?;
+    ///     tx.insert(&Book {
+    ///         id: Uuid::new_v4(),
+    ///         author_id: author.id,
+    ///     })
+    /// })?;
+    /// let (author, books) = entity_manager.read(|reader| {
+    ///     let author = reader.find(author.id())?;
+    ///     let books = reader.find_all::<Book>()?;
+    ///     automerge_orm::Result::Ok((author, books))
+    /// })?;
+    /// assert!(author.is_some());
+    /// assert_eq!(books.len(), 1);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    ///
+    /// # Performance
+    ///
+    /// Within the scope of the function `f`, a read lock is held on the
+    /// document. Do not perform expensive operations within the function `f`.
+    pub fn read<F, O>(&self, f: F) -> O
+    where
+        F: FnOnce(&Reader<'_>) -> O,
+    {
+        self.doc.with_doc(|doc| f(&Reader::new(doc, self)))
+    }
+
+    /// Returns the Automerge object id of the table which `T` is mapped to, or
+    /// `None` if the table does not exist.
+    ///
+    /// This gives access to the raw Automerge object, e.g. to perform custom
+    /// queries through [`ReadDoc`] on the handle returned by [`doc`].
+    ///
+    /// [`ReadDoc`]: autosurgeon::ReadDoc
+    /// [`doc`]: EntityManager::doc
+    ///
+    /// # Performance
+    ///
+    /// Within the scope of this method, a read lock is held on the document.
+    pub fn table_id<T>(&self) -> Result<Option<ObjId>>
+    where
+        T: Mapped,
+    {
+        self.doc
+            .with_doc(|doc| get_table_under::<_, T>(doc, self.root()))
+    }
+
+    /// Returns whether the table of the entity exists in the Automerge
+    /// document, and the number of objects in it.
+    ///
+    /// Unlike [`EntityRepository::find_all`], which returns an empty map in
+    /// both cases, this distinguishes a missing table from an empty one. No
+    /// objects are hydrated.
+    ///
+    /// [`EntityRepository::find_all`]: crate::EntityRepository::find_all
+    ///
+    /// # Performance
+    ///
+    /// Within the scope of this method, a read lock is held on the document.
+    pub fn table_status<T>(&self) -> Result<TableStatus>
+    where
+        T: Mapped,
+    {
+        self.doc.with_doc(|doc| {
+            let Some(table_id) = get_table_under::<_, T>(doc, self.root())? else {
+                return Ok(TableStatus::Missing);
+            };
+            let status = match doc.length(&table_id) {
+                0 => TableStatus::Empty,
+                len => TableStatus::NonEmpty(len),
+            };
+
+            Ok(status)
+        })
+    }
+
+    /// Renders the whole Automerge document as a readable tree of tables,
+    /// keys and field values, for debugging.
+    ///
+    /// Unlike converting entities into JSON, this does not require knowing the
+    /// entity types, so it also renders unexpected or foreign tables. The
+    /// format is meant for humans and may change between versions.
+    ///
+    /// # Performance
+    ///
+    /// Within the scope of this method, a read lock is held on the document.
+    pub fn dump(&self) -> Result<String> {
+        self.doc
+            .with_doc(|doc| dump::dump_object(doc, &automerge::ROOT, 0))
+    }
+
+    /// Returns the current heads of the Automerge document.
+    pub fn heads(&self) -> Vec<ChangeHash> {
+        self.doc.with_doc(|doc| doc.get_heads())
+    }
+
+    /// Returns the history of changes which were committed to the Automerge
+    /// document, as a list of [`CommitInfo`] in causal order.
+    ///
+    /// This includes changes received from remote peers, as well as changes
+    /// which were not made through the ORM.
+    ///
+    /// # Performance
+    ///
+    /// Within the scope of this method, a read lock is held on the document.
+    pub fn history(&self) -> Result<Vec<CommitInfo>> {
+        self.doc.with_doc(|doc| {
+            let changes = doc.get_changes(&[])?;
+
+            Ok(changes.into_iter().map(CommitInfo::from).collect())
+        })
+    }
+
+    /// Creates a [`Savepoint`] which marks the current state of the Automerge
+    /// document.
+    ///
+    /// The document can later be reverted to the savepoint using
+    /// [`rollback_to`]. This allows a long-running operation which is split
+    /// into multiple transactions to checkpoint its progress, and roll back
+    /// only the transactions after the checkpoint.
+    ///
+    /// [`rollback_to`]: EntityManager::rollback_to
+    pub fn savepoint(&self) -> Savepoint {
+        Savepoint::new(self.heads())
+    }
+
+    /// Reverts the Automerge document to the state at the [`Savepoint`].
+    ///
+    /// Automerge transactions do not nest, and changes which have been
+    /// committed cannot be removed from the history of the document. Instead,
+    /// this creates a new change which compensates for all changes made since
+    /// the savepoint. Note that this includes changes received from remote
+    /// peers since the savepoint.
+    ///
+    /// Returns an [`Error::RollbackUnsupported`] if marks on text have changed
+    /// since the savepoint, as they cannot be rolled back.
+    ///
+    /// # Performance
+    ///
+    /// Within the scope of this method, a write lock is held on the document.
+    pub fn rollback_to(&self, savepoint: &Savepoint) -> Result<()> {
         self.doc.with_doc_mut(|doc| {
+            for hash in savepoint.heads() {
+                if doc.get_change_by_hash(hash).is_none() {
+                    Err(AutomergeError::InvalidHash(*hash))?;
+                }
+            }
+            let patches = doc.diff(&doc.get_heads(), savepoint.heads());
+            if patches.is_empty() {
+                return Ok(());
+            }
             let mut tx = Transaction::new(doc.transaction());
-            let result = f(&mut tx);
-            match result {
-                Ok(result) => {
-                    tx.commit()?;
-                    Ok(result)
-                },
-                Err(e) => {
-                    tx.rollback();
-                    Err(Error::TransactionAborted(Arc::new(e)))?
-                },
+            tx.apply_patches(patches)?;
+            tx.commit()
+        })
+    }
+
+    /// Merges another Automerge document into the Automerge document, and
+    /// returns the hashes of the changes which were newly applied.
+    ///
+    /// Merging follows the semantics of Automerge, i.e. all changes from
+    /// `other` which are not yet present in the document are applied, and
+    /// concurrent edits to the same objects are reconciled according to the
+    /// rules of Automerge. No ORM-level conflict handling takes place, so
+    /// e.g. concurrent updates to the same field of an object instance result
+    /// in one of the values winning deterministically.
+    ///
+    /// This is useful for documents which are received from remote peers
+    /// outside of the sync protocol of the repo.
+    ///
+    /// # Performance
+    ///
+    /// Within the scope of this method, a write lock is held on the document.
+    pub fn merge(&self, other: &Automerge) -> Result<Vec<ChangeHash>> {
+        let mut other = other.clone();
+        let hashes = self.doc.with_doc_mut(|doc| doc.merge(&mut other))?;
+
+        Ok(hashes)
+    }
+
+    /// Merges a saved Automerge document, e.g. as produced by
+    /// [`Automerge::save`], into the Automerge document, and returns the
+    /// hashes of the changes which were newly applied.
+    ///
+    /// See [`merge`] for more.
+    ///
+    /// [`merge`]: EntityManager::merge
+    pub fn merge_bytes(&self, bytes: &[u8]) -> Result<Vec<ChangeHash>> {
+        let mut other = Automerge::load(bytes)?;
+        let hashes = self.doc.with_doc_mut(|doc| doc.merge(&mut other))?;
+
+        Ok(hashes)
+    }
+
+    /// Saves the Automerge document in its compressed binary format.
+    ///
+    /// # Performance
+    ///
+    /// Within the scope of this method, a read lock is held on the document.
+    pub fn save(&self) -> Vec<u8> {
+        self.doc.with_doc(|doc| doc.save())
+    }
+
+    /// Forks the Automerge document into a new `EntityManager` for a
+    /// standalone Automerge document.
+    ///
+    /// Transactions performed on the fork do not affect this `EntityManager`
+    /// until the fork is merged back, e.g. by passing the output of [`save`]
+    /// on the fork to [`merge_bytes`]. This allows speculative changes to be
+    /// validated before they are committed to the document.
+    ///
+    /// [`save`]: EntityManager::save
+    /// [`merge_bytes`]: EntityManager::merge_bytes
+    ///
+    /// # Performance
+    ///
+    /// Within the scope of this method, a read lock is held on the document.
+    pub fn fork(&self) -> Result<Self> {
+        Ok(Self {
+            doc: self.doc.fork(),
+            root: self.root.clone(),
+            metrics: self.metrics.clone(),
+        })
+    }
+
+    /// Returns a stream which yields a [`ChangeEvent`] whenever the Automerge
+    /// document changes, whether locally or as a result of syncing with
+    /// remote peers.
+    ///
+    /// Changes are observed relative to the heads of the document at the time
+    /// this method is called. Multiple changes which happen in between polls
+    /// of the stream are coalesced into a single event.
+    ///
+    /// The stream ends when changes to the document can no longer be observed,
+    /// e.g. after the repo has been stopped. For a standalone document, which
+    /// is not managed by a repo, the stream ends once all changes made so far
+    /// have been observed.
+    #[cfg(feature = "stream")]
+    pub fn changes(&self) -> impl Stream<Item = ChangeEvent> {
+        let heads = self.heads();
+        let state = (self.doc.clone(), self.root.clone(), heads);
+        stream::unfold(state, |(doc, root, heads)| async move {
+            loop {
+                let event = doc.with_doc(|doc| ChangeEvent::since(doc, root.as_deref(), &heads));
+                if let Some(event) = event {
+                    let heads = event.heads().to_vec();
+                    return Some((event, (doc, root, heads)));
+                }
+                doc.changed().await?;
             }
         })
     }
 
     /// Returns a handle to the Automerge document.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the document is a standalone document which is not managed
+    /// by a repo, e.g. one created by [`from_doc`]. Use [`try_doc`] if the
+    /// `EntityManager` may manage a standalone document.
+    ///
+    /// [`from_doc`]: EntityManager::from_doc
+    /// [`try_doc`]: EntityManager::try_doc
     pub fn doc(&self) -> DocHandle {
-        self.doc.clone()
+        self.try_doc()
+            .expect("document of the entity manager is not managed by a repo")
+    }
+
+    /// Returns a handle to the Automerge document, or `None` if the document
+    /// is a standalone document which is not managed by a repo.
+    pub fn try_doc(&self) -> Option<DocHandle> {
+        self.doc.handle().cloned()
+    }
+
+    /// Returns the Automerge document.
+    pub(crate) fn document(&self) -> &Document {
+        &self.doc
+    }
+
+    /// Returns the key of the map in the root of the Automerge document under
+    /// which tables are stored, or `None` if tables are stored in the root of
+    /// the document itself.
+    pub(crate) fn root(&self) -> Option<&str> {
+        self.root.as_deref()
+    }
+
+    /// Records an event with the [`Metrics`] sink, if any.
+    pub(crate) fn record(&self, event: MetricsEvent) {
+        if let Some(metrics) = &self.metrics {
+            metrics.record(&event);
+        }
+    }
+}
+
+/// Runs the provided function `f` within the context of a [`Transaction`] on
+/// the document, and commits the transaction, unless `f` returns an error.
+///
+/// `started` is the time when the write lock on the document was requested.
+fn run_transaction<F, O, E>(
+    doc: &mut Automerge,
+    entity_manager: &EntityManager,
+    started: Instant,
+    options: TransactionOptions,
+    f: F,
+) -> std::result::Result<O, TransactError<E>>
+where
+    F: FnOnce(&mut Transaction<'_>) -> std::result::Result<O, E>,
+{
+    let locked = Instant::now();
+    entity_manager.record(MetricsEvent::LockWait {
+        elapsed: locked - started,
+    });
+    let root = entity_manager.root.clone();
+    let mut tx = Transaction::with_options(doc.transaction(), options, root);
+    // If `f` panics, the Automerge transaction rolls back its changes when it
+    // is dropped during unwinding.
+    let result = f(&mut tx);
+    match result {
+        Ok(result) => {
+            let ops = tx.pending_ops();
+            tx.commit()?;
+            entity_manager.record(MetricsEvent::Commit {
+                ops,
+                elapsed: locked.elapsed(),
+            });
+            Ok(result)
+        },
+        Err(e) => {
+            tx.rollback();
+            Err(TransactError::Aborted(e))
+        },
     }
 }
@@ -1,13 +1,56 @@
-use std::{collections::BTreeMap, marker::PhantomData, sync::Arc};
+use std::{
+    borrow::Borrow, cmp::Ordering, collections::BTreeMap, io::Write, marker::PhantomData,
+    sync::Arc, time::Instant,
+};
 
+use automerge::{Automerge, ChangeHash, Cursor, Prop, ReadDoc, Value};
 use autosurgeon::Hydrate;
+#[cfg(feature = "stream")]
+use futures::{
+    future,
+    stream::{self, Stream, StreamExt},
+};
 
-use crate::{find, find_all, EntityManager, Key, Mapped, Result};
+#[cfg(feature = "serde_json")]
+use crate::impls::{table_to_json_under, to_json_under};
+use crate::{
+    conflict_policy::{resolve_entity, resolve_table},
+    cursor,
+    impls::{
+        find_all_at_under, find_all_by_under, find_all_keys_under, find_all_under, find_at_under,
+        find_first_under, find_under, for_each_under, get_table_under, list_all_under,
+    },
+    ConflictPolicy, EntityManager, Error, Key, Mapped, MetricsEvent, Result,
+};
+#[cfg(feature = "stream")]
+use crate::{watch, EntityEvent};
 
 /// A default implementation for [`EntityRepository`].
+///
+/// The repository holds on to the [`EntityManager`] through `M`, which is an
+/// `Arc<EntityManager>` by default, but can be any type which borrows an
+/// `EntityManager`, e.g. an owned `EntityManager`, a `&EntityManager`, or an
+/// `Rc<EntityManager>` for single-threaded use without atomic reference
+/// counting.
+///
+/// # Staleness
+///
+/// Reads through the repository always reflect the changes which have been
+/// applied to the document, whether locally or as a result of syncing with
+/// remote peers, as the document is shared by all clones of its
+/// [`DocHandle`]. However, waiting for changes, e.g. by [`watch`], relies on
+/// [`DocHandle::changed`], which tracks the changes observed per handle. To
+/// observe changes independently of other repositories, create the repository
+/// with a clone of the `EntityManager`, which holds a fresh clone of the
+/// handle.
+///
+/// [`DocHandle`]: automerge_repo::DocHandle
+/// [`DocHandle::changed`]: automerge_repo::DocHandle::changed
+/// [`watch`]: DefaultEntityRepository::watch
 #[derive(Clone, Debug)]
-pub struct DefaultEntityRepository<T> {
-    entity_manager: Arc<EntityManager>,
+pub struct DefaultEntityRepository<T, M = Arc<EntityManager>> {
+    entity_manager: M,
+    conflict_policy: ConflictPolicy,
     phantom: PhantomData<fn(T) -> T>,
 }
 
@@ -101,6 +144,97 @@ pub trait EntityRepository<T> {
     /// ```
     fn find(&self, id: Key<T>) -> Result<Option<T>>;
 
+    /// Finds an object by its key / identifier, or returns an
+    /// [`Error::ObjectDoesNotExist`] if it does not exist.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use automerge::ChangeHash;
+    /// # use automerge_repo::{DocumentId, Repo, Storage, StorageError};
+    /// # use futures::future::{self, BoxFuture, FutureExt};
+    /// #
+    /// # pub struct NoopStorage;
+    /// #
+    /// # impl Storage for NoopStorage {
+    /// #     fn get(
+    /// #         &self,
+    /// #         _id: DocumentId
+    /// #     ) -> BoxFuture<'static, Result<Option<Vec<u8>>, StorageError>> {
+    /// #         future::ready(Ok(None)).boxed()
+    /// #     }
+    /// #
+    /// #     fn list_all(
+    /// #         &self
+    /// #     ) -> BoxFuture<'static, Result<Vec<DocumentId>, StorageError>> {
+    /// #         future::ready(Ok(Vec::new())).boxed()
+    /// #     }
+    /// #
+    /// #     fn append(
+    /// #         &self,
+    /// #         _id: DocumentId,
+    /// #         _chunk: Vec<u8>,
+    /// #     ) -> BoxFuture<'static, Result<(), StorageError>> {
+    /// #         future::ready(Ok(())).boxed()
+    /// #     }
+    /// #
+    /// #     fn compact(
+    /// #         &self,
+    /// #         _id: DocumentId,
+    /// #         _chunk: Vec<u8>,
+    /// #         _new_heads: Vec<ChangeHash>,
+    /// #     ) -> BoxFuture<'static, Result<(), StorageError>> {
+    /// #         future::ready(Ok(())).boxed()
+    /// #     }
+    /// # }
+    /// #
+    /// use std::sync::Arc;
+    ///
+    /// use automerge_orm::{DefaultEntityRepository, Entity, EntityManager, EntityRepository, Keyed};
+    /// use autosurgeon::{Hydrate, Reconcile};
+    /// use uuid::Uuid;
+    ///
+    /// #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    /// struct Book {
+    ///     #[key]
+    ///     id: Uuid,
+    /// }
+    ///
+    /// type BookRepository = DefaultEntityRepository<Book>;
+    ///
+    /// impl Book {
+    ///     pub fn new() -> Self {
+    ///         Self { id: Uuid::new_v4() }
+    ///     }
+    /// }
+    ///
+    /// # let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    /// # let doc_handle = repo_handle.new_document();
+    /// let entity_manager = Arc::new(EntityManager::new(doc_handle));
+    /// let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+    ///
+    /// let book = Book::new();
+    /// entity_manager.transact(|tx| {
+    ///     tx.insert(&book)?;
+    ///     automerge_orm::Result::Ok(())
+    /// })?;
+    /// let book_out = book_repository.find_or_error(book.id())?;
+    /// assert_eq!(book_out.id(), book.id());
+    /// let result = book_repository.find_or_error(Book::new().id());
+    /// assert!(result.unwrap_err().is_not_found());
+    /// # repo_handle.stop().unwrap();
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    fn find_or_error(&self, id: Key<T>) -> Result<T>
+    where
+        T: Mapped,
+    {
+        self.find(id)?.ok_or_else(|| Error::ObjectDoesNotExist {
+            table_name: <T as Mapped>::table_name().to_owned(),
+            id: id.into(),
+        })
+    }
+
     /// Finds all objects in the repository.
     ///
     /// # Examples
@@ -151,7 +285,301 @@ pub trait EntityRepository<T> {
     ///     Entity,
     ///     EntityManager,
     ///     EntityRepository,
-    ///     Keyed,
+    ///     Keyed,
+    /// };
+    /// use autosurgeon::{Hydrate, Reconcile};
+    /// use uuid::Uuid;
+    ///
+    /// #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    /// struct Book {
+    ///     #[key]
+    ///     id: Uuid,
+    /// }
+    ///
+    /// impl Book {
+    ///     pub fn new() -> Self {
+    ///         Self { id: Uuid::new_v4() }
+    ///     }
+    /// }
+    ///
+    /// type BookRepository = DefaultEntityRepository<Book>;
+    ///
+    /// # let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    /// # let doc_handle = repo_handle.new_document();
+    /// let entity_manager = Arc::new(EntityManager::new(doc_handle));
+    /// let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+    ///
+    /// let books_in = vec![Book::new(), Book::new()];
+    /// entity_manager.transact(|tx| {
+    ///     for book_in in &books_in {
+    ///         tx.insert(book_in)?;
+    ///     }
+    ///     automerge_orm::Result::Ok(())
+    /// })?;
+    /// let books = book_repository.find_all()?;
+    /// assert_eq!(books.len(), 2);
+    /// assert!(books.get(&books_in[0].id().to_string()).is_some());
+    /// assert!(books.get(&books_in[1].id().to_string()).is_some());
+    /// # repo_handle.stop().unwrap();
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    fn find_all(&self) -> Result<BTreeMap<String, T>>;
+
+    /// Lists all objects in the repository, ordered by key.
+    ///
+    /// Unlike [`find_all`], this does not build a map from keys to objects.
+    ///
+    /// [`find_all`]: EntityRepository::find_all
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use automerge::ChangeHash;
+    /// # use automerge_repo::{DocumentId, Repo, Storage, StorageError};
+    /// # use futures::future::{self, BoxFuture, FutureExt};
+    /// #
+    /// # pub struct NoopStorage;
+    /// #
+    /// # impl Storage for NoopStorage {
+    /// #     fn get(
+    /// #         &self,
+    /// #         _id: DocumentId
+    /// #     ) -> BoxFuture<'static, Result<Option<Vec<u8>>, StorageError>> {
+    /// #         future::ready(Ok(None)).boxed()
+    /// #     }
+    /// #
+    /// #     fn list_all(
+    /// #         &self
+    /// #     ) -> BoxFuture<'static, Result<Vec<DocumentId>, StorageError>> {
+    /// #         future::ready(Ok(Vec::new())).boxed()
+    /// #     }
+    /// #
+    /// #     fn append(
+    /// #         &self,
+    /// #         _id: DocumentId,
+    /// #         _chunk: Vec<u8>,
+    /// #     ) -> BoxFuture<'static, Result<(), StorageError>> {
+    /// #         future::ready(Ok(())).boxed()
+    /// #     }
+    /// #
+    /// #     fn compact(
+    /// #         &self,
+    /// #         _id: DocumentId,
+    /// #         _chunk: Vec<u8>,
+    /// #         _new_heads: Vec<ChangeHash>,
+    /// #     ) -> BoxFuture<'static, Result<(), StorageError>> {
+    /// #         future::ready(Ok(())).boxed()
+    /// #     }
+    /// # }
+    /// #
+    /// use std::sync::Arc;
+    ///
+    /// use automerge_orm::{
+    ///     DefaultEntityRepository,
+    ///     Entity,
+    ///     EntityManager,
+    ///     EntityRepository,
+    /// };
+    /// use autosurgeon::{Hydrate, Reconcile};
+    /// use uuid::Uuid;
+    ///
+    /// #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    /// struct Book {
+    ///     #[key]
+    ///     id: Uuid,
+    /// }
+    ///
+    /// impl Book {
+    ///     pub fn new() -> Self {
+    ///         Self { id: Uuid::new_v4() }
+    ///     }
+    /// }
+    ///
+    /// type BookRepository = DefaultEntityRepository<Book>;
+    ///
+    /// # let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    /// # let doc_handle = repo_handle.new_document();
+    /// let entity_manager = Arc::new(EntityManager::new(doc_handle));
+    /// let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+    ///
+    /// let mut books_in = vec![Book::new(), Book::new()];
+    /// entity_manager.transact(|tx| {
+    ///     for book_in in &books_in {
+    ///         tx.insert(book_in)?;
+    ///     }
+    ///     automerge_orm::Result::Ok(())
+    /// })?;
+    /// books_in.sort_by_key(|book| book.id);
+    /// let books = book_repository.list()?;
+    /// assert_eq!(books.len(), 2);
+    /// assert_eq!(books[0].id, books_in[0].id);
+    /// assert_eq!(books[1].id, books_in[1].id);
+    /// # repo_handle.stop().unwrap();
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    fn list(&self) -> Result<Vec<T>> {
+        Ok(self.find_all()?.into_values().collect())
+    }
+
+    /// Finds the keys of all objects in the repository, ordered by key.
+    ///
+    /// This does not hydrate any of the objects, so it is much cheaper than
+    /// [`find_all`] when only the keys are needed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidKey`] if a key in the repository is not a valid
+    /// [`Uuid`].
+    ///
+    /// [`find_all`]: EntityRepository::find_all
+    /// [`Error::InvalidKey`]: crate::Error::InvalidKey
+    /// [`Uuid`]: uuid::Uuid
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use automerge::ChangeHash;
+    /// # use automerge_repo::{DocumentId, Repo, Storage, StorageError};
+    /// # use futures::future::{self, BoxFuture, FutureExt};
+    /// #
+    /// # pub struct NoopStorage;
+    /// #
+    /// # impl Storage for NoopStorage {
+    /// #     fn get(
+    /// #         &self,
+    /// #         _id: DocumentId
+    /// #     ) -> BoxFuture<'static, Result<Option<Vec<u8>>, StorageError>> {
+    /// #         future::ready(Ok(None)).boxed()
+    /// #     }
+    /// #
+    /// #     fn list_all(
+    /// #         &self
+    /// #     ) -> BoxFuture<'static, Result<Vec<DocumentId>, StorageError>> {
+    /// #         future::ready(Ok(Vec::new())).boxed()
+    /// #     }
+    /// #
+    /// #     fn append(
+    /// #         &self,
+    /// #         _id: DocumentId,
+    /// #         _chunk: Vec<u8>,
+    /// #     ) -> BoxFuture<'static, Result<(), StorageError>> {
+    /// #         future::ready(Ok(())).boxed()
+    /// #     }
+    /// #
+    /// #     fn compact(
+    /// #         &self,
+    /// #         _id: DocumentId,
+    /// #         _chunk: Vec<u8>,
+    /// #         _new_heads: Vec<ChangeHash>,
+    /// #     ) -> BoxFuture<'static, Result<(), StorageError>> {
+    /// #         future::ready(Ok(())).boxed()
+    /// #     }
+    /// # }
+    /// #
+    /// use std::sync::Arc;
+    ///
+    /// use automerge_orm::{
+    ///     DefaultEntityRepository,
+    ///     Entity,
+    ///     EntityManager,
+    ///     EntityRepository,
+    ///     Keyed,
+    /// };
+    /// use autosurgeon::{Hydrate, Reconcile};
+    /// use uuid::Uuid;
+    ///
+    /// #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    /// struct Book {
+    ///     #[key]
+    ///     id: Uuid,
+    /// }
+    ///
+    /// impl Book {
+    ///     pub fn new() -> Self {
+    ///         Self { id: Uuid::new_v4() }
+    ///     }
+    /// }
+    ///
+    /// type BookRepository = DefaultEntityRepository<Book>;
+    ///
+    /// # let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    /// # let doc_handle = repo_handle.new_document();
+    /// let entity_manager = Arc::new(EntityManager::new(doc_handle));
+    /// let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+    ///
+    /// let book_in = Book::new();
+    /// entity_manager.transact(|tx| {
+    ///     tx.insert(&book_in)?;
+    ///     automerge_orm::Result::Ok(())
+    /// })?;
+    /// let keys = book_repository.keys()?;
+    /// assert_eq!(keys, vec![book_in.id()]);
+    /// # repo_handle.stop().unwrap();
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    fn keys(&self) -> Result<Vec<Key<T>>>
+    where
+        T: Mapped,
+    {
+        self.find_all()?
+            .keys()
+            .map(|key| Key::from_prop(key))
+            .collect()
+    }
+
+    /// Finds the object with the smallest key in the repository.
+    ///
+    /// Only that object is hydrated. Returns `None` if the repository is
+    /// empty.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use automerge::ChangeHash;
+    /// # use automerge_repo::{DocumentId, Repo, Storage, StorageError};
+    /// # use futures::future::{self, BoxFuture, FutureExt};
+    /// #
+    /// # pub struct NoopStorage;
+    /// #
+    /// # impl Storage for NoopStorage {
+    /// #     fn get(
+    /// #         &self,
+    /// #         _id: DocumentId
+    /// #     ) -> BoxFuture<'static, Result<Option<Vec<u8>>, StorageError>> {
+    /// #         future::ready(Ok(None)).boxed()
+    /// #     }
+    /// #
+    /// #     fn list_all(
+    /// #         &self
+    /// #     ) -> BoxFuture<'static, Result<Vec<DocumentId>, StorageError>> {
+    /// #         future::ready(Ok(Vec::new())).boxed()
+    /// #     }
+    /// #
+    /// #     fn append(
+    /// #         &self,
+    /// #         _id: DocumentId,
+    /// #         _chunk: Vec<u8>,
+    /// #     ) -> BoxFuture<'static, Result<(), StorageError>> {
+    /// #         future::ready(Ok(())).boxed()
+    /// #     }
+    /// #
+    /// #     fn compact(
+    /// #         &self,
+    /// #         _id: DocumentId,
+    /// #         _chunk: Vec<u8>,
+    /// #         _new_heads: Vec<ChangeHash>,
+    /// #     ) -> BoxFuture<'static, Result<(), StorageError>> {
+    /// #         future::ready(Ok(())).boxed()
+    /// #     }
+    /// # }
+    /// #
+    /// use std::sync::Arc;
+    ///
+    /// use automerge_orm::{
+    ///     DefaultEntityRepository,
+    ///     Entity,
+    ///     EntityManager,
+    ///     EntityRepository,
     /// };
     /// use autosurgeon::{Hydrate, Reconcile};
     /// use uuid::Uuid;
@@ -175,6 +603,8 @@ pub trait EntityRepository<T> {
     /// let entity_manager = Arc::new(EntityManager::new(doc_handle));
     /// let book_repository = BookRepository::new(Arc::clone(&entity_manager));
     ///
+    /// assert!(book_repository.first()?.is_none());
+    ///
     /// let books_in = vec![Book::new(), Book::new()];
     /// entity_manager.transact(|tx| {
     ///     for book_in in &books_in {
@@ -182,26 +612,261 @@ pub trait EntityRepository<T> {
     ///     }
     ///     automerge_orm::Result::Ok(())
     /// })?;
-    /// let books = book_repository.find_all()?;
+    /// let book = book_repository.first()?;
+    /// assert!(book.is_some());
+    /// assert_eq!(book.unwrap().id, books_in[0].id.min(books_in[1].id));
+    /// # repo_handle.stop().unwrap();
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    fn first(&self) -> Result<Option<T>> {
+        Ok(self.find_all()?.into_values().next())
+    }
+
+    /// Finds an arbitrary object in the repository.
+    ///
+    /// This is the cheapest way to check whether the repository is non-empty
+    /// while also obtaining an example object. No guarantee is made about
+    /// which object is returned. Returns `None` if the repository is empty.
+    ///
+    /// The default implementation delegates to [`first`], since entries of the
+    /// underlying table are visited in sorted order anyway.
+    ///
+    /// [`first`]: EntityRepository::first
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use automerge::ChangeHash;
+    /// # use automerge_repo::{DocumentId, Repo, Storage, StorageError};
+    /// # use futures::future::{self, BoxFuture, FutureExt};
+    /// #
+    /// # pub struct NoopStorage;
+    /// #
+    /// # impl Storage for NoopStorage {
+    /// #     fn get(
+    /// #         &self,
+    /// #         _id: DocumentId
+    /// #     ) -> BoxFuture<'static, Result<Option<Vec<u8>>, StorageError>> {
+    /// #         future::ready(Ok(None)).boxed()
+    /// #     }
+    /// #
+    /// #     fn list_all(
+    /// #         &self
+    /// #     ) -> BoxFuture<'static, Result<Vec<DocumentId>, StorageError>> {
+    /// #         future::ready(Ok(Vec::new())).boxed()
+    /// #     }
+    /// #
+    /// #     fn append(
+    /// #         &self,
+    /// #         _id: DocumentId,
+    /// #         _chunk: Vec<u8>,
+    /// #     ) -> BoxFuture<'static, Result<(), StorageError>> {
+    /// #         future::ready(Ok(())).boxed()
+    /// #     }
+    /// #
+    /// #     fn compact(
+    /// #         &self,
+    /// #         _id: DocumentId,
+    /// #         _chunk: Vec<u8>,
+    /// #         _new_heads: Vec<ChangeHash>,
+    /// #     ) -> BoxFuture<'static, Result<(), StorageError>> {
+    /// #         future::ready(Ok(())).boxed()
+    /// #     }
+    /// # }
+    /// #
+    /// use std::sync::Arc;
+    ///
+    /// use automerge_orm::{
+    ///     DefaultEntityRepository,
+    ///     Entity,
+    ///     EntityManager,
+    ///     EntityRepository,
+    ///     Keyed,
+    /// };
+    /// use autosurgeon::{Hydrate, Reconcile};
+    /// use uuid::Uuid;
+    ///
+    /// #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    /// struct Book {
+    ///     #[key]
+    ///     id: Uuid,
+    /// }
+    ///
+    /// impl Book {
+    ///     pub fn new() -> Self {
+    ///         Self { id: Uuid::new_v4() }
+    ///     }
+    /// }
+    ///
+    /// type BookRepository = DefaultEntityRepository<Book>;
+    ///
+    /// # let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    /// # let doc_handle = repo_handle.new_document();
+    /// let entity_manager = Arc::new(EntityManager::new(doc_handle));
+    /// let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+    ///
+    /// assert!(book_repository.any()?.is_none());
+    ///
+    /// let book_in = Book::new();
+    /// entity_manager.transact(|tx| {
+    ///     tx.insert(&book_in)?;
+    ///     automerge_orm::Result::Ok(())
+    /// })?;
+    /// let book = book_repository.any()?;
+    /// assert!(book.is_some());
+    /// assert_eq!(book.unwrap().id(), book_in.id());
+    /// # repo_handle.stop().unwrap();
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    fn any(&self) -> Result<Option<T>> {
+        self.first()
+    }
+
+    /// Finds all objects in the repository, sorted by the comparator `cmp`.
+    ///
+    /// Sorting happens in memory after all objects have been hydrated, so the
+    /// cost of this operation is proportional to the size of the repository.
+    /// The sort is stable, so objects which compare equal are ordered by key.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use automerge::ChangeHash;
+    /// # use automerge_repo::{DocumentId, Repo, Storage, StorageError};
+    /// # use futures::future::{self, BoxFuture, FutureExt};
+    /// #
+    /// # pub struct NoopStorage;
+    /// #
+    /// # impl Storage for NoopStorage {
+    /// #     fn get(
+    /// #         &self,
+    /// #         _id: DocumentId
+    /// #     ) -> BoxFuture<'static, Result<Option<Vec<u8>>, StorageError>> {
+    /// #         future::ready(Ok(None)).boxed()
+    /// #     }
+    /// #
+    /// #     fn list_all(
+    /// #         &self
+    /// #     ) -> BoxFuture<'static, Result<Vec<DocumentId>, StorageError>> {
+    /// #         future::ready(Ok(Vec::new())).boxed()
+    /// #     }
+    /// #
+    /// #     fn append(
+    /// #         &self,
+    /// #         _id: DocumentId,
+    /// #         _chunk: Vec<u8>,
+    /// #     ) -> BoxFuture<'static, Result<(), StorageError>> {
+    /// #         future::ready(Ok(())).boxed()
+    /// #     }
+    /// #
+    /// #     fn compact(
+    /// #         &self,
+    /// #         _id: DocumentId,
+    /// #         _chunk: Vec<u8>,
+    /// #         _new_heads: Vec<ChangeHash>,
+    /// #     ) -> BoxFuture<'static, Result<(), StorageError>> {
+    /// #         future::ready(Ok(())).boxed()
+    /// #     }
+    /// # }
+    /// #
+    /// use std::sync::Arc;
+    ///
+    /// use automerge_orm::{
+    ///     DefaultEntityRepository,
+    ///     Entity,
+    ///     EntityManager,
+    ///     EntityRepository,
+    /// };
+    /// use autosurgeon::{Hydrate, Reconcile};
+    /// use uuid::Uuid;
+    ///
+    /// #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    /// struct Book {
+    ///     #[key]
+    ///     id: Uuid,
+    ///     title: String,
+    /// }
+    ///
+    /// impl Book {
+    ///     pub fn new(title: &str) -> Self {
+    ///         Self {
+    ///             id: Uuid::new_v4(),
+    ///             title: title.to_owned(),
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// type BookRepository = DefaultEntityRepository<Book>;
+    ///
+    /// # let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    /// # let doc_handle = repo_handle.new_document();
+    /// let entity_manager = Arc::new(EntityManager::new(doc_handle));
+    /// let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+    ///
+    /// entity_manager.transact(|tx| {
+    ///     tx.insert(&Book::new("Spirited Away"))?;
+    ///     tx.insert(&Book::new("Kiki's Delivery Service"))?;
+    ///     automerge_orm::Result::Ok(())
+    /// })?;
+    /// let books = book_repository.find_all_sorted_by(|a, b| a.title.cmp(&b.title))?;
     /// assert_eq!(books.len(), 2);
-    /// assert!(books.get(&books_in[0].id().to_string()).is_some());
-    /// assert!(books.get(&books_in[1].id().to_string()).is_some());
+    /// assert_eq!(books[0].title, "Kiki's Delivery Service");
+    /// assert_eq!(books[1].title, "Spirited Away");
     /// # repo_handle.stop().unwrap();
     /// # Ok::<(), Box<dyn std::error::Error>>(())
     /// ```
-    fn find_all(&self) -> Result<BTreeMap<String, T>>;
+    fn find_all_sorted_by<F>(&self, cmp: F) -> Result<Vec<T>>
+    where
+        Self: Sized,
+        F: FnMut(&T, &T) -> Ordering,
+    {
+        let mut entities = self.list()?;
+        entities.sort_by(cmp);
+
+        Ok(entities)
+    }
 }
 
-impl<T> EntityRepository<T> for DefaultEntityRepository<T>
+impl<T, M> EntityRepository<T> for DefaultEntityRepository<T, M>
 where
     T: Mapped + Hydrate,
+    M: Borrow<EntityManager>,
 {
     fn find(&self, id: Key<T>) -> Result<Option<T>> {
-        self.entity_manager.doc().with_doc(|doc| find(doc, id))
+        self.entity_manager()
+            .document()
+            .with_doc(|doc| match self.conflict_policy {
+                ConflictPolicy::Automerge => find_under(doc, self.root(), id),
+                ConflictPolicy::Greatest => {
+                    find_under(&resolve_entity(doc, self.root(), id)?, None, id)
+                },
+            })
     }
 
     fn find_all(&self) -> Result<BTreeMap<String, T>> {
-        self.entity_manager.doc().with_doc(|doc| find_all(doc))
+        let started = Instant::now();
+        let entities = self.with_resolved_doc(find_all_under)?;
+        self.entity_manager().record(MetricsEvent::FindAll {
+            table: <T as Mapped>::table_name(),
+            count: entities.len(),
+            elapsed: started.elapsed(),
+        });
+
+        Ok(entities)
+    }
+
+    fn list(&self) -> Result<Vec<T>> {
+        self.with_resolved_doc(list_all_under)
+    }
+
+    fn keys(&self) -> Result<Vec<Key<T>>> {
+        self.entity_manager()
+            .document()
+            .with_doc(|doc| find_all_keys_under(doc, self.root()))
+    }
+
+    fn first(&self) -> Result<Option<T>> {
+        self.with_resolved_doc(find_first_under)
     }
 }
 
@@ -209,9 +874,321 @@ impl<T> DefaultEntityRepository<T> {
     /// Creates a new `DefaultEntityRepository` which uses the
     /// [`EntityManager`].
     pub fn new(entity_manager: Arc<EntityManager>) -> Self {
+        Self::new_with(entity_manager)
+    }
+}
+
+impl<T, M> DefaultEntityRepository<T, M>
+where
+    M: Borrow<EntityManager>,
+{
+    /// Creates a new `DefaultEntityRepository` which uses the
+    /// [`EntityManager`] borrowed from `entity_manager`.
+    ///
+    /// Unlike [`new`], this does not require the `EntityManager` to be wrapped
+    /// in an [`Arc`], e.g. in single-threaded programs which hold on to a
+    /// `&EntityManager` for their entire lifetime.
+    ///
+    /// [`new`]: DefaultEntityRepository::new
+    pub fn new_with(entity_manager: M) -> Self {
         Self {
             entity_manager,
+            conflict_policy: ConflictPolicy::default(),
             phantom: PhantomData,
         }
     }
+
+    /// Configures the repository to resolve conflicting values of properties
+    /// according to `conflict_policy`, when objects are found by [`find`],
+    /// [`find_all`], [`list`] or [`first`].
+    ///
+    /// By default, the value which Automerge picks as the winner of a conflict
+    /// is used, which depends on the actor ids of the conflicting changes.
+    /// With [`ConflictPolicy::Greatest`], replicas which have received the
+    /// same changes find identical objects, regardless of their actor ids.
+    ///
+    /// [`find`]: EntityRepository::find
+    /// [`find_all`]: EntityRepository::find_all
+    /// [`list`]: EntityRepository::list
+    /// [`first`]: EntityRepository::first
+    pub fn with_conflict_policy(mut self, conflict_policy: ConflictPolicy) -> Self {
+        self.conflict_policy = conflict_policy;
+        self
+    }
+
+    /// Finds an object in the repository by its key / identifier, as it was
+    /// when the Automerge document was at `heads`.
+    ///
+    /// See [`impls::find_at`] for the cost of reading historical state.
+    ///
+    /// [`impls::find_at`]: crate::impls::find_at
+    pub fn find_at(&self, heads: &[ChangeHash], id: Key<T>) -> Result<Option<T>>
+    where
+        T: Mapped + Hydrate,
+    {
+        self.entity_manager()
+            .document()
+            .with_doc(|doc| find_at_under(doc, self.root(), heads, id))
+    }
+
+    /// Finds all objects in the repository, as they were when the Automerge
+    /// document was at `heads`.
+    ///
+    /// See [`impls::find_at`] for the cost of reading historical state.
+    ///
+    /// [`impls::find_at`]: crate::impls::find_at
+    pub fn find_all_at(&self, heads: &[ChangeHash]) -> Result<BTreeMap<String, T>>
+    where
+        T: Mapped + Hydrate,
+    {
+        self.entity_manager()
+            .document()
+            .with_doc(|doc| find_all_at_under(doc, self.root(), heads))
+    }
+
+    /// Finds an object related to the objects in the repository by its key,
+    /// e.g. the object referenced by a foreign key field.
+    ///
+    /// See [`EntityManager::find_related`] for more.
+    pub fn find_related<R>(&self, key: Key<R>) -> Result<Option<R>>
+    where
+        R: Mapped + Hydrate,
+    {
+        self.entity_manager().find_related(key)
+    }
+
+    /// Finds all objects in the repository which reference the `parent` object
+    /// via the `foreign_field` field.
+    ///
+    /// See [`impls::find_all_by`] for the requirements on the foreign key
+    /// field.
+    ///
+    /// [`impls::find_all_by`]: crate::impls::find_all_by
+    pub fn find_all_by<R>(&self, foreign_field: &str, parent: Key<R>) -> Result<Vec<T>>
+    where
+        T: Mapped + Hydrate,
+    {
+        self.entity_manager()
+            .document()
+            .with_doc(|doc| find_all_by_under(doc, self.root(), foreign_field, parent))
+    }
+
+    /// Returns the properties of an object in the repository which hold
+    /// conflicting values, keyed by property name, along with all of the
+    /// conflicting values.
+    ///
+    /// Concurrent changes to the same property, e.g. from different peers, are
+    /// merged by Automerge into a single winning value, which is the value
+    /// that is hydrated. The other values are kept, and can be presented to
+    /// users to resolve the conflict. Only the top-level properties of the
+    /// object are inspected.
+    ///
+    /// Returns an empty map if the object does not exist.
+    ///
+    /// # Performance
+    ///
+    /// Within the scope of this method, a read lock is held on the document.
+    pub fn conflicts(&self, id: Key<T>) -> Result<BTreeMap<String, Vec<Value<'static>>>>
+    where
+        T: Mapped,
+    {
+        self.entity_manager().document().with_doc(|doc| {
+            let mut conflicts = BTreeMap::new();
+            let Some(table_id) = get_table_under::<_, T>(doc, self.root())? else {
+                return Ok(conflicts);
+            };
+            let Some((_, entity_id)) = doc.get(&table_id, Prop::Map(id.to_prop()))? else {
+                return Ok(conflicts);
+            };
+            for prop in doc.keys(&entity_id) {
+                let values = doc.get_all(&entity_id, prop.as_str())?;
+                if values.len() > 1 {
+                    let values = values
+                        .into_iter()
+                        .map(|(value, _)| value.into_owned())
+                        .collect();
+                    conflicts.insert(prop, values);
+                }
+            }
+
+            Ok(conflicts)
+        })
+    }
+
+    /// Returns a cursor which points to the element at `index` in the `field`
+    /// list of an object in the repository.
+    ///
+    /// Unlike an index, the cursor keeps pointing to the same element while
+    /// elements are inserted or removed before it, e.g. by concurrent edits.
+    /// Use [`resolve_cursor`] to find the current index of the element. The
+    /// field must be a list or a text object, e.g. an [`autosurgeon::Text`].
+    ///
+    /// Writing the whole object, e.g. by [`Transaction::update`], reconciles a
+    /// `Vec` field element by element by index, so that the cursor then points
+    /// to whichever element is at its previous index, rather than following
+    /// the element. Cursors into text objects follow their element, as do
+    /// cursors into lists which are changed with [`Transaction::list_push`]
+    /// and [`Transaction::list_remove`].
+    ///
+    /// Returns `None` if the object or the field does not exist.
+    ///
+    /// [`resolve_cursor`]: DefaultEntityRepository::resolve_cursor
+    /// [`Transaction::update`]: crate::Transaction::update
+    /// [`Transaction::list_push`]: crate::Transaction::list_push
+    /// [`Transaction::list_remove`]: crate::Transaction::list_remove
+    pub fn cursor(&self, id: Key<T>, field: &str, index: usize) -> Result<Option<Cursor>>
+    where
+        T: Mapped,
+    {
+        self.entity_manager()
+            .document()
+            .with_doc(|doc| cursor::get_cursor(doc, self.root(), id, field, index))
+    }
+
+    /// Resolves a cursor obtained from [`cursor`] to the current index of the
+    /// element it points to in the `field` list of an object in the
+    /// repository.
+    ///
+    /// Returns `None` if the object or the field does not exist.
+    ///
+    /// [`cursor`]: DefaultEntityRepository::cursor
+    pub fn resolve_cursor(&self, id: Key<T>, field: &str, cursor: &Cursor) -> Result<Option<usize>>
+    where
+        T: Mapped,
+    {
+        self.entity_manager()
+            .document()
+            .with_doc(|doc| cursor::resolve_cursor(doc, self.root(), id, field, cursor))
+    }
+
+    /// Exports all objects in the repository to `writer`, one object per line,
+    /// e.g. as newline-delimited JSON.
+    ///
+    /// Each object is hydrated and passed to `serialize` one at a time, which
+    /// must write it to the writer without a trailing newline. Unlike
+    /// [`find_all`], this never holds all objects in memory at once, so it is
+    /// suited for exporting large tables, e.g. for backups.
+    ///
+    /// [`find_all`]: EntityRepository::find_all
+    ///
+    /// # Performance
+    ///
+    /// Within the scope of this method, a read lock is held on the document,
+    /// including while writing to `writer`.
+    pub fn export_to<W, F>(&self, mut writer: W, mut serialize: F) -> Result<()>
+    where
+        T: Mapped + Hydrate,
+        W: Write,
+        F: FnMut(&mut W, &T) -> Result<()>,
+    {
+        self.entity_manager().document().with_doc(|doc| {
+            for_each_under(doc, self.root(), |entity: T| {
+                serialize(&mut writer, &entity)?;
+                writer.write_all(b"\n")?;
+                Ok(())
+            })
+        })?;
+        writer.flush()?;
+
+        Ok(())
+    }
+
+    /// Converts an object in the repository into JSON.
+    ///
+    /// See [`impls::to_json`] for how Automerge values are represented in
+    /// JSON.
+    ///
+    /// [`impls::to_json`]: crate::impls::to_json
+    #[cfg(feature = "serde_json")]
+    pub fn to_json(&self, id: Key<T>) -> Result<Option<serde_json::Value>>
+    where
+        T: Mapped,
+    {
+        self.entity_manager()
+            .document()
+            .with_doc(|doc| to_json_under(doc, self.root(), id))
+    }
+
+    /// Converts all objects in the repository into a JSON object keyed by the
+    /// key of each object.
+    ///
+    /// The result can be imported using [`Transaction::import_json`].
+    ///
+    /// See [`impls::to_json`] for how Automerge values are represented in
+    /// JSON.
+    ///
+    /// [`Transaction::import_json`]: crate::Transaction::import_json
+    /// [`impls::to_json`]: crate::impls::to_json
+    #[cfg(feature = "serde_json")]
+    pub fn export_json(&self) -> Result<serde_json::Value>
+    where
+        T: Mapped,
+    {
+        self.entity_manager()
+            .document()
+            .with_doc(|doc| table_to_json_under::<_, T>(doc, self.root()))
+    }
+
+    /// Returns a stream which yields an [`EntityEvent`] whenever an entity in
+    /// the repository is inserted, updated or removed.
+    ///
+    /// Events are computed by diffing the heads of the Automerge document
+    /// before and after each change, and only the changed entities are
+    /// hydrated. Inserted and updated entities reflect the state of the
+    /// document at the time the event is produced.
+    ///
+    /// The stream ends when changes to the document can no longer be observed,
+    /// e.g. after the repo has been stopped.
+    #[cfg(feature = "stream")]
+    pub fn watch(&self) -> impl Stream<Item = Result<EntityEvent<T>>>
+    where
+        T: Mapped + Hydrate,
+    {
+        let doc = self.entity_manager().document().clone();
+        let root = self.root().map(str::to_owned);
+        let heads = self.entity_manager().heads();
+        self.entity_manager()
+            .changes()
+            .scan(heads, move |before, event| {
+                let after = event.heads().to_vec();
+                let events = if event.contains_table::<T>() {
+                    doc.with_doc(|doc| watch::entity_events(doc, root.as_deref(), before, &after))
+                } else {
+                    Ok(Vec::new())
+                };
+                *before = after;
+                future::ready(Some(events))
+            })
+            .flat_map(|events| {
+                stream::iter(match events {
+                    Ok(events) => events.into_iter().map(Ok).collect(),
+                    Err(e) => vec![Err(e)],
+                })
+            })
+    }
+
+    /// Returns the [`EntityManager`] which is used by the repository.
+    fn entity_manager(&self) -> &EntityManager {
+        self.entity_manager.borrow()
+    }
+
+    fn root(&self) -> Option<&str> {
+        self.entity_manager().root()
+    }
+
+    /// Runs `f` with a read lock held on the document, or on a copy of the
+    /// table in which conflicts are resolved according to the conflict policy
+    /// of the repository, along with the key under which tables are stored.
+    fn with_resolved_doc<F, O>(&self, f: F) -> Result<O>
+    where
+        T: Mapped,
+        F: FnOnce(&Automerge, Option<&str>) -> Result<O>,
+    {
+        self.entity_manager()
+            .document()
+            .with_doc(|doc| match self.conflict_policy {
+                ConflictPolicy::Automerge => f(doc, self.root()),
+                ConflictPolicy::Greatest => f(&resolve_table::<T>(doc, self.root())?, None),
+            })
+    }
 }
@@ -2,21 +2,56 @@ use std::{
     any::TypeId,
     fmt::{self, Display},
     sync::Arc,
+    time::Duration,
 };
 
-use automerge::AutomergeError;
+use automerge::{AutomergeError, ScalarValue};
 use autosurgeon::{HydrateError, ReconcileError};
 use uuid::Uuid;
 
+use crate::ValidationError;
+
 /// An error in the Automerge ORM.
-#[derive(Debug)]
+///
+/// Errors are cheap to clone, so that a single error can be passed on to
+/// multiple consumers. Source errors which do not implement [`Clone`] are
+/// shared behind an [`Arc`].
+#[derive(Clone, Debug)]
+#[non_exhaustive]
 pub enum Error {
-    Automerge(AutomergeError),
+    Automerge(Arc<AutomergeError>),
     Autosurgeon(AutosurgeonError),
+    Custom(Arc<dyn std::error::Error + Send + Sync + 'static>),
+    #[cfg(feature = "crypto")]
+    EncryptionKeyMissing {
+        table_name: String,
+    },
+    /// Hydrating an entity failed, e.g. as its stored shape has diverged
+    /// from the entity type.
+    ///
+    /// `key` is the property which the entity is stored under, and `id` is
+    /// the key which it is parsed into, or `None` if the property is not a
+    /// valid key, so that malformed entries can be tracked down as well.
+    Hydrate {
+        table_name: String,
+        id: Option<Uuid>,
+        key: String,
+        source: Arc<HydrateError>,
+    },
     InvalidKey {
         key: String,
         source: uuid::Error,
     },
+    InvalidShortKey {
+        key: String,
+    },
+    Io(Arc<std::io::Error>),
+    #[cfg(feature = "serde_json")]
+    Json(Arc<serde_json::Error>),
+    KeyFieldUpdate {
+        table_name: String,
+        field: String,
+    },
     KeyMismatch {
         actual: Uuid,
         expected: Uuid,
@@ -31,17 +66,77 @@ pub enum Error {
         id: Uuid,
     },
     Observer(Arc<dyn std::error::Error + Send + Sync + 'static>),
+    /// Reconciling an entity, or one of its fields, failed while writing it
+    /// in a [`Transaction`].
+    ///
+    /// Write paths of a `Transaction`, e.g. [`Transaction::insert`] and
+    /// [`Transaction::update_field`], report reconcile errors as this variant
+    /// along with the table and key of the entity, rather than as
+    /// [`Error::Autosurgeon`] holding an [`AutosurgeonError::Reconcile`].
+    ///
+    /// [`Transaction`]: crate::Transaction
+    /// [`Transaction::insert`]: crate::Transaction::insert
+    /// [`Transaction::update_field`]: crate::Transaction::update_field
+    Reconcile {
+        table_name: String,
+        id: Uuid,
+        source: Arc<ReconcileError>,
+    },
+    ReservedTableName {
+        table_name: String,
+    },
+    RollbackUnsupported {
+        msg: String,
+    },
+    Timeout(Duration),
     TransactionAborted(Arc<dyn std::error::Error + Send + Sync + 'static>),
+    TransactionTooLarge {
+        max_ops: usize,
+        pending_ops: usize,
+    },
+    TypeMismatch {
+        table_name: String,
+        id: Uuid,
+        expected: String,
+        actual: String,
+    },
+    UniqueViolation {
+        table_name: String,
+        field: String,
+        value: ScalarValue,
+    },
     UnsupportedType {
         type_id: TypeId,
+        type_name: &'static str,
         msg: String,
     },
+    Validation {
+        table_name: String,
+        id: Uuid,
+        source: ValidationError,
+    },
+}
+
+/// An error in a transaction performed by [`EntityManager::try_transact`].
+///
+/// Unlike [`Error::TransactionAborted`], the error which aborted the
+/// transaction keeps its concrete type.
+///
+/// [`EntityManager::try_transact`]: crate::EntityManager::try_transact
+#[derive(Clone, Debug)]
+pub enum TransactError<E> {
+    /// The transaction was aborted by the provided error, and its changes were
+    /// rolled back.
+    Aborted(E),
+    /// The transaction failed in the Automerge ORM, e.g. when committing its
+    /// changes.
+    Orm(Error),
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum AutosurgeonError {
-    Hydrate(HydrateError),
-    Reconcile(ReconcileError),
+    Hydrate(Arc<HydrateError>),
+    Reconcile(Arc<ReconcileError>),
 }
 
 /// A specialized [`Result`] type for Automerge ORM errors.
@@ -54,13 +149,30 @@ impl std::error::Error for Error {
         match self {
             Error::Automerge(err) => Some(err),
             Error::Autosurgeon(err) => err.source(),
+            Error::Custom(err) => Some(err),
+            #[cfg(feature = "crypto")]
+            Error::EncryptionKeyMissing { .. } => None,
+            Error::Hydrate { source, .. } => Some(source),
             Error::InvalidKey { source, .. } => Some(source),
+            Error::InvalidShortKey { .. } => None,
+            Error::Io(err) => Some(err),
+            #[cfg(feature = "serde_json")]
+            Error::Json(err) => Some(err),
+            Error::KeyFieldUpdate { .. } => None,
             Error::KeyMismatch { .. } => None,
             Error::ObjectAlreadyExists { .. } => None,
             Error::ObjectDoesNotExist { .. } => None,
             Error::Observer(err) => Some(err),
+            Error::Reconcile { source, .. } => Some(source),
+            Error::ReservedTableName { .. } => None,
+            Error::RollbackUnsupported { .. } => None,
+            Error::Timeout(_) => None,
             Error::TransactionAborted(err) => Some(err),
+            Error::TransactionTooLarge { .. } => None,
+            Error::TypeMismatch { .. } => None,
+            Error::UniqueViolation { .. } => None,
             Error::UnsupportedType { .. } => None,
+            Error::Validation { source, .. } => Some(source),
         }
     }
 }
@@ -70,7 +182,28 @@ impl Display for Error {
         match self {
             Error::Automerge(err) => write!(f, "automerge: {err}"),
             Error::Autosurgeon(err) => write!(f, "autosurgeon: {err}"),
+            Error::Custom(err) => write!(f, "{err}"),
+            #[cfg(feature = "crypto")]
+            Error::EncryptionKeyMissing { table_name } => write!(
+                f,
+                "no encryption key configured for encrypted fields of table \"{table_name}\""
+            ),
+            Error::Hydrate {
+                table_name,
+                key,
+                source,
+                ..
+            } => write!(f, "failed to hydrate {table_name}[{key}]: {source}"),
             Error::InvalidKey { source, .. } => write!(f, "{source}"),
+            Error::InvalidShortKey { key } => write!(f, "invalid short key \"{key}\""),
+            Error::Io(err) => write!(f, "io: {err}"),
+            #[cfg(feature = "serde_json")]
+            Error::Json(err) => write!(f, "json: {err}"),
+            Error::KeyFieldUpdate { table_name, field } => write!(
+                f,
+                "field \"{field}\" holds the key of objects in table \"{table_name}\" and \
+                cannot be updated"
+            ),
             Error::KeyMismatch { msg, .. } => write!(f, "{msg}"),
             Error::ObjectAlreadyExists { table_name, id } => write!(
                 f,
@@ -81,15 +214,169 @@ impl Display for Error {
                 "object with id \"{id}\" does not exist in table \"{table_name}\""
             ),
             Error::Observer(err) => write!(f, "observer: {err}"),
+            Error::Reconcile {
+                table_name,
+                id,
+                source,
+            } => write!(f, "failed to reconcile {table_name}[{id}]: {source}"),
+            Error::ReservedTableName { table_name } => write!(
+                f,
+                "table name \"{table_name}\" is reserved for metadata of the Automerge ORM"
+            ),
+            Error::RollbackUnsupported { msg } => write!(f, "cannot roll back: {msg}"),
+            Error::Timeout(timeout) => write!(
+                f,
+                "timed out after {timeout:?} waiting for write lock on document"
+            ),
             Error::TransactionAborted(err) => write!(f, "transaction aborted: {err}"),
-            Error::UnsupportedType { msg, .. } => write!(f, "{msg}"),
+            Error::TransactionTooLarge {
+                max_ops,
+                pending_ops,
+            } => write!(
+                f,
+                "transaction has {pending_ops} pending operations, exceeding the limit of \
+                {max_ops}"
+            ),
+            Error::TypeMismatch {
+                table_name,
+                id,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "object with id \"{id}\" in table \"{table_name}\" has type \"{actual}\", \
+                expected \"{expected}\""
+            ),
+            Error::UniqueViolation {
+                table_name,
+                field,
+                value,
+            } => write!(
+                f,
+                "value {value} of field \"{field}\" is not unique in table \"{table_name}\""
+            ),
+            Error::UnsupportedType { type_name, msg, .. } => {
+                write!(f, "unsupported type `{type_name}`: {msg}")
+            },
+            Error::Validation {
+                table_name,
+                id,
+                source,
+            } => write!(
+                f,
+                "object with id \"{id}\" in table \"{table_name}\" is invalid: {source}"
+            ),
+        }
+    }
+}
+
+impl Error {
+    /// Creates a new `Error` from a custom error.
+    ///
+    /// This can be used to surface domain-specific errors through the Automerge
+    /// ORM, e.g. to abort a transaction.
+    pub fn custom<E>(err: E) -> Self
+    where
+        E: std::error::Error + Send + Sync + 'static,
+    {
+        Self::Custom(Arc::new(err))
+    }
+
+    /// Creates a new [`Error::UnsupportedType`] for the type `T`, capturing
+    /// its [`TypeId`] and its name.
+    pub fn unsupported_type<T>(msg: impl Into<String>) -> Self
+    where
+        T: ?Sized + 'static,
+    {
+        Self::UnsupportedType {
+            type_id: TypeId::of::<T>(),
+            type_name: std::any::type_name::<T>(),
+            msg: msg.into(),
+        }
+    }
+
+    /// Returns `true` if the error was caused by an object which does not
+    /// exist.
+    ///
+    /// Like the other accessors, this looks through an aborted transaction to
+    /// the Automerge ORM error which caused it.
+    pub fn is_not_found(&self) -> bool {
+        matches!(self.root(), Error::ObjectDoesNotExist { .. })
+    }
+
+    /// Returns `true` if the error was caused by an object which already
+    /// exists.
+    pub fn is_already_exists(&self) -> bool {
+        matches!(self.root(), Error::ObjectAlreadyExists { .. })
+    }
+
+    /// Returns the name of the table related to the error, if any.
+    pub fn table_name(&self) -> Option<&str> {
+        match self.root() {
+            #[cfg(feature = "crypto")]
+            Error::EncryptionKeyMissing { table_name } => Some(table_name),
+            Error::Hydrate { table_name, .. }
+            | Error::KeyFieldUpdate { table_name, .. }
+            | Error::ObjectAlreadyExists { table_name, .. }
+            | Error::ObjectDoesNotExist { table_name, .. }
+            | Error::Reconcile { table_name, .. }
+            | Error::ReservedTableName { table_name }
+            | Error::TypeMismatch { table_name, .. }
+            | Error::UniqueViolation { table_name, .. }
+            | Error::Validation { table_name, .. } => Some(table_name),
+            _ => None,
+        }
+    }
+
+    /// Returns the key of the object related to the error, if any.
+    pub fn key(&self) -> Option<Uuid> {
+        match self.root() {
+            Error::Hydrate { id, .. } => *id,
+            Error::ObjectAlreadyExists { id, .. }
+            | Error::ObjectDoesNotExist { id, .. }
+            | Error::Reconcile { id, .. }
+            | Error::TypeMismatch { id, .. }
+            | Error::Validation { id, .. } => Some(*id),
+            _ => None,
+        }
+    }
+
+    /// Returns the name of the type related to the error, if any.
+    ///
+    /// The name is the one returned by [`std::any::type_name`], so it is only
+    /// meant for diagnostics.
+    pub fn type_name(&self) -> Option<&str> {
+        match self.root() {
+            Error::UnsupportedType { type_name, .. } => Some(type_name),
+            _ => None,
+        }
+    }
+
+    /// Returns the Automerge ORM error which caused a transaction to be
+    /// aborted, or `self` otherwise.
+    fn root(&self) -> &Error {
+        match self {
+            Error::TransactionAborted(err) => err.downcast_ref::<Error>().map_or(self, Error::root),
+            _ => self,
+        }
+    }
+}
+
+impl<E> From<TransactError<E>> for Error
+where
+    E: std::error::Error + Send + Sync + 'static,
+{
+    fn from(err: TransactError<E>) -> Self {
+        match err {
+            TransactError::Aborted(err) => Self::TransactionAborted(Arc::new(err)),
+            TransactError::Orm(err) => err,
         }
     }
 }
 
 impl From<AutomergeError> for Error {
     fn from(err: AutomergeError) -> Self {
-        Self::Automerge(err)
+        Self::Automerge(Arc::new(err))
     }
 }
 
@@ -99,6 +386,19 @@ impl From<AutosurgeonError> for Error {
     }
 }
 
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Self::Io(Arc::new(err))
+    }
+}
+
+#[cfg(feature = "serde_json")]
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Self::Json(Arc::new(err))
+    }
+}
+
 impl From<HydrateError> for Error {
     fn from(err: HydrateError) -> Self {
         Self::Autosurgeon(err.into())
@@ -111,6 +411,36 @@ impl From<ReconcileError> for Error {
     }
 }
 
+impl<E> std::error::Error for TransactError<E>
+where
+    E: std::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TransactError::Aborted(err) => Some(err),
+            TransactError::Orm(err) => Some(err),
+        }
+    }
+}
+
+impl<E> Display for TransactError<E>
+where
+    E: Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TransactError::Aborted(err) => write!(f, "transaction aborted: {err}"),
+            TransactError::Orm(err) => Display::fmt(err, f),
+        }
+    }
+}
+
+impl<E> From<Error> for TransactError<E> {
+    fn from(err: Error) -> Self {
+        Self::Orm(err)
+    }
+}
+
 impl std::error::Error for AutosurgeonError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
@@ -131,12 +461,12 @@ impl Display for AutosurgeonError {
 
 impl From<HydrateError> for AutosurgeonError {
     fn from(err: HydrateError) -> Self {
-        AutosurgeonError::Hydrate(err)
+        AutosurgeonError::Hydrate(Arc::new(err))
     }
 }
 
 impl From<ReconcileError> for AutosurgeonError {
     fn from(err: ReconcileError) -> Self {
-        AutosurgeonError::Reconcile(err)
+        AutosurgeonError::Reconcile(Arc::new(err))
     }
 }
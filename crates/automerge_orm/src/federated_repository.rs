@@ -0,0 +1,96 @@
+use std::{collections::BTreeMap, marker::PhantomData, sync::Arc};
+
+use automerge_repo::DocumentId;
+use autosurgeon::Hydrate;
+
+use crate::{
+    impls::{find_all_under, find_under},
+    EntityManager, Key, Mapped, Result,
+};
+
+/// A repository where instances of an entity can be retrieved across multiple
+/// Automerge documents.
+///
+/// Each document is queried through its own [`EntityManager`], in the order in
+/// which the entity managers were provided. Every object is returned together
+/// with the [`DocumentId`] of the document it was found in.
+#[derive(Clone, Debug)]
+pub struct FederatedRepository<T> {
+    entity_managers: Vec<(DocumentId, Arc<EntityManager>)>,
+    phantom: PhantomData<fn(T) -> T>,
+}
+
+impl<T> FederatedRepository<T> {
+    /// Creates a new `FederatedRepository` which uses the [`EntityManager`]s,
+    /// each together with the [`DocumentId`] which objects found through it
+    /// are tagged with.
+    ///
+    /// For an entity manager of a document which is managed by a repo, this is
+    /// usually the id of its document, as returned by
+    /// [`DocHandle::document_id`]. Standalone documents, e.g. as created by
+    /// [`EntityManager::from_doc`], can be given any id.
+    ///
+    /// [`DocHandle::document_id`]: automerge_repo::DocHandle::document_id
+    pub fn new(entity_managers: Vec<(DocumentId, Arc<EntityManager>)>) -> Self {
+        Self {
+            entity_managers,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<T> FederatedRepository<T>
+where
+    T: Mapped + Hydrate,
+{
+    /// Finds an object by its key / identifier, together with the id of the
+    /// document it was found in.
+    ///
+    /// The documents are queried in order, and the first object found is
+    /// returned.
+    ///
+    /// # Performance
+    ///
+    /// Within the scope of this method, a read lock is held on each document in
+    /// turn.
+    pub fn find(&self, id: Key<T>) -> Result<Option<(DocumentId, T)>> {
+        for (document_id, entity_manager) in &self.entity_managers {
+            let entity = entity_manager
+                .document()
+                .with_doc(|doc| find_under(doc, entity_manager.root(), id))?;
+            if let Some(entity) = entity {
+                return Ok(Some((document_id.clone(), entity)));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Finds all objects in all documents, together with the id of the
+    /// document each object was found in.
+    ///
+    /// If objects with the same key are stored in multiple documents, the
+    /// object found in the first document is returned, as with [`find`].
+    ///
+    /// [`find`]: FederatedRepository::find
+    ///
+    /// # Performance
+    ///
+    /// Within the scope of this method, a read lock is held on each document in
+    /// turn.
+    pub fn find_all(&self) -> Result<BTreeMap<String, (DocumentId, T)>> {
+        let mut entities = BTreeMap::new();
+        for (document_id, entity_manager) in &self.entity_managers {
+            let found: BTreeMap<String, T> = entity_manager
+                .document()
+                .with_doc(|doc| find_all_under(doc, entity_manager.root()))?;
+            for (key, entity) in found {
+                entities
+                    .entry(key)
+                    .or_insert_with(|| (document_id.clone(), entity));
+            }
+        }
+
+        Ok(entities)
+    }
+}
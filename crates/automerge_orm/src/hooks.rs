@@ -0,0 +1,71 @@
+use crate::Result;
+
+/// Lifecycle hooks of an entity, which are invoked around write operations.
+///
+/// [`Transaction::insert`] invokes [`before_insert`] and
+/// [`after_insert_write`], and [`Transaction::update`] invokes
+/// [`before_update`] and [`after_update_write`]. [`Transaction::upsert`]
+/// invokes either pair depending on whether the entity already exists.
+///
+/// A `before_*` hook runs before the entity is validated and written to the
+/// document, and may return a modified copy of the entity to be written
+/// instead, e.g. to fill in denormalized or derived fields. An `after_*_write`
+/// hook runs after the entity has been written to the transaction, with the
+/// value which was written, but before the transaction is committed. The
+/// write is therefore not yet visible outside of the transaction, and is
+/// discarded if the transaction is aborted later on. A hook which returns an
+/// error aborts the write operation, and in turn the transaction, e.g. by
+/// returning [`Error::custom`].
+///
+/// The [`derive@Entity`] derive implements this trait with no-op hooks,
+/// unless the type is annotated with `#[automerge_orm(hooks)]`, in which case
+/// the trait must be implemented manually. All hooks are no-ops by default, so
+/// only the hooks of interest need to be implemented.
+///
+/// Hooks are opt-in, so that entities which implement [`Mapped`] manually do
+/// not have to implement this trait. Such entities only invoke hooks if
+/// [`Mapped::lifecycle`] returns a [`Lifecycle`] created with
+/// [`Lifecycle::hooked`], which the derive does.
+///
+/// [`Transaction::insert`]: crate::Transaction::insert
+/// [`Transaction::update`]: crate::Transaction::update
+/// [`Transaction::upsert`]: crate::Transaction::upsert
+/// [`before_insert`]: Hooks::before_insert
+/// [`after_insert_write`]: Hooks::after_insert_write
+/// [`before_update`]: Hooks::before_update
+/// [`after_update_write`]: Hooks::after_update_write
+/// [`Error::custom`]: crate::Error::custom
+/// [`derive@Entity`]: crate::Entity
+/// [`Mapped`]: crate::Mapped
+/// [`Mapped::lifecycle`]: crate::Mapped::lifecycle
+/// [`Lifecycle`]: crate::Lifecycle
+/// [`Lifecycle::hooked`]: crate::Lifecycle::hooked
+pub trait Hooks: Sized {
+    /// Invoked before the entity is inserted.
+    ///
+    /// Returns a modified copy of the entity to be inserted instead, or `None`
+    /// to insert the entity as is.
+    fn before_insert(&self) -> Result<Option<Self>> {
+        Ok(None)
+    }
+
+    /// Invoked after the entity has been inserted within the transaction,
+    /// before the transaction is committed.
+    fn after_insert_write(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Invoked before the entity is updated.
+    ///
+    /// Returns a modified copy of the entity to be updated instead, or `None`
+    /// to update the entity as is.
+    fn before_update(&self) -> Result<Option<Self>> {
+        Ok(None)
+    }
+
+    /// Invoked after the entity has been updated within the transaction,
+    /// before the transaction is committed.
+    fn after_update_write(&self) -> Result<()> {
+        Ok(())
+    }
+}
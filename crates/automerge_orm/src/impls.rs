@@ -3,26 +3,47 @@
 //! These functions can be used in custom implementations of traits defined in
 //! this crate.
 
-use std::collections::BTreeMap;
+use std::{collections::BTreeMap, sync::Arc};
 
-use automerge::{AutomergeError, ObjId, ObjType, Prop, Value};
+use automerge::{Automerge, AutomergeError, ChangeHash, ObjId, ObjType, Prop, Value};
 use autosurgeon::{hydrate_prop, Doc, Hydrate, ReadDoc};
 
-use crate::{Key, Mapped, Result};
+#[cfg(feature = "serde_json")]
+use crate::json;
+use crate::{mapped, Error, Key, Mapped, Result};
 
 /// Finds an entity by key from the Automerge document.
+///
+/// Returns `None` if the table or the entity does not exist, and an error if
+/// either exists but is not a map.
 pub fn find<D, T>(doc: &D, id: Key<T>) -> Result<Option<T>>
 where
     D: ReadDoc,
     T: Mapped + Hydrate,
 {
-    let Some(table_id) = get_table::<D, T>(doc)? else {
+    find_under::<D, T>(doc, None, id)
+}
+
+/// Same as [`find`], but for tables which are stored in the map under `root`
+/// rather than in the root of the document, as configured by
+/// [`EntityManager::with_root`].
+///
+/// [`EntityManager::with_root`]: crate::EntityManager::with_root
+pub fn find_under<D, T>(doc: &D, root: Option<&str>, id: Key<T>) -> Result<Option<T>>
+where
+    D: ReadDoc,
+    T: Mapped + Hydrate,
+{
+    let Some(table_id) = get_table_under::<D, T>(doc, root)? else {
         return Ok(None);
     };
-    if doc.get(&table_id, Prop::Map(id.to_string()))?.is_none() {
+    let key = id.to_prop();
+    let Some((value, entity_id)) = doc.get(&table_id, Prop::Map(key.clone()))? else {
         return Ok(None);
-    }
-    let entity = hydrate_prop(doc, table_id, &*id.to_string())?;
+    };
+    expect_map(&value)?;
+    check_type_tag::<D, T>(doc, &entity_id, &key)?;
+    let entity = hydrate_entity::<D, T>(doc, &table_id, &key)?;
 
     Ok(Some(entity))
 }
@@ -33,47 +54,596 @@ where
     D: ReadDoc,
     T: Mapped + Hydrate,
 {
-    if get_table::<D, T>(doc)?.is_none() {
+    find_all_under::<D, T>(doc, None)
+}
+
+/// Same as [`find_all`], but for tables which are stored in the map under `root`
+/// rather than in the root of the document, as configured by
+/// [`EntityManager::with_root`].
+///
+/// [`EntityManager::with_root`]: crate::EntityManager::with_root
+pub fn find_all_under<D, T>(doc: &D, root: Option<&str>) -> Result<BTreeMap<String, T>>
+where
+    D: ReadDoc,
+    T: Mapped + Hydrate,
+{
+    let Some(table_id) = get_table_under::<D, T>(doc, root)? else {
         return Ok(BTreeMap::new());
     };
-    let entities = hydrate_prop(doc, automerge::ROOT, &*<T as Mapped>::table_name())?;
+    let mut entities = BTreeMap::new();
+    for (key, value, entity_id) in doc.map_range(&table_id, ..) {
+        expect_map(&value)?;
+        check_type_tag::<D, T>(doc, &entity_id, key)?;
+        entities.insert(key.to_owned(), hydrate_entity(doc, &table_id, key)?);
+    }
+
+    Ok(entities)
+}
+
+/// Finds an entity by key from the Automerge document as it was at `heads`.
+///
+/// The historical state of the document is materialized by forking it at
+/// `heads`, so the cost of this operation is proportional to the size of the
+/// document history rather than the size of the entity.
+pub fn find_at<T>(doc: &Automerge, heads: &[ChangeHash], id: Key<T>) -> Result<Option<T>>
+where
+    T: Mapped + Hydrate,
+{
+    find_at_under::<T>(doc, None, heads, id)
+}
+
+/// Same as [`find_at`], but for tables which are stored in the map under `root`
+/// rather than in the root of the document, as configured by
+/// [`EntityManager::with_root`].
+///
+/// [`EntityManager::with_root`]: crate::EntityManager::with_root
+pub fn find_at_under<T>(
+    doc: &Automerge,
+    root: Option<&str>,
+    heads: &[ChangeHash],
+    id: Key<T>,
+) -> Result<Option<T>>
+where
+    T: Mapped + Hydrate,
+{
+    find_under(&doc.fork_at(heads)?, root, id)
+}
+
+/// Finds all entities of a specific type from the Automerge document as it was
+/// at `heads`.
+///
+/// See [`find_at`] for the cost of reading historical state.
+pub fn find_all_at<T>(doc: &Automerge, heads: &[ChangeHash]) -> Result<BTreeMap<String, T>>
+where
+    T: Mapped + Hydrate,
+{
+    find_all_at_under::<T>(doc, None, heads)
+}
+
+/// Same as [`find_all_at`], but for tables which are stored in the map under `root`
+/// rather than in the root of the document, as configured by
+/// [`EntityManager::with_root`].
+///
+/// [`EntityManager::with_root`]: crate::EntityManager::with_root
+pub fn find_all_at_under<T>(
+    doc: &Automerge,
+    root: Option<&str>,
+    heads: &[ChangeHash],
+) -> Result<BTreeMap<String, T>>
+where
+    T: Mapped + Hydrate,
+{
+    find_all_under(&doc.fork_at(heads)?, root)
+}
+
+/// Finds the entity of a specific type with the smallest key from the
+/// Automerge document.
+pub fn find_first<D, T>(doc: &D) -> Result<Option<T>>
+where
+    D: ReadDoc,
+    T: Mapped + Hydrate,
+{
+    find_first_under::<D, T>(doc, None)
+}
+
+/// Same as [`find_first`], but for tables which are stored in the map under `root`
+/// rather than in the root of the document, as configured by
+/// [`EntityManager::with_root`].
+///
+/// [`EntityManager::with_root`]: crate::EntityManager::with_root
+pub fn find_first_under<D, T>(doc: &D, root: Option<&str>) -> Result<Option<T>>
+where
+    D: ReadDoc,
+    T: Mapped + Hydrate,
+{
+    let Some(table_id) = get_table_under::<D, T>(doc, root)? else {
+        return Ok(None);
+    };
+    let Some((key, value, entity_id)) = doc.map_range(&table_id, ..).next() else {
+        return Ok(None);
+    };
+    expect_map(&value)?;
+    check_type_tag::<D, T>(doc, &entity_id, key)?;
+    let entity = hydrate_entity(doc, &table_id, key)?;
+
+    Ok(Some(entity))
+}
+
+/// Finds the keys of all entities of a specific type from the Automerge
+/// document, without hydrating the entities.
+pub fn find_all_keys<D, T>(doc: &D) -> Result<Vec<Key<T>>>
+where
+    D: ReadDoc,
+    T: Mapped,
+{
+    find_all_keys_under::<D, T>(doc, None)
+}
+
+/// Same as [`find_all_keys`], but for tables which are stored in the map under `root`
+/// rather than in the root of the document, as configured by
+/// [`EntityManager::with_root`].
+///
+/// [`EntityManager::with_root`]: crate::EntityManager::with_root
+pub fn find_all_keys_under<D, T>(doc: &D, root: Option<&str>) -> Result<Vec<Key<T>>>
+where
+    D: ReadDoc,
+    T: Mapped,
+{
+    let Some(table_id) = get_table_under::<D, T>(doc, root)? else {
+        return Ok(Vec::new());
+    };
+
+    doc.map_range(&table_id, ..)
+        .map(|(key, ..)| Key::from_prop(key))
+        .collect()
+}
+
+/// Lists all entities of a specific type from the Automerge document, ordered
+/// by key.
+pub fn list_all<D, T>(doc: &D) -> Result<Vec<T>>
+where
+    D: ReadDoc,
+    T: Mapped + Hydrate,
+{
+    list_all_under::<D, T>(doc, None)
+}
+
+/// Same as [`list_all`], but for tables which are stored in the map under `root`
+/// rather than in the root of the document, as configured by
+/// [`EntityManager::with_root`].
+///
+/// [`EntityManager::with_root`]: crate::EntityManager::with_root
+pub fn list_all_under<D, T>(doc: &D, root: Option<&str>) -> Result<Vec<T>>
+where
+    D: ReadDoc,
+    T: Mapped + Hydrate,
+{
+    let Some(table_id) = get_table_under::<D, T>(doc, root)? else {
+        return Ok(Vec::new());
+    };
+    let mut entities = Vec::with_capacity(doc.length(&table_id));
+    for (key, value, entity_id) in doc.map_range(&table_id, ..) {
+        expect_map(&value)?;
+        check_type_tag::<D, T>(doc, &entity_id, key)?;
+        entities.push(hydrate_entity(doc, &table_id, key)?);
+    }
+
+    Ok(entities)
+}
+
+/// Hydrates the entities of a specific type from the Automerge document one at
+/// a time, ordered by key, and passes each entity to `f`.
+///
+/// Unlike [`list_all`], this never holds all entities in memory at once.
+/// Iteration stops at the first error returned by `f`.
+pub fn for_each<D, T, F>(doc: &D, f: F) -> Result<()>
+where
+    D: ReadDoc,
+    T: Mapped + Hydrate,
+    F: FnMut(T) -> Result<()>,
+{
+    for_each_under::<D, T, F>(doc, None, f)
+}
+
+/// Same as [`for_each`], but for tables which are stored in the map under `root`
+/// rather than in the root of the document, as configured by
+/// [`EntityManager::with_root`].
+///
+/// [`EntityManager::with_root`]: crate::EntityManager::with_root
+pub fn for_each_under<D, T, F>(doc: &D, root: Option<&str>, mut f: F) -> Result<()>
+where
+    D: ReadDoc,
+    T: Mapped + Hydrate,
+    F: FnMut(T) -> Result<()>,
+{
+    let Some(table_id) = get_table_under::<D, T>(doc, root)? else {
+        return Ok(());
+    };
+    for (key, value, entity_id) in doc.map_range(&table_id, ..) {
+        expect_map(&value)?;
+        check_type_tag::<D, T>(doc, &entity_id, key)?;
+        f(hydrate_entity(doc, &table_id, key)?)?;
+    }
+
+    Ok(())
+}
+
+/// Finds all entities of a specific type which reference the `parent` entity
+/// via the `foreign_field` field from the Automerge document.
+///
+/// The foreign key field must store the key either in the same representation
+/// as [`Key`] itself, i.e. as a [`ScalarValue::Bytes`] scalar, which is the
+/// case for [`Uuid`] fields reconciled by `autosurgeon`, or as a hyphenated
+/// UUID string, as with [`key_string`].
+///
+/// This performs a full scan of the table.
+///
+/// [`ScalarValue::Bytes`]: automerge::ScalarValue::Bytes
+/// [`Uuid`]: uuid::Uuid
+/// [`key_string`]: crate::key_string
+pub fn find_all_by<D, T, R>(doc: &D, foreign_field: &str, parent: Key<R>) -> Result<Vec<T>>
+where
+    D: ReadDoc,
+    T: Mapped + Hydrate,
+{
+    find_all_by_under::<D, T, R>(doc, None, foreign_field, parent)
+}
+
+/// Same as [`find_all_by`], but for tables which are stored in the map under `root`
+/// rather than in the root of the document, as configured by
+/// [`EntityManager::with_root`].
+///
+/// [`EntityManager::with_root`]: crate::EntityManager::with_root
+pub fn find_all_by_under<D, T, R>(
+    doc: &D,
+    root: Option<&str>,
+    foreign_field: &str,
+    parent: Key<R>,
+) -> Result<Vec<T>>
+where
+    D: ReadDoc,
+    T: Mapped + Hydrate,
+{
+    let Some(table_id) = get_table_under::<D, T>(doc, root)? else {
+        return Ok(Vec::new());
+    };
+    let foreign_field = <T as Mapped>::prop_name(foreign_field);
+    let mut entities = Vec::new();
+    for (key, value, entity_id) in doc.map_range(&table_id, ..) {
+        expect_map(&value)?;
+        let Some((Value::Scalar(value), _)) =
+            doc.get(&entity_id, Prop::Map(foreign_field.to_owned()))?
+        else {
+            continue;
+        };
+        if Key::<R>::try_from(value.into_owned()).ok() == Some(parent) {
+            check_type_tag::<D, T>(doc, &entity_id, key)?;
+            entities.push(hydrate_entity(doc, &table_id, key)?);
+        }
+    }
 
     Ok(entities)
 }
 
+/// Converts an entity from the Automerge document into JSON.
+///
+/// Automerge values are represented in JSON as follows:
+///
+/// * Maps are converted into JSON objects, and lists into JSON arrays.
+/// * Text objects and strings are converted into JSON strings.
+/// * Bytes which hold the key of the entity, e.g. its key field, are converted
+///   into hyphenated UUID strings. Other bytes, including foreign keys, are
+///   converted into JSON arrays of numbers, as they cannot be told apart from
+///   other bytes of the same length.
+/// * Counters are converted into JSON numbers holding the current value of the
+///   counter.
+/// * Timestamps are converted into JSON numbers holding the number of
+///   milliseconds since the Unix epoch.
+#[cfg(feature = "serde_json")]
+pub fn to_json<D, T>(doc: &D, id: Key<T>) -> Result<Option<serde_json::Value>>
+where
+    D: ReadDoc,
+    T: Mapped,
+{
+    to_json_under::<D, T>(doc, None, id)
+}
+
+/// Same as [`to_json`], but for tables which are stored in the map under `root`
+/// rather than in the root of the document, as configured by
+/// [`EntityManager::with_root`].
+///
+/// [`EntityManager::with_root`]: crate::EntityManager::with_root
+#[cfg(feature = "serde_json")]
+pub fn to_json_under<D, T>(
+    doc: &D,
+    root: Option<&str>,
+    id: Key<T>,
+) -> Result<Option<serde_json::Value>>
+where
+    D: ReadDoc,
+    T: Mapped,
+{
+    let Some(table_id) = get_table_under::<D, T>(doc, root)? else {
+        return Ok(None);
+    };
+    let Some((value, entity_id)) = doc.get(&table_id, Prop::Map(id.to_prop()))? else {
+        return Ok(None);
+    };
+    let entity = json::value_to_json(doc, value, &entity_id, Some(id.into()))?;
+
+    Ok(Some(entity))
+}
+
+/// Converts all entities of a specific type from the Automerge document into
+/// a JSON object keyed by the key of each entity.
+///
+/// See [`to_json`] for how Automerge values are represented in JSON.
+#[cfg(feature = "serde_json")]
+pub fn table_to_json<D, T>(doc: &D) -> Result<serde_json::Value>
+where
+    D: ReadDoc,
+    T: Mapped,
+{
+    table_to_json_under::<D, T>(doc, None)
+}
+
+/// Same as [`table_to_json`], but for tables which are stored in the map under `root`
+/// rather than in the root of the document, as configured by
+/// [`EntityManager::with_root`].
+///
+/// [`EntityManager::with_root`]: crate::EntityManager::with_root
+#[cfg(feature = "serde_json")]
+pub fn table_to_json_under<D, T>(doc: &D, root: Option<&str>) -> Result<serde_json::Value>
+where
+    D: ReadDoc,
+    T: Mapped,
+{
+    let Some(table_id) = get_table_under::<D, T>(doc, root)? else {
+        return Ok(serde_json::Value::Object(serde_json::Map::new()));
+    };
+    let mut entities = serde_json::Map::new();
+    for (prop, value, entity_id) in doc.map_range(&table_id, ..) {
+        let key = Key::<T>::from_prop(prop).ok().map(uuid::Uuid::from);
+        let entity = json::value_to_json(doc, value, &entity_id, key)?;
+        entities.insert(prop.to_owned(), entity);
+    }
+
+    Ok(serde_json::Value::Object(entities))
+}
+
 /// Returns the Automerge object id of a table in the Automerge document.
 pub fn get_table<D, T>(doc: &D) -> Result<Option<ObjId>>
 where
     D: ReadDoc,
     T: Mapped,
 {
-    let Some((value, table_id)) =
-        doc.get(&automerge::ROOT, Prop::Map(<T as Mapped>::table_name()))?
-    else {
+    get_table_under::<D, T>(doc, None)
+}
+
+/// Same as [`get_table`], but for tables which are stored in the map under `root`
+/// rather than in the root of the document, as configured by
+/// [`EntityManager::with_root`].
+///
+/// [`EntityManager::with_root`]: crate::EntityManager::with_root
+pub fn get_table_under<D, T>(doc: &D, root: Option<&str>) -> Result<Option<ObjId>>
+where
+    D: ReadDoc,
+    T: Mapped,
+{
+    let Some(root_id) = get_root(doc, root)? else {
         return Ok(None);
     };
-    let Value::Object(ObjType::Map) = value else {
-        Err(AutomergeError::InvalidValueType {
-            expected: format!("{}", Value::Object(ObjType::Map)),
-            unexpected: format!("{value}"),
-        })?
+    let Some((value, table_id)) = doc.get(&root_id, <T as Mapped>::table_name())? else {
+        return Ok(None);
     };
+    expect_map(&value)?;
 
     Ok(Some(table_id))
 }
 
+/// Returns the Automerge object id of an entity in the Automerge document.
+pub fn get_entity<D, T>(doc: &D, id: Key<T>) -> Result<Option<ObjId>>
+where
+    D: ReadDoc,
+    T: Mapped,
+{
+    get_entity_under::<D, T>(doc, None, id)
+}
+
+/// Same as [`get_entity`], but for tables which are stored in the map under `root`
+/// rather than in the root of the document, as configured by
+/// [`EntityManager::with_root`].
+///
+/// [`EntityManager::with_root`]: crate::EntityManager::with_root
+pub fn get_entity_under<D, T>(doc: &D, root: Option<&str>, id: Key<T>) -> Result<Option<ObjId>>
+where
+    D: ReadDoc,
+    T: Mapped,
+{
+    let Some(table_id) = get_table_under::<D, T>(doc, root)? else {
+        return Ok(None);
+    };
+    let Some((value, entity_id)) = doc.get(&table_id, Prop::Map(id.to_prop()))? else {
+        return Ok(None);
+    };
+    expect_map(&value)?;
+
+    Ok(Some(entity_id))
+}
+
+/// Returns the raw Automerge value of the `field` property of an entity in the
+/// Automerge document, together with its Automerge object id.
+///
+/// This is a low-level escape hatch, e.g. to read properties which are not
+/// modelled by the entity type. Returns `None` if the table, the entity or the
+/// property does not exist. The name of the field is mapped to the name of
+/// its property using [`Mapped::prop_name`].
+pub fn get_raw<'a, D, T>(doc: &'a D, id: Key<T>, field: &str) -> Result<Option<(Value<'a>, ObjId)>>
+where
+    D: ReadDoc,
+    T: Mapped,
+{
+    get_raw_under::<D, T>(doc, None, id, field)
+}
+
+/// Same as [`get_raw`], but for tables which are stored in the map under `root`
+/// rather than in the root of the document, as configured by
+/// [`EntityManager::with_root`].
+///
+/// [`EntityManager::with_root`]: crate::EntityManager::with_root
+pub fn get_raw_under<'a, D, T>(
+    doc: &'a D,
+    root: Option<&str>,
+    id: Key<T>,
+    field: &str,
+) -> Result<Option<(Value<'a>, ObjId)>>
+where
+    D: ReadDoc,
+    T: Mapped,
+{
+    let Some(entity_id) = get_entity_under::<D, T>(doc, root, id)? else {
+        return Ok(None);
+    };
+    let prop = Prop::Map(<T as Mapped>::prop_name(field).to_owned());
+
+    Ok(doc.get(&entity_id, prop)?)
+}
+
 /// Creates a table in the Automerge document, and returns the Automerge object
 /// id of the table.
+///
+/// If the table already exists, the Automerge object id of the existing table
+/// is returned instead, so that the entities in the table are never replaced.
+///
+/// Returns [`Error::ReservedTableName`] if the name of the table starts with
+/// the prefix reserved for metadata of the Automerge ORM, `__automerge_orm`.
 pub fn create_table<D, T>(doc: &mut D) -> Result<ObjId>
 where
     D: Doc,
     T: Mapped,
 {
-    let table_id = doc.put_object(
-        automerge::ROOT,
-        Prop::Map(<T as Mapped>::table_name()),
-        ObjType::Map,
-    )?;
+    create_table_under::<D, T>(doc, None)
+}
+
+/// Same as [`create_table`], but for tables which are stored in the map under
+/// `root` rather than in the root of the document, as configured by
+/// [`EntityManager::with_root`]. The map is created if it does not exist.
+///
+/// [`EntityManager::with_root`]: crate::EntityManager::with_root
+pub fn create_table_under<D, T>(doc: &mut D, root: Option<&str>) -> Result<ObjId>
+where
+    D: Doc,
+    T: Mapped,
+{
+    let table_name = <T as Mapped>::table_name();
+    if table_name.starts_with(mapped::RESERVED_PREFIX) {
+        return Err(Error::ReservedTableName {
+            table_name: table_name.to_owned(),
+        });
+    }
+    if let Some(table_id) = get_table_under::<D, T>(doc, root)? {
+        return Ok(table_id);
+    }
+    let root_id = get_or_create_root(doc, root)?;
+    let table_id = doc.put_object(root_id, table_name, ObjType::Map)?;
 
     Ok(table_id)
 }
+
+/// Returns the Automerge object id of the map under which tables are stored,
+/// i.e. the map stored under `root` in the root of the Automerge document, or
+/// the root of the document itself if `root` is `None`.
+///
+/// Returns `None` if `root` does not exist.
+pub(crate) fn get_root<D>(doc: &D, root: Option<&str>) -> Result<Option<ObjId>>
+where
+    D: ReadDoc,
+{
+    let Some(root) = root else {
+        return Ok(Some(automerge::ROOT));
+    };
+    let Some((value, root_id)) = doc.get(&automerge::ROOT, root)? else {
+        return Ok(None);
+    };
+    expect_map(&value)?;
+
+    Ok(Some(root_id))
+}
+
+/// Returns the Automerge object id of the map under which tables are stored,
+/// as with [`get_root`], creating the map under `root` if it does not exist.
+pub(crate) fn get_or_create_root<D>(doc: &mut D, root: Option<&str>) -> Result<ObjId>
+where
+    D: Doc,
+{
+    let root_id = match (get_root(doc, root)?, root) {
+        (Some(root_id), _) => root_id,
+        (None, Some(root)) => doc.put_object(automerge::ROOT, root, ObjType::Map)?,
+        (None, None) => automerge::ROOT,
+    };
+
+    Ok(root_id)
+}
+
+/// Returns an error if the entity identified by `entity_id` carries a type tag
+/// which differs from the type tag of `T`.
+///
+/// See [`Mapped::type_tag`] for more.
+fn check_type_tag<D, T>(doc: &D, entity_id: &ObjId, key: &str) -> Result<()>
+where
+    D: ReadDoc,
+    T: Mapped,
+{
+    let Some(expected) = <T as Mapped>::type_tag() else {
+        return Ok(());
+    };
+    let Some((actual, _)) = doc.get(entity_id, mapped::TYPE_TAG_KEY)? else {
+        return Ok(());
+    };
+    if actual.to_str() == Some(expected) {
+        return Ok(());
+    }
+
+    Err(Error::TypeMismatch {
+        table_name: <T as Mapped>::table_name().to_owned(),
+        id: Key::<T>::from_prop(key)?.into(),
+        expected: expected.to_owned(),
+        actual: actual
+            .to_str()
+            .map_or_else(|| actual.to_string(), str::to_owned),
+    })
+}
+
+/// Hydrates the entity stored under `key` in the table identified by
+/// `table_id`.
+///
+/// Hydration errors are reported as [`Error::Hydrate`], together with the
+/// name of the table and the key of the entity, so that entities whose stored
+/// shape has diverged from the entity type can be tracked down. The property
+/// `key` is reported as is, even if it is not a valid key.
+fn hydrate_entity<D, T>(doc: &D, table_id: &ObjId, key: &str) -> Result<T>
+where
+    D: ReadDoc,
+    T: Mapped + Hydrate,
+{
+    #[cfg(feature = "crypto")]
+    crate::encrypted::check_key::<T>()?;
+    hydrate_prop(doc, table_id, key).map_err(|err| Error::Hydrate {
+        table_name: <T as Mapped>::table_name().to_owned(),
+        id: Key::<T>::from_prop(key).ok().map(uuid::Uuid::from),
+        key: key.to_owned(),
+        source: Arc::new(err),
+    })
+}
+
+/// Returns an error if the value is not a map, i.e. the document is corrupt.
+pub(crate) fn expect_map(value: &Value<'_>) -> Result<()> {
+    let Value::Object(ObjType::Map) = value else {
+        Err(AutomergeError::InvalidValueType {
+            expected: format!("{}", Value::Object(ObjType::Map)),
+            unexpected: format!("{value}"),
+        })?
+    };
+
+    Ok(())
+}
@@ -0,0 +1,63 @@
+/// How [`Transaction::import`] handles an object which already exists.
+///
+/// [`Transaction::import`]: crate::Transaction::import
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OnConflict {
+    /// Keeps the existing object, and skips the imported object.
+    Skip,
+    /// Updates the existing object with the imported object, as with
+    /// [`Transaction::upsert`].
+    ///
+    /// [`Transaction::upsert`]: crate::Transaction::upsert
+    Overwrite,
+    /// Fails with an [`Error::ObjectAlreadyExists`].
+    ///
+    /// [`Error::ObjectAlreadyExists`]: crate::Error::ObjectAlreadyExists
+    Error,
+}
+
+/// The number of objects which were inserted, updated or skipped by an import.
+///
+/// This `struct` is created by the [`import`] method on [`Transaction`]. See
+/// its documentation for more.
+///
+/// [`import`]: crate::Transaction::import
+/// [`Transaction`]: crate::Transaction
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ImportReport {
+    inserted: usize,
+    updated: usize,
+    skipped: usize,
+}
+
+impl ImportReport {
+    /// Returns the number of objects which did not exist, and were inserted.
+    pub fn inserted(&self) -> usize {
+        self.inserted
+    }
+
+    /// Returns the number of existing objects which were updated.
+    pub fn updated(&self) -> usize {
+        self.updated
+    }
+
+    /// Returns the number of existing objects which were skipped.
+    pub fn skipped(&self) -> usize {
+        self.skipped
+    }
+
+    /// Records an object which was inserted.
+    pub(crate) fn record_inserted(&mut self) {
+        self.inserted += 1;
+    }
+
+    /// Records an existing object which was updated.
+    pub(crate) fn record_updated(&mut self) {
+        self.updated += 1;
+    }
+
+    /// Records an existing object which was skipped.
+    pub(crate) fn record_skipped(&mut self) {
+        self.skipped += 1;
+    }
+}
@@ -0,0 +1,71 @@
+//! Conversion between Automerge values and JSON.
+
+use automerge::{AutomergeError, ObjId, ObjType, ScalarValue, Value};
+use autosurgeon::ReadDoc;
+use serde_json::{Map, Number, Value as JsonValue};
+use uuid::Uuid;
+
+use crate::Result;
+
+/// Converts an Automerge value, which is identified by `id` if it is an
+/// object, into JSON.
+///
+/// Bytes which hold `key`, i.e. the key of the entity which is converted, are
+/// converted into a UUID string.
+pub(crate) fn value_to_json<D>(
+    doc: &D,
+    value: Value<'_>,
+    id: &ObjId,
+    key: Option<Uuid>,
+) -> Result<JsonValue>
+where
+    D: ReadDoc,
+{
+    match value {
+        Value::Object(_) => object_to_json(doc, id, key),
+        Value::Scalar(scalar) => Ok(scalar_to_json(&scalar, key)),
+    }
+}
+
+/// Converts an Automerge object into JSON.
+fn object_to_json<D>(doc: &D, obj: &ObjId, key: Option<Uuid>) -> Result<JsonValue>
+where
+    D: ReadDoc,
+{
+    match doc.object_type(obj) {
+        Some(ObjType::Map | ObjType::Table) => {
+            let mut map = Map::new();
+            for (prop, value, id) in doc.map_range(obj, ..) {
+                map.insert(prop.to_owned(), value_to_json(doc, value, &id, key)?);
+            }
+            Ok(JsonValue::Object(map))
+        },
+        Some(ObjType::List) => {
+            let mut array = Vec::with_capacity(doc.length(obj));
+            for (_, value, id) in doc.list_range(obj, ..) {
+                array.push(value_to_json(doc, value, &id, key)?);
+            }
+            Ok(JsonValue::Array(array))
+        },
+        Some(ObjType::Text) => Ok(JsonValue::String(doc.text(obj)?)),
+        None => Err(AutomergeError::NotAnObject)?,
+    }
+}
+
+/// Converts an Automerge scalar value into JSON.
+fn scalar_to_json(scalar: &ScalarValue, key: Option<Uuid>) -> JsonValue {
+    match scalar {
+        ScalarValue::Bytes(bytes) => match key {
+            Some(key) if key.as_bytes() == bytes.as_slice() => JsonValue::String(key.to_string()),
+            _ => JsonValue::Array(bytes.iter().map(|&b| JsonValue::from(b)).collect()),
+        },
+        ScalarValue::Str(s) => JsonValue::String(s.to_string()),
+        ScalarValue::Int(n) => JsonValue::from(*n),
+        ScalarValue::Uint(n) => JsonValue::from(*n),
+        ScalarValue::F64(n) => Number::from_f64(*n).map_or(JsonValue::Null, JsonValue::Number),
+        ScalarValue::Counter(counter) => JsonValue::from(i64::from(counter)),
+        ScalarValue::Timestamp(millis) => JsonValue::from(*millis),
+        ScalarValue::Boolean(b) => JsonValue::Bool(*b),
+        ScalarValue::Unknown { .. } | ScalarValue::Null => JsonValue::Null,
+    }
+}
@@ -5,12 +5,34 @@ use std::{
     marker::PhantomData,
 };
 
-use automerge::ScalarValue;
+use automerge::{AutomergeError, ScalarValue};
+use autosurgeon::{reconcile::NoKey, Hydrate, HydrateError, Reconcile, Reconciler};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use uuid::Uuid;
 
-use crate::{Error, Result};
+use crate::{Error, Mapped, Result};
+
+/// The digits of the short form of a key, in ascending order.
+const BASE62: &[u8; 62] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+/// The length of the short form of a key, i.e. the number of base62 digits of
+/// the largest [`Uuid`].
+const SHORT_LEN: usize = 22;
 
 /// A key which identifies an entity.
+///
+/// Keys are typed by the entity type `T`, so that e.g. a `Key<Book>` cannot be
+/// passed where a `Key<Author>` is expected. A key is usually obtained from
+/// the entity itself with [`Keyed::id`], or parsed with one of the `TryFrom`
+/// implementations.
+///
+/// A key can also be created from an arbitrary [`Uuid`] with [`Key::assume`],
+/// [`Key::new`] or `From<Uuid>`, none of which check that it identifies an
+/// entity of type `T`. Prefer `Key::assume`, whose name makes it visible at
+/// the call site that the key is unchecked.
+///
+/// [`Keyed::id`]: crate::Keyed::id
 pub struct Key<T: ?Sized>(Uuid, PhantomData<fn(T) -> T>);
 
 impl<T: ?Sized> Copy for Key<T> {}
@@ -69,7 +91,7 @@ impl<T: ?Sized> AsRef<Uuid> for Key<T> {
 
 impl<T: ?Sized> From<Uuid> for Key<T> {
     fn from(uuid: Uuid) -> Self {
-        Self::new(uuid)
+        Self::assume(uuid)
     }
 }
 
@@ -82,7 +104,35 @@ impl<T: ?Sized> TryFrom<&str> for Key<T> {
             source: e,
         })?;
 
-        Ok(Self::new(uuid))
+        Ok(Self::assume(uuid))
+    }
+}
+
+impl<T: ?Sized> TryFrom<&[u8]> for Key<T> {
+    type Error = Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self> {
+        let uuid = Uuid::from_slice(bytes).map_err(|e| Error::InvalidKey {
+            key: format!("{bytes:?}"),
+            source: e,
+        })?;
+
+        Ok(Self::assume(uuid))
+    }
+}
+
+impl<T: ?Sized> TryFrom<ScalarValue> for Key<T> {
+    type Error = Error;
+
+    fn try_from(value: ScalarValue) -> Result<Self> {
+        match value {
+            ScalarValue::Bytes(bytes) => Self::try_from(&*bytes),
+            ScalarValue::Str(s) => Self::try_from(&*s),
+            _ => Err(AutomergeError::InvalidValueType {
+                expected: "bytes or str".to_owned(),
+                unexpected: format!("{value}"),
+            })?,
+        }
     }
 }
 
@@ -98,11 +148,155 @@ impl<T: ?Sized> From<Key<T>> for ScalarValue {
     }
 }
 
+/// Hydrates the key from 16 bytes, as stored for a [`Uuid`], or from a UUID
+/// string, as stored by [`key_string`].
+///
+/// [`key_string`]: crate::key_string
+impl<T: ?Sized> Hydrate for Key<T> {
+    fn hydrate_bytes(bytes: &[u8]) -> std::result::Result<Self, HydrateError> {
+        Self::try_from(bytes)
+            .map_err(|_| HydrateError::unexpected("16 bytes", format!("{bytes:?}")))
+    }
+
+    fn hydrate_string(string: &'_ str) -> std::result::Result<Self, HydrateError> {
+        Self::try_from(string)
+            .map_err(|_| HydrateError::unexpected("a UUID string", string.to_owned()))
+    }
+}
+
+/// Reconciles the key in the same way as [`Uuid`], i.e. as 16 bytes, so that
+/// entities can store typed foreign keys as fields.
+impl<T: ?Sized> Reconcile for Key<T> {
+    type Key<'a> = NoKey;
+
+    fn reconcile<R: Reconciler>(&self, mut reconciler: R) -> std::result::Result<(), R::Error> {
+        reconciler.bytes(self.0.as_bytes())
+    }
+}
+
+/// Serializes the key in the same way as [`Uuid`], i.e. as a hyphenated UUID
+/// string in human-readable formats, and as 16 bytes otherwise.
+#[cfg(feature = "serde")]
+impl<T: ?Sized> Serialize for Key<T> {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.0.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T: ?Sized> Deserialize<'de> for Key<T> {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Uuid::deserialize(deserializer).map(Self::assume)
+    }
+}
+
 impl<T: ?Sized> Key<T> {
     /// Creates a new `Key` from a [`Uuid`].
     ///
-    /// The key is specific to the entity type `T`.
+    /// The key is specific to the entity type `T`. Like [`Key::assume`], this
+    /// does not check that the [`Uuid`] identifies an entity of type `T`.
     pub fn new(uuid: Uuid) -> Self {
+        Self::assume(uuid)
+    }
+
+    /// Creates a new `Key` from a [`Uuid`], assuming that it identifies an
+    /// entity of type `T`.
+    ///
+    /// This is not checked, so a key of one entity type can be turned into a
+    /// key of another by way of its [`Uuid`]. Prefer obtaining keys from the
+    /// entity itself, or parsing them, where possible.
+    pub fn assume(uuid: Uuid) -> Self {
         Self(uuid, PhantomData)
     }
+
+    /// Creates a new random `Key`, backed by a version 4 [`Uuid`].
+    pub fn random() -> Self {
+        Self::assume(Uuid::new_v4())
+    }
+
+    /// Creates a new `Key` which is all zeros, backed by the nil [`Uuid`].
+    pub fn nil() -> Self {
+        Self::assume(Uuid::nil())
+    }
+
+    /// Returns the short form of the key, which encodes its [`Uuid`] as 22
+    /// base62 characters, e.g. for compact, URL-safe paths.
+    ///
+    /// Short forms are fixed-width, so they sort in the same order as keys.
+    /// The key can be parsed back with [`Key::from_short`].
+    pub fn to_short(self) -> String {
+        let mut value = self.0.as_u128();
+        let mut short = [0u8; SHORT_LEN];
+        for c in short.iter_mut().rev() {
+            *c = BASE62[(value % 62) as usize];
+            value /= 62;
+        }
+
+        short.iter().map(|&c| char::from(c)).collect()
+    }
+
+    /// Parses the short form of a key, as returned by [`Key::to_short`].
+    ///
+    /// Returns an [`Error::InvalidShortKey`] if `s` is not 22 base62
+    /// characters, or does not encode a [`Uuid`].
+    pub fn from_short(s: &str) -> Result<Self> {
+        let invalid = || Error::InvalidShortKey { key: s.to_owned() };
+        if s.len() != SHORT_LEN {
+            return Err(invalid());
+        }
+        let mut value: u128 = 0;
+        for c in s.bytes() {
+            let digit = BASE62.iter().position(|&d| d == c).ok_or_else(invalid)?;
+            value = value
+                .checked_mul(62)
+                .and_then(|value| value.checked_add(digit as u128))
+                .ok_or_else(invalid)?;
+        }
+
+        Ok(Self::assume(Uuid::from_u128(value)))
+    }
+}
+
+impl<T: Mapped + ?Sized> Key<T> {
+    /// Returns the property in the table which the entity identified by this
+    /// key is stored under, as mapped by [`Mapped::key_prop`].
+    pub(crate) fn to_prop(self) -> String {
+        T::key_prop(self.0)
+    }
+
+    /// Returns the key of the entity stored under the property `prop` in the
+    /// table, as mapped by [`Mapped::key_from_prop`].
+    pub(crate) fn from_prop(prop: &str) -> Result<Self> {
+        T::key_from_prop(prop).map(Self::assume)
+    }
+}
+
+/// Converts the key field of an entity into a [`Key`], as used by the
+/// [`derive@Entity`] derive.
+///
+/// Unlike [`Into`], this is only implemented for [`Uuid`] and `Key<T>`, so
+/// that a key field of any other type is rejected by the derive.
+///
+/// [`derive@Entity`]: crate::Entity
+#[doc(hidden)]
+pub trait IntoKey<T: ?Sized> {
+    fn into_key(self) -> Key<T>;
+}
+
+impl<T: ?Sized> IntoKey<T> for Uuid {
+    fn into_key(self) -> Key<T> {
+        Key::assume(self)
+    }
+}
+
+impl<T: ?Sized> IntoKey<T> for Key<T> {
+    fn into_key(self) -> Key<T> {
+        self
+    }
 }
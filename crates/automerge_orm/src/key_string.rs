@@ -0,0 +1,42 @@
+//! Stores a key field as a hyphenated UUID string, rather than as bytes.
+//!
+//! By default, `autosurgeon` reconciles [`Uuid`] fields as 16 bytes, which are
+//! opaque to other Automerge clients, e.g. a JavaScript client which expects
+//! string UUIDs. Annotating the key field with
+//! `#[autosurgeon(with = "automerge_orm::key_string")]` stores it as a string
+//! scalar instead:
+//!
+//! ```
+//! use automerge_orm::Entity;
+//! use autosurgeon::{Hydrate, Reconcile};
+//! use uuid::Uuid;
+//!
+//! #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+//! struct Book {
+//!     #[key]
+//!     #[autosurgeon(with = "automerge_orm::key_string")]
+//!     id: Uuid,
+//! }
+//! ```
+//!
+//! Only the stored value of the field changes. Entities are still stored under
+//! their hyphenated key in the table, and foreign key fields in either format
+//! are found by [`find_all_by`].
+//!
+//! [`find_all_by`]: crate::find_all_by
+
+use automerge::ObjId;
+use autosurgeon::{Hydrate, HydrateError, Prop, ReadDoc, Reconciler};
+use uuid::Uuid;
+
+/// Hydrates a [`Uuid`] from a hyphenated UUID string.
+pub fn hydrate<D: ReadDoc>(doc: &D, obj: &ObjId, prop: Prop<'_>) -> Result<Uuid, HydrateError> {
+    let s = String::hydrate(doc, obj, prop)?;
+
+    Uuid::parse_str(&s).map_err(|_| HydrateError::unexpected("a hyphenated UUID string", s))
+}
+
+/// Reconciles a [`Uuid`] as a hyphenated UUID string.
+pub fn reconcile<R: Reconciler>(uuid: &Uuid, mut reconciler: R) -> Result<(), R::Error> {
+    reconciler.str(uuid.hyphenated().to_string())
+}
@@ -8,3 +8,25 @@ pub trait Keyed {
     /// Returns the key which identifies this entity.
     fn id(&self) -> Key<Self::Entity>;
 }
+
+impl<T> Keyed for &T
+where
+    T: Keyed + ?Sized,
+{
+    type Entity = T::Entity;
+
+    fn id(&self) -> Key<Self::Entity> {
+        (**self).id()
+    }
+}
+
+impl<T> Keyed for Box<T>
+where
+    T: Keyed + ?Sized,
+{
+    type Entity = T::Entity;
+
+    fn id(&self) -> Key<Self::Entity> {
+        (**self).id()
+    }
+}
@@ -11,34 +11,253 @@
 //!   instances of an entity can be retrieved. Queries to be performed on the
 //!   set of entities should be done through the repository.
 //!
+//! * **Federated repository** ([`FederatedRepository<T>`]): A repository which
+//!   retrieves instances of an entity across multiple documents, e.g. one
+//!   document per workspace.
+//!
 //! ## Derives
 //!
 //! * [`derive@Entity`]: Implements the [`Entity`] trait for the type.
+//!
+//! * [`derive@Patch`]: Generates a patch type for the entity, which implements
+//!   the [`Patch`] trait.
+//!
+//! ## Field types
+//!
+//! Entity fields are stored and retrieved using [`autosurgeon`], so any type
+//! which implements [`Reconcile`] and [`Hydrate`] can be used. In particular:
+//!
+//! * [`autosurgeon::Text`]: Stored as an Automerge text object. Concurrent
+//!   edits to the field are merged character by character, rather than one of
+//!   the edits winning. Use it for strings which are edited collaboratively.
+//!
+//! * [`autosurgeon::Counter`]: Stored as an Automerge counter. Concurrent
+//!   increments to the field are summed, rather than one of the values
+//!   winning. Use [`Transaction::increment`] to increment the counter, or
+//!   [`counter`] to store an integer field as a counter.
+//!
+//! * [`uuid::Uuid`]: Stored as 16 bytes, e.g. for keys. Use [`key_string`] to
+//!   store a key field as a hyphenated UUID string instead.
+//!
+//! * [`Key<T>`]: Stored as 16 bytes, like a [`uuid::Uuid`], e.g. for typed
+//!   foreign keys, which can be resolved with [`EntityManager::find_related`].
+//!
+//! * [`Vec<u8>`]: Stored as a list of integers. Use [`bytes`] to store the
+//!   field as a single Automerge bytes scalar instead.
+//!
+//! [`Reconcile`]: autosurgeon::Reconcile
+//! [`Hydrate`]: autosurgeon::Hydrate
+//!
+//! ## Optional features
+//!
+//! * `async`: Enables [`AsyncEntityRepository<T>`], which retrieves entities
+//!   on the blocking thread pool of the Tokio runtime, so that the async
+//!   runtime is not blocked.
+//!
+//! * `chrono`: Enables the [`timestamp`] module, which stores
+//!   `DateTime<Utc>` fields of `chrono` as Automerge timestamps.
+//!
+//! * `crypto`: Enables the [`encrypted`] module, which encrypts fields with
+//!   the key configured by [`EntityManager::with_encryption_key`].
+//!
+//! * `serde`: Implements `Serialize` and `Deserialize` of [`serde`] for
+//!   [`Key<T>`].
+//!
+//! * `serde_json`: Enables conversion of entities into / from JSON, e.g.
+//!   [`DefaultEntityRepository::export_json`] and
+//!   [`Transaction::import_json`].
+//!
+//! * `stream`: Enables [`EntityManager::changes`], which returns a stream of
+//!   changes to the Automerge document, and [`DefaultEntityRepository::watch`],
+//!   which returns a stream of changes to the entities in a repository.
+//!
+//! * `testing`: Enables the [`testing`] module, which provides utilities for
+//!   testing entity types, e.g. [`testing::assert_round_trip`].
 
 /// Implements the [`Entity`] trait for the type.
+///
+/// The derive also generates an inherent `const fn table_name()` on the type,
+/// which returns the same name as [`Mapped::table_name`] without requiring the
+/// trait to be in scope.
+///
+/// # Attributes
+///
+/// The derive can be configured using the `#[automerge_orm(...)]` attribute
+/// on the type:
+///
+/// * `id = "..."`: An expression which evaluates to the key of the entity.
+///   Defaults to `self.id`, in which case a field annotated with `#[key]` must
+///   be of type [`Uuid`](uuid::Uuid) or [`Key<Self>`](Key).
+///
+/// * `table_name = "..."`: The name of the table which the entity is mapped
+///   to. Defaults to the name of the type in snake case, e.g. `library_branch`
+///   for `LibraryBranch`.
+///
+/// * `pluralize`: Pluralizes the default name of the table, e.g.
+///   `library_branches` for `LibraryBranch`. Has no effect if `table_name` is
+///   given.
+///
+/// * `type_tag` or `type_tag = "..."`: Enables type tagging, as described in
+///   [`Mapped::type_tag`]. The type tag defaults to the name of the type.
+///
+/// * `key_prop = "..."`: The path of a module which maps the key of the entity
+///   to the property which the entity is stored under, as described in
+///   [`Mapped::key_prop`]. The module must provide the functions
+///   `to_prop(Uuid) -> String` and `from_prop(&str) -> Result<Uuid>`.
+///
+/// * `hooks`: Opts out of the no-op implementation of the [`Hooks`] trait, so
+///   that it can be implemented manually. The derive invokes the hooks around
+///   writes, as returned by [`Mapped::lifecycle`].
+///
+/// * `validate`: Opts out of the no-op implementation of the [`Validate`]
+///   trait, so that it can be implemented manually. The derive validates
+///   entities before they are written, as returned by [`Mapped::lifecycle`].
+///
+/// The following attributes can be used on the fields of the type:
+///
+/// * `rename = "..."`: The name of the property which the field is mapped to,
+///   as returned by [`Mapped::prop_name`]. Fields are stored and retrieved by
+///   `autosurgeon`, so the field must be renamed with
+///   `#[autosurgeon(rename = "...")]` as well.
+///
+/// * `unique`: Requires the values of the field to be unique within the table,
+///   as returned by [`Mapped::unique_fields`]. Uniqueness is only enforced
+///   locally, so it can still be violated by concurrent changes.
+///
+/// * `counter`: Stores an integer field as an Automerge counter, as described
+///   in the [`counter`] module and returned by [`Mapped::counter_fields`].
+///   Fields are stored and retrieved by `autosurgeon`, so the field must be
+///   annotated with `#[autosurgeon(with = "automerge_orm::counter")]` as well.
+///   Either attribute without the other is rejected by the derive.
+///
+/// * `encrypt`: Encrypts a `String` field with the encryption key of the
+///   [`EntityManager`], as described in the `encrypted` module, which
+///   requires the `crypto` feature, and returned by
+///   [`Mapped::encrypted_fields`]. As with `counter`, the field must be
+///   annotated with `#[autosurgeon(with = "automerge_orm::encrypted")]` as
+///   well.
+///
+/// Without `id`, a field annotated with `#[key]` which is neither a
+/// [`Uuid`](uuid::Uuid) nor a [`Key<Self>`](Key) is rejected at compile time:
+///
+/// ```compile_fail
+/// use automerge_orm::Entity;
+/// use autosurgeon::{Hydrate, Reconcile};
+///
+/// #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+/// struct Book {
+///     #[key]
+///     id: String,
+/// }
+/// ```
 pub use automerge_orm_macros::Entity;
 
+/// Generates a patch type for an entity, which implements the [`Patch`] trait.
+///
+/// The patch type is named after the entity with a `Patch` suffix, e.g.
+/// `BookPatch` for `Book`, and implements [`Default`]. It has an [`Option`] of
+/// each field of the entity, except for fields annotated with `#[key]`, which
+/// cannot be changed by a patch. Applying the patch updates only the fields
+/// which are `Some`, using [`Transaction::update_field`], without validating
+/// the entity or invoking its hooks.
+///
+/// Fields annotated with `#[autosurgeon(with = "...")]` or
+/// `#[autosurgeon(reconcile = "...")]` are reconciled by the same function
+/// when the patch is applied, so that they are stored in the same way as by
+/// writing the entity.
+pub use automerge_orm_macros::Patch;
+
+#[cfg(feature = "async")]
+pub use self::async_entity_repository::{AsyncEntityRepository, DefaultAsyncEntityRepository};
+#[cfg(feature = "stream")]
+pub use self::change_event::ChangeEvent;
+pub use self::commit_info::CommitInfo;
+pub use self::conflict_policy::ConflictPolicy;
+pub use self::dyn_entity::DynEntity;
+#[cfg(feature = "crypto")]
+pub use self::encrypted::EncryptionKey;
 pub use self::entity::Entity;
 pub use self::entity_manager::EntityManager;
 pub use self::entity_repository::{DefaultEntityRepository, EntityRepository};
-pub use self::error::{Error, Result};
-pub use self::impls::{create_table, find, find_all, get_table};
+pub use self::error::{Error, Result, TransactError};
+pub use self::federated_repository::FederatedRepository;
+pub use self::hooks::Hooks;
+pub use self::impls::{
+    create_table, find, find_all, find_all_at, find_all_by, find_all_keys, find_at, find_first,
+    for_each, get_entity, get_raw, get_table, list_all,
+};
+#[cfg(feature = "serde_json")]
+pub use self::impls::{table_to_json, to_json};
+pub use self::import::{ImportReport, OnConflict};
 pub use self::key::Key;
 pub use self::keyed::Keyed;
+pub use self::lifecycle::Lifecycle;
 pub use self::mapped::Mapped;
+pub use self::metrics::{Metrics, MetricsEvent};
+pub use self::migration::Migration;
+pub use self::patch::Patch;
+pub use self::reader::Reader;
+pub use self::repository_ext::RepositoryExt;
+pub use self::savepoint::Savepoint;
+pub use self::table_status::TableStatus;
 pub use self::transaction::Transaction;
+pub use self::transaction_options::TransactionOptions;
+pub use self::validate::{Validate, ValidationError};
+#[cfg(feature = "stream")]
+pub use self::watch::EntityEvent;
 
+#[cfg(feature = "async")]
+mod async_entity_repository;
+pub mod bytes;
+#[cfg(feature = "stream")]
+mod change_event;
+mod commit_info;
+mod conflict_policy;
+pub mod counter;
+mod cursor;
+mod document;
+mod dump;
+mod dyn_entity;
+#[cfg(feature = "crypto")]
+pub mod encrypted;
 mod entity;
 mod entity_manager;
 mod entity_repository;
 mod error;
+mod federated_repository;
+mod hooks;
 pub mod impls;
+mod import;
+#[cfg(feature = "serde_json")]
+mod json;
 mod key;
+pub mod key_string;
 mod keyed;
+mod lifecycle;
 mod mapped;
+mod metrics;
+mod migration;
+mod patch;
+mod reader;
+mod repository_ext;
+mod savepoint;
+mod table_status;
+#[cfg(feature = "testing")]
+pub mod testing;
+#[cfg(feature = "chrono")]
+pub mod timestamp;
 mod transaction;
+mod transaction_options;
+mod validate;
+#[cfg(feature = "stream")]
+mod watch;
 
 #[doc(hidden)]
 pub mod __macro_support {
-    pub use std::{borrow::ToOwned, convert::Into, string::String};
+    pub use std::{option::Option, result::Result, string::String};
+
+    pub use autosurgeon::{reconcile::NoKey, Reconcile, Reconciler};
+    pub use uuid::Uuid;
+
+    pub use crate::key::IntoKey;
 }
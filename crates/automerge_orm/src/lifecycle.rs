@@ -0,0 +1,83 @@
+use std::fmt::{self, Debug};
+
+use crate::{Hooks, Result, Validate, ValidationError};
+
+/// The checks and hooks which are run when an entity is written to an
+/// Automerge document, as returned by [`Mapped::lifecycle`].
+///
+/// A new `Lifecycle` does not check anything and invokes no hooks. Validation
+/// and hooks are opted into with [`validated`] and [`hooked`], which require
+/// the entity to implement [`Validate`] and [`Hooks`], respectively, so that
+/// entities which need neither do not have to implement them.
+///
+/// [`Mapped::lifecycle`]: crate::Mapped::lifecycle
+/// [`validated`]: Lifecycle::validated
+/// [`hooked`]: Lifecycle::hooked
+pub struct Lifecycle<T> {
+    pub(crate) validate: fn(&T) -> std::result::Result<(), ValidationError>,
+    pub(crate) before_insert: fn(&T) -> Result<Option<T>>,
+    pub(crate) after_insert_write: fn(&T) -> Result<()>,
+    pub(crate) before_update: fn(&T) -> Result<Option<T>>,
+    pub(crate) after_update_write: fn(&T) -> Result<()>,
+}
+
+impl<T> Lifecycle<T> {
+    /// Creates a new `Lifecycle` which accepts every entity and invokes no
+    /// hooks.
+    pub fn new() -> Self {
+        Self {
+            validate: |_| Ok(()),
+            before_insert: |_| Ok(None),
+            after_insert_write: |_| Ok(()),
+            before_update: |_| Ok(None),
+            after_update_write: |_| Ok(()),
+        }
+    }
+
+    /// Returns a `Lifecycle` which validates the entity with
+    /// [`Validate::validate`] before it is written.
+    pub fn validated(self) -> Self
+    where
+        T: Validate,
+    {
+        Self {
+            validate: <T as Validate>::validate,
+            ..self
+        }
+    }
+
+    /// Returns a `Lifecycle` which invokes the [`Hooks`] of the entity around
+    /// writing it.
+    pub fn hooked(self) -> Self
+    where
+        T: Hooks,
+    {
+        Self {
+            before_insert: <T as Hooks>::before_insert,
+            after_insert_write: <T as Hooks>::after_insert_write,
+            before_update: <T as Hooks>::before_update,
+            after_update_write: <T as Hooks>::after_update_write,
+            ..self
+        }
+    }
+}
+
+impl<T> Clone for Lifecycle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Lifecycle<T> {}
+
+impl<T> Debug for Lifecycle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Lifecycle").finish_non_exhaustive()
+    }
+}
+
+impl<T> Default for Lifecycle<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
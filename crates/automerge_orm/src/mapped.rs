@@ -1,4 +1,170 @@
+use uuid::Uuid;
+
+use crate::{Key, Lifecycle, Result};
+
+/// The prefix of keys in the root of the Automerge document which are reserved
+/// for metadata of the Automerge ORM, e.g. the schema version.
+pub(crate) const RESERVED_PREFIX: &str = "__automerge_orm";
+
+/// The key in each entity at which its type tag is stored, if any.
+pub(crate) const TYPE_TAG_KEY: &str = "__automerge_orm_type";
+
 /// An entity which is mapped to an Automerge document.
 pub trait Mapped {
-    fn table_name() -> String;
+    /// Returns the name of the table which the entity is mapped to.
+    ///
+    /// Table names starting with `__automerge_orm` are reserved for metadata
+    /// of the Automerge ORM, and cannot be created.
+    fn table_name() -> &'static str;
+
+    /// Returns the name of the property in the Automerge document which the
+    /// field named `field` is mapped to.
+    ///
+    /// Defaults to the name of the field.
+    fn prop_name(field: &str) -> &str {
+        field
+    }
+
+    /// Returns the type tag of the entity, if type tagging is enabled.
+    ///
+    /// The type tag is written into each entity when it is written to the
+    /// document, and checked before an entity is hydrated, so that an entity
+    /// which was written by a different entity type sharing the same table
+    /// fails with [`Error::TypeMismatch`] rather than hydrating into garbage.
+    /// Entities without a type tag, e.g. which were written before type
+    /// tagging was enabled, are not checked.
+    ///
+    /// Defaults to `None`, i.e. type tagging is disabled.
+    ///
+    /// [`Error::TypeMismatch`]: crate::Error::TypeMismatch
+    fn type_tag() -> Option<&'static str> {
+        None
+    }
+
+    /// Returns the names of the fields whose values must be unique within
+    /// the table.
+    ///
+    /// Before an entity is written by [`Transaction::insert`],
+    /// [`Transaction::update`] and the like, the table is scanned for another
+    /// object with the same value of a unique field, which fails with
+    /// [`Error::UniqueViolation`]. The same applies to a unique field which
+    /// is written by [`Transaction::update_field`], and in turn by
+    /// [`Transaction::apply_patch`]. Only fields holding scalar values, e.g.
+    /// strings and numbers, are checked.
+    ///
+    /// Each check scans the whole table, so writing `n` objects with unique
+    /// fields, e.g. by [`Transaction::import`], takes quadratic time in `n`.
+    ///
+    /// This is best-effort, local enforcement. Objects which are inserted or
+    /// updated concurrently, e.g. on different peers, are not checked against
+    /// each other, so uniqueness can still be violated once their changes are
+    /// merged.
+    ///
+    /// Defaults to no fields.
+    ///
+    /// [`Transaction::insert`]: crate::Transaction::insert
+    /// [`Transaction::update`]: crate::Transaction::update
+    /// [`Transaction::update_field`]: crate::Transaction::update_field
+    /// [`Transaction::apply_patch`]: crate::Transaction::apply_patch
+    /// [`Transaction::import`]: crate::Transaction::import
+    /// [`Error::UniqueViolation`]: crate::Error::UniqueViolation
+    fn unique_fields() -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Returns the names of the fields which are stored as Automerge counters
+    /// by the [`counter`] module.
+    ///
+    /// When an entity is written by [`Transaction::insert`],
+    /// [`Transaction::update`] and the like, or a field is written by
+    /// [`Transaction::update_field`], the counter of such a field is changed
+    /// by the difference to the value of the field, rather than being
+    /// replaced, so that concurrent increments are kept.
+    ///
+    /// Defaults to no fields.
+    ///
+    /// [`counter`]: crate::counter
+    /// [`Transaction::insert`]: crate::Transaction::insert
+    /// [`Transaction::update`]: crate::Transaction::update
+    /// [`Transaction::update_field`]: crate::Transaction::update_field
+    fn counter_fields() -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Returns the names of the fields which are encrypted by the
+    /// [`encrypted`] module.
+    ///
+    /// Reading or writing an entity with encrypted fields without an
+    /// encryption key fails with an [`Error::EncryptionKeyMissing`].
+    ///
+    /// Defaults to no fields.
+    ///
+    /// [`encrypted`]: crate::encrypted
+    /// [`Error::EncryptionKeyMissing`]: crate::Error::EncryptionKeyMissing
+    fn encrypted_fields() -> &'static [&'static str] {
+        &[]
+    }
+
+    /// Returns the name of the field which holds the key of the entity, if
+    /// any.
+    ///
+    /// The key of a stored entity must match the property which it is stored
+    /// under, so [`Transaction::update_field`] rejects updates of this field
+    /// with an [`Error::KeyFieldUpdate`]. The [`derive@Entity`] derive returns
+    /// the field annotated with `#[key]`.
+    ///
+    /// Defaults to `None`.
+    ///
+    /// [`Transaction::update_field`]: crate::Transaction::update_field
+    /// [`Error::KeyFieldUpdate`]: crate::Error::KeyFieldUpdate
+    /// [`derive@Entity`]: crate::Entity
+    fn key_field() -> Option<&'static str> {
+        None
+    }
+
+    /// Returns the property in the table which the entity identified by `id`
+    /// is stored under.
+    ///
+    /// Defaults to the hyphenated form of the key. A custom mapping, e.g. a
+    /// shorter encoding of the key, must be injective, so that distinct keys
+    /// are never stored under the same property, and must be reversed by
+    /// [`key_from_prop`].
+    ///
+    /// [`key_from_prop`]: Mapped::key_from_prop
+    fn key_prop(id: Uuid) -> String {
+        id.to_string()
+    }
+
+    /// Returns the key of the entity stored under the property `prop` in the
+    /// table, as the reverse of [`key_prop`].
+    ///
+    /// Defaults to parsing the property as a UUID.
+    ///
+    /// [`key_prop`]: Mapped::key_prop
+    fn key_from_prop(prop: &str) -> Result<Uuid> {
+        Key::<Self>::try_from(prop).map(Uuid::from)
+    }
+
+    /// Returns the checks and hooks which are run when the entity is written
+    /// to the document by [`Transaction::insert`], [`Transaction::update`] and
+    /// the like.
+    ///
+    /// Defaults to a [`Lifecycle`] which accepts every entity and invokes no
+    /// hooks. The [`derive@Entity`] derive returns a [`Lifecycle`] which
+    /// validates the entity with [`Validate`] and invokes its [`Hooks`], whose
+    /// implementations are no-ops unless the type is annotated with
+    /// `#[automerge_orm(validate)]` and `#[automerge_orm(hooks)]`,
+    /// respectively.
+    ///
+    /// [`Transaction::insert`]: crate::Transaction::insert
+    /// [`Transaction::update`]: crate::Transaction::update
+    /// [`Validate`]: crate::Validate
+    /// [`Hooks`]: crate::Hooks
+    /// [`derive@Entity`]: crate::Entity
+    fn lifecycle() -> Lifecycle<Self>
+    where
+        Self: Sized,
+    {
+        Lifecycle::new()
+    }
 }
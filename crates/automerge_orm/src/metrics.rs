@@ -0,0 +1,51 @@
+use std::time::Duration;
+
+/// A sink for metrics of the operations performed through an
+/// [`EntityManager`], e.g. for performance monitoring.
+///
+/// Metrics are recorded only if the `EntityManager` is configured with a sink
+/// using [`with_metrics`]. The sink is invoked synchronously, while the lock
+/// on the document may still be held, so it should not perform expensive
+/// operations itself.
+///
+/// Closures which take a [`MetricsEvent`] implement this trait.
+///
+/// [`EntityManager`]: crate::EntityManager
+/// [`with_metrics`]: crate::EntityManager::with_metrics
+pub trait Metrics: Send + Sync {
+    /// Records an event of an operation.
+    fn record(&self, event: &MetricsEvent);
+}
+
+impl<F> Metrics for F
+where
+    F: Fn(&MetricsEvent) + Send + Sync,
+{
+    fn record(&self, event: &MetricsEvent) {
+        self(event);
+    }
+}
+
+/// An event of an operation, which is recorded by [`Metrics`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum MetricsEvent {
+    /// All objects of a table were found, by [`EntityRepository::find_all`]
+    /// or [`Reader::find_all`]. `elapsed` is the time spent reading and
+    /// hydrating the objects.
+    ///
+    /// [`EntityRepository::find_all`]: crate::EntityRepository::find_all
+    /// [`Reader::find_all`]: crate::Reader::find_all
+    FindAll {
+        table: &'static str,
+        count: usize,
+        elapsed: Duration,
+    },
+    /// A transaction waited for the write lock on the document. `elapsed` is
+    /// the time until the lock was acquired.
+    LockWait { elapsed: Duration },
+    /// A transaction was committed. `elapsed` is the time from acquiring the
+    /// write lock until the commit, during which other operations on the
+    /// document are blocked.
+    Commit { ops: usize, elapsed: Duration },
+}
@@ -0,0 +1,54 @@
+use automerge::{AutomergeError, ScalarValue, Value};
+use autosurgeon::ReadDoc;
+
+use crate::{impls::get_root, Result, Transaction};
+
+/// The key in the map under which tables are stored, e.g. the root of the
+/// Automerge document, at which the schema version is stored. It starts with
+/// the reserved prefix, so that it cannot collide with a table.
+pub(crate) const SCHEMA_VERSION_KEY: &str = "__automerge_orm_schema_version";
+
+/// A migration which upgrades the Automerge document to a schema version.
+///
+/// Migrations are run by [`EntityManager::migrate`] in the order of their
+/// versions. A migration is only run if its version is greater than the
+/// schema version stored in the document.
+///
+/// [`EntityManager::migrate`]: crate::EntityManager::migrate
+pub trait Migration {
+    /// Returns the schema version which the document is upgraded to.
+    fn version(&self) -> u32;
+
+    /// Upgrades the document within the context of the [`Transaction`].
+    fn up(&self, tx: &mut Transaction<'_>) -> Result<()>;
+}
+
+/// Returns the schema version stored in the map under `root` in the Automerge
+/// document, or `0` if no migration has been run yet.
+pub(crate) fn schema_version<D>(doc: &D, root: Option<&str>) -> Result<u32>
+where
+    D: ReadDoc,
+{
+    let Some(root_id) = get_root(doc, root)? else {
+        return Ok(0);
+    };
+    let Some((value, _)) = doc.get(&root_id, SCHEMA_VERSION_KEY)? else {
+        return Ok(0);
+    };
+    let Value::Scalar(scalar) = &value else {
+        Err(invalid_schema_version(&value))?
+    };
+    let ScalarValue::Uint(version) = &**scalar else {
+        Err(invalid_schema_version(&value))?
+    };
+    let version = u32::try_from(*version).map_err(|_| invalid_schema_version(&value))?;
+
+    Ok(version)
+}
+
+fn invalid_schema_version(value: &Value<'_>) -> AutomergeError {
+    AutomergeError::InvalidValueType {
+        expected: "uint".to_owned(),
+        unexpected: format!("{value}"),
+    }
+}
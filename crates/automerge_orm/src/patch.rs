@@ -0,0 +1,25 @@
+use crate::{Key, Mapped, Result, Transaction};
+
+/// A partial update of an entity, which changes only some of its fields.
+///
+/// Patches are applied with [`Transaction::apply_patch`], without loading the
+/// stored object instance. Unlike [`Transaction::update`], applying a patch
+/// does not invoke the [`Hooks`] of the entity, and does not validate it, even
+/// if they are enabled by [`Mapped::lifecycle`].
+///
+/// The [`derive@Patch`] derive generates a patch type for an entity, with an
+/// optional field for each field of the entity, and implements this trait for
+/// it.
+///
+/// [`Hooks`]: crate::Hooks
+/// [`derive@Patch`]: crate::Patch
+pub trait Patch {
+    /// The entity type which the patch is applied to.
+    type Entity: Mapped;
+
+    /// Applies the patch to the existing object instance identified by `id`.
+    ///
+    /// Implementations typically call [`Transaction::update_field`] for each
+    /// field which is changed by the patch.
+    fn apply(&self, tx: &mut Transaction<'_>, id: Key<Self::Entity>) -> Result<()>;
+}
@@ -0,0 +1,101 @@
+use std::{collections::BTreeMap, time::Instant};
+
+use automerge::Automerge;
+use autosurgeon::Hydrate;
+
+use crate::{
+    impls::{find_all_under, find_under},
+    EntityManager, Key, Mapped, MetricsEvent, Result,
+};
+
+/// A reader which finds objects of any type under a single read lock.
+///
+/// This `struct` is created by the [`read`] method on [`EntityManager`]. See
+/// its documentation for more.
+///
+/// [`read`]: crate::EntityManager::read
+/// [`EntityManager`]: crate::EntityManager
+#[derive(Debug)]
+pub struct Reader<'a> {
+    doc: &'a Automerge,
+    entity_manager: &'a EntityManager,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) fn new(doc: &'a Automerge, entity_manager: &'a EntityManager) -> Self {
+        Self {
+            doc,
+            entity_manager,
+        }
+    }
+
+    /// Finds an object by its key / identifier.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use automerge_orm::{Entity, EntityManager, Keyed};
+    /// use autosurgeon::{Hydrate, Reconcile};
+    /// use uuid::Uuid;
+    ///
+    /// #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    /// struct Book {
+    ///     #[key]
+    ///     id: Uuid,
+    /// }
+    ///
+    /// let entity_manager = EntityManager::ephemeral();
+    ///
+    /// let book = Book { id: Uuid::new_v4() };
+    /// entity_manager.transact(|tx| tx.insert(&book))?;
+    /// let found = entity_manager.read(|reader| reader.find(book.id()))?;
+    /// assert_eq!(found.map(|book| book.id), Some(book.id));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn find<T>(&self, id: Key<T>) -> Result<Option<T>>
+    where
+        T: Mapped + Hydrate,
+    {
+        find_under(self.doc, self.entity_manager.root(), id)
+    }
+
+    /// Finds all objects of a specific type.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use automerge_orm::{Entity, EntityManager};
+    /// use autosurgeon::{Hydrate, Reconcile};
+    /// use uuid::Uuid;
+    ///
+    /// #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    /// struct Book {
+    ///     #[key]
+    ///     id: Uuid,
+    /// }
+    ///
+    /// let entity_manager = EntityManager::ephemeral();
+    ///
+    /// entity_manager.transact(|tx| {
+    ///     tx.insert(&Book { id: Uuid::new_v4() })?;
+    ///     tx.insert(&Book { id: Uuid::new_v4() })
+    /// })?;
+    /// let books = entity_manager.read(|reader| reader.find_all::<Book>())?;
+    /// assert_eq!(books.len(), 2);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn find_all<T>(&self) -> Result<BTreeMap<String, T>>
+    where
+        T: Mapped + Hydrate,
+    {
+        let started = Instant::now();
+        let entities = find_all_under(self.doc, self.entity_manager.root())?;
+        self.entity_manager.record(MetricsEvent::FindAll {
+            table: <T as Mapped>::table_name(),
+            count: entities.len(),
+            elapsed: started.elapsed(),
+        });
+
+        Ok(entities)
+    }
+}
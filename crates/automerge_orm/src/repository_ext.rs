@@ -0,0 +1,204 @@
+use crate::{EntityRepository, Key, Result};
+
+/// Combinators for an [`EntityRepository`].
+///
+/// This trait is implemented for every [`EntityRepository`], including custom
+/// ones, so its methods never need to be implemented manually.
+pub trait RepositoryExt<T>: EntityRepository<T> {
+    /// Finds an object by its key / identifier, and maps it using `f`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use automerge::ChangeHash;
+    /// # use automerge_repo::{DocumentId, Repo, Storage, StorageError};
+    /// # use futures::future::{self, BoxFuture, FutureExt};
+    /// #
+    /// # pub struct NoopStorage;
+    /// #
+    /// # impl Storage for NoopStorage {
+    /// #     fn get(
+    /// #         &self,
+    /// #         _id: DocumentId
+    /// #     ) -> BoxFuture<'static, Result<Option<Vec<u8>>, StorageError>> {
+    /// #         future::ready(Ok(None)).boxed()
+    /// #     }
+    /// #
+    /// #     fn list_all(
+    /// #         &self
+    /// #     ) -> BoxFuture<'static, Result<Vec<DocumentId>, StorageError>> {
+    /// #         future::ready(Ok(Vec::new())).boxed()
+    /// #     }
+    /// #
+    /// #     fn append(
+    /// #         &self,
+    /// #         _id: DocumentId,
+    /// #         _chunk: Vec<u8>,
+    /// #     ) -> BoxFuture<'static, Result<(), StorageError>> {
+    /// #         future::ready(Ok(())).boxed()
+    /// #     }
+    /// #
+    /// #     fn compact(
+    /// #         &self,
+    /// #         _id: DocumentId,
+    /// #         _chunk: Vec<u8>,
+    /// #         _new_heads: Vec<ChangeHash>,
+    /// #     ) -> BoxFuture<'static, Result<(), StorageError>> {
+    /// #         future::ready(Ok(())).boxed()
+    /// #     }
+    /// # }
+    /// #
+    /// use std::sync::Arc;
+    ///
+    /// use automerge_orm::{
+    ///     DefaultEntityRepository,
+    ///     Entity,
+    ///     EntityManager,
+    ///     Keyed,
+    ///     RepositoryExt,
+    /// };
+    /// use autosurgeon::{Hydrate, Reconcile};
+    /// use uuid::Uuid;
+    ///
+    /// #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    /// struct Book {
+    ///     #[key]
+    ///     id: Uuid,
+    ///     title: String,
+    ///     year: u16,
+    /// }
+    ///
+    /// type BookRepository = DefaultEntityRepository<Book>;
+    ///
+    /// impl Book {
+    ///     pub fn new(title: &str, year: u16) -> Self {
+    ///         Self {
+    ///             id: Uuid::new_v4(),
+    ///             title: title.to_owned(),
+    ///             year,
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// # let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    /// # let doc_handle = repo_handle.new_document();
+    /// let entity_manager = Arc::new(EntityManager::new(doc_handle));
+    /// let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+    ///
+    /// let book = Book::new("Spirited Away", 2001);
+    /// entity_manager.transact(|tx| {
+    ///     tx.insert(&book)?;
+    ///     automerge_orm::Result::Ok(())
+    /// })?;
+    /// let title = book_repository.find_map(book.id(), |book| book.title)?;
+    /// assert_eq!(title.as_deref(), Some("Spirited Away"));
+    /// # repo_handle.stop().unwrap();
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    fn find_map<U, F>(&self, id: Key<T>, f: F) -> Result<Option<U>>
+    where
+        F: FnOnce(T) -> U,
+    {
+        Ok(self.find(id)?.map(f))
+    }
+
+    /// Finds all objects in the repository, ordered by key, and keeps the
+    /// results of `f` which are `Some`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use automerge::ChangeHash;
+    /// # use automerge_repo::{DocumentId, Repo, Storage, StorageError};
+    /// # use futures::future::{self, BoxFuture, FutureExt};
+    /// #
+    /// # pub struct NoopStorage;
+    /// #
+    /// # impl Storage for NoopStorage {
+    /// #     fn get(
+    /// #         &self,
+    /// #         _id: DocumentId
+    /// #     ) -> BoxFuture<'static, Result<Option<Vec<u8>>, StorageError>> {
+    /// #         future::ready(Ok(None)).boxed()
+    /// #     }
+    /// #
+    /// #     fn list_all(
+    /// #         &self
+    /// #     ) -> BoxFuture<'static, Result<Vec<DocumentId>, StorageError>> {
+    /// #         future::ready(Ok(Vec::new())).boxed()
+    /// #     }
+    /// #
+    /// #     fn append(
+    /// #         &self,
+    /// #         _id: DocumentId,
+    /// #         _chunk: Vec<u8>,
+    /// #     ) -> BoxFuture<'static, Result<(), StorageError>> {
+    /// #         future::ready(Ok(())).boxed()
+    /// #     }
+    /// #
+    /// #     fn compact(
+    /// #         &self,
+    /// #         _id: DocumentId,
+    /// #         _chunk: Vec<u8>,
+    /// #         _new_heads: Vec<ChangeHash>,
+    /// #     ) -> BoxFuture<'static, Result<(), StorageError>> {
+    /// #         future::ready(Ok(())).boxed()
+    /// #     }
+    /// # }
+    /// #
+    /// use std::sync::Arc;
+    ///
+    /// use automerge_orm::{
+    ///     DefaultEntityRepository,
+    ///     Entity,
+    ///     EntityManager,
+    ///     RepositoryExt,
+    /// };
+    /// use autosurgeon::{Hydrate, Reconcile};
+    /// use uuid::Uuid;
+    ///
+    /// #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    /// struct Book {
+    ///     #[key]
+    ///     id: Uuid,
+    ///     title: String,
+    ///     year: u16,
+    /// }
+    ///
+    /// type BookRepository = DefaultEntityRepository<Book>;
+    ///
+    /// impl Book {
+    ///     pub fn new(title: &str, year: u16) -> Self {
+    ///         Self {
+    ///             id: Uuid::new_v4(),
+    ///             title: title.to_owned(),
+    ///             year,
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// # let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    /// # let doc_handle = repo_handle.new_document();
+    /// let entity_manager = Arc::new(EntityManager::new(doc_handle));
+    /// let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+    ///
+    /// entity_manager.transact(|tx| {
+    ///     tx.insert(&Book::new("Spirited Away", 2001))?;
+    ///     tx.insert(&Book::new("Your Name", 2016))?;
+    ///     automerge_orm::Result::Ok(())
+    /// })?;
+    /// let titles = book_repository
+    ///     .find_all_filter_map(|book| (book.year > 2010).then_some(book.title))?;
+    /// assert_eq!(titles, vec!["Your Name".to_owned()]);
+    /// # repo_handle.stop().unwrap();
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    fn find_all_filter_map<U, F>(&self, f: F) -> Result<Vec<U>>
+    where
+        F: FnMut(T) -> Option<U>,
+    {
+        Ok(self.list()?.into_iter().filter_map(f).collect())
+    }
+}
+
+impl<T, R> RepositoryExt<T> for R where R: EntityRepository<T> + ?Sized {}
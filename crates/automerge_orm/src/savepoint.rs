@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+
+use automerge::{transaction::Transactable, ChangeHash, ObjId, Patch, PatchAction, Value};
+
+use crate::{Error, Result};
+
+/// A savepoint which marks the state of the Automerge document at a point in
+/// time.
+///
+/// This `struct` is created by the [`savepoint`] method on [`EntityManager`],
+/// and the document can be reverted to it using the [`rollback_to`] method.
+/// See their documentation for more.
+///
+/// [`savepoint`]: crate::EntityManager::savepoint
+/// [`rollback_to`]: crate::EntityManager::rollback_to
+/// [`EntityManager`]: crate::EntityManager
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Savepoint {
+    heads: Vec<ChangeHash>,
+}
+
+impl Savepoint {
+    pub(crate) fn new(heads: Vec<ChangeHash>) -> Self {
+        Self { heads }
+    }
+
+    /// Returns the heads of the document at the savepoint.
+    pub fn heads(&self) -> &[ChangeHash] {
+        &self.heads
+    }
+}
+
+/// Applies patches to the document.
+///
+/// Objects which are created by the patches are assigned new object ids, and
+/// subsequent patches to those objects are applied to the new objects.
+///
+/// Returns an [`Error::RollbackUnsupported`] if the patches change marks on text.
+pub(crate) fn apply_patches<D>(doc: &mut D, patches: Vec<Patch>) -> Result<()>
+where
+    D: Transactable,
+{
+    let mut obj_ids = HashMap::new();
+    for patch in patches {
+        let obj = obj_ids.get(&patch.obj).cloned().unwrap_or(patch.obj);
+        match patch.action {
+            PatchAction::PutMap {
+                key,
+                value: (value, id),
+                ..
+            } => match value {
+                Value::Object(obj_type) => {
+                    let new_id = doc.put_object(&obj, key, obj_type)?;
+                    obj_ids.insert(id, new_id);
+                },
+                Value::Scalar(scalar) => doc.put(&obj, key, scalar.into_owned())?,
+            },
+            PatchAction::PutSeq {
+                index,
+                value: (value, id),
+                ..
+            } => match value {
+                Value::Object(obj_type) => {
+                    let new_id = doc.put_object(&obj, index, obj_type)?;
+                    obj_ids.insert(id, new_id);
+                },
+                Value::Scalar(scalar) => doc.put(&obj, index, scalar.into_owned())?,
+            },
+            PatchAction::Insert { index, values, .. } => {
+                for (offset, (value, id, ..)) in values.iter().enumerate() {
+                    match value {
+                        Value::Object(obj_type) => {
+                            let new_id = doc.insert_object(&obj, index + offset, *obj_type)?;
+                            obj_ids.insert(id.clone(), new_id);
+                        },
+                        Value::Scalar(scalar) => {
+                            doc.insert(&obj, index + offset, scalar.clone().into_owned())?
+                        },
+                    }
+                }
+            },
+            PatchAction::SpliceText { index, value, .. } => {
+                doc.splice_text(&obj, index, 0, &value.make_string())?;
+            },
+            PatchAction::Increment { prop, value } => doc.increment(&obj, prop, value)?,
+            PatchAction::DeleteMap { key } => doc.delete(&obj, key)?,
+            PatchAction::DeleteSeq { index, length } => {
+                for _ in 0..length {
+                    doc.delete(&obj, index)?;
+                }
+            },
+            // Conflicts are resolved by the values which are put by the other
+            // patches, so there is nothing to apply.
+            PatchAction::Conflict { .. } => {},
+            // Entities are not stored with marks, so changes to marks are
+            // reported as unsupported rather than dropped silently.
+            PatchAction::Mark { .. } => {
+                return Err(Error::RollbackUnsupported {
+                    msg: "marks on text have changed".to_owned(),
+                });
+            },
+        }
+    }
+
+    Ok(())
+}
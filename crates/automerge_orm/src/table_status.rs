@@ -0,0 +1,16 @@
+/// Whether a table exists in the Automerge document, and whether it is empty.
+///
+/// This `enum` is created by the [`table_status`] method on
+/// [`EntityManager`]. See its documentation for more.
+///
+/// [`table_status`]: crate::EntityManager::table_status
+/// [`EntityManager`]: crate::EntityManager
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TableStatus {
+    /// The table does not exist.
+    Missing,
+    /// The table exists, but has no objects.
+    Empty,
+    /// The table exists, and has the given number of objects.
+    NonEmpty(usize),
+}
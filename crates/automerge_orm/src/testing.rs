@@ -0,0 +1,43 @@
+//! Utilities for testing entity types.
+
+use std::fmt::Debug;
+
+use autosurgeon::{Hydrate, Reconcile};
+
+use crate::{find, Entity, EntityManager, Keyed};
+
+/// Asserts that an entity survives a round trip through an Automerge
+/// document.
+///
+/// The entity is inserted into an [ephemeral] document, then found by its key
+/// and compared with the original entity. This surfaces mismatches between
+/// the [`Reconcile`] and [`Hydrate`] implementations of the entity type, e.g.
+/// fields which are renamed in only one of the derives.
+///
+/// [ephemeral]: EntityManager::ephemeral
+///
+/// # Panics
+///
+/// Panics if the entity cannot be inserted or found, or if the entity which is
+/// found is not equal to the original entity.
+pub fn assert_round_trip<T>(entity: &T)
+where
+    T: Entity + Keyed<Entity = T> + Hydrate + Reconcile + PartialEq + Debug,
+{
+    let entity_manager = EntityManager::ephemeral();
+    if let Err(err) = entity_manager.transact(|tx| tx.insert(entity)) {
+        panic!("failed to insert entity: {err}");
+    }
+    let found = match entity_manager
+        .document()
+        .with_doc(|doc| find(doc, entity.id()))
+    {
+        Ok(found) => found,
+        Err(err) => panic!("failed to find entity: {err}"),
+    };
+    assert_eq!(
+        found.as_ref(),
+        Some(entity),
+        "entity did not survive a round trip through the document"
+    );
+}
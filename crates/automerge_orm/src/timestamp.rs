@@ -0,0 +1,65 @@
+//! Stores a [`DateTime<Utc>`] field as an Automerge timestamp.
+//!
+//! `chrono` types do not implement [`Reconcile`] and [`Hydrate`]. Annotating a
+//! field with `#[autosurgeon(with = "automerge_orm::timestamp")]` stores it as
+//! a [`ScalarValue::Timestamp`] scalar, i.e. the number of milliseconds since
+//! the Unix epoch, which other Automerge clients read as a date:
+//!
+//! ```
+//! use automerge_orm::Entity;
+//! use autosurgeon::{Hydrate, Reconcile};
+//! use chrono::{DateTime, Utc};
+//! use uuid::Uuid;
+//!
+//! #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+//! struct Book {
+//!     #[key]
+//!     id: Uuid,
+//!     #[autosurgeon(with = "automerge_orm::timestamp")]
+//!     published_at: DateTime<Utc>,
+//! }
+//! ```
+//!
+//! Automerge timestamps have millisecond precision, so any sub-millisecond
+//! part of the value is truncated when it is stored.
+//!
+//! [`Reconcile`]: autosurgeon::Reconcile
+//! [`ScalarValue::Timestamp`]: automerge::ScalarValue::Timestamp
+
+use automerge::ObjId;
+use autosurgeon::{Hydrate, HydrateError, Prop, ReadDoc, Reconciler};
+use chrono::{DateTime, TimeZone, Utc};
+
+/// Hydrates a [`DateTime<Utc>`] from an Automerge timestamp.
+pub fn hydrate<D: ReadDoc>(
+    doc: &D,
+    obj: &ObjId,
+    prop: Prop<'_>,
+) -> Result<DateTime<Utc>, HydrateError> {
+    let Timestamp(millis) = Timestamp::hydrate(doc, obj, prop)?;
+
+    Utc.timestamp_millis_opt(millis).single().ok_or_else(|| {
+        HydrateError::unexpected(
+            "a timestamp within the range of `DateTime<Utc>`",
+            millis.to_string(),
+        )
+    })
+}
+
+/// Reconciles a [`DateTime<Utc>`] as an Automerge timestamp.
+pub fn reconcile<R: Reconciler>(
+    date_time: &DateTime<Utc>,
+    mut reconciler: R,
+) -> Result<(), R::Error> {
+    reconciler.timestamp(date_time.timestamp_millis())
+}
+
+/// The number of milliseconds since the Unix epoch, as stored in an Automerge
+/// timestamp.
+struct Timestamp(i64);
+
+impl Hydrate for Timestamp {
+    fn hydrate_timestamp(millis: i64) -> Result<Self, HydrateError> {
+        Ok(Self(millis))
+    }
+}
@@ -1,12 +1,28 @@
-use std::time::SystemTime;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::Arc,
+    time::SystemTime,
+};
 
 use automerge::{
     transaction::{CommitOptions, Transactable, Transaction as AutomergeTransaction},
-    Prop,
+    Automerge, AutomergeError, ChangeHash, ObjId, ObjType, Prop, ScalarValue, Value,
+};
+use autosurgeon::{
+    hydrate_prop, reconcile_insert, reconcile_prop, Hydrate, ReadDoc, Reconcile, ReconcileError,
 };
-use autosurgeon::{reconcile_prop, Hydrate, ReadDoc, Reconcile};
+#[cfg(feature = "serde_json")]
+use serde::de::DeserializeOwned;
 
-use crate::{create_table, find, get_table, Error, Key, Keyed, Mapped, Result};
+use crate::{
+    counter,
+    impls::{
+        create_table_under, expect_map, find_all_under, find_under, get_or_create_root,
+        get_raw_under, get_root, get_table_under, list_all_under,
+    },
+    mapped, migration, savepoint, DynEntity, Error, ImportReport, Key, Keyed, Lifecycle, Mapped,
+    OnConflict, Patch, Result,
+};
 
 /// A transaction which groups operations together.
 ///
@@ -18,19 +34,37 @@ use crate::{create_table, find, get_table, Error, Key, Keyed, Mapped, Result};
 #[derive(Debug)]
 pub struct Transaction<'a> {
     tx: AutomergeTransaction<'a>,
+    options: TransactionOptions,
+    /// The key of the map in the root of the document under which tables are
+    /// stored, or `None` if tables are stored in the root itself.
+    root: Option<String>,
+    /// The Automerge object ids of the tables resolved within this
+    /// transaction, keyed by table name.
+    tables: HashMap<&'static str, ObjId>,
 }
 
 impl<'a> Transaction<'a> {
     pub(crate) fn new(tx: AutomergeTransaction<'a>) -> Self {
-        Self { tx }
+        Self::with_options(tx, TransactionOptions::default(), None)
     }
 
-    /// Inserts a new object instance.
-    ///
-    /// The object will be inserted into the document as a result of the
-    /// [`commit`] operation.
+    pub(crate) fn with_options(
+        tx: AutomergeTransaction<'a>,
+        options: TransactionOptions,
+        root: Option<String>,
+    ) -> Self {
+        Self {
+            tx,
+            options,
+            root,
+            tables: HashMap::new(),
+        }
+    }
+
+    /// Finds an object by its key / identifier.
     ///
-    /// [`commit`]: Transaction::commit
+    /// Changes which have been queued up in this transaction are visible to
+    /// this operation.
     ///
     /// # Examples
     ///
@@ -75,94 +109,71 @@ impl<'a> Transaction<'a> {
     /// #
     /// use std::sync::Arc;
     ///
-    /// use automerge::ScalarValue;
-    /// use automerge_orm::{
-    ///     Entity,
-    ///     EntityManager,
-    ///     Keyed,
-    ///     Mapped,
-    /// };
-    /// use automerge_test::{assert_doc, map};
+    /// use automerge_orm::{Entity, EntityManager, Keyed};
     /// use autosurgeon::{Hydrate, Reconcile};
     /// use uuid::Uuid;
     ///
     /// #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    /// struct Author {
+    ///     #[key]
+    ///     id: Uuid,
+    ///     name: String,
+    /// }
+    ///
+    /// #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
     /// struct Book {
     ///     #[key]
     ///     id: Uuid,
+    ///     author_id: Uuid,
+    ///     author_name: String,
     /// }
     ///
-    /// impl Book {
-    ///     pub fn new() -> Self {
-    ///         Self { id: Uuid::new_v4() }
+    /// impl Author {
+    ///     pub fn new(name: &str) -> Self {
+    ///         Self {
+    ///             id: Uuid::new_v4(),
+    ///             name: name.to_owned(),
+    ///         }
     ///     }
     /// }
     ///
     /// # let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
     /// # let doc_handle = repo_handle.new_document();
-    /// let entity_manager = Arc::new(EntityManager::new(doc_handle.clone()));
+    /// let entity_manager = Arc::new(EntityManager::new(doc_handle));
     ///
-    /// let book = Book::new();
+    /// let author = Author::new("Miyazaki Hayao");
     /// entity_manager.transact(|tx| {
-    ///     tx.insert(&book)?;
+    ///     tx.insert(&author)?;
     ///     automerge_orm::Result::Ok(())
     /// })?;
-    ///
-    /// doc_handle.with_doc(|doc| {
-    ///     assert_doc!(
-    ///         doc,
-    ///         map!{
-    ///             Book::table_name() => {
-    ///                 map!{
-    ///                     book.id() => {
-    ///                         map!{
-    ///                             "id" => { ScalarValue::from(book.id()) },
-    ///                         },
-    ///                     },
-    ///                 },
-    ///             },
-    ///         }
-    ///     );
-    /// });
+    /// let book = entity_manager.transact(|tx| {
+    ///     let author = tx.find(author.id())?.unwrap();
+    ///     let book = Book {
+    ///         id: Uuid::new_v4(),
+    ///         author_id: author.id,
+    ///         author_name: author.name,
+    ///     };
+    ///     tx.insert(&book)?;
+    ///     automerge_orm::Result::Ok(book)
+    /// })?;
+    /// assert_eq!(book.author_name, author.name);
     /// # repo_handle.stop().unwrap();
     /// # Ok::<(), Box<dyn std::error::Error>>(())
     /// ```
-    pub fn insert<T>(&mut self, entity: &T) -> Result<()>
+    pub fn find<T>(&self, id: Key<T>) -> Result<Option<T>>
     where
-        T: Mapped + Keyed<Entity = T> + Reconcile,
+        T: Mapped + Hydrate,
     {
-        let table_id = if let Some(table_id) = get_table::<_, T>(&self.tx)? {
-            if self
-                .tx
-                .get(&table_id, Prop::Map(entity.id().to_string()))?
-                .is_some()
-            {
-                return Err(Error::ObjectAlreadyExists {
-                    table_name: <T as Mapped>::table_name(),
-                    id: entity.id().into(),
-                });
-            }
-            table_id
-        } else {
-            create_table::<_, T>(&mut self.tx)?
-        };
-        reconcile_prop(&mut self.tx, &table_id, &*entity.id().to_string(), entity)?;
-
-        Ok(())
+        find_under(&self.tx, self.root.as_deref(), id)
     }
 
-    /// Inserts a new object instance computed from `f` if an object with the
-    /// same `id` does not exist, then returns the object identified by `id`.
-    ///
-    /// The new object will be inserted into the document as a result of the
-    /// [`commit`] operation.
+    /// Finds all objects of a specific type.
     ///
-    /// [`commit`]: Transaction::commit
+    /// Changes which have been queued up in this transaction are visible to
+    /// this operation.
     ///
     /// # Examples
     ///
-    /// Get an existing object instance:
-    ///
     /// ```
     /// # use automerge::ChangeHash;
     /// # use automerge_repo::{DocumentId, Repo, Storage, StorageError};
@@ -204,14 +215,7 @@ impl<'a> Transaction<'a> {
     /// #
     /// use std::sync::Arc;
     ///
-    /// use automerge::ScalarValue;
-    /// use automerge_orm::{
-    ///     Entity,
-    ///     EntityManager,
-    ///     Keyed,
-    ///     Mapped,
-    /// };
-    /// use automerge_test::{assert_doc, map};
+    /// use automerge_orm::{Entity, EntityManager};
     /// use autosurgeon::{Hydrate, Reconcile};
     /// use uuid::Uuid;
     ///
@@ -219,63 +223,40 @@ impl<'a> Transaction<'a> {
     /// struct Book {
     ///     #[key]
     ///     id: Uuid,
-    ///     author: String,
     /// }
     ///
     /// impl Book {
-    ///     pub fn new(id: Uuid, author: &str) -> Self {
-    ///         Self {
-    ///             id,
-    ///             author: author.to_owned(),
-    ///         }
-    ///     }
-    ///
-    ///     pub fn author(&self) -> &str {
-    ///         &self.author
+    ///     pub fn new() -> Self {
+    ///         Self { id: Uuid::new_v4() }
     ///     }
     /// }
     ///
     /// # let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
     /// # let doc_handle = repo_handle.new_document();
-    /// let entity_manager = Arc::new(EntityManager::new(doc_handle.clone()));
+    /// let entity_manager = Arc::new(EntityManager::new(doc_handle));
     ///
-    /// let book_id = Uuid::new_v4();
-    /// let book_in = Book::new(book_id, "Miyazaki Hayao");
-    /// entity_manager.transact(|tx| {
-    ///     tx.insert(&book_in)?;
-    ///     automerge_orm::Result::Ok(())
-    /// })?;
-    /// let book = entity_manager.transact(|tx| {
-    ///     let book = tx.get_or_insert(book_id.into(), || {
-    ///         Book::new(book_id, "Shinkai Makoto")
-    ///     })?;
-    ///     automerge_orm::Result::Ok(book)
+    /// let books = entity_manager.transact(|tx| {
+    ///     tx.insert(&Book::new())?;
+    ///     tx.insert(&Book::new())?;
+    ///     tx.find_all::<Book>()
     /// })?;
-    /// assert_eq!(book.id(), book_in.id());
-    /// assert_eq!(book.author(), book_in.author());
-    ///
-    /// doc_handle.with_doc(|doc| {
-    ///     assert_doc!(
-    ///         doc,
-    ///         map! {
-    ///             Book::table_name() => {
-    ///                 map!{
-    ///                     book_id => {
-    ///                         map!{
-    ///                             "id" => { ScalarValue::from(book.id()) },
-    ///                             "author" => { book_in.author() },
-    ///                         },
-    ///                     },
-    ///                 },
-    ///             },
-    ///         }
-    ///     );
-    /// });
+    /// assert_eq!(books.len(), 2);
     /// # repo_handle.stop().unwrap();
     /// # Ok::<(), Box<dyn std::error::Error>>(())
     /// ```
+    pub fn find_all<T>(&self) -> Result<BTreeMap<String, T>>
+    where
+        T: Mapped + Hydrate,
+    {
+        find_all_under(&self.tx, self.root.as_deref())
+    }
+
+    /// Lists all objects of a specific type, ordered by key.
     ///
-    /// Insert a new object instance:
+    /// Changes which have been queued up in this transaction are visible to
+    /// this operation.
+    ///
+    /// # Examples
     ///
     /// ```
     /// # use automerge::ChangeHash;
@@ -318,14 +299,7 @@ impl<'a> Transaction<'a> {
     /// #
     /// use std::sync::Arc;
     ///
-    /// use automerge::ScalarValue;
-    /// use automerge_orm::{
-    ///     Entity,
-    ///     EntityManager,
-    ///     Keyed,
-    ///     Mapped,
-    /// };
-    /// use automerge_test::{assert_doc, map};
+    /// use automerge_orm::{Entity, EntityManager};
     /// use autosurgeon::{Hydrate, Reconcile};
     /// use uuid::Uuid;
     ///
@@ -336,75 +310,44 @@ impl<'a> Transaction<'a> {
     /// }
     ///
     /// impl Book {
-    ///     pub fn new(id: Uuid) -> Self {
-    ///         Self { id }
+    ///     pub fn new() -> Self {
+    ///         Self { id: Uuid::new_v4() }
     ///     }
     /// }
     ///
     /// # let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
     /// # let doc_handle = repo_handle.new_document();
-    /// let entity_manager = Arc::new(EntityManager::new(doc_handle.clone()));
+    /// let entity_manager = Arc::new(EntityManager::new(doc_handle));
     ///
-    /// let book_id = Uuid::new_v4();
-    /// let book = entity_manager.transact(|tx| {
-    ///     let book = tx.get_or_insert(book_id.into(), || Book::new(book_id))?;
-    ///     automerge_orm::Result::Ok(book)
+    /// let books = entity_manager.transact(|tx| {
+    ///     tx.insert(&Book::new())?;
+    ///     tx.insert(&Book::new())?;
+    ///     tx.list::<Book>()
     /// })?;
-    /// assert_eq!(book.id(), book_id.into());
-    ///
-    /// doc_handle.with_doc(|doc| {
-    ///     assert_doc!(
-    ///         doc,
-    ///         map!{
-    ///             Book::table_name() => {
-    ///                 map!{
-    ///                     book_id => {
-    ///                         map!{
-    ///                             "id" => { ScalarValue::from(book.id()) },
-    ///                         },
-    ///                     },
-    ///                 },
-    ///             },
-    ///         }
-    ///     );
-    /// });
+    /// assert_eq!(books.len(), 2);
+    /// assert!(books[0].id < books[1].id);
     /// # repo_handle.stop().unwrap();
     /// # Ok::<(), Box<dyn std::error::Error>>(())
     /// ```
-    pub fn get_or_insert<T, F>(&mut self, id: Key<T>, f: F) -> Result<T>
+    pub fn list<T>(&self) -> Result<Vec<T>>
     where
-        T: Mapped + Keyed<Entity = T> + Hydrate + Reconcile,
-        F: FnOnce() -> T,
+        T: Mapped + Hydrate,
     {
-        let entity = find(&self.tx, id)?;
-        let entity = if let Some(entity) = entity {
-            entity
-        } else {
-            let entity = f();
-            if entity.id() != id {
-                return Err(Error::KeyMismatch {
-                    actual: entity.id().into(),
-                    expected: id.into(),
-                    msg: format!(
-                        "key obtained from `<{} as automerge_orm::Keyed>::id()` does not match \
-                        provided `id` key",
-                        std::any::type_name::<T>()
-                    ),
-                });
-            }
-            self.insert(&entity)?;
-            entity
-        };
-
-        Ok(entity)
+        list_all_under(&self.tx, self.root.as_deref())
     }
 
-    /// Updates an existing object instance.
+    /// Returns the raw Automerge value of the `field` property of an object,
+    /// together with its Automerge object id.
     ///
-    /// The object will be updated in the document as a result of the [`commit`]
-    /// operation.
+    /// This is a low-level escape hatch, e.g. to read properties which are not
+    /// modelled by the entity type yet. Prefer [`find`] otherwise. See
+    /// [`impls::get_raw`] for more.
     ///
-    /// [`commit`]: Transaction::commit
+    /// Changes which have been queued up in this transaction are visible to
+    /// this operation.
+    ///
+    /// [`find`]: Transaction::find
+    /// [`impls::get_raw`]: crate::impls::get_raw
     ///
     /// # Examples
     ///
@@ -449,14 +392,7 @@ impl<'a> Transaction<'a> {
     /// #
     /// use std::sync::Arc;
     ///
-    /// use automerge::ScalarValue;
-    /// use automerge_orm::{
-    ///     Entity,
-    ///     EntityManager,
-    ///     Keyed,
-    ///     Mapped,
-    /// };
-    /// use automerge_test::{assert_doc, map};
+    /// use automerge_orm::{Entity, EntityManager, Keyed};
     /// use autosurgeon::{Hydrate, Reconcile};
     /// use uuid::Uuid;
     ///
@@ -474,88 +410,40 @@ impl<'a> Transaction<'a> {
     ///             author: author.to_owned(),
     ///         }
     ///     }
-    ///
-    ///     pub fn author(&self) -> &str {
-    ///         &self.author
-    ///     }
-    ///
-    ///     pub fn set_author(&mut self, author: &str) {
-    ///         self.author = author.to_owned();
-    ///     }
     /// }
     ///
     /// # let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
     /// # let doc_handle = repo_handle.new_document();
-    /// let entity_manager = Arc::new(EntityManager::new(doc_handle.clone()));
+    /// let entity_manager = Arc::new(EntityManager::new(doc_handle));
     ///
-    /// let mut book = Book::new("Miyazaki Hayao");
-    /// entity_manager.transact(|tx| {
+    /// let book = Book::new("Miyazaki Hayao");
+    /// let author = entity_manager.transact(|tx| {
     ///     tx.insert(&book)?;
-    ///     automerge_orm::Result::Ok(())
-    /// })?;
-    /// book.set_author("Shinkai Makoto");
-    /// entity_manager.transact(|tx| {
-    ///     tx.update(&book)?;
-    ///     automerge_orm::Result::Ok(())
+    ///     let author = tx
+    ///         .get_raw(book.id(), "author")?
+    ///         .and_then(|(value, _)| value.to_str().map(str::to_owned));
+    ///     automerge_orm::Result::Ok(author)
     /// })?;
-    ///
-    /// doc_handle.with_doc(|doc| {
-    ///     assert_doc!(
-    ///         doc,
-    ///         map! {
-    ///             Book::table_name() => {
-    ///                 map!{
-    ///                     book.id() => {
-    ///                         map!{
-    ///                             "id" => { ScalarValue::from(book.id()) },
-    ///                             "author" => { book.author() },
-    ///                         },
-    ///                     },
-    ///                 },
-    ///             },
-    ///         }
-    ///     );
-    /// });
+    /// assert_eq!(author.as_deref(), Some("Miyazaki Hayao"));
     /// # repo_handle.stop().unwrap();
     /// # Ok::<(), Box<dyn std::error::Error>>(())
     /// ```
-    pub fn update<T>(&mut self, entity: &T) -> Result<()>
+    pub fn get_raw<T>(&self, id: Key<T>, field: &str) -> Result<Option<(Value<'_>, ObjId)>>
     where
-        T: Mapped + Keyed<Entity = T> + Reconcile,
+        T: Mapped,
     {
-        let Some(table_id) = get_table::<_, T>(&self.tx)? else {
-            return Err(Error::ObjectDoesNotExist {
-                table_name: <T as Mapped>::table_name(),
-                id: entity.id().into(),
-            });
-        };
-        if self
-            .tx
-            .get(&table_id, Prop::Map(entity.id().to_string()))?
-            .is_none()
-        {
-            return Err(Error::ObjectDoesNotExist {
-                table_name: <T as Mapped>::table_name(),
-                id: entity.id().into(),
-            });
-        }
-        reconcile_prop(&mut self.tx, &table_id, &*entity.id().to_string(), entity)?;
-
-        Ok(())
+        get_raw_under(&self.tx, self.root.as_deref(), id, field)
     }
 
-    /// Updates an existing object instance, or inserts a new object instance if
-    /// it does not already exist.
+    /// Inserts a new object instance.
     ///
-    /// The object will be updated in / inserted into the document as a result
-    /// of the [`commit`] operation.
+    /// The object will be inserted into the document as a result of the
+    /// [`commit`] operation.
     ///
     /// [`commit`]: Transaction::commit
     ///
     /// # Examples
     ///
-    /// Update an existing object instance:
-    ///
     /// ```
     /// # use automerge::ChangeHash;
     /// # use automerge_repo::{DocumentId, Repo, Storage, StorageError};
@@ -612,23 +500,11 @@ impl<'a> Transaction<'a> {
     /// struct Book {
     ///     #[key]
     ///     id: Uuid,
-    ///     author: String,
     /// }
     ///
     /// impl Book {
-    ///     pub fn new(author: &str) -> Self {
-    ///         Self {
-    ///             id: Uuid::new_v4(),
-    ///             author: author.to_owned(),
-    ///         }
-    ///     }
-    ///
-    ///     pub fn author(&self) -> &str {
-    ///         &self.author
-    ///     }
-    ///
-    ///     pub fn set_author(&mut self, author: &str) {
-    ///         self.author = author.to_owned();
+    ///     pub fn new() -> Self {
+    ///         Self { id: Uuid::new_v4() }
     ///     }
     /// }
     ///
@@ -636,27 +512,21 @@ impl<'a> Transaction<'a> {
     /// # let doc_handle = repo_handle.new_document();
     /// let entity_manager = Arc::new(EntityManager::new(doc_handle.clone()));
     ///
-    /// let mut book = Book::new("Miyazaki Hayao");
-    /// entity_manager.transact(|tx| {
-    ///     tx.upsert(&book)?;
-    ///     automerge_orm::Result::Ok(())
-    /// })?;
-    /// book.set_author("Shinkai Makoto");
+    /// let book = Book::new();
     /// entity_manager.transact(|tx| {
-    ///     tx.upsert(&book)?;
+    ///     tx.insert(&book)?;
     ///     automerge_orm::Result::Ok(())
     /// })?;
     ///
     /// doc_handle.with_doc(|doc| {
     ///     assert_doc!(
     ///         doc,
-    ///         map! {
+    ///         map!{
     ///             Book::table_name() => {
     ///                 map!{
     ///                     book.id() => {
     ///                         map!{
     ///                             "id" => { ScalarValue::from(book.id()) },
-    ///                             "author" => { book.author() },
     ///                         },
     ///                     },
     ///                 },
@@ -667,8 +537,133 @@ impl<'a> Transaction<'a> {
     /// # repo_handle.stop().unwrap();
     /// # Ok::<(), Box<dyn std::error::Error>>(())
     /// ```
+    pub fn insert<T>(&mut self, entity: &T) -> Result<()>
+    where
+        T: Mapped + Keyed<Entity = T> + Reconcile,
+    {
+        let table_id = self.get_or_create_table::<T>()?;
+        if self
+            .tx
+            .get(&table_id, Prop::Map(entity.id().to_prop()))?
+            .is_some()
+        {
+            return Err(Error::ObjectAlreadyExists {
+                table_name: <T as Mapped>::table_name().to_owned(),
+                id: entity.id().into(),
+            });
+        }
+        self.write_entity(
+            &table_id,
+            entity.id(),
+            entity,
+            WriteHooks::Insert,
+            WriteMode::Reconcile,
+        )
+    }
+
+    /// Inserts a new object instance of an entity type which is only known at
+    /// runtime, e.g. one of a heterogeneous collection of entities.
     ///
-    /// Insert a new object instance:
+    /// This behaves like [`insert`] for the concrete entity type.
+    ///
+    /// [`insert`]: Transaction::insert
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use automerge::ChangeHash;
+    /// # use automerge_repo::{DocumentId, Repo, Storage, StorageError};
+    /// # use futures::future::{self, BoxFuture, FutureExt};
+    /// #
+    /// # pub struct NoopStorage;
+    /// #
+    /// # impl Storage for NoopStorage {
+    /// #     fn get(
+    /// #         &self,
+    /// #         _id: DocumentId
+    /// #     ) -> BoxFuture<'static, Result<Option<Vec<u8>>, StorageError>> {
+    /// #         future::ready(Ok(None)).boxed()
+    /// #     }
+    /// #
+    /// #     fn list_all(
+    /// #         &self
+    /// #     ) -> BoxFuture<'static, Result<Vec<DocumentId>, StorageError>> {
+    /// #         future::ready(Ok(Vec::new())).boxed()
+    /// #     }
+    /// #
+    /// #     fn append(
+    /// #         &self,
+    /// #         _id: DocumentId,
+    /// #         _chunk: Vec<u8>,
+    /// #     ) -> BoxFuture<'static, Result<(), StorageError>> {
+    /// #         future::ready(Ok(())).boxed()
+    /// #     }
+    /// #
+    /// #     fn compact(
+    /// #         &self,
+    /// #         _id: DocumentId,
+    /// #         _chunk: Vec<u8>,
+    /// #         _new_heads: Vec<ChangeHash>,
+    /// #     ) -> BoxFuture<'static, Result<(), StorageError>> {
+    /// #         future::ready(Ok(())).boxed()
+    /// #     }
+    /// # }
+    /// #
+    /// use std::sync::Arc;
+    ///
+    /// use automerge_orm::{DynEntity, Entity, EntityManager};
+    /// use autosurgeon::{Hydrate, Reconcile};
+    /// use uuid::Uuid;
+    ///
+    /// #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    /// struct Book {
+    ///     #[key]
+    ///     id: Uuid,
+    /// }
+    ///
+    /// #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    /// struct Author {
+    ///     #[key]
+    ///     id: Uuid,
+    /// }
+    ///
+    /// # let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    /// # let doc_handle = repo_handle.new_document();
+    /// let entity_manager = Arc::new(EntityManager::new(doc_handle));
+    ///
+    /// let entities: Vec<Box<dyn DynEntity>> = vec![
+    ///     Box::new(Book { id: Uuid::new_v4() }),
+    ///     Box::new(Author { id: Uuid::new_v4() }),
+    /// ];
+    /// entity_manager.transact(|tx| {
+    ///     for entity in &entities {
+    ///         tx.insert_dyn(entity.as_ref())?;
+    ///     }
+    ///     automerge_orm::Result::Ok(())
+    /// })?;
+    /// # repo_handle.stop().unwrap();
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn insert_dyn(&mut self, entity: &dyn DynEntity) -> Result<()> {
+        entity.insert_into(self)
+    }
+
+    /// Inserts a new object instance, and returns it.
+    ///
+    /// This behaves like [`insert`], but takes ownership of the object
+    /// instance, so that it can be constructed and inserted in a single
+    /// expression. The object instance is returned as it was passed in, i.e.
+    /// modifications made by the [`before_insert`] hook are only written to the
+    /// document.
+    ///
+    /// The new object will be inserted into the document as a result of the
+    /// [`commit`] operation.
+    ///
+    /// [`insert`]: Transaction::insert
+    /// [`before_insert`]: crate::Hooks::before_insert
+    /// [`commit`]: Transaction::commit
+    ///
+    /// # Examples
     ///
     /// ```
     /// # use automerge::ChangeHash;
@@ -711,14 +706,13 @@ impl<'a> Transaction<'a> {
     /// #
     /// use std::sync::Arc;
     ///
-    /// use automerge::ScalarValue;
     /// use automerge_orm::{
+    ///     DefaultEntityRepository,
     ///     Entity,
     ///     EntityManager,
+    ///     EntityRepository,
     ///     Keyed,
-    ///     Mapped,
     /// };
-    /// use automerge_test::{assert_doc, map};
     /// use autosurgeon::{Hydrate, Reconcile};
     /// use uuid::Uuid;
     ///
@@ -728,6 +722,8 @@ impl<'a> Transaction<'a> {
     ///     id: Uuid,
     /// }
     ///
+    /// type BookRepository = DefaultEntityRepository<Book>;
+    ///
     /// impl Book {
     ///     pub fn new() -> Self {
     ///         Self { id: Uuid::new_v4() }
@@ -736,56 +732,35 @@ impl<'a> Transaction<'a> {
     ///
     /// # let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
     /// # let doc_handle = repo_handle.new_document();
-    /// let entity_manager = Arc::new(EntityManager::new(doc_handle.clone()));
-    ///
-    /// let book = Book::new();
-    /// entity_manager.transact(|tx| {
-    ///     tx.upsert(&book)?;
-    ///     automerge_orm::Result::Ok(())
-    /// })?;
+    /// let entity_manager = Arc::new(EntityManager::new(doc_handle));
+    /// let book_repository = BookRepository::new(Arc::clone(&entity_manager));
     ///
-    /// doc_handle.with_doc(|doc| {
-    ///     assert_doc!(
-    ///         doc,
-    ///         map!{
-    ///             Book::table_name() => {
-    ///                 map!{
-    ///                     book.id() => {
-    ///                         map!{
-    ///                             "id" => { ScalarValue::from(book.id()) },
-    ///                         },
-    ///                     },
-    ///                 },
-    ///             },
-    ///         }
-    ///     );
-    /// });
+    /// let book = entity_manager.transact(|tx| tx.insert_owned(Book::new()))?;
+    /// assert!(book_repository.find(book.id())?.is_some());
     /// # repo_handle.stop().unwrap();
     /// # Ok::<(), Box<dyn std::error::Error>>(())
     /// ```
-    pub fn upsert<T>(&mut self, entity: &T) -> Result<()>
+    pub fn insert_owned<T>(&mut self, entity: T) -> Result<T>
     where
         T: Mapped + Keyed<Entity = T> + Reconcile,
     {
-        let table_id = if let Some(table_id) = get_table::<_, T>(&self.tx)? {
-            table_id
-        } else {
-            create_table::<_, T>(&mut self.tx)?
-        };
-        reconcile_prop(&mut self.tx, &table_id, &*entity.id().to_string(), entity)?;
+        self.insert(&entity)?;
 
-        Ok(())
+        Ok(entity)
     }
 
-    /// Removes an object by its identifier.
+    /// Inserts a new object instance computed from `f` if an object with the
+    /// same `id` does not exist, then returns the object identified by `id`.
     ///
-    /// The object will be removed from the document as a result of the
+    /// The new object will be inserted into the document as a result of the
     /// [`commit`] operation.
     ///
     /// [`commit`]: Transaction::commit
     ///
     /// # Examples
     ///
+    /// Get an existing object instance:
+    ///
     /// ```
     /// # use automerge::ChangeHash;
     /// # use automerge_repo::{DocumentId, Repo, Storage, StorageError};
@@ -827,11 +802,10 @@ impl<'a> Transaction<'a> {
     /// #
     /// use std::sync::Arc;
     ///
+    /// use automerge::ScalarValue;
     /// use automerge_orm::{
-    ///     DefaultEntityRepository,
     ///     Entity,
     ///     EntityManager,
-    ///     EntityRepository,
     ///     Keyed,
     ///     Mapped,
     /// };
@@ -843,42 +817,54 @@ impl<'a> Transaction<'a> {
     /// struct Book {
     ///     #[key]
     ///     id: Uuid,
+    ///     author: String,
     /// }
     ///
-    /// type BookRepository = DefaultEntityRepository<Book>;
-    ///
     /// impl Book {
-    ///     pub fn new() -> Self {
-    ///         Self { id: Uuid::new_v4() }
+    ///     pub fn new(id: Uuid, author: &str) -> Self {
+    ///         Self {
+    ///             id,
+    ///             author: author.to_owned(),
+    ///         }
+    ///     }
+    ///
+    ///     pub fn author(&self) -> &str {
+    ///         &self.author
     ///     }
     /// }
     ///
     /// # let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
     /// # let doc_handle = repo_handle.new_document();
     /// let entity_manager = Arc::new(EntityManager::new(doc_handle.clone()));
-    /// let book_repository = BookRepository::new(Arc::clone(&entity_manager));
     ///
-    /// let book = Book::new();
+    /// let book_id = Uuid::new_v4();
+    /// let book_in = Book::new(book_id, "Miyazaki Hayao");
     /// entity_manager.transact(|tx| {
-    ///     tx.insert(&book)?;
+    ///     tx.insert(&book_in)?;
     ///     automerge_orm::Result::Ok(())
     /// })?;
-    /// let book = book_repository.find(book.id())?;
-    /// assert!(book.is_some());
-    /// let book = book.unwrap();
-    /// entity_manager.transact(|tx| {
-    ///     tx.remove(book.id())?;
-    ///     automerge_orm::Result::Ok(())
+    /// let book = entity_manager.transact(|tx| {
+    ///     let book = tx.get_or_insert(book_id.into(), || {
+    ///         Book::new(book_id, "Shinkai Makoto")
+    ///     })?;
+    ///     automerge_orm::Result::Ok(book)
     /// })?;
-    /// let book = book_repository.find(book.id())?;
-    /// assert!(book.is_none());
+    /// assert_eq!(book.id(), book_in.id());
+    /// assert_eq!(book.author(), book_in.author());
     ///
     /// doc_handle.with_doc(|doc| {
     ///     assert_doc!(
     ///         doc,
-    ///         map!{
+    ///         map! {
     ///             Book::table_name() => {
-    ///                 map!{},
+    ///                 map!{
+    ///                     book_id => {
+    ///                         map!{
+    ///                             "id" => { ScalarValue::from(book.id()) },
+    ///                             "author" => { book_in.author() },
+    ///                         },
+    ///                     },
+    ///                 },
     ///             },
     ///         }
     ///     );
@@ -886,34 +872,2782 @@ impl<'a> Transaction<'a> {
     /// # repo_handle.stop().unwrap();
     /// # Ok::<(), Box<dyn std::error::Error>>(())
     /// ```
-    pub fn remove<T>(&mut self, id: Key<T>) -> Result<()>
-    where
-        T: Mapped,
-    {
-        let Some(table_id) = get_table::<_, T>(&self.tx)? else {
-            return Ok(());
-        };
-        self.tx.delete(&table_id, Prop::Map(id.to_string()))?;
-
-        Ok(())
-    }
-
-    /// Commits all changes that have been queued up to now to the document.
-    pub fn commit(self) -> Result<()> {
-        let now = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .unwrap();
-        self.tx.commit_with(
-            CommitOptions::default()
-                .with_message("automerge_orm::Transaction::commit")
-                .with_time(now.as_secs() as i64),
-        );
-
-        Ok(())
-    }
-
-    /// Rolls back all changes that have been queued up.
-    pub fn rollback(self) {
-        self.tx.rollback();
+    ///
+    /// Insert a new object instance:
+    ///
+    /// ```
+    /// # use automerge::ChangeHash;
+    /// # use automerge_repo::{DocumentId, Repo, Storage, StorageError};
+    /// # use futures::future::{self, BoxFuture, FutureExt};
+    /// #
+    /// # pub struct NoopStorage;
+    /// #
+    /// # impl Storage for NoopStorage {
+    /// #     fn get(
+    /// #         &self,
+    /// #         _id: DocumentId
+    /// #     ) -> BoxFuture<'static, Result<Option<Vec<u8>>, StorageError>> {
+    /// #         future::ready(Ok(None)).boxed()
+    /// #     }
+    /// #
+    /// #     fn list_all(
+    /// #         &self
+    /// #     ) -> BoxFuture<'static, Result<Vec<DocumentId>, StorageError>> {
+    /// #         future::ready(Ok(Vec::new())).boxed()
+    /// #     }
+    /// #
+    /// #     fn append(
+    /// #         &self,
+    /// #         _id: DocumentId,
+    /// #         _chunk: Vec<u8>,
+    /// #     ) -> BoxFuture<'static, Result<(), StorageError>> {
+    /// #         future::ready(Ok(())).boxed()
+    /// #     }
+    /// #
+    /// #     fn compact(
+    /// #         &self,
+    /// #         _id: DocumentId,
+    /// #         _chunk: Vec<u8>,
+    /// #         _new_heads: Vec<ChangeHash>,
+    /// #     ) -> BoxFuture<'static, Result<(), StorageError>> {
+    /// #         future::ready(Ok(())).boxed()
+    /// #     }
+    /// # }
+    /// #
+    /// use std::sync::Arc;
+    ///
+    /// use automerge::ScalarValue;
+    /// use automerge_orm::{
+    ///     Entity,
+    ///     EntityManager,
+    ///     Keyed,
+    ///     Mapped,
+    /// };
+    /// use automerge_test::{assert_doc, map};
+    /// use autosurgeon::{Hydrate, Reconcile};
+    /// use uuid::Uuid;
+    ///
+    /// #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    /// struct Book {
+    ///     #[key]
+    ///     id: Uuid,
+    /// }
+    ///
+    /// impl Book {
+    ///     pub fn new(id: Uuid) -> Self {
+    ///         Self { id }
+    ///     }
+    /// }
+    ///
+    /// # let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    /// # let doc_handle = repo_handle.new_document();
+    /// let entity_manager = Arc::new(EntityManager::new(doc_handle.clone()));
+    ///
+    /// let book_id = Uuid::new_v4();
+    /// let book = entity_manager.transact(|tx| {
+    ///     let book = tx.get_or_insert(book_id.into(), || Book::new(book_id))?;
+    ///     automerge_orm::Result::Ok(book)
+    /// })?;
+    /// assert_eq!(book.id(), book_id.into());
+    ///
+    /// doc_handle.with_doc(|doc| {
+    ///     assert_doc!(
+    ///         doc,
+    ///         map!{
+    ///             Book::table_name() => {
+    ///                 map!{
+    ///                     book_id => {
+    ///                         map!{
+    ///                             "id" => { ScalarValue::from(book.id()) },
+    ///                         },
+    ///                     },
+    ///                 },
+    ///             },
+    ///         }
+    ///     );
+    /// });
+    /// # repo_handle.stop().unwrap();
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn get_or_insert<T, F>(&mut self, id: Key<T>, f: F) -> Result<T>
+    where
+        T: Mapped + Keyed<Entity = T> + Hydrate + Reconcile,
+        F: FnOnce() -> T,
+    {
+        let entity = find_under(&self.tx, self.root.as_deref(), id)?;
+        let entity = if let Some(entity) = entity {
+            entity
+        } else {
+            let entity = f();
+            if entity.id() != id {
+                return Err(key_mismatch(entity.id(), id, "provided `id` key"));
+            }
+            self.insert(&entity)?;
+            entity
+        };
+
+        Ok(entity)
+    }
+
+    /// Modifies the object identified by `id` using `update` if it exists, or
+    /// inserts a new object instance computed from `insert` otherwise, then
+    /// returns the object which was written.
+    ///
+    /// Unlike [`get_or_insert`], an existing object is updated in the same
+    /// way as by [`update`].
+    ///
+    /// The object will be inserted into / updated in the document as a result
+    /// of the [`commit`] operation.
+    ///
+    /// [`get_or_insert`]: Transaction::get_or_insert
+    /// [`update`]: Transaction::update
+    /// [`commit`]: Transaction::commit
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use automerge::ChangeHash;
+    /// # use automerge_repo::{DocumentId, Repo, Storage, StorageError};
+    /// # use futures::future::{self, BoxFuture, FutureExt};
+    /// #
+    /// # pub struct NoopStorage;
+    /// #
+    /// # impl Storage for NoopStorage {
+    /// #     fn get(
+    /// #         &self,
+    /// #         _id: DocumentId
+    /// #     ) -> BoxFuture<'static, Result<Option<Vec<u8>>, StorageError>> {
+    /// #         future::ready(Ok(None)).boxed()
+    /// #     }
+    /// #
+    /// #     fn list_all(
+    /// #         &self
+    /// #     ) -> BoxFuture<'static, Result<Vec<DocumentId>, StorageError>> {
+    /// #         future::ready(Ok(Vec::new())).boxed()
+    /// #     }
+    /// #
+    /// #     fn append(
+    /// #         &self,
+    /// #         _id: DocumentId,
+    /// #         _chunk: Vec<u8>,
+    /// #     ) -> BoxFuture<'static, Result<(), StorageError>> {
+    /// #         future::ready(Ok(())).boxed()
+    /// #     }
+    /// #
+    /// #     fn compact(
+    /// #         &self,
+    /// #         _id: DocumentId,
+    /// #         _chunk: Vec<u8>,
+    /// #         _new_heads: Vec<ChangeHash>,
+    /// #     ) -> BoxFuture<'static, Result<(), StorageError>> {
+    /// #         future::ready(Ok(())).boxed()
+    /// #     }
+    /// # }
+    /// #
+    /// use std::sync::Arc;
+    ///
+    /// use automerge_orm::{Entity, EntityManager};
+    /// use autosurgeon::{Hydrate, Reconcile};
+    /// use uuid::Uuid;
+    ///
+    /// #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    /// struct Book {
+    ///     #[key]
+    ///     id: Uuid,
+    ///     copies: u64,
+    /// }
+    ///
+    /// impl Book {
+    ///     pub fn new(id: Uuid) -> Self {
+    ///         Self { id, copies: 1 }
+    ///     }
+    /// }
+    ///
+    /// # let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    /// # let doc_handle = repo_handle.new_document();
+    /// let entity_manager = Arc::new(EntityManager::new(doc_handle));
+    ///
+    /// let book_id = Uuid::new_v4();
+    /// let book = entity_manager.transact(|tx| {
+    ///     tx.modify_or_insert(book_id.into(), |book: &mut Book| book.copies += 1, || {
+    ///         Book::new(book_id)
+    ///     })
+    /// })?;
+    /// assert_eq!(book.copies, 1);
+    /// let book = entity_manager.transact(|tx| {
+    ///     tx.modify_or_insert(book_id.into(), |book: &mut Book| book.copies += 1, || {
+    ///         Book::new(book_id)
+    ///     })
+    /// })?;
+    /// assert_eq!(book.copies, 2);
+    /// # repo_handle.stop().unwrap();
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn modify_or_insert<T, U, I>(&mut self, id: Key<T>, update: U, insert: I) -> Result<T>
+    where
+        T: Mapped + Keyed<Entity = T> + Hydrate + Reconcile,
+        U: FnOnce(&mut T),
+        I: FnOnce() -> T,
+    {
+        let entity = if let Some(mut entity) = find_under(&self.tx, self.root.as_deref(), id)? {
+            update(&mut entity);
+            if entity.id() != id {
+                return Err(key_mismatch(entity.id(), id, "provided `id` key"));
+            }
+            self.update(&entity)?;
+            entity
+        } else {
+            let entity = insert();
+            if entity.id() != id {
+                return Err(key_mismatch(entity.id(), id, "provided `id` key"));
+            }
+            self.insert(&entity)?;
+            entity
+        };
+
+        Ok(entity)
+    }
+
+    /// Updates an existing object instance.
+    ///
+    /// The object instance is reconciled with the object stored in the
+    /// document. Properties of fields which are set from a value to `None`
+    /// are deleted from the stored object. Fields which are `None` but whose
+    /// properties are missing are stored as null, as `autosurgeon` does, and
+    /// properties which are already null are kept as is. Values nested in
+    /// fields, e.g. the fields of a nested struct or the entries of a map, are
+    /// stored as null rather than deleted when they are set to `None`.
+    ///
+    /// The object will be updated in the document as a result of the [`commit`]
+    /// operation.
+    ///
+    /// [`commit`]: Transaction::commit
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use automerge::ChangeHash;
+    /// # use automerge_repo::{DocumentId, Repo, Storage, StorageError};
+    /// # use futures::future::{self, BoxFuture, FutureExt};
+    /// #
+    /// # pub struct NoopStorage;
+    /// #
+    /// # impl Storage for NoopStorage {
+    /// #     fn get(
+    /// #         &self,
+    /// #         _id: DocumentId
+    /// #     ) -> BoxFuture<'static, Result<Option<Vec<u8>>, StorageError>> {
+    /// #         future::ready(Ok(None)).boxed()
+    /// #     }
+    /// #
+    /// #     fn list_all(
+    /// #         &self
+    /// #     ) -> BoxFuture<'static, Result<Vec<DocumentId>, StorageError>> {
+    /// #         future::ready(Ok(Vec::new())).boxed()
+    /// #     }
+    /// #
+    /// #     fn append(
+    /// #         &self,
+    /// #         _id: DocumentId,
+    /// #         _chunk: Vec<u8>,
+    /// #     ) -> BoxFuture<'static, Result<(), StorageError>> {
+    /// #         future::ready(Ok(())).boxed()
+    /// #     }
+    /// #
+    /// #     fn compact(
+    /// #         &self,
+    /// #         _id: DocumentId,
+    /// #         _chunk: Vec<u8>,
+    /// #         _new_heads: Vec<ChangeHash>,
+    /// #     ) -> BoxFuture<'static, Result<(), StorageError>> {
+    /// #         future::ready(Ok(())).boxed()
+    /// #     }
+    /// # }
+    /// #
+    /// use std::sync::Arc;
+    ///
+    /// use automerge::ScalarValue;
+    /// use automerge_orm::{
+    ///     Entity,
+    ///     EntityManager,
+    ///     Keyed,
+    ///     Mapped,
+    /// };
+    /// use automerge_test::{assert_doc, map};
+    /// use autosurgeon::{Hydrate, Reconcile};
+    /// use uuid::Uuid;
+    ///
+    /// #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    /// struct Book {
+    ///     #[key]
+    ///     id: Uuid,
+    ///     author: String,
+    /// }
+    ///
+    /// impl Book {
+    ///     pub fn new(author: &str) -> Self {
+    ///         Self {
+    ///             id: Uuid::new_v4(),
+    ///             author: author.to_owned(),
+    ///         }
+    ///     }
+    ///
+    ///     pub fn author(&self) -> &str {
+    ///         &self.author
+    ///     }
+    ///
+    ///     pub fn set_author(&mut self, author: &str) {
+    ///         self.author = author.to_owned();
+    ///     }
+    /// }
+    ///
+    /// # let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    /// # let doc_handle = repo_handle.new_document();
+    /// let entity_manager = Arc::new(EntityManager::new(doc_handle.clone()));
+    ///
+    /// let mut book = Book::new("Miyazaki Hayao");
+    /// entity_manager.transact(|tx| {
+    ///     tx.insert(&book)?;
+    ///     automerge_orm::Result::Ok(())
+    /// })?;
+    /// book.set_author("Shinkai Makoto");
+    /// entity_manager.transact(|tx| {
+    ///     tx.update(&book)?;
+    ///     automerge_orm::Result::Ok(())
+    /// })?;
+    ///
+    /// doc_handle.with_doc(|doc| {
+    ///     assert_doc!(
+    ///         doc,
+    ///         map! {
+    ///             Book::table_name() => {
+    ///                 map!{
+    ///                     book.id() => {
+    ///                         map!{
+    ///                             "id" => { ScalarValue::from(book.id()) },
+    ///                             "author" => { book.author() },
+    ///                         },
+    ///                     },
+    ///                 },
+    ///             },
+    ///         }
+    ///     );
+    /// });
+    /// # repo_handle.stop().unwrap();
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn update<T>(&mut self, entity: &T) -> Result<()>
+    where
+        T: Mapped + Keyed<Entity = T> + Reconcile,
+    {
+        self.update_at(entity.id(), entity)
+    }
+
+    /// Updates the existing object instance identified by `id`.
+    ///
+    /// This fails with [`Error::KeyMismatch`] if the key of `entity` does not
+    /// match `id`, e.g. when trying to update the record of one object with
+    /// the data of another object.
+    ///
+    /// The object will be updated in the document as a result of the [`commit`]
+    /// operation.
+    ///
+    /// [`commit`]: Transaction::commit
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use automerge::ChangeHash;
+    /// # use automerge_repo::{DocumentId, Repo, Storage, StorageError};
+    /// # use futures::future::{self, BoxFuture, FutureExt};
+    /// #
+    /// # pub struct NoopStorage;
+    /// #
+    /// # impl Storage for NoopStorage {
+    /// #     fn get(
+    /// #         &self,
+    /// #         _id: DocumentId
+    /// #     ) -> BoxFuture<'static, Result<Option<Vec<u8>>, StorageError>> {
+    /// #         future::ready(Ok(None)).boxed()
+    /// #     }
+    /// #
+    /// #     fn list_all(
+    /// #         &self
+    /// #     ) -> BoxFuture<'static, Result<Vec<DocumentId>, StorageError>> {
+    /// #         future::ready(Ok(Vec::new())).boxed()
+    /// #     }
+    /// #
+    /// #     fn append(
+    /// #         &self,
+    /// #         _id: DocumentId,
+    /// #         _chunk: Vec<u8>,
+    /// #     ) -> BoxFuture<'static, Result<(), StorageError>> {
+    /// #         future::ready(Ok(())).boxed()
+    /// #     }
+    /// #
+    /// #     fn compact(
+    /// #         &self,
+    /// #         _id: DocumentId,
+    /// #         _chunk: Vec<u8>,
+    /// #         _new_heads: Vec<ChangeHash>,
+    /// #     ) -> BoxFuture<'static, Result<(), StorageError>> {
+    /// #         future::ready(Ok(())).boxed()
+    /// #     }
+    /// # }
+    /// #
+    /// use std::sync::Arc;
+    ///
+    /// use automerge_orm::{Entity, EntityManager, Keyed};
+    /// use autosurgeon::{Hydrate, Reconcile};
+    /// use uuid::Uuid;
+    ///
+    /// #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    /// struct Book {
+    ///     #[key]
+    ///     id: Uuid,
+    /// }
+    ///
+    /// impl Book {
+    ///     pub fn new() -> Self {
+    ///         Self { id: Uuid::new_v4() }
+    ///     }
+    /// }
+    ///
+    /// # let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    /// # let doc_handle = repo_handle.new_document();
+    /// let entity_manager = Arc::new(EntityManager::new(doc_handle));
+    ///
+    /// let books = vec![Book::new(), Book::new()];
+    /// entity_manager.transact(|tx| {
+    ///     for book in &books {
+    ///         tx.insert(book)?;
+    ///     }
+    ///     automerge_orm::Result::Ok(())
+    /// })?;
+    /// let result = entity_manager.transact(|tx| {
+    ///     tx.update_at(books[0].id(), &books[1])?;
+    ///     automerge_orm::Result::Ok(())
+    /// });
+    /// assert!(result.is_err());
+    /// # repo_handle.stop().unwrap();
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn update_at<T>(&mut self, id: Key<T>, entity: &T) -> Result<()>
+    where
+        T: Mapped + Keyed<Entity = T> + Reconcile,
+    {
+        if entity.id() != id {
+            return Err(key_mismatch(entity.id(), id, "key of the object to update"));
+        }
+        let table_id = self.get_existing_table(id)?;
+        self.write_entity(
+            &table_id,
+            id,
+            entity,
+            WriteHooks::Update,
+            WriteMode::Reconcile,
+        )
+    }
+
+    /// Replaces an existing object instance.
+    ///
+    /// Unlike [`update`], which reconciles the object instance with the object
+    /// stored in the document, this replaces the stored object with a new
+    /// object. Properties of the stored object which are absent from the new
+    /// object instance, e.g. those of fields which have been removed from the
+    /// type, are therefore not kept. Concurrent changes made to the stored object by
+    /// other peers are discarded when merging, so prefer [`update`] for
+    /// collaboratively edited objects.
+    ///
+    /// Like [`update`], this fails with [`Error::ObjectDoesNotExist`] if the
+    /// object does not exist, and invokes the update hooks.
+    ///
+    /// The object will be replaced in the document as a result of the
+    /// [`commit`] operation.
+    ///
+    /// [`update`]: Transaction::update
+    /// [`commit`]: Transaction::commit
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use automerge::ChangeHash;
+    /// # use automerge_repo::{DocumentId, Repo, Storage, StorageError};
+    /// # use futures::future::{self, BoxFuture, FutureExt};
+    /// #
+    /// # pub struct NoopStorage;
+    /// #
+    /// # impl Storage for NoopStorage {
+    /// #     fn get(
+    /// #         &self,
+    /// #         _id: DocumentId
+    /// #     ) -> BoxFuture<'static, Result<Option<Vec<u8>>, StorageError>> {
+    /// #         future::ready(Ok(None)).boxed()
+    /// #     }
+    /// #
+    /// #     fn list_all(
+    /// #         &self
+    /// #     ) -> BoxFuture<'static, Result<Vec<DocumentId>, StorageError>> {
+    /// #         future::ready(Ok(Vec::new())).boxed()
+    /// #     }
+    /// #
+    /// #     fn append(
+    /// #         &self,
+    /// #         _id: DocumentId,
+    /// #         _chunk: Vec<u8>,
+    /// #     ) -> BoxFuture<'static, Result<(), StorageError>> {
+    /// #         future::ready(Ok(())).boxed()
+    /// #     }
+    /// #
+    /// #     fn compact(
+    /// #         &self,
+    /// #         _id: DocumentId,
+    /// #         _chunk: Vec<u8>,
+    /// #         _new_heads: Vec<ChangeHash>,
+    /// #     ) -> BoxFuture<'static, Result<(), StorageError>> {
+    /// #         future::ready(Ok(())).boxed()
+    /// #     }
+    /// # }
+    /// #
+    /// use std::sync::Arc;
+    ///
+    /// use automerge_orm::{
+    ///     DefaultEntityRepository,
+    ///     Entity,
+    ///     EntityManager,
+    ///     EntityRepository,
+    ///     Keyed,
+    /// };
+    /// use autosurgeon::{Hydrate, Reconcile};
+    /// use uuid::Uuid;
+    ///
+    /// #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    /// struct Book {
+    ///     #[key]
+    ///     id: Uuid,
+    ///     author: String,
+    /// }
+    ///
+    /// type BookRepository = DefaultEntityRepository<Book>;
+    ///
+    /// impl Book {
+    ///     pub fn new(author: &str) -> Self {
+    ///         Self {
+    ///             id: Uuid::new_v4(),
+    ///             author: author.to_owned(),
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// # let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    /// # let doc_handle = repo_handle.new_document();
+    /// let entity_manager = Arc::new(EntityManager::new(doc_handle));
+    /// let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+    ///
+    /// let mut book = Book::new("Miyazaki Hayao");
+    /// entity_manager.transact(|tx| {
+    ///     tx.insert(&book)?;
+    ///     automerge_orm::Result::Ok(())
+    /// })?;
+    /// book.author = "Shinkai Makoto".to_owned();
+    /// entity_manager.transact(|tx| {
+    ///     tx.replace(&book)?;
+    ///     automerge_orm::Result::Ok(())
+    /// })?;
+    /// let book = book_repository.find(book.id())?.unwrap();
+    /// assert_eq!(book.author, "Shinkai Makoto");
+    /// # repo_handle.stop().unwrap();
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn replace<T>(&mut self, entity: &T) -> Result<()>
+    where
+        T: Mapped + Keyed<Entity = T> + Reconcile,
+    {
+        let id = entity.id();
+        let table_id = self.get_existing_table(id)?;
+        self.write_entity(
+            &table_id,
+            id,
+            entity,
+            WriteHooks::Update,
+            WriteMode::Replace,
+        )
+    }
+
+    /// Updates an existing object instance only if it differs from the object
+    /// instance stored in the document, and returns whether it was updated.
+    ///
+    /// The stored object instance is hydrated and compared with `entity`. If
+    /// they are equal, neither hooks nor validation are invoked, and no
+    /// operations are added to the transaction. Otherwise, this behaves like
+    /// [`update`]. As `autosurgeon` only reconciles the fields which differ
+    /// from the document, the update itself is limited to the changed fields.
+    ///
+    /// The object will be updated in the document as a result of the
+    /// [`commit`] operation.
+    ///
+    /// [`update`]: Transaction::update
+    /// [`commit`]: Transaction::commit
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use automerge::ChangeHash;
+    /// # use automerge_repo::{DocumentId, Repo, Storage, StorageError};
+    /// # use futures::future::{self, BoxFuture, FutureExt};
+    /// #
+    /// # pub struct NoopStorage;
+    /// #
+    /// # impl Storage for NoopStorage {
+    /// #     fn get(
+    /// #         &self,
+    /// #         _id: DocumentId
+    /// #     ) -> BoxFuture<'static, Result<Option<Vec<u8>>, StorageError>> {
+    /// #         future::ready(Ok(None)).boxed()
+    /// #     }
+    /// #
+    /// #     fn list_all(
+    /// #         &self
+    /// #     ) -> BoxFuture<'static, Result<Vec<DocumentId>, StorageError>> {
+    /// #         future::ready(Ok(Vec::new())).boxed()
+    /// #     }
+    /// #
+    /// #     fn append(
+    /// #         &self,
+    /// #         _id: DocumentId,
+    /// #         _chunk: Vec<u8>,
+    /// #     ) -> BoxFuture<'static, Result<(), StorageError>> {
+    /// #         future::ready(Ok(())).boxed()
+    /// #     }
+    /// #
+    /// #     fn compact(
+    /// #         &self,
+    /// #         _id: DocumentId,
+    /// #         _chunk: Vec<u8>,
+    /// #         _new_heads: Vec<ChangeHash>,
+    /// #     ) -> BoxFuture<'static, Result<(), StorageError>> {
+    /// #         future::ready(Ok(())).boxed()
+    /// #     }
+    /// # }
+    /// #
+    /// use std::sync::Arc;
+    ///
+    /// use automerge_orm::{Entity, EntityManager, Keyed};
+    /// use autosurgeon::{Hydrate, Reconcile};
+    /// use uuid::Uuid;
+    ///
+    /// #[derive(Clone, Debug, Entity, Hydrate, PartialEq, Reconcile)]
+    /// struct Book {
+    ///     #[key]
+    ///     id: Uuid,
+    ///     author: String,
+    /// }
+    ///
+    /// impl Book {
+    ///     pub fn new(author: &str) -> Self {
+    ///         Self {
+    ///             id: Uuid::new_v4(),
+    ///             author: author.to_owned(),
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// # let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    /// # let doc_handle = repo_handle.new_document();
+    /// let entity_manager = Arc::new(EntityManager::new(doc_handle));
+    ///
+    /// let mut book = Book::new("Miyazaki Hayao");
+    /// entity_manager.transact(|tx| {
+    ///     tx.insert(&book)?;
+    ///     automerge_orm::Result::Ok(())
+    /// })?;
+    /// let updated = entity_manager.transact(|tx| tx.update_diffed(&book))?;
+    /// assert!(!updated);
+    /// book.author = "Shinkai Makoto".to_owned();
+    /// let updated = entity_manager.transact(|tx| tx.update_diffed(&book))?;
+    /// assert!(updated);
+    /// # repo_handle.stop().unwrap();
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn update_diffed<T>(&mut self, entity: &T) -> Result<bool>
+    where
+        T: Mapped + Keyed<Entity = T> + Hydrate + Reconcile + PartialEq,
+    {
+        let Some(current) = find_under::<_, T>(&self.tx, self.root.as_deref(), entity.id())? else {
+            return Err(Error::ObjectDoesNotExist {
+                table_name: <T as Mapped>::table_name().to_owned(),
+                id: entity.id().into(),
+            });
+        };
+        if current == *entity {
+            return Ok(false);
+        }
+        self.update(entity)?;
+
+        Ok(true)
+    }
+
+    /// Updates an existing object instance, or inserts a new object instance if
+    /// it does not already exist.
+    ///
+    /// The object will be updated in / inserted into the document as a result
+    /// of the [`commit`] operation.
+    ///
+    /// [`commit`]: Transaction::commit
+    ///
+    /// # Examples
+    ///
+    /// Update an existing object instance:
+    ///
+    /// ```
+    /// # use automerge::ChangeHash;
+    /// # use automerge_repo::{DocumentId, Repo, Storage, StorageError};
+    /// # use futures::future::{self, BoxFuture, FutureExt};
+    /// #
+    /// # pub struct NoopStorage;
+    /// #
+    /// # impl Storage for NoopStorage {
+    /// #     fn get(
+    /// #         &self,
+    /// #         _id: DocumentId
+    /// #     ) -> BoxFuture<'static, Result<Option<Vec<u8>>, StorageError>> {
+    /// #         future::ready(Ok(None)).boxed()
+    /// #     }
+    /// #
+    /// #     fn list_all(
+    /// #         &self
+    /// #     ) -> BoxFuture<'static, Result<Vec<DocumentId>, StorageError>> {
+    /// #         future::ready(Ok(Vec::new())).boxed()
+    /// #     }
+    /// #
+    /// #     fn append(
+    /// #         &self,
+    /// #         _id: DocumentId,
+    /// #         _chunk: Vec<u8>,
+    /// #     ) -> BoxFuture<'static, Result<(), StorageError>> {
+    /// #         future::ready(Ok(())).boxed()
+    /// #     }
+    /// #
+    /// #     fn compact(
+    /// #         &self,
+    /// #         _id: DocumentId,
+    /// #         _chunk: Vec<u8>,
+    /// #         _new_heads: Vec<ChangeHash>,
+    /// #     ) -> BoxFuture<'static, Result<(), StorageError>> {
+    /// #         future::ready(Ok(())).boxed()
+    /// #     }
+    /// # }
+    /// #
+    /// use std::sync::Arc;
+    ///
+    /// use automerge::ScalarValue;
+    /// use automerge_orm::{
+    ///     Entity,
+    ///     EntityManager,
+    ///     Keyed,
+    ///     Mapped,
+    /// };
+    /// use automerge_test::{assert_doc, map};
+    /// use autosurgeon::{Hydrate, Reconcile};
+    /// use uuid::Uuid;
+    ///
+    /// #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    /// struct Book {
+    ///     #[key]
+    ///     id: Uuid,
+    ///     author: String,
+    /// }
+    ///
+    /// impl Book {
+    ///     pub fn new(author: &str) -> Self {
+    ///         Self {
+    ///             id: Uuid::new_v4(),
+    ///             author: author.to_owned(),
+    ///         }
+    ///     }
+    ///
+    ///     pub fn author(&self) -> &str {
+    ///         &self.author
+    ///     }
+    ///
+    ///     pub fn set_author(&mut self, author: &str) {
+    ///         self.author = author.to_owned();
+    ///     }
+    /// }
+    ///
+    /// # let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    /// # let doc_handle = repo_handle.new_document();
+    /// let entity_manager = Arc::new(EntityManager::new(doc_handle.clone()));
+    ///
+    /// let mut book = Book::new("Miyazaki Hayao");
+    /// entity_manager.transact(|tx| {
+    ///     tx.upsert(&book)?;
+    ///     automerge_orm::Result::Ok(())
+    /// })?;
+    /// book.set_author("Shinkai Makoto");
+    /// entity_manager.transact(|tx| {
+    ///     tx.upsert(&book)?;
+    ///     automerge_orm::Result::Ok(())
+    /// })?;
+    ///
+    /// doc_handle.with_doc(|doc| {
+    ///     assert_doc!(
+    ///         doc,
+    ///         map! {
+    ///             Book::table_name() => {
+    ///                 map!{
+    ///                     book.id() => {
+    ///                         map!{
+    ///                             "id" => { ScalarValue::from(book.id()) },
+    ///                             "author" => { book.author() },
+    ///                         },
+    ///                     },
+    ///                 },
+    ///             },
+    ///         }
+    ///     );
+    /// });
+    /// # repo_handle.stop().unwrap();
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    ///
+    /// Insert a new object instance:
+    ///
+    /// ```
+    /// # use automerge::ChangeHash;
+    /// # use automerge_repo::{DocumentId, Repo, Storage, StorageError};
+    /// # use futures::future::{self, BoxFuture, FutureExt};
+    /// #
+    /// # pub struct NoopStorage;
+    /// #
+    /// # impl Storage for NoopStorage {
+    /// #     fn get(
+    /// #         &self,
+    /// #         _id: DocumentId
+    /// #     ) -> BoxFuture<'static, Result<Option<Vec<u8>>, StorageError>> {
+    /// #         future::ready(Ok(None)).boxed()
+    /// #     }
+    /// #
+    /// #     fn list_all(
+    /// #         &self
+    /// #     ) -> BoxFuture<'static, Result<Vec<DocumentId>, StorageError>> {
+    /// #         future::ready(Ok(Vec::new())).boxed()
+    /// #     }
+    /// #
+    /// #     fn append(
+    /// #         &self,
+    /// #         _id: DocumentId,
+    /// #         _chunk: Vec<u8>,
+    /// #     ) -> BoxFuture<'static, Result<(), StorageError>> {
+    /// #         future::ready(Ok(())).boxed()
+    /// #     }
+    /// #
+    /// #     fn compact(
+    /// #         &self,
+    /// #         _id: DocumentId,
+    /// #         _chunk: Vec<u8>,
+    /// #         _new_heads: Vec<ChangeHash>,
+    /// #     ) -> BoxFuture<'static, Result<(), StorageError>> {
+    /// #         future::ready(Ok(())).boxed()
+    /// #     }
+    /// # }
+    /// #
+    /// use std::sync::Arc;
+    ///
+    /// use automerge::ScalarValue;
+    /// use automerge_orm::{
+    ///     Entity,
+    ///     EntityManager,
+    ///     Keyed,
+    ///     Mapped,
+    /// };
+    /// use automerge_test::{assert_doc, map};
+    /// use autosurgeon::{Hydrate, Reconcile};
+    /// use uuid::Uuid;
+    ///
+    /// #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    /// struct Book {
+    ///     #[key]
+    ///     id: Uuid,
+    /// }
+    ///
+    /// impl Book {
+    ///     pub fn new() -> Self {
+    ///         Self { id: Uuid::new_v4() }
+    ///     }
+    /// }
+    ///
+    /// # let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    /// # let doc_handle = repo_handle.new_document();
+    /// let entity_manager = Arc::new(EntityManager::new(doc_handle.clone()));
+    ///
+    /// let book = Book::new();
+    /// entity_manager.transact(|tx| {
+    ///     tx.upsert(&book)?;
+    ///     automerge_orm::Result::Ok(())
+    /// })?;
+    ///
+    /// doc_handle.with_doc(|doc| {
+    ///     assert_doc!(
+    ///         doc,
+    ///         map!{
+    ///             Book::table_name() => {
+    ///                 map!{
+    ///                     book.id() => {
+    ///                         map!{
+    ///                             "id" => { ScalarValue::from(book.id()) },
+    ///                         },
+    ///                     },
+    ///                 },
+    ///             },
+    ///         }
+    ///     );
+    /// });
+    /// # repo_handle.stop().unwrap();
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn upsert<T>(&mut self, entity: &T) -> Result<()>
+    where
+        T: Mapped + Keyed<Entity = T> + Reconcile,
+    {
+        let table_id = self.get_or_create_table::<T>()?;
+        if self
+            .tx
+            .get(&table_id, Prop::Map(entity.id().to_prop()))?
+            .is_some()
+        {
+            self.write_entity(
+                &table_id,
+                entity.id(),
+                entity,
+                WriteHooks::Update,
+                WriteMode::Reconcile,
+            )
+        } else {
+            self.write_entity(
+                &table_id,
+                entity.id(),
+                entity,
+                WriteHooks::Insert,
+                WriteMode::Reconcile,
+            )
+        }
+    }
+
+    /// Imports multiple object instances, and returns the number of objects
+    /// which were inserted, updated or skipped.
+    ///
+    /// Objects which do not exist are inserted, as with [`insert`]. Objects
+    /// which already exist are handled according to `on_conflict`. With
+    /// [`OnConflict::Error`], the import stops at the first object which
+    /// already exists, and the error aborts the transaction.
+    ///
+    /// The objects will be imported into the document as a result of the
+    /// [`commit`] operation.
+    ///
+    /// [`insert`]: Transaction::insert
+    /// [`commit`]: Transaction::commit
+    ///
+    /// # Performance
+    ///
+    /// If the entity type has unique fields, each object is checked against
+    /// every object in the table before it is written, as described in
+    /// [`Mapped::unique_fields`]. Importing `n` objects therefore takes
+    /// quadratic time in `n`, which is noticeable for large imports.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use automerge::ChangeHash;
+    /// # use automerge_repo::{DocumentId, Repo, Storage, StorageError};
+    /// # use futures::future::{self, BoxFuture, FutureExt};
+    /// #
+    /// # pub struct NoopStorage;
+    /// #
+    /// # impl Storage for NoopStorage {
+    /// #     fn get(
+    /// #         &self,
+    /// #         _id: DocumentId
+    /// #     ) -> BoxFuture<'static, Result<Option<Vec<u8>>, StorageError>> {
+    /// #         future::ready(Ok(None)).boxed()
+    /// #     }
+    /// #
+    /// #     fn list_all(
+    /// #         &self
+    /// #     ) -> BoxFuture<'static, Result<Vec<DocumentId>, StorageError>> {
+    /// #         future::ready(Ok(Vec::new())).boxed()
+    /// #     }
+    /// #
+    /// #     fn append(
+    /// #         &self,
+    /// #         _id: DocumentId,
+    /// #         _chunk: Vec<u8>,
+    /// #     ) -> BoxFuture<'static, Result<(), StorageError>> {
+    /// #         future::ready(Ok(())).boxed()
+    /// #     }
+    /// #
+    /// #     fn compact(
+    /// #         &self,
+    /// #         _id: DocumentId,
+    /// #         _chunk: Vec<u8>,
+    /// #         _new_heads: Vec<ChangeHash>,
+    /// #     ) -> BoxFuture<'static, Result<(), StorageError>> {
+    /// #         future::ready(Ok(())).boxed()
+    /// #     }
+    /// # }
+    /// #
+    /// use std::sync::Arc;
+    ///
+    /// use automerge_orm::{
+    ///     DefaultEntityRepository,
+    ///     Entity,
+    ///     EntityManager,
+    ///     EntityRepository,
+    ///     OnConflict,
+    /// };
+    /// use autosurgeon::{Hydrate, Reconcile};
+    /// use uuid::Uuid;
+    ///
+    /// #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    /// struct Book {
+    ///     #[key]
+    ///     id: Uuid,
+    ///     author: String,
+    /// }
+    ///
+    /// type BookRepository = DefaultEntityRepository<Book>;
+    ///
+    /// impl Book {
+    ///     pub fn new(author: &str) -> Self {
+    ///         Self {
+    ///             id: Uuid::new_v4(),
+    ///             author: author.to_owned(),
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// # let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    /// # let doc_handle = repo_handle.new_document();
+    /// let entity_manager = Arc::new(EntityManager::new(doc_handle));
+    /// let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+    ///
+    /// let book = Book::new("Miyazaki Hayao");
+    /// entity_manager.transact(|tx| {
+    ///     tx.insert(&book)?;
+    ///     automerge_orm::Result::Ok(())
+    /// })?;
+    ///
+    /// let books = vec![book, Book::new("Shinkai Makoto")];
+    /// let report = entity_manager.transact(|tx| tx.import(books, OnConflict::Skip))?;
+    /// assert_eq!(report.inserted(), 1);
+    /// assert_eq!(report.skipped(), 1);
+    /// assert_eq!(book_repository.list()?.len(), 2);
+    /// # repo_handle.stop().unwrap();
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn import<T, I>(&mut self, entities: I, on_conflict: OnConflict) -> Result<ImportReport>
+    where
+        T: Mapped + Keyed<Entity = T> + Reconcile,
+        I: IntoIterator<Item = T>,
+    {
+        let table_id = self.get_or_create_table::<T>()?;
+        let mut report = ImportReport::default();
+        for entity in entities {
+            let id = entity.id();
+            if self.tx.get(&table_id, Prop::Map(id.to_prop()))?.is_none() {
+                self.write_entity(
+                    &table_id,
+                    id,
+                    &entity,
+                    WriteHooks::Insert,
+                    WriteMode::Reconcile,
+                )?;
+                report.record_inserted();
+                continue;
+            }
+            match on_conflict {
+                OnConflict::Skip => report.record_skipped(),
+                OnConflict::Overwrite => {
+                    self.write_entity(
+                        &table_id,
+                        id,
+                        &entity,
+                        WriteHooks::Update,
+                        WriteMode::Reconcile,
+                    )?;
+                    report.record_updated();
+                },
+                OnConflict::Error => {
+                    return Err(Error::ObjectAlreadyExists {
+                        table_name: <T as Mapped>::table_name().to_owned(),
+                        id: id.into(),
+                    });
+                },
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Removes an object by its identifier.
+    ///
+    /// The object will be removed from the document as a result of the
+    /// [`commit`] operation.
+    ///
+    /// [`commit`]: Transaction::commit
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use automerge::ChangeHash;
+    /// # use automerge_repo::{DocumentId, Repo, Storage, StorageError};
+    /// # use futures::future::{self, BoxFuture, FutureExt};
+    /// #
+    /// # pub struct NoopStorage;
+    /// #
+    /// # impl Storage for NoopStorage {
+    /// #     fn get(
+    /// #         &self,
+    /// #         _id: DocumentId
+    /// #     ) -> BoxFuture<'static, Result<Option<Vec<u8>>, StorageError>> {
+    /// #         future::ready(Ok(None)).boxed()
+    /// #     }
+    /// #
+    /// #     fn list_all(
+    /// #         &self
+    /// #     ) -> BoxFuture<'static, Result<Vec<DocumentId>, StorageError>> {
+    /// #         future::ready(Ok(Vec::new())).boxed()
+    /// #     }
+    /// #
+    /// #     fn append(
+    /// #         &self,
+    /// #         _id: DocumentId,
+    /// #         _chunk: Vec<u8>,
+    /// #     ) -> BoxFuture<'static, Result<(), StorageError>> {
+    /// #         future::ready(Ok(())).boxed()
+    /// #     }
+    /// #
+    /// #     fn compact(
+    /// #         &self,
+    /// #         _id: DocumentId,
+    /// #         _chunk: Vec<u8>,
+    /// #         _new_heads: Vec<ChangeHash>,
+    /// #     ) -> BoxFuture<'static, Result<(), StorageError>> {
+    /// #         future::ready(Ok(())).boxed()
+    /// #     }
+    /// # }
+    /// #
+    /// use std::sync::Arc;
+    ///
+    /// use automerge_orm::{
+    ///     DefaultEntityRepository,
+    ///     Entity,
+    ///     EntityManager,
+    ///     EntityRepository,
+    ///     Keyed,
+    ///     Mapped,
+    /// };
+    /// use automerge_test::{assert_doc, map};
+    /// use autosurgeon::{Hydrate, Reconcile};
+    /// use uuid::Uuid;
+    ///
+    /// #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    /// struct Book {
+    ///     #[key]
+    ///     id: Uuid,
+    /// }
+    ///
+    /// type BookRepository = DefaultEntityRepository<Book>;
+    ///
+    /// impl Book {
+    ///     pub fn new() -> Self {
+    ///         Self { id: Uuid::new_v4() }
+    ///     }
+    /// }
+    ///
+    /// # let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    /// # let doc_handle = repo_handle.new_document();
+    /// let entity_manager = Arc::new(EntityManager::new(doc_handle.clone()));
+    /// let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+    ///
+    /// let book = Book::new();
+    /// entity_manager.transact(|tx| {
+    ///     tx.insert(&book)?;
+    ///     automerge_orm::Result::Ok(())
+    /// })?;
+    /// let book = book_repository.find(book.id())?;
+    /// assert!(book.is_some());
+    /// let book = book.unwrap();
+    /// entity_manager.transact(|tx| {
+    ///     tx.remove(book.id())?;
+    ///     automerge_orm::Result::Ok(())
+    /// })?;
+    /// let book = book_repository.find(book.id())?;
+    /// assert!(book.is_none());
+    ///
+    /// doc_handle.with_doc(|doc| {
+    ///     assert_doc!(
+    ///         doc,
+    ///         map!{
+    ///             Book::table_name() => {
+    ///                 map!{},
+    ///             },
+    ///         }
+    ///     );
+    /// });
+    /// # repo_handle.stop().unwrap();
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn remove<T>(&mut self, id: Key<T>) -> Result<()>
+    where
+        T: Mapped,
+    {
+        self.check_max_ops()?;
+        let Some(table_id) = self.resolve_table::<T>()? else {
+            return Ok(());
+        };
+        self.tx.delete(&table_id, Prop::Map(id.to_prop()))?;
+
+        Ok(())
+    }
+
+    /// Removes an existing object by its identifier, and returns an
+    /// [`Error::ObjectDoesNotExist`] if the object does not exist.
+    ///
+    /// Unlike [`remove`], which ignores objects which do not exist, this is
+    /// for flows in which removing an object which does not exist indicates a
+    /// bug.
+    ///
+    /// The object will be removed from the document as a result of the
+    /// [`commit`] operation.
+    ///
+    /// [`remove`]: Transaction::remove
+    /// [`commit`]: Transaction::commit
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use automerge_orm::{Entity, EntityManager, Keyed};
+    /// use autosurgeon::{Hydrate, Reconcile};
+    /// use uuid::Uuid;
+    ///
+    /// #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    /// struct Book {
+    ///     #[key]
+    ///     id: Uuid,
+    /// }
+    ///
+    /// let entity_manager = EntityManager::ephemeral();
+    ///
+    /// let book = Book { id: Uuid::new_v4() };
+    /// entity_manager.transact(|tx| tx.insert(&book))?;
+    /// entity_manager.transact(|tx| tx.remove_existing(book.id()))?;
+    /// let result = entity_manager.transact(|tx| tx.remove_existing(book.id()));
+    /// assert!(result.unwrap_err().is_not_found());
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn remove_existing<T>(&mut self, id: Key<T>) -> Result<()>
+    where
+        T: Mapped,
+    {
+        self.check_max_ops()?;
+        let table_id = self.get_existing_table(id)?;
+        self.tx.delete(&table_id, Prop::Map(id.to_prop()))?;
+
+        Ok(())
+    }
+
+    /// Removes multiple objects by their identifiers, and returns the number
+    /// of objects which were actually removed.
+    ///
+    /// Like [`remove`], identifiers of objects which do not exist are ignored.
+    ///
+    /// The objects will be removed from the document as a result of the
+    /// [`commit`] operation.
+    ///
+    /// [`remove`]: Transaction::remove
+    /// [`commit`]: Transaction::commit
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use automerge::ChangeHash;
+    /// # use automerge_repo::{DocumentId, Repo, Storage, StorageError};
+    /// # use futures::future::{self, BoxFuture, FutureExt};
+    /// #
+    /// # pub struct NoopStorage;
+    /// #
+    /// # impl Storage for NoopStorage {
+    /// #     fn get(
+    /// #         &self,
+    /// #         _id: DocumentId
+    /// #     ) -> BoxFuture<'static, Result<Option<Vec<u8>>, StorageError>> {
+    /// #         future::ready(Ok(None)).boxed()
+    /// #     }
+    /// #
+    /// #     fn list_all(
+    /// #         &self
+    /// #     ) -> BoxFuture<'static, Result<Vec<DocumentId>, StorageError>> {
+    /// #         future::ready(Ok(Vec::new())).boxed()
+    /// #     }
+    /// #
+    /// #     fn append(
+    /// #         &self,
+    /// #         _id: DocumentId,
+    /// #         _chunk: Vec<u8>,
+    /// #     ) -> BoxFuture<'static, Result<(), StorageError>> {
+    /// #         future::ready(Ok(())).boxed()
+    /// #     }
+    /// #
+    /// #     fn compact(
+    /// #         &self,
+    /// #         _id: DocumentId,
+    /// #         _chunk: Vec<u8>,
+    /// #         _new_heads: Vec<ChangeHash>,
+    /// #     ) -> BoxFuture<'static, Result<(), StorageError>> {
+    /// #         future::ready(Ok(())).boxed()
+    /// #     }
+    /// # }
+    /// #
+    /// use std::sync::Arc;
+    ///
+    /// use automerge_orm::{
+    ///     DefaultEntityRepository,
+    ///     Entity,
+    ///     EntityManager,
+    ///     EntityRepository,
+    ///     Keyed,
+    /// };
+    /// use autosurgeon::{Hydrate, Reconcile};
+    /// use uuid::Uuid;
+    ///
+    /// #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    /// struct Book {
+    ///     #[key]
+    ///     id: Uuid,
+    /// }
+    ///
+    /// type BookRepository = DefaultEntityRepository<Book>;
+    ///
+    /// impl Book {
+    ///     pub fn new() -> Self {
+    ///         Self { id: Uuid::new_v4() }
+    ///     }
+    /// }
+    ///
+    /// # let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    /// # let doc_handle = repo_handle.new_document();
+    /// let entity_manager = Arc::new(EntityManager::new(doc_handle));
+    /// let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+    ///
+    /// let books = vec![Book::new(), Book::new(), Book::new()];
+    /// entity_manager.transact(|tx| {
+    ///     for book in &books {
+    ///         tx.insert(book)?;
+    ///     }
+    ///     automerge_orm::Result::Ok(())
+    /// })?;
+    /// let removed = entity_manager.transact(|tx| {
+    ///     tx.remove_many([books[0].id(), books[1].id(), Uuid::new_v4().into()])
+    /// })?;
+    /// assert_eq!(removed, 2);
+    /// assert_eq!(book_repository.find_all()?.len(), 1);
+    /// # repo_handle.stop().unwrap();
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn remove_many<T, I>(&mut self, ids: I) -> Result<usize>
+    where
+        T: Mapped,
+        I: IntoIterator<Item = Key<T>>,
+    {
+        self.check_max_ops()?;
+        let Some(table_id) = self.resolve_table::<T>()? else {
+            return Ok(0);
+        };
+        let mut removed = 0;
+        for id in ids {
+            let key = Prop::Map(id.to_prop());
+            if self.tx.get(&table_id, key.clone())?.is_some() {
+                self.tx.delete(&table_id, key)?;
+                removed += 1;
+            }
+        }
+
+        Ok(removed)
+    }
+
+    /// Updates a single field of an existing object instance.
+    ///
+    /// Only the `field` property of the object instance is reconciled, so
+    /// concurrent changes to other fields of the same object instance are
+    /// preserved when merging. The name of the field is resolved using
+    /// [`Mapped::prop_name`], so renamed fields are addressed by the name of
+    /// the Rust field.
+    ///
+    /// The field which holds the key of the object instance, as returned by
+    /// [`Mapped::key_field`], cannot be updated, and is rejected with an
+    /// [`Error::KeyFieldUpdate`].
+    ///
+    /// The field will be updated in the document as a result of the
+    /// [`commit`] operation.
+    ///
+    /// [`commit`]: Transaction::commit
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use automerge::ChangeHash;
+    /// # use automerge_repo::{DocumentId, Repo, Storage, StorageError};
+    /// # use futures::future::{self, BoxFuture, FutureExt};
+    /// #
+    /// # pub struct NoopStorage;
+    /// #
+    /// # impl Storage for NoopStorage {
+    /// #     fn get(
+    /// #         &self,
+    /// #         _id: DocumentId
+    /// #     ) -> BoxFuture<'static, Result<Option<Vec<u8>>, StorageError>> {
+    /// #         future::ready(Ok(None)).boxed()
+    /// #     }
+    /// #
+    /// #     fn list_all(
+    /// #         &self
+    /// #     ) -> BoxFuture<'static, Result<Vec<DocumentId>, StorageError>> {
+    /// #         future::ready(Ok(Vec::new())).boxed()
+    /// #     }
+    /// #
+    /// #     fn append(
+    /// #         &self,
+    /// #         _id: DocumentId,
+    /// #         _chunk: Vec<u8>,
+    /// #     ) -> BoxFuture<'static, Result<(), StorageError>> {
+    /// #         future::ready(Ok(())).boxed()
+    /// #     }
+    /// #
+    /// #     fn compact(
+    /// #         &self,
+    /// #         _id: DocumentId,
+    /// #         _chunk: Vec<u8>,
+    /// #         _new_heads: Vec<ChangeHash>,
+    /// #     ) -> BoxFuture<'static, Result<(), StorageError>> {
+    /// #         future::ready(Ok(())).boxed()
+    /// #     }
+    /// # }
+    /// #
+    /// use std::sync::Arc;
+    ///
+    /// use automerge_orm::{
+    ///     DefaultEntityRepository,
+    ///     Entity,
+    ///     EntityManager,
+    ///     EntityRepository,
+    ///     Keyed,
+    /// };
+    /// use autosurgeon::{Hydrate, Reconcile};
+    /// use uuid::Uuid;
+    ///
+    /// #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    /// struct Book {
+    ///     #[key]
+    ///     id: Uuid,
+    ///     author: String,
+    /// }
+    ///
+    /// type BookRepository = DefaultEntityRepository<Book>;
+    ///
+    /// impl Book {
+    ///     pub fn new(author: &str) -> Self {
+    ///         Self {
+    ///             id: Uuid::new_v4(),
+    ///             author: author.to_owned(),
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// # let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    /// # let doc_handle = repo_handle.new_document();
+    /// let entity_manager = Arc::new(EntityManager::new(doc_handle));
+    /// let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+    ///
+    /// let book = Book::new("Miyazaki Hayao");
+    /// entity_manager.transact(|tx| {
+    ///     tx.insert(&book)?;
+    ///     automerge_orm::Result::Ok(())
+    /// })?;
+    /// entity_manager.transact(|tx| {
+    ///     tx.update_field(book.id(), "author", &"Shinkai Makoto")?;
+    ///     automerge_orm::Result::Ok(())
+    /// })?;
+    /// let book = book_repository.find(book.id())?.unwrap();
+    /// assert_eq!(book.author, "Shinkai Makoto");
+    /// # repo_handle.stop().unwrap();
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn update_field<T, V>(&mut self, id: Key<T>, field: &str, value: &V) -> Result<()>
+    where
+        T: Mapped,
+        V: Reconcile,
+    {
+        self.check_max_ops()?;
+        let entity_id = self.get_existing_entity(id)?;
+        if let Some(key_field) = <T as Mapped>::key_field() {
+            if <T as Mapped>::prop_name(field) == <T as Mapped>::prop_name(key_field) {
+                return Err(Error::KeyFieldUpdate {
+                    table_name: <T as Mapped>::table_name().to_owned(),
+                    field: field.to_owned(),
+                });
+            }
+        }
+        let is_unique = <T as Mapped>::unique_fields().contains(&field);
+        let is_counter = <T as Mapped>::counter_fields().contains(&field);
+        if is_unique || is_counter {
+            // As with entities, the value is compared, or written into the
+            // counter, as it is stored.
+            let mut scratch = Automerge::new();
+            let mut scratch_tx = scratch.transaction();
+            reconcile_prop(&mut scratch_tx, automerge::ROOT, "value", value)
+                .map_err(reconcile_error(id))?;
+            let scalar = match scratch_tx.get(&automerge::ROOT, "value")? {
+                Some((Value::Scalar(scalar), _)) => Some(scalar.into_owned()),
+                _ => None,
+            };
+            if let Some(scalar) = scalar.as_ref().filter(|_| is_unique) {
+                if let Some(table_id) = self.resolve_table::<T>()? {
+                    self.check_unique_value::<T>(&table_id, &id.to_prop(), field, scalar)?;
+                }
+            }
+            if is_counter {
+                let scalar = scalar.unwrap_or(ScalarValue::Null);
+                return self.write_counter(&entity_id, <T as Mapped>::prop_name(field), &scalar);
+            }
+        }
+        let field = <T as Mapped>::prop_name(field);
+        reconcile_prop(&mut self.tx, &entity_id, field, value).map_err(reconcile_error(id))?;
+
+        Ok(())
+    }
+
+    /// Applies a patch to an existing object instance, updating only the fields
+    /// which are changed by the patch.
+    ///
+    /// The stored object instance is not loaded, and concurrent changes to
+    /// other fields of the same object instance are preserved when merging.
+    /// See [`Patch`] for details.
+    ///
+    /// As the object instance is not loaded, it is neither validated nor are
+    /// its hooks invoked, even if they are enabled by [`Mapped::lifecycle`].
+    /// Unique fields are still checked, as by [`update_field`].
+    ///
+    /// [`update_field`]: Transaction::update_field
+    ///
+    /// The fields will be updated in the document as a result of the
+    /// [`commit`] operation.
+    ///
+    /// [`commit`]: Transaction::commit
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use automerge::ChangeHash;
+    /// # use automerge_repo::{DocumentId, Repo, Storage, StorageError};
+    /// # use futures::future::{self, BoxFuture, FutureExt};
+    /// #
+    /// # pub struct NoopStorage;
+    /// #
+    /// # impl Storage for NoopStorage {
+    /// #     fn get(
+    /// #         &self,
+    /// #         _id: DocumentId
+    /// #     ) -> BoxFuture<'static, Result<Option<Vec<u8>>, StorageError>> {
+    /// #         future::ready(Ok(None)).boxed()
+    /// #     }
+    /// #
+    /// #     fn list_all(
+    /// #         &self
+    /// #     ) -> BoxFuture<'static, Result<Vec<DocumentId>, StorageError>> {
+    /// #         future::ready(Ok(Vec::new())).boxed()
+    /// #     }
+    /// #
+    /// #     fn append(
+    /// #         &self,
+    /// #         _id: DocumentId,
+    /// #         _chunk: Vec<u8>,
+    /// #     ) -> BoxFuture<'static, Result<(), StorageError>> {
+    /// #         future::ready(Ok(())).boxed()
+    /// #     }
+    /// #
+    /// #     fn compact(
+    /// #         &self,
+    /// #         _id: DocumentId,
+    /// #         _chunk: Vec<u8>,
+    /// #         _new_heads: Vec<ChangeHash>,
+    /// #     ) -> BoxFuture<'static, Result<(), StorageError>> {
+    /// #         future::ready(Ok(())).boxed()
+    /// #     }
+    /// # }
+    /// #
+    /// use std::sync::Arc;
+    ///
+    /// use automerge_orm::{
+    ///     DefaultEntityRepository,
+    ///     Entity,
+    ///     EntityManager,
+    ///     EntityRepository,
+    ///     Keyed,
+    ///     Patch,
+    /// };
+    /// use autosurgeon::{Hydrate, Reconcile};
+    /// use uuid::Uuid;
+    ///
+    /// #[derive(Clone, Debug, Entity, Hydrate, Patch, Reconcile)]
+    /// struct Book {
+    ///     #[key]
+    ///     id: Uuid,
+    ///     author: String,
+    ///     year: u16,
+    /// }
+    ///
+    /// type BookRepository = DefaultEntityRepository<Book>;
+    ///
+    /// impl Book {
+    ///     pub fn new(author: &str, year: u16) -> Self {
+    ///         Self {
+    ///             id: Uuid::new_v4(),
+    ///             author: author.to_owned(),
+    ///             year,
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// # let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    /// # let doc_handle = repo_handle.new_document();
+    /// let entity_manager = Arc::new(EntityManager::new(doc_handle));
+    /// let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+    ///
+    /// let book = Book::new("Miyazaki Hayao", 1982);
+    /// entity_manager.transact(|tx| {
+    ///     tx.insert(&book)?;
+    ///     automerge_orm::Result::Ok(())
+    /// })?;
+    /// let patch = BookPatch {
+    ///     year: Some(1994),
+    ///     ..BookPatch::default()
+    /// };
+    /// entity_manager.transact(|tx| {
+    ///     tx.apply_patch(book.id(), &patch)?;
+    ///     automerge_orm::Result::Ok(())
+    /// })?;
+    /// let book = book_repository.find(book.id())?.unwrap();
+    /// assert_eq!(book.author, "Miyazaki Hayao");
+    /// assert_eq!(book.year, 1994);
+    /// # repo_handle.stop().unwrap();
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn apply_patch<P>(&mut self, id: Key<P::Entity>, patch: &P) -> Result<()>
+    where
+        P: Patch,
+    {
+        self.get_existing_entity(id)?;
+        patch.apply(self, id)
+    }
+
+    /// Increments a counter field of an existing object instance by `delta`.
+    ///
+    /// The field must be stored as an Automerge counter, e.g. by using
+    /// [`autosurgeon::Counter`] as the type of the field. Concurrent
+    /// increments to the same counter are summed.
+    ///
+    /// The counter will be incremented in the document as a result of the
+    /// [`commit`] operation.
+    ///
+    /// [`commit`]: Transaction::commit
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use automerge::ChangeHash;
+    /// # use automerge_repo::{DocumentId, Repo, Storage, StorageError};
+    /// # use futures::future::{self, BoxFuture, FutureExt};
+    /// #
+    /// # pub struct NoopStorage;
+    /// #
+    /// # impl Storage for NoopStorage {
+    /// #     fn get(
+    /// #         &self,
+    /// #         _id: DocumentId
+    /// #     ) -> BoxFuture<'static, Result<Option<Vec<u8>>, StorageError>> {
+    /// #         future::ready(Ok(None)).boxed()
+    /// #     }
+    /// #
+    /// #     fn list_all(
+    /// #         &self
+    /// #     ) -> BoxFuture<'static, Result<Vec<DocumentId>, StorageError>> {
+    /// #         future::ready(Ok(Vec::new())).boxed()
+    /// #     }
+    /// #
+    /// #     fn append(
+    /// #         &self,
+    /// #         _id: DocumentId,
+    /// #         _chunk: Vec<u8>,
+    /// #     ) -> BoxFuture<'static, Result<(), StorageError>> {
+    /// #         future::ready(Ok(())).boxed()
+    /// #     }
+    /// #
+    /// #     fn compact(
+    /// #         &self,
+    /// #         _id: DocumentId,
+    /// #         _chunk: Vec<u8>,
+    /// #         _new_heads: Vec<ChangeHash>,
+    /// #     ) -> BoxFuture<'static, Result<(), StorageError>> {
+    /// #         future::ready(Ok(())).boxed()
+    /// #     }
+    /// # }
+    /// #
+    /// use std::sync::Arc;
+    ///
+    /// use automerge_orm::{
+    ///     DefaultEntityRepository,
+    ///     Entity,
+    ///     EntityManager,
+    ///     EntityRepository,
+    ///     Keyed,
+    /// };
+    /// use autosurgeon::{Counter, Hydrate, Reconcile};
+    /// use uuid::Uuid;
+    ///
+    /// #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    /// struct Book {
+    ///     #[key]
+    ///     id: Uuid,
+    ///     likes: Counter,
+    /// }
+    ///
+    /// type BookRepository = DefaultEntityRepository<Book>;
+    ///
+    /// impl Book {
+    ///     pub fn new() -> Self {
+    ///         Self {
+    ///             id: Uuid::new_v4(),
+    ///             likes: Counter::default(),
+    ///         }
+    ///     }
+    /// }
+    ///
+    /// # let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    /// # let doc_handle = repo_handle.new_document();
+    /// let entity_manager = Arc::new(EntityManager::new(doc_handle));
+    /// let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+    ///
+    /// let book = Book::new();
+    /// entity_manager.transact(|tx| {
+    ///     tx.insert(&book)?;
+    ///     automerge_orm::Result::Ok(())
+    /// })?;
+    /// entity_manager.transact(|tx| {
+    ///     tx.increment(book.id(), "likes", 1)?;
+    ///     automerge_orm::Result::Ok(())
+    /// })?;
+    /// let book = book_repository.find(book.id())?.unwrap();
+    /// assert_eq!(book.likes.value(), 1);
+    /// # repo_handle.stop().unwrap();
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn increment<T>(&mut self, id: Key<T>, field: &str, delta: i64) -> Result<()>
+    where
+        T: Mapped,
+    {
+        self.check_max_ops()?;
+        let entity_id = self.get_existing_entity(id)?;
+        let field = <T as Mapped>::prop_name(field);
+        self.tx.increment(&entity_id, field, delta)?;
+
+        Ok(())
+    }
+
+    /// Appends `value` to a list field of an existing object instance.
+    ///
+    /// Unlike updating the object with the whole list, the element is
+    /// inserted into the Automerge list directly, so that elements appended
+    /// concurrently, e.g. on different peers, are all kept. The list is
+    /// created if the field does not exist.
+    ///
+    /// The element will be appended in the document as a result of the
+    /// [`commit`] operation.
+    ///
+    /// [`commit`]: Transaction::commit
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use automerge_orm::{Entity, EntityManager, Keyed};
+    /// use autosurgeon::{Hydrate, Reconcile};
+    /// use uuid::Uuid;
+    ///
+    /// #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    /// struct Book {
+    ///     #[key]
+    ///     id: Uuid,
+    ///     tags: Vec<String>,
+    /// }
+    ///
+    /// let entity_manager = EntityManager::ephemeral();
+    ///
+    /// let book = Book {
+    ///     id: Uuid::new_v4(),
+    ///     tags: vec!["anime".to_owned()],
+    /// };
+    /// entity_manager.transact(|tx| tx.insert(&book))?;
+    /// entity_manager.transact(|tx| tx.list_push(book.id(), "tags", &"fantasy"))?;
+    /// let book = entity_manager.find_related(book.id())?.unwrap();
+    /// assert_eq!(book.tags, ["anime", "fantasy"]);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn list_push<T, V>(&mut self, id: Key<T>, field: &str, value: &V) -> Result<()>
+    where
+        T: Mapped,
+        V: Reconcile,
+    {
+        self.check_max_ops()?;
+        let entity_id = self.get_existing_entity(id)?;
+        let field = <T as Mapped>::prop_name(field);
+        let list_id = match self.get_field_object(&entity_id, field, ObjType::List)? {
+            Some(list_id) => list_id,
+            None => self.tx.put_object(&entity_id, field, ObjType::List)?,
+        };
+        let index = self.tx.length(&list_id);
+        reconcile_insert(&mut self.tx, &list_id, index, value).map_err(reconcile_error(id))?;
+
+        Ok(())
+    }
+
+    /// Removes the element at `index` from a list field of an existing object
+    /// instance.
+    ///
+    /// Unlike updating the object with the whole list, the element is deleted
+    /// from the Automerge list directly, so that concurrent changes to other
+    /// elements are kept. Returns an error if `index` is out of bounds.
+    ///
+    /// The element will be removed from the document as a result of the
+    /// [`commit`] operation.
+    ///
+    /// [`commit`]: Transaction::commit
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use automerge_orm::{Entity, EntityManager, Keyed};
+    /// use autosurgeon::{Hydrate, Reconcile};
+    /// use uuid::Uuid;
+    ///
+    /// #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    /// struct Book {
+    ///     #[key]
+    ///     id: Uuid,
+    ///     tags: Vec<String>,
+    /// }
+    ///
+    /// let entity_manager = EntityManager::ephemeral();
+    ///
+    /// let book = Book {
+    ///     id: Uuid::new_v4(),
+    ///     tags: vec!["anime".to_owned(), "fantasy".to_owned()],
+    /// };
+    /// entity_manager.transact(|tx| tx.insert(&book))?;
+    /// entity_manager.transact(|tx| tx.list_remove(book.id(), "tags", 0))?;
+    /// let book = entity_manager.find_related(book.id())?.unwrap();
+    /// assert_eq!(book.tags, ["fantasy"]);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn list_remove<T>(&mut self, id: Key<T>, field: &str, index: usize) -> Result<()>
+    where
+        T: Mapped,
+    {
+        self.check_max_ops()?;
+        let entity_id = self.get_existing_entity(id)?;
+        let field = <T as Mapped>::prop_name(field);
+        let list_id = self.get_field_object(&entity_id, field, ObjType::List)?;
+        match list_id {
+            Some(list_id) if index < self.tx.length(&list_id) => {
+                self.tx.delete(&list_id, index)?;
+            },
+            _ => Err(AutomergeError::InvalidIndex(index))?,
+        }
+
+        Ok(())
+    }
+
+    /// Sets the entry `key` of a map field of an existing object instance to
+    /// `value`.
+    ///
+    /// Unlike updating the object with the whole map, only the entry is
+    /// reconciled, so that concurrent changes to other entries, e.g. on
+    /// different peers, are kept. The map is created if the field does not
+    /// exist.
+    ///
+    /// The entry will be set in the document as a result of the [`commit`]
+    /// operation.
+    ///
+    /// [`commit`]: Transaction::commit
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::collections::BTreeMap;
+    ///
+    /// use automerge_orm::{Entity, EntityManager, Keyed};
+    /// use autosurgeon::{Hydrate, Reconcile};
+    /// use uuid::Uuid;
+    ///
+    /// #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    /// struct Book {
+    ///     #[key]
+    ///     id: Uuid,
+    ///     titles: BTreeMap<String, String>,
+    /// }
+    ///
+    /// let entity_manager = EntityManager::ephemeral();
+    ///
+    /// let book = Book {
+    ///     id: Uuid::new_v4(),
+    ///     titles: BTreeMap::new(),
+    /// };
+    /// entity_manager.transact(|tx| tx.insert(&book))?;
+    /// entity_manager.transact(|tx| tx.map_set(book.id(), "titles", "en", &"Spirited Away"))?;
+    /// let book = entity_manager.find_related(book.id())?.unwrap();
+    /// assert_eq!(book.titles["en"], "Spirited Away");
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn map_set<T, V>(&mut self, id: Key<T>, field: &str, key: &str, value: &V) -> Result<()>
+    where
+        T: Mapped,
+        V: Reconcile,
+    {
+        self.check_max_ops()?;
+        let entity_id = self.get_existing_entity(id)?;
+        let field = <T as Mapped>::prop_name(field);
+        let map_id = match self.get_field_object(&entity_id, field, ObjType::Map)? {
+            Some(map_id) => map_id,
+            None => self.tx.put_object(&entity_id, field, ObjType::Map)?,
+        };
+        reconcile_prop(&mut self.tx, &map_id, key, value).map_err(reconcile_error(id))?;
+
+        Ok(())
+    }
+
+    /// Removes the entry `key` from a map field of an existing object
+    /// instance.
+    ///
+    /// Unlike updating the object with the whole map, only the entry is
+    /// deleted, so that concurrent changes to other entries are kept. Does
+    /// nothing if the field or the entry does not exist.
+    ///
+    /// The entry will be removed from the document as a result of the
+    /// [`commit`] operation.
+    ///
+    /// [`commit`]: Transaction::commit
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::collections::BTreeMap;
+    ///
+    /// use automerge_orm::{Entity, EntityManager, Keyed};
+    /// use autosurgeon::{Hydrate, Reconcile};
+    /// use uuid::Uuid;
+    ///
+    /// #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    /// struct Book {
+    ///     #[key]
+    ///     id: Uuid,
+    ///     titles: BTreeMap<String, String>,
+    /// }
+    ///
+    /// let entity_manager = EntityManager::ephemeral();
+    ///
+    /// let book = Book {
+    ///     id: Uuid::new_v4(),
+    ///     titles: BTreeMap::from([("en".to_owned(), "Spirited Away".to_owned())]),
+    /// };
+    /// entity_manager.transact(|tx| tx.insert(&book))?;
+    /// entity_manager.transact(|tx| tx.map_remove(book.id(), "titles", "en"))?;
+    /// let book = entity_manager.find_related(book.id())?.unwrap();
+    /// assert!(book.titles.is_empty());
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn map_remove<T>(&mut self, id: Key<T>, field: &str, key: &str) -> Result<()>
+    where
+        T: Mapped,
+    {
+        self.check_max_ops()?;
+        let entity_id = self.get_existing_entity(id)?;
+        let field = <T as Mapped>::prop_name(field);
+        if let Some(map_id) = self.get_field_object(&entity_id, field, ObjType::Map)? {
+            if self.tx.get(&map_id, key)?.is_some() {
+                self.tx.delete(&map_id, key)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Imports objects from JSON, inserting new object instances and updating
+    /// existing object instances.
+    ///
+    /// The JSON value must be an object keyed by the key of each object, as
+    /// returned by [`DefaultEntityRepository::export_json`]. Each object is
+    /// deserialized into `T`, and its key must match the key it is stored
+    /// under. Bytes other than the key of each object, e.g. foreign keys, are
+    /// exported as arrays of numbers, as described in [`impls::to_json`], so
+    /// the fields holding them must be deserializable from such arrays.
+    ///
+    /// The objects will be inserted into / updated in the document as a result
+    /// of the [`commit`] operation.
+    ///
+    /// [`DefaultEntityRepository::export_json`]: crate::DefaultEntityRepository::export_json
+    /// [`impls::to_json`]: crate::impls::to_json
+    /// [`commit`]: Transaction::commit
+    #[cfg(feature = "serde_json")]
+    pub fn import_json<T>(&mut self, value: serde_json::Value) -> Result<()>
+    where
+        T: Mapped + Keyed<Entity = T> + Reconcile + DeserializeOwned,
+    {
+        let entities: BTreeMap<String, T> = serde_json::from_value(value)?;
+        for (key, entity) in &entities {
+            let id = Key::from_prop(key)?;
+            if entity.id() != id {
+                return Err(key_mismatch(entity.id(), id, "key of JSON object"));
+            }
+        }
+        for entity in entities.values() {
+            self.upsert(entity)?;
+        }
+
+        Ok(())
+    }
+
+    /// Adopts a table which was created outside of the Automerge ORM, e.g. by
+    /// another Automerge client, and returns the number of objects in it.
+    ///
+    /// The ORM expects a table to be a map of objects keyed by their key. A
+    /// table which is such a map is validated, so that every property is a
+    /// key and every value is a map. A table which is a list of objects
+    /// instead is normalized into a map, by inserting each object like
+    /// [`insert`], and returns an error if two objects have the same key. Any
+    /// other value of the table is an error. If the table does not exist,
+    /// nothing is done.
+    ///
+    /// The table will be normalized in the document as a result of the
+    /// [`commit`] operation.
+    ///
+    /// [`insert`]: Transaction::insert
+    /// [`commit`]: Transaction::commit
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use automerge::{transaction::Transactable, Automerge, ObjType, ScalarValue};
+    /// use automerge_orm::{Entity, EntityManager};
+    /// use autosurgeon::{Hydrate, Reconcile};
+    /// use uuid::Uuid;
+    ///
+    /// #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    /// struct Book {
+    ///     #[key]
+    ///     id: Uuid,
+    /// }
+    ///
+    /// let mut doc = Automerge::new();
+    /// let mut tx = doc.transaction();
+    /// let table_id = tx.put_object(automerge::ROOT, "book", ObjType::List)?;
+    /// let book_id = tx.insert_object(&table_id, 0, ObjType::Map)?;
+    /// tx.put(&book_id, "id", ScalarValue::Bytes(Uuid::new_v4().as_bytes().to_vec()))?;
+    /// tx.commit();
+    /// let entity_manager = EntityManager::from_doc(doc);
+    ///
+    /// assert!(entity_manager.read(|reader| reader.find_all::<Book>()).is_err());
+    /// let adopted = entity_manager.transact(|tx| tx.adopt_table::<Book>())?;
+    /// assert_eq!(adopted, 1);
+    /// let books = entity_manager.read(|reader| reader.find_all::<Book>())?;
+    /// assert_eq!(books.len(), 1);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn adopt_table<T>(&mut self) -> Result<usize>
+    where
+        T: Mapped + Keyed<Entity = T> + Hydrate + Reconcile,
+    {
+        let table_name = <T as Mapped>::table_name();
+        let Some(root_id) = get_root(&self.tx, self.root.as_deref())? else {
+            return Ok(0);
+        };
+        let Some((value, table_id)) = self.tx.get(&root_id, table_name)? else {
+            return Ok(0);
+        };
+        if let Value::Object(ObjType::List) = value {
+            let entities: Vec<T> = hydrate_prop(&self.tx, &root_id, table_name)?;
+            self.tx.delete(&root_id, table_name)?;
+            self.tables.remove(table_name);
+            for entity in &entities {
+                self.insert(entity)?;
+            }
+            return Ok(entities.len());
+        }
+        expect_map(&value)?;
+        let mut len = 0;
+        for (key, value, _) in self.tx.map_range(&table_id, ..) {
+            Key::<T>::from_prop(key)?;
+            expect_map(&value)?;
+            len += 1;
+        }
+
+        Ok(len)
+    }
+
+    /// Returns the number of operations which have been queued up in this
+    /// transaction.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use automerge::ChangeHash;
+    /// # use automerge_repo::{DocumentId, Repo, Storage, StorageError};
+    /// # use futures::future::{self, BoxFuture, FutureExt};
+    /// #
+    /// # pub struct NoopStorage;
+    /// #
+    /// # impl Storage for NoopStorage {
+    /// #     fn get(
+    /// #         &self,
+    /// #         _id: DocumentId
+    /// #     ) -> BoxFuture<'static, Result<Option<Vec<u8>>, StorageError>> {
+    /// #         future::ready(Ok(None)).boxed()
+    /// #     }
+    /// #
+    /// #     fn list_all(
+    /// #         &self
+    /// #     ) -> BoxFuture<'static, Result<Vec<DocumentId>, StorageError>> {
+    /// #         future::ready(Ok(Vec::new())).boxed()
+    /// #     }
+    /// #
+    /// #     fn append(
+    /// #         &self,
+    /// #         _id: DocumentId,
+    /// #         _chunk: Vec<u8>,
+    /// #     ) -> BoxFuture<'static, Result<(), StorageError>> {
+    /// #         future::ready(Ok(())).boxed()
+    /// #     }
+    /// #
+    /// #     fn compact(
+    /// #         &self,
+    /// #         _id: DocumentId,
+    /// #         _chunk: Vec<u8>,
+    /// #         _new_heads: Vec<ChangeHash>,
+    /// #     ) -> BoxFuture<'static, Result<(), StorageError>> {
+    /// #         future::ready(Ok(())).boxed()
+    /// #     }
+    /// # }
+    /// #
+    /// use std::sync::Arc;
+    ///
+    /// use automerge_orm::{Entity, EntityManager};
+    /// use autosurgeon::{Hydrate, Reconcile};
+    /// use uuid::Uuid;
+    ///
+    /// #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    /// struct Book {
+    ///     #[key]
+    ///     id: Uuid,
+    /// }
+    ///
+    /// impl Book {
+    ///     pub fn new() -> Self {
+    ///         Self { id: Uuid::new_v4() }
+    ///     }
+    /// }
+    ///
+    /// # let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    /// # let doc_handle = repo_handle.new_document();
+    /// let entity_manager = Arc::new(EntityManager::new(doc_handle));
+    ///
+    /// entity_manager.transact(|tx| {
+    ///     assert_eq!(tx.pending_ops(), 0);
+    ///     tx.insert(&Book::new())?;
+    ///     assert!(tx.pending_ops() > 0);
+    ///     automerge_orm::Result::Ok(())
+    /// })?;
+    /// # repo_handle.stop().unwrap();
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn pending_ops(&self) -> usize {
+        self.tx.pending_ops()
+    }
+
+    /// Returns the heads of the document which this transaction is based on,
+    /// i.e. the dependencies of the change which is created by [`commit`].
+    ///
+    /// This allows the provenance of the change to be recorded, e.g. by tools
+    /// building on the change graph.
+    ///
+    /// [`commit`]: Transaction::commit
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use automerge_orm::{Entity, EntityManager};
+    /// use autosurgeon::{Hydrate, Reconcile};
+    /// use uuid::Uuid;
+    ///
+    /// #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    /// struct Book {
+    ///     #[key]
+    ///     id: Uuid,
+    /// }
+    ///
+    /// let entity_manager = EntityManager::ephemeral();
+    ///
+    /// entity_manager.transact(|tx| tx.insert(&Book { id: Uuid::new_v4() }))?;
+    /// let heads = entity_manager.heads();
+    /// let base_heads = entity_manager.transact(|tx| {
+    ///     tx.insert(&Book { id: Uuid::new_v4() })?;
+    ///     automerge_orm::Result::Ok(tx.base_heads())
+    /// })?;
+    /// assert_eq!(base_heads, heads);
+    /// assert_ne!(entity_manager.heads(), heads);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn base_heads(&self) -> Vec<ChangeHash> {
+        automerge::ReadDoc::get_heads(&self.tx)
+    }
+
+    /// Returns `true` if no operations have been queued up in this
+    /// transaction, i.e. committing it would not change the document.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use automerge::ChangeHash;
+    /// # use automerge_repo::{DocumentId, Repo, Storage, StorageError};
+    /// # use futures::future::{self, BoxFuture, FutureExt};
+    /// #
+    /// # pub struct NoopStorage;
+    /// #
+    /// # impl Storage for NoopStorage {
+    /// #     fn get(
+    /// #         &self,
+    /// #         _id: DocumentId
+    /// #     ) -> BoxFuture<'static, Result<Option<Vec<u8>>, StorageError>> {
+    /// #         future::ready(Ok(None)).boxed()
+    /// #     }
+    /// #
+    /// #     fn list_all(
+    /// #         &self
+    /// #     ) -> BoxFuture<'static, Result<Vec<DocumentId>, StorageError>> {
+    /// #         future::ready(Ok(Vec::new())).boxed()
+    /// #     }
+    /// #
+    /// #     fn append(
+    /// #         &self,
+    /// #         _id: DocumentId,
+    /// #         _chunk: Vec<u8>,
+    /// #     ) -> BoxFuture<'static, Result<(), StorageError>> {
+    /// #         future::ready(Ok(())).boxed()
+    /// #     }
+    /// #
+    /// #     fn compact(
+    /// #         &self,
+    /// #         _id: DocumentId,
+    /// #         _chunk: Vec<u8>,
+    /// #         _new_heads: Vec<ChangeHash>,
+    /// #     ) -> BoxFuture<'static, Result<(), StorageError>> {
+    /// #         future::ready(Ok(())).boxed()
+    /// #     }
+    /// # }
+    /// #
+    /// use std::sync::Arc;
+    ///
+    /// use automerge_orm::{Entity, EntityManager};
+    /// use autosurgeon::{Hydrate, Reconcile};
+    /// use uuid::Uuid;
+    ///
+    /// #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    /// struct Book {
+    ///     #[key]
+    ///     id: Uuid,
+    /// }
+    ///
+    /// impl Book {
+    ///     pub fn new() -> Self {
+    ///         Self { id: Uuid::new_v4() }
+    ///     }
+    /// }
+    ///
+    /// # let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    /// # let doc_handle = repo_handle.new_document();
+    /// let entity_manager = Arc::new(EntityManager::new(doc_handle));
+    ///
+    /// entity_manager.transact(|tx| {
+    ///     assert!(tx.is_empty());
+    ///     tx.insert(&Book::new())?;
+    ///     assert!(!tx.is_empty());
+    ///     automerge_orm::Result::Ok(())
+    /// })?;
+    /// # repo_handle.stop().unwrap();
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn is_empty(&self) -> bool {
+        self.pending_ops() == 0
+    }
+
+    /// Commits all changes that have been queued up to now to the document.
+    ///
+    /// The commit message and timestamp of the change can be set using
+    /// [`TransactionOptions`]. If [`TransactionOptions::skip_empty`] is set
+    /// and no operations have been queued up, the transaction is rolled back
+    /// instead.
+    pub fn commit(self) -> Result<()> {
+        if self.options.skip_empty() && self.is_empty() {
+            self.rollback();
+            return Ok(());
+        }
+        let message = self
+            .options
+            .message()
+            .unwrap_or("automerge_orm::Transaction::commit");
+        let time = self.options.time().unwrap_or_else(|| {
+            let now = SystemTime::now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap();
+            now.as_secs() as i64
+        });
+        self.tx.commit_with(
+            CommitOptions::default()
+                .with_message(message)
+                .with_time(time),
+        );
+
+        Ok(())
+    }
+
+    /// Rolls back all changes that have been queued up.
+    pub fn rollback(self) {
+        self.tx.rollback();
+    }
+
+    /// Applies patches to the document, e.g. to revert it to a [`Savepoint`].
+    ///
+    /// [`Savepoint`]: crate::Savepoint
+    pub(crate) fn apply_patches(&mut self, patches: Vec<automerge::Patch>) -> Result<()> {
+        // Patches may delete or replace tables.
+        self.tables.clear();
+        savepoint::apply_patches(&mut self.tx, patches)
+    }
+
+    /// Returns the schema version stored in the document.
+    pub(crate) fn schema_version(&self) -> Result<u32> {
+        migration::schema_version(&self.tx, self.root.as_deref())
+    }
+
+    /// Stores the schema version in the document.
+    pub(crate) fn set_schema_version(&mut self, version: u32) -> Result<()> {
+        let root_id = get_or_create_root(&mut self.tx, self.root.as_deref())?;
+        self.tx.put(
+            root_id,
+            migration::SCHEMA_VERSION_KEY,
+            ScalarValue::Uint(version.into()),
+        )?;
+
+        Ok(())
+    }
+
+    /// Returns the Automerge object id of a table, resolving it only once
+    /// within this transaction.
+    fn resolve_table<T>(&mut self) -> Result<Option<ObjId>>
+    where
+        T: Mapped,
+    {
+        let table_name = <T as Mapped>::table_name();
+        if let Some(table_id) = self.tables.get(table_name) {
+            return Ok(Some(table_id.clone()));
+        }
+        let Some(table_id) = get_table_under::<_, T>(&self.tx, self.root.as_deref())? else {
+            return Ok(None);
+        };
+        self.tables.insert(table_name, table_id.clone());
+
+        Ok(Some(table_id))
+    }
+
+    /// Returns the Automerge object id of a table, creating the table if it
+    /// does not exist.
+    fn get_or_create_table<T>(&mut self) -> Result<ObjId>
+    where
+        T: Mapped,
+    {
+        if let Some(table_id) = self.resolve_table::<T>()? {
+            return Ok(table_id);
+        }
+        let table_id = create_table_under::<_, T>(&mut self.tx, self.root.as_deref())?;
+        self.tables
+            .insert(<T as Mapped>::table_name(), table_id.clone());
+
+        Ok(table_id)
+    }
+
+    /// Returns the Automerge object id of the table of an existing entity, or
+    /// an [`Error::ObjectDoesNotExist`] if the entity does not exist.
+    fn get_existing_table<T>(&mut self, id: Key<T>) -> Result<ObjId>
+    where
+        T: Mapped,
+    {
+        match self.resolve_table::<T>()? {
+            Some(table_id) if self.tx.get(&table_id, Prop::Map(id.to_prop()))?.is_some() => {
+                Ok(table_id)
+            },
+            _ => Err(Error::ObjectDoesNotExist {
+                table_name: <T as Mapped>::table_name().to_owned(),
+                id: id.into(),
+            }),
+        }
+    }
+
+    /// Returns the Automerge object id of an existing entity, or an
+    /// [`Error::ObjectDoesNotExist`] if it does not exist.
+    fn get_existing_entity<T>(&mut self, id: Key<T>) -> Result<ObjId>
+    where
+        T: Mapped,
+    {
+        let entity_id = match self.resolve_table::<T>()? {
+            Some(table_id) => self.tx.get(&table_id, Prop::Map(id.to_prop()))?,
+            None => None,
+        };
+        let Some((_, entity_id)) = entity_id else {
+            return Err(Error::ObjectDoesNotExist {
+                table_name: <T as Mapped>::table_name().to_owned(),
+                id: id.into(),
+            });
+        };
+
+        Ok(entity_id)
+    }
+
+    /// Returns the Automerge object id of the `obj_type` object stored in the
+    /// property `prop` of an entity, or `None` if the property does not exist.
+    ///
+    /// Returns an error if the property holds a value of another type.
+    fn get_field_object(
+        &self,
+        entity_id: &ObjId,
+        prop: &str,
+        obj_type: ObjType,
+    ) -> Result<Option<ObjId>> {
+        match self.tx.get(entity_id, prop)? {
+            Some((Value::Object(actual), obj_id)) if actual == obj_type => Ok(Some(obj_id)),
+            Some((value, _)) => Err(AutomergeError::InvalidValueType {
+                expected: format!("{}", Value::Object(obj_type)),
+                unexpected: format!("{value}"),
+            })?,
+            None => Ok(None),
+        }
+    }
+
+    /// Deletes the properties of an entity which have been set to null by
+    /// the current write, i.e. which are null but are contained in
+    /// `non_null_props`.
+    ///
+    /// `autosurgeon` reconciles fields which are set to `None` as null rather
+    /// than deleting their properties. A missing property is hydrated as
+    /// `None` as well. Properties which were null before the write, e.g. which
+    /// were stored as null on purpose, are kept, so that writing an unchanged
+    /// entity does not produce any operations.
+    ///
+    /// Only the properties of the entity itself are deleted, as the maps
+    /// nested in it may hold the entries of a map field, e.g. of a
+    /// `HashMap<String, Option<T>>`, whose nulls must be kept for the field to
+    /// be hydrated as it was written.
+    fn delete_nulled_props(
+        &mut self,
+        entity_id: &ObjId,
+        non_null_props: &HashSet<String>,
+    ) -> Result<()> {
+        let nulled_props: Vec<_> = self
+            .tx
+            .map_range(entity_id, ..)
+            .filter(|(key, value, _)| is_null(value) && non_null_props.contains(*key))
+            .map(|(key, ..)| key.to_owned())
+            .collect();
+        for prop in nulled_props {
+            self.tx.delete(entity_id, prop)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the counter fields of `entity`, which are not reconciled, into
+    /// the object `entity_id`.
+    fn write_counters<T>(&mut self, entity_id: &ObjId, id: Key<T>, entity: &T) -> Result<()>
+    where
+        T: Mapped + Reconcile,
+    {
+        let counter_fields = <T as Mapped>::counter_fields();
+        if counter_fields.is_empty() {
+            return Ok(());
+        }
+        // The values of the fields are read as they are stored, so the entity
+        // is reconciled into a scratch document, which is discarded afterwards.
+        let mut scratch = Automerge::new();
+        let mut scratch_tx = scratch.transaction();
+        reconcile_prop(&mut scratch_tx, automerge::ROOT, "entity", entity)
+            .map_err(reconcile_error(id))?;
+        let Some((_, scratch_id)) = scratch_tx.get(&automerge::ROOT, "entity")? else {
+            return Ok(());
+        };
+        for field in counter_fields {
+            let prop = <T as Mapped>::prop_name(field);
+            if let Some((Value::Scalar(value), _)) = scratch_tx.get(&scratch_id, prop)? {
+                self.write_counter(entity_id, prop, &value)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Changes the counter stored in the property `prop` of the object
+    /// `entity_id` to `value` by incrementing it by the difference, or stores
+    /// a new counter if the property does not hold one.
+    fn write_counter(&mut self, entity_id: &ObjId, prop: &str, value: &ScalarValue) -> Result<()> {
+        let value = match value {
+            ScalarValue::Counter(counter) => i64::from(counter),
+            ScalarValue::Int(value) => *value,
+            ScalarValue::Uint(value) => {
+                i64::try_from(*value).map_err(|_| AutomergeError::InvalidValueType {
+                    expected: "an integer which fits into a counter".to_owned(),
+                    unexpected: format!("{value}"),
+                })?
+            },
+            value => Err(AutomergeError::InvalidValueType {
+                expected: "an integer".to_owned(),
+                unexpected: format!("{value}"),
+            })?,
+        };
+        let current = match self.tx.get(entity_id, prop)? {
+            Some((Value::Scalar(stored), _)) => match &*stored {
+                ScalarValue::Counter(counter) => Some(i64::from(counter)),
+                _ => None,
+            },
+            _ => None,
+        };
+        match current {
+            Some(current) if current == value => {},
+            Some(current) => self
+                .tx
+                .increment(entity_id, prop, value.wrapping_sub(current))?,
+            None => self.tx.put(entity_id, prop, ScalarValue::counter(value))?,
+        }
+
+        Ok(())
+    }
+
+    /// Returns an [`Error::TransactionTooLarge`] if the transaction has queued
+    /// up more operations than allowed by its options.
+    fn check_max_ops(&self) -> Result<()> {
+        let Some(max_ops) = self.options.max_ops() else {
+            return Ok(());
+        };
+        let pending_ops = self.tx.pending_ops();
+        if pending_ops > max_ops {
+            return Err(Error::TransactionTooLarge {
+                max_ops,
+                pending_ops,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Returns an [`Error::UniqueViolation`] if another object in the table
+    /// has the same value of a unique field as `entity`.
+    fn check_unique<T>(&self, table_id: &ObjId, id: Key<T>, entity: &T) -> Result<()>
+    where
+        T: Mapped + Reconcile,
+    {
+        let unique_fields = <T as Mapped>::unique_fields();
+        if unique_fields.is_empty() {
+            return Ok(());
+        }
+        // Values are compared as they are stored, so the entity is reconciled
+        // into a scratch document, which is discarded afterwards.
+        let mut scratch = Automerge::new();
+        let mut scratch_tx = scratch.transaction();
+        reconcile_prop(&mut scratch_tx, automerge::ROOT, "entity", entity)
+            .map_err(reconcile_error(id))?;
+        let Some((_, entity_id)) = scratch_tx.get(&automerge::ROOT, "entity")? else {
+            return Ok(());
+        };
+        let key = id.to_prop();
+        for field in unique_fields {
+            let prop = <T as Mapped>::prop_name(field);
+            let Some((Value::Scalar(value), _)) = scratch_tx.get(&entity_id, prop)? else {
+                continue;
+            };
+            self.check_unique_value::<T>(table_id, &key, field, &value)?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns an [`Error::UniqueViolation`] if an object in the table other
+    /// than the one stored under `key` has the value `value` of the unique
+    /// field `field`.
+    fn check_unique_value<T>(
+        &self,
+        table_id: &ObjId,
+        key: &str,
+        field: &str,
+        value: &ScalarValue,
+    ) -> Result<()>
+    where
+        T: Mapped,
+    {
+        let prop = <T as Mapped>::prop_name(field);
+        for (other_key, other_value, other_id) in self.tx.map_range(table_id, ..) {
+            if other_key == key || other_value != Value::Object(ObjType::Map) {
+                continue;
+            }
+            if let Some((Value::Scalar(other), _)) = self.tx.get(&other_id, prop)? {
+                if *other == *value {
+                    return Err(Error::UniqueViolation {
+                        table_name: <T as Mapped>::table_name().to_owned(),
+                        field: field.to_owned(),
+                        value: value.clone(),
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes an entity as the object identified by `id` into the table
+    /// identified by `table_id`, invoking the insert or update `hooks` around
+    /// validating and writing it according to `mode`.
+    ///
+    /// The key of an entity modified by the `before_*` hook must still match
+    /// `id`.
+    fn write_entity<T>(
+        &mut self,
+        table_id: &ObjId,
+        id: Key<T>,
+        entity: &T,
+        hooks: WriteHooks,
+        mode: WriteMode,
+    ) -> Result<()>
+    where
+        T: Mapped + Keyed<Entity = T> + Reconcile,
+    {
+        self.check_max_ops()?;
+        let lifecycle = T::lifecycle();
+        let (before, after) = match hooks {
+            WriteHooks::Insert => (lifecycle.before_insert, lifecycle.after_insert_write),
+            WriteHooks::Update => (lifecycle.before_update, lifecycle.after_update_write),
+        };
+        let modified = before(entity)?;
+        let entity = modified.as_ref().unwrap_or(entity);
+        if entity.id() != id {
+            return Err(key_mismatch(entity.id(), id, "key of the object to write"));
+        }
+        validate(&lifecycle, entity)?;
+        #[cfg(feature = "crypto")]
+        crate::encrypted::check_key::<T>()?;
+        self.check_unique(table_id, id, entity)?;
+        let non_null_props: HashSet<_> = match self.tx.get(table_id, Prop::Map(id.to_prop()))? {
+            Some((Value::Object(ObjType::Map), entity_id)) => self
+                .tx
+                .map_range(&entity_id, ..)
+                .filter(|(_, value, _)| !is_null(value))
+                .map(|(key, ..)| key.to_owned())
+                .collect(),
+            _ => HashSet::new(),
+        };
+        if let WriteMode::Replace = mode {
+            self.tx
+                .put_object(table_id, Prop::Map(id.to_prop()), ObjType::Map)?;
+        }
+        counter::deferred(|| reconcile_prop(&mut self.tx, table_id, &*id.to_prop(), entity))
+            .map_err(reconcile_error(id))?;
+        let entity_id = self.get_existing_entity(id)?;
+        self.delete_nulled_props(&entity_id, &non_null_props)?;
+        self.write_counters(&entity_id, id, entity)?;
+        if let Some(type_tag) = <T as Mapped>::type_tag() {
+            let tagged = matches!(
+                self.tx.get(&entity_id, mapped::TYPE_TAG_KEY)?,
+                Some((value, _)) if value.to_str() == Some(type_tag)
+            );
+            if !tagged {
+                self.tx.put(&entity_id, mapped::TYPE_TAG_KEY, type_tag)?;
+            }
+        }
+        after(entity)
+    }
+}
+
+/// Which hooks are invoked when an entity is written into its table.
+#[derive(Clone, Copy, Debug)]
+enum WriteHooks {
+    /// The hooks of an entity which is inserted.
+    Insert,
+    /// The hooks of an entity which is updated.
+    Update,
+}
+
+/// How an entity is written into its table.
+#[derive(Clone, Copy, Debug)]
+enum WriteMode {
+    /// The entity is reconciled with the object stored in the document.
+    Reconcile,
+    /// The object stored in the document is replaced by a new object.
+    Replace,
+}
+
+/// Returns whether a value is the null scalar.
+fn is_null(value: &Value<'_>) -> bool {
+    matches!(value, Value::Scalar(scalar) if **scalar == ScalarValue::Null)
+}
+
+/// Validates an entity according to its `lifecycle` before it is written to
+/// the document.
+fn validate<T>(lifecycle: &Lifecycle<T>, entity: &T) -> Result<()>
+where
+    T: Mapped + Keyed<Entity = T>,
+{
+    (lifecycle.validate)(entity).map_err(|e| Error::Validation {
+        table_name: <T as Mapped>::table_name().to_owned(),
+        id: entity.id().into(),
+        source: e,
+    })
+}
+
+/// Creates an [`Error::KeyMismatch`] for a key obtained from an entity which
+/// does not match the `expected` key.
+fn key_mismatch<T>(actual: Key<T>, expected: Key<T>, expected_desc: &str) -> Error {
+    Error::KeyMismatch {
+        actual: actual.into(),
+        expected: expected.into(),
+        msg: format!(
+            "key obtained from `<{} as automerge_orm::Keyed>::id()` does not match \
+            {expected_desc}",
+            std::any::type_name::<T>()
+        ),
+    }
+}
+
+/// Returns a function which creates an [`Error::Reconcile`] for an error of
+/// reconciling the object identified by `id`, or one of its fields.
+fn reconcile_error<T>(id: Key<T>) -> impl FnOnce(ReconcileError) -> Error
+where
+    T: Mapped,
+{
+    move |err| Error::Reconcile {
+        table_name: <T as Mapped>::table_name().to_owned(),
+        id: id.into(),
+        source: Arc::new(err),
     }
 }
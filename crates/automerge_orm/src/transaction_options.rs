@@ -0,0 +1,91 @@
+/// Options for a transaction, which control how its changes are committed to
+/// the Automerge document.
+///
+/// This `struct` is passed to the [`transact_with_options`] method on
+/// [`EntityManager`]. See its documentation for more.
+///
+/// [`transact_with_options`]: crate::EntityManager::transact_with_options
+/// [`EntityManager`]: crate::EntityManager
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct TransactionOptions {
+    message: Option<String>,
+    time: Option<i64>,
+    skip_empty: bool,
+    max_ops: Option<usize>,
+}
+
+impl TransactionOptions {
+    /// Creates new `TransactionOptions` with the default commit message and
+    /// timestamp.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the commit message of the change.
+    ///
+    /// Defaults to `"automerge_orm::Transaction::commit"`.
+    pub fn with_message<S>(mut self, message: S) -> Self
+    where
+        S: Into<String>,
+    {
+        self.message = Some(message.into());
+        self
+    }
+
+    /// Sets the timestamp of the change, in seconds since the Unix epoch.
+    ///
+    /// Defaults to the current time when the transaction is committed.
+    pub fn with_time(mut self, time: i64) -> Self {
+        self.time = Some(time);
+        self
+    }
+
+    /// Sets whether a transaction which has not queued up any operations is
+    /// rolled back instead of committed, so that no change is added to the
+    /// history of the document.
+    ///
+    /// Defaults to `false`.
+    pub fn with_skip_empty(mut self, skip_empty: bool) -> Self {
+        self.skip_empty = skip_empty;
+        self
+    }
+
+    /// Sets the maximum number of operations which the transaction may queue
+    /// up.
+    ///
+    /// Once the transaction has queued up more than `max_ops` operations, each
+    /// further write operation fails with an [`Error::TransactionTooLarge`],
+    /// which in turn aborts the transaction. This protects shared documents
+    /// from runaway transactions. A single write operation may still queue up
+    /// many operations, so the limit is only checked before each write.
+    ///
+    /// Defaults to no limit.
+    ///
+    /// [`Error::TransactionTooLarge`]: crate::Error::TransactionTooLarge
+    pub fn with_max_ops(mut self, max_ops: usize) -> Self {
+        self.max_ops = Some(max_ops);
+        self
+    }
+
+    /// Returns the commit message of the change, if set.
+    pub fn message(&self) -> Option<&str> {
+        self.message.as_deref()
+    }
+
+    /// Returns the timestamp of the change, if set.
+    pub fn time(&self) -> Option<i64> {
+        self.time
+    }
+
+    /// Returns whether an empty transaction is rolled back instead of
+    /// committed.
+    pub fn skip_empty(&self) -> bool {
+        self.skip_empty
+    }
+
+    /// Returns the maximum number of operations which the transaction may
+    /// queue up, if set.
+    pub fn max_ops(&self) -> Option<usize> {
+        self.max_ops
+    }
+}
@@ -0,0 +1,91 @@
+use std::fmt::{self, Display};
+
+/// An entity which can check its own invariants before it is written to an
+/// Automerge document.
+///
+/// [`Transaction::insert`], [`Transaction::update`] and
+/// [`Transaction::upsert`] call [`validate`] before the entity is reconciled
+/// into the document, and fail with [`Error::Validation`] if the entity is
+/// invalid.
+///
+/// The [`derive@Entity`] derive implements this trait with a no-op
+/// [`validate`], unless the type is annotated with
+/// `#[automerge_orm(validate)]`, in which case the trait must be implemented
+/// manually.
+///
+/// Validation is opt-in, so that entities which implement [`Mapped`] manually
+/// do not have to implement this trait. Such entities are only validated if
+/// [`Mapped::lifecycle`] returns a [`Lifecycle`] created with
+/// [`Lifecycle::validated`], which the derive does.
+///
+/// [`Transaction::insert`]: crate::Transaction::insert
+/// [`Transaction::update`]: crate::Transaction::update
+/// [`Transaction::upsert`]: crate::Transaction::upsert
+/// [`validate`]: Validate::validate
+/// [`Error::Validation`]: crate::Error::Validation
+/// [`derive@Entity`]: crate::Entity
+/// [`Mapped`]: crate::Mapped
+/// [`Mapped::lifecycle`]: crate::Mapped::lifecycle
+/// [`Lifecycle`]: crate::Lifecycle
+/// [`Lifecycle::validated`]: crate::Lifecycle::validated
+pub trait Validate {
+    /// Checks the invariants of the entity.
+    ///
+    /// The default implementation accepts every entity.
+    fn validate(&self) -> Result<(), ValidationError> {
+        Ok(())
+    }
+}
+
+/// An error which describes why an entity is invalid.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ValidationError {
+    field: Option<String>,
+    msg: String,
+}
+
+impl ValidationError {
+    /// Creates a new `ValidationError` for the entity as a whole.
+    pub fn new<S>(msg: S) -> Self
+    where
+        S: Into<String>,
+    {
+        Self {
+            field: None,
+            msg: msg.into(),
+        }
+    }
+
+    /// Creates a new `ValidationError` for a specific field of the entity.
+    pub fn for_field<F, S>(field: F, msg: S) -> Self
+    where
+        F: Into<String>,
+        S: Into<String>,
+    {
+        Self {
+            field: Some(field.into()),
+            msg: msg.into(),
+        }
+    }
+
+    /// Returns the name of the invalid field, if any.
+    pub fn field(&self) -> Option<&str> {
+        self.field.as_deref()
+    }
+
+    /// Returns the message which describes why the entity is invalid.
+    pub fn message(&self) -> &str {
+        &self.msg
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.field {
+            Some(field) => write!(f, "{field}: {}", self.msg),
+            None => write!(f, "{}", self.msg),
+        }
+    }
+}
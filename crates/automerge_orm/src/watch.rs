@@ -0,0 +1,160 @@
+use std::collections::BTreeSet;
+
+use automerge::{Automerge, ChangeHash, ObjId, ObjType, PatchAction, Prop, ReadDoc, Value};
+use autosurgeon::Hydrate;
+
+use crate::{impls::find_under, Key, Mapped, Result};
+
+/// An event which is emitted when an entity is changed in the Automerge
+/// document.
+///
+/// This `enum` is yielded by the stream returned from the [`watch`] method on
+/// [`DefaultEntityRepository`]. See its documentation for more.
+///
+/// [`watch`]: crate::DefaultEntityRepository::watch
+/// [`DefaultEntityRepository`]: crate::DefaultEntityRepository
+#[derive(Debug)]
+pub enum EntityEvent<T> {
+    /// An entity was inserted.
+    Inserted(T),
+    /// An existing entity was updated.
+    Updated(T),
+    /// An entity was removed.
+    Removed(Key<T>),
+}
+
+/// Computes the events for entities of type `T` which were changed between
+/// `before` and `after`.
+///
+/// Inserted and updated entities are hydrated from the current state of the
+/// document. Tables are looked up under `root`, as configured for the entity
+/// manager.
+pub(crate) fn entity_events<T>(
+    doc: &Automerge,
+    root: Option<&str>,
+    before: &[ChangeHash],
+    after: &[ChangeHash],
+) -> Result<Vec<EntityEvent<T>>>
+where
+    T: Mapped + Hydrate,
+{
+    let mut events = Vec::new();
+    for key in changed_keys::<T>(doc, root, before, after)? {
+        let id = Key::<T>::from_prop(&key)?;
+        let existed = contains_at::<T>(doc, root, before, &key)?;
+        match (existed, find_under(doc, root, id)?) {
+            (false, Some(entity)) => events.push(EntityEvent::Inserted(entity)),
+            (true, Some(entity)) => events.push(EntityEvent::Updated(entity)),
+            (true, None) => events.push(EntityEvent::Removed(id)),
+            (false, None) => {},
+        }
+    }
+
+    Ok(events)
+}
+
+/// Returns the keys of the entities of type `T` which were changed between
+/// `before` and `after`.
+fn changed_keys<T>(
+    doc: &Automerge,
+    root: Option<&str>,
+    before: &[ChangeHash],
+    after: &[ChangeHash],
+) -> Result<BTreeSet<String>>
+where
+    T: Mapped,
+{
+    let table_name = <T as Mapped>::table_name();
+    let mut keys = BTreeSet::new();
+    for patch in doc.diff(before, after) {
+        let mut path = patch.path.into_iter().map(|(_, prop)| prop);
+        if let Some(root) = root {
+            match path.next() {
+                Some(Prop::Map(key)) if key == root => {},
+                _ => continue,
+            }
+        }
+        match (path.next(), path.next(), patch.action) {
+            (Some(Prop::Map(table)), Some(Prop::Map(key)), _) if table == table_name => {
+                keys.insert(key);
+            },
+            (
+                Some(Prop::Map(table)),
+                None,
+                PatchAction::PutMap { key, .. } | PatchAction::DeleteMap { key },
+            ) if table == table_name => {
+                keys.insert(key);
+            },
+            (None, None, PatchAction::PutMap { key, .. } | PatchAction::DeleteMap { key })
+                if key == table_name =>
+            {
+                // The table itself was created, replaced or deleted, so every
+                // entity in the table may have changed.
+                keys.extend(table_keys_at(doc, root, table_name, before)?);
+                keys.extend(table_keys_at(doc, root, table_name, after)?);
+            },
+            _ => {},
+        }
+    }
+
+    Ok(keys)
+}
+
+/// Returns whether an entity of type `T` identified by `key` existed in the
+/// document at `heads`.
+fn contains_at<T>(
+    doc: &Automerge,
+    root: Option<&str>,
+    heads: &[ChangeHash],
+    key: &str,
+) -> Result<bool>
+where
+    T: Mapped,
+{
+    let Some(table_id) = table_at(doc, root, <T as Mapped>::table_name(), heads)? else {
+        return Ok(false);
+    };
+
+    Ok(doc.get_at(&table_id, key, heads)?.is_some())
+}
+
+/// Returns the keys in the table named `table_name` at `heads`.
+fn table_keys_at(
+    doc: &Automerge,
+    root: Option<&str>,
+    table_name: &str,
+    heads: &[ChangeHash],
+) -> Result<Vec<String>> {
+    let Some(table_id) = table_at(doc, root, table_name, heads)? else {
+        return Ok(Vec::new());
+    };
+
+    Ok(doc.keys_at(&table_id, heads).collect())
+}
+
+/// Returns the Automerge object id of the table named `table_name` under
+/// `root` at `heads`, if it exists.
+fn table_at(
+    doc: &Automerge,
+    root: Option<&str>,
+    table_name: &str,
+    heads: &[ChangeHash],
+) -> Result<Option<ObjId>> {
+    let root_id = match root {
+        Some(root) => {
+            let Some((Value::Object(ObjType::Map), root_id)) =
+                doc.get_at(automerge::ROOT, root, heads)?
+            else {
+                return Ok(None);
+            };
+            root_id
+        },
+        None => automerge::ROOT,
+    };
+    let Some((Value::Object(ObjType::Map), table_id)) = doc.get_at(&root_id, table_name, heads)?
+    else {
+        return Ok(None);
+    };
+
+    Ok(Some(table_id))
+}
@@ -0,0 +1,348 @@
+use automerge_orm::{Entity, Mapped};
+use autosurgeon::{Hydrate, Reconcile};
+use uuid::Uuid;
+
+#[test]
+fn it_maps_entity_to_snake_cased_table_name() {
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct LibraryBranch {
+        #[key]
+        id: Uuid,
+    }
+
+    assert_eq!(LibraryBranch::table_name(), "library_branch");
+}
+
+#[test]
+fn it_maps_entity_to_pluralized_table_name() {
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    #[automerge_orm(pluralize)]
+    struct Book {
+        #[key]
+        id: Uuid,
+    }
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    #[automerge_orm(pluralize)]
+    struct LibraryBranch {
+        #[key]
+        id: Uuid,
+    }
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    #[automerge_orm(pluralize)]
+    struct Category {
+        #[key]
+        id: Uuid,
+    }
+
+    assert_eq!(Book::table_name(), "books");
+    assert_eq!(LibraryBranch::table_name(), "library_branches");
+    assert_eq!(Category::table_name(), "categories");
+}
+
+#[test]
+fn it_maps_entity_to_explicit_table_name_even_when_pluralized() {
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    #[automerge_orm(pluralize, table_name = "library")]
+    struct Book {
+        #[key]
+        id: Uuid,
+    }
+
+    assert_eq!(Book::table_name(), "library");
+}
+
+#[test]
+fn it_maps_renamed_field_to_property() -> anyhow::Result<()> {
+    use std::sync::Arc;
+
+    use automerge::{transaction::Transactable, Automerge, ObjType, ReadDoc, ScalarValue};
+    use automerge_orm::{DefaultEntityRepository, EntityManager, EntityRepository, Keyed};
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+        #[autosurgeon(rename = "authorName")]
+        #[automerge_orm(rename = "authorName")]
+        author_name: String,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    assert_eq!(Book::prop_name("author_name"), "authorName");
+    assert_eq!(Book::prop_name("id"), "id");
+
+    let id = Uuid::new_v4();
+    let mut doc = Automerge::new();
+    let mut tx = doc.transaction();
+    let table_id = tx.put_object(automerge::ROOT, Book::table_name(), ObjType::Map)?;
+    let book_id = tx.put_object(&table_id, id.to_string(), ObjType::Map)?;
+    tx.put(&book_id, "id", ScalarValue::Bytes(id.as_bytes().to_vec()))?;
+    tx.put(&book_id, "authorName", "Miyazaki Hayao")?;
+    tx.commit();
+    let entity_manager = Arc::new(EntityManager::from_doc(doc));
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+
+    let book = book_repository.find(id.into())?.unwrap();
+    assert_eq!(book.author_name, "Miyazaki Hayao");
+
+    entity_manager.transact(|tx| {
+        tx.update_field(book.id(), "author_name", &"Shinkai Makoto")?;
+        automerge_orm::Result::Ok(())
+    })?;
+    let book = book_repository.find(id.into())?.unwrap();
+    assert_eq!(book.author_name, "Shinkai Makoto");
+
+    let doc = Automerge::load(&entity_manager.save())?;
+    let (_, table_id) = doc.get(automerge::ROOT, Book::table_name())?.unwrap();
+    let (_, book_id) = doc.get(&table_id, id.to_string())?.unwrap();
+    assert!(doc.get(&book_id, "authorName")?.is_some());
+    assert!(doc.get(&book_id, "author_name")?.is_none());
+
+    Ok(())
+}
+
+#[test]
+fn it_maps_key_to_custom_property() -> anyhow::Result<()> {
+    use std::sync::Arc;
+
+    use automerge::{Automerge, ReadDoc};
+    use automerge_orm::{DefaultEntityRepository, EntityManager, EntityRepository, Keyed};
+
+    mod simple_key {
+        use uuid::Uuid;
+
+        pub fn to_prop(id: Uuid) -> String {
+            id.simple().to_string()
+        }
+
+        pub fn from_prop(prop: &str) -> automerge_orm::Result<Uuid> {
+            Uuid::parse_str(prop).map_err(automerge_orm::Error::custom)
+        }
+    }
+
+    #[derive(Clone, Debug, Entity, Hydrate, PartialEq, Reconcile)]
+    #[automerge_orm(key_prop = "simple_key")]
+    struct Book {
+        #[key]
+        id: Uuid,
+        author: String,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    let id = Uuid::new_v4();
+    assert_eq!(Book::key_prop(id), id.simple().to_string());
+    assert_eq!(Book::key_from_prop(&Book::key_prop(id))?, id);
+
+    let entity_manager = Arc::new(EntityManager::ephemeral());
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+
+    let book = Book {
+        id,
+        author: "Miyazaki Hayao".to_owned(),
+    };
+    entity_manager.transact(|tx| {
+        tx.insert(&book)?;
+        automerge_orm::Result::Ok(())
+    })?;
+    assert_eq!(book_repository.find(book.id())?, Some(book.clone()));
+    assert_eq!(book_repository.keys()?, vec![book.id()]);
+
+    let doc = Automerge::load(&entity_manager.save())?;
+    let (_, table_id) = doc.get(automerge::ROOT, Book::table_name())?.unwrap();
+    assert!(doc.get(&table_id, id.simple().to_string())?.is_some());
+    assert!(doc.get(&table_id, id.to_string())?.is_none());
+
+    entity_manager.transact(|tx| {
+        tx.remove(book.id())?;
+        automerge_orm::Result::Ok(())
+    })?;
+    assert_eq!(book_repository.find(book.id())?, None);
+
+    Ok(())
+}
+
+#[cfg(feature = "testing")]
+#[test]
+fn it_survives_round_trip_through_document() {
+    use automerge_orm::testing::assert_round_trip;
+
+    #[derive(Clone, Debug, Entity, Hydrate, PartialEq, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+        author: String,
+        subtitle: Option<String>,
+    }
+
+    assert_round_trip(&Book {
+        id: Uuid::new_v4(),
+        author: "Miyazaki Hayao".to_owned(),
+        subtitle: None,
+    });
+}
+
+#[cfg(feature = "chrono")]
+#[test]
+fn it_stores_date_time_field_as_timestamp() -> anyhow::Result<()> {
+    use std::sync::Arc;
+
+    use automerge::{ScalarValue, Value};
+    use automerge_orm::{DefaultEntityRepository, EntityManager, EntityRepository, Keyed};
+    use chrono::{DateTime, TimeZone, Utc};
+
+    #[derive(Clone, Debug, Entity, Hydrate, PartialEq, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+        #[autosurgeon(with = "automerge_orm::timestamp")]
+        published_at: DateTime<Utc>,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    let entity_manager = Arc::new(EntityManager::ephemeral());
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+
+    let mut book = Book {
+        id: Uuid::new_v4(),
+        published_at: Utc.timestamp_millis_opt(1_700_000_000_123).unwrap(),
+    };
+    let stored = entity_manager.transact(|tx| {
+        tx.insert(&book)?;
+        let stored = tx
+            .get_raw(book.id(), "published_at")?
+            .map(|(value, _)| value.into_owned());
+        automerge_orm::Result::Ok(stored)
+    })?;
+    assert!(matches!(
+        stored,
+        Some(Value::Scalar(value)) if *value == ScalarValue::Timestamp(1_700_000_000_123)
+    ));
+    assert_eq!(book_repository.find(book.id())?, Some(book.clone()));
+
+    book.published_at = Utc.timestamp_millis_opt(1_800_000_000_000).unwrap();
+    entity_manager.transact(|tx| {
+        tx.update(&book)?;
+        automerge_orm::Result::Ok(())
+    })?;
+    assert_eq!(book_repository.find(book.id())?, Some(book));
+
+    Ok(())
+}
+
+#[test]
+fn it_stores_bytes_field_as_bytes_scalar() -> anyhow::Result<()> {
+    use automerge::{Automerge, ReadDoc, ScalarValue, Value};
+    use automerge_orm::{EntityManager, Keyed};
+
+    #[derive(Clone, Debug, Entity, Hydrate, PartialEq, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+        #[autosurgeon(with = "automerge_orm::bytes")]
+        thumbnail: Vec<u8>,
+    }
+
+    let entity_manager = EntityManager::ephemeral();
+
+    let book = Book {
+        id: Uuid::new_v4(),
+        thumbnail: vec![0x89, b'P', b'N', b'G'],
+    };
+    entity_manager.transact(|tx| tx.insert(&book))?;
+
+    let doc = Automerge::load(&entity_manager.save())?;
+    let table_id = entity_manager.table_id::<Book>()?.unwrap();
+    let (_, book_id) = doc.get(&table_id, book.id.to_string())?.unwrap();
+    let Some((Value::Scalar(thumbnail), _)) = doc.get(&book_id, "thumbnail")? else {
+        anyhow::bail!("thumbnail is not a scalar");
+    };
+    assert_eq!(
+        thumbnail.into_owned(),
+        ScalarValue::Bytes(book.thumbnail.clone())
+    );
+    assert_eq!(entity_manager.find_related(book.id())?, Some(book));
+
+    Ok(())
+}
+
+#[cfg(feature = "crypto")]
+#[test]
+fn it_encrypts_field_with_key_of_entity_manager() -> anyhow::Result<()> {
+    use automerge::{Automerge, ReadDoc, ScalarValue, Value};
+    use automerge_orm::{EncryptionKey, EntityManager, Error, Keyed};
+
+    #[derive(Clone, Debug, Entity, Hydrate, PartialEq, Reconcile)]
+    struct User {
+        #[key]
+        id: Uuid,
+        #[automerge_orm(encrypt)]
+        #[autosurgeon(with = "automerge_orm::encrypted")]
+        email: String,
+    }
+
+    let entity_manager =
+        EntityManager::ephemeral().with_encryption_key(EncryptionKey::new([7; 32]));
+
+    let user = User {
+        id: Uuid::new_v4(),
+        email: "alice@example.com".to_owned(),
+    };
+    entity_manager.transact(|tx| tx.insert(&user))?;
+    assert_eq!(entity_manager.find_related(user.id())?, Some(user.clone()));
+
+    let saved = entity_manager.save();
+    let doc = Automerge::load(&saved)?;
+    let table_id = entity_manager.table_id::<User>()?.unwrap();
+    let (_, user_id) = doc.get(&table_id, user.id.to_string())?.unwrap();
+    let Some((Value::Scalar(email), _)) = doc.get(&user_id, "email")? else {
+        anyhow::bail!("email is not a scalar");
+    };
+    let ScalarValue::Bytes(ciphertext) = email.into_owned() else {
+        anyhow::bail!("email is not stored as bytes");
+    };
+    assert!(!ciphertext
+        .windows(user.email.len())
+        .any(|window| window == user.email.as_bytes()));
+
+    let other_key = EntityManager::from_doc(Automerge::load(&saved)?)
+        .with_encryption_key(EncryptionKey::new([8; 32]));
+    assert!(other_key.find_related(user.id()).is_err());
+    let no_key = EntityManager::from_doc(Automerge::load(&saved)?);
+    assert!(matches!(
+        no_key.find_related(user.id()),
+        Err(Error::EncryptionKeyMissing { .. })
+    ));
+    let err = no_key.transact(|tx| tx.update(&user)).unwrap_err();
+    assert!(err.to_string().contains("no encryption key configured"));
+    assert!(matches!(
+        automerge_orm::find(&doc, user.id()),
+        Err(Error::EncryptionKeyMissing { .. })
+    ));
+
+    Ok(())
+}
+
+mod inherent_table_name {
+    use automerge_orm::Entity;
+    use autosurgeon::{Hydrate, Reconcile};
+    use uuid::Uuid;
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    #[automerge_orm(pluralize)]
+    struct LibraryBranch {
+        #[key]
+        id: Uuid,
+    }
+
+    const TABLE_NAME: &str = LibraryBranch::table_name();
+
+    #[test]
+    fn it_provides_table_name_without_mapped_in_scope() {
+        assert_eq!(TABLE_NAME, "library_branches");
+    }
+}
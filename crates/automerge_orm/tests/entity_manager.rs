@@ -0,0 +1,1102 @@
+use std::{fmt, sync::Arc};
+
+use anyhow::Result;
+use automerge_orm::{
+    DefaultEntityRepository, Entity, EntityManager, EntityRepository, Error, Keyed, TransactError,
+};
+use automerge_repo::Repo;
+use autosurgeon::{Hydrate, Reconcile};
+use test_utils::automerge_repo::NoopStorage;
+use uuid::Uuid;
+
+#[test]
+fn it_aborts_transaction_with_custom_error() -> Result<()> {
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    impl Book {
+        pub fn new() -> Self {
+            Self { id: Uuid::new_v4() }
+        }
+    }
+
+    #[derive(Debug)]
+    struct OutOfStock;
+
+    impl fmt::Display for OutOfStock {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "out of stock")
+        }
+    }
+
+    impl std::error::Error for OutOfStock {}
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle));
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+
+    let book = Book::new();
+    let result = entity_manager.transact(|tx| {
+        tx.insert(&book)?;
+        Err(Error::custom(OutOfStock))
+    });
+    assert!(matches!(result, Err(Error::TransactionAborted(_))));
+    assert_eq!(
+        result.unwrap_err().to_string(),
+        "transaction aborted: out of stock"
+    );
+    assert!(book_repository.find(book.id())?.is_none());
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_preserves_type_of_error_which_aborted_transaction() -> Result<()> {
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    impl Book {
+        pub fn new() -> Self {
+            Self { id: Uuid::new_v4() }
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct OutOfStock {
+        remaining: u32,
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle));
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+
+    let book = Book::new();
+    let result = entity_manager.try_transact(|tx| {
+        tx.insert(&book).unwrap();
+        Err::<(), _>(OutOfStock { remaining: 0 })
+    });
+    assert!(matches!(
+        result,
+        Err(TransactError::Aborted(OutOfStock { remaining: 0 }))
+    ));
+    assert!(book_repository.find(book.id())?.is_none());
+
+    let result = entity_manager.try_transact(|tx| tx.insert(&book));
+    assert!(matches!(result, Ok(())));
+    assert!(book_repository.find(book.id())?.is_some());
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_performs_transaction_on_ephemeral_document() -> Result<()> {
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    impl Book {
+        pub fn new() -> Self {
+            Self { id: Uuid::new_v4() }
+        }
+    }
+
+    let entity_manager = Arc::new(EntityManager::ephemeral());
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+    assert!(entity_manager.try_doc().is_none());
+
+    let book = Book::new();
+    entity_manager.transact(|tx| {
+        tx.insert(&book)?;
+        automerge_orm::Result::Ok(())
+    })?;
+    assert!(book_repository.find(book.id())?.is_some());
+
+    Ok(())
+}
+
+#[test]
+fn it_times_out_waiting_for_write_lock_on_document() -> Result<()> {
+    use std::time::Duration;
+
+    use automerge::Automerge;
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    impl Book {
+        pub fn new() -> Self {
+            Self { id: Uuid::new_v4() }
+        }
+    }
+
+    let entity_manager = Arc::new(EntityManager::from_doc(Automerge::new()));
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+
+    let book = Book::new();
+    let result = entity_manager.transact(|_| {
+        let result = entity_manager.transact_timeout(Duration::from_millis(10), |tx| {
+            tx.insert(&book)?;
+            automerge_orm::Result::Ok(())
+        });
+        automerge_orm::Result::Ok(result)
+    })?;
+    assert!(matches!(result, Err(Error::Timeout(_))));
+    assert!(book_repository.find(book.id())?.is_none());
+
+    entity_manager.transact_timeout(Duration::from_millis(10), |tx| {
+        tx.insert(&book)?;
+        automerge_orm::Result::Ok(())
+    })?;
+    assert!(book_repository.find(book.id())?.is_some());
+
+    Ok(())
+}
+
+#[test]
+fn it_rolls_back_to_savepoint() -> Result<()> {
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+        author: String,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    impl Book {
+        pub fn new(author: &str) -> Self {
+            Self {
+                id: Uuid::new_v4(),
+                author: author.to_owned(),
+            }
+        }
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle));
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+
+    let mut books_in = vec![Book::new("Miyazaki Hayao"), Book::new("Shinkai Makoto")];
+    entity_manager.transact(|tx| {
+        tx.insert(&books_in[0])?;
+        tx.insert(&books_in[1])?;
+        automerge_orm::Result::Ok(())
+    })?;
+    let savepoint = entity_manager.savepoint();
+    let book_in = Book::new("Hosoda Mamoru");
+    books_in[0].author = "Takahata Isao".to_owned();
+    entity_manager.transact(|tx| {
+        tx.insert(&book_in)?;
+        tx.update(&books_in[0])?;
+        tx.remove(books_in[1].id())?;
+        automerge_orm::Result::Ok(())
+    })?;
+    entity_manager.rollback_to(&savepoint)?;
+
+    let books = book_repository.find_all()?;
+    assert_eq!(books.len(), 2);
+    assert!(books.get(&book_in.id().to_string()).is_none());
+    let book = books.get(&books_in[0].id().to_string());
+    assert!(book.is_some());
+    assert_eq!(book.unwrap().author, "Miyazaki Hayao");
+    let book = books.get(&books_in[1].id().to_string());
+    assert!(book.is_some());
+    assert_eq!(book.unwrap().author, "Shinkai Makoto");
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_returns_current_heads_of_the_document() -> Result<()> {
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+    }
+
+    impl Book {
+        pub fn new() -> Self {
+            Self { id: Uuid::new_v4() }
+        }
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle.clone()));
+
+    let heads = entity_manager.heads();
+    assert_eq!(heads, doc_handle.with_doc(|doc| doc.get_heads()));
+    entity_manager.transact(|tx| {
+        tx.insert(&Book::new())?;
+        automerge_orm::Result::Ok(())
+    })?;
+    assert_ne!(entity_manager.heads(), heads);
+    assert_eq!(
+        entity_manager.heads(),
+        doc_handle.with_doc(|doc| doc.get_heads())
+    );
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_returns_object_id_of_table() -> Result<()> {
+    use automerge::ReadDoc;
+    use automerge_orm::Mapped;
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+    }
+
+    impl Book {
+        pub fn new() -> Self {
+            Self { id: Uuid::new_v4() }
+        }
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle.clone()));
+
+    assert!(entity_manager.table_id::<Book>()?.is_none());
+    let book = Book::new();
+    entity_manager.transact(|tx| {
+        tx.insert(&book)?;
+        automerge_orm::Result::Ok(())
+    })?;
+    let table_id = entity_manager.table_id::<Book>()?.unwrap();
+    doc_handle.with_doc(|doc| {
+        let (_, expected) = doc.get(automerge::ROOT, Book::table_name())?.unwrap();
+        assert_eq!(table_id, expected);
+        assert!(doc.get(&table_id, book.id().to_string())?.is_some());
+        Ok::<_, automerge::AutomergeError>(())
+    })?;
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_stores_tables_under_root_map() -> Result<()> {
+    use automerge::{transaction::Transactable, ReadDoc};
+    use automerge_orm::Mapped;
+
+    #[derive(Clone, Debug, Entity, Hydrate, PartialEq, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+        title: String,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    doc_handle.with_doc_mut(|doc| {
+        let mut tx = doc.transaction();
+        tx.put(automerge::ROOT, "title", "Shared document")?;
+        tx.commit();
+        Ok::<_, automerge::AutomergeError>(())
+    })?;
+    let entity_manager = Arc::new(EntityManager::with_root(doc_handle.clone(), "orm"));
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+
+    assert!(entity_manager.table_id::<Book>()?.is_none());
+    let book = Book {
+        id: Uuid::new_v4(),
+        title: "Spirited Away".to_owned(),
+    };
+    entity_manager.transact(|tx| {
+        tx.insert(&book)?;
+        automerge_orm::Result::Ok(())
+    })?;
+    assert_eq!(book_repository.find(book.id())?, Some(book.clone()));
+    assert_eq!(book_repository.list()?, vec![book.clone()]);
+
+    let table_id = entity_manager.table_id::<Book>()?.unwrap();
+    doc_handle.with_doc(|doc| {
+        let (_, root_id) = doc.get(automerge::ROOT, "orm")?.unwrap();
+        let (_, expected) = doc.get(&root_id, Book::table_name())?.unwrap();
+        assert_eq!(table_id, expected);
+        assert!(doc.get(automerge::ROOT, Book::table_name())?.is_none());
+        assert!(doc.get(automerge::ROOT, "title")?.is_some());
+        Ok::<_, automerge::AutomergeError>(())
+    })?;
+
+    let unrooted_repository = BookRepository::new(Arc::new(EntityManager::new(doc_handle)));
+    assert_eq!(unrooted_repository.find(book.id())?, None);
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_distinguishes_missing_table_from_empty_table() -> Result<()> {
+    use automerge_orm::TableStatus;
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+    }
+
+    impl Book {
+        pub fn new() -> Self {
+            Self { id: Uuid::new_v4() }
+        }
+    }
+
+    let entity_manager = EntityManager::ephemeral();
+    assert_eq!(entity_manager.table_status::<Book>()?, TableStatus::Missing);
+
+    let books = [Book::new(), Book::new()];
+    entity_manager.transact(|tx| {
+        for book in &books {
+            tx.insert(book)?;
+        }
+        automerge_orm::Result::Ok(())
+    })?;
+    assert_eq!(
+        entity_manager.table_status::<Book>()?,
+        TableStatus::NonEmpty(2)
+    );
+
+    entity_manager.transact(|tx| {
+        tx.remove_many(books.iter().map(Keyed::id))?;
+        automerge_orm::Result::Ok(())
+    })?;
+    assert_eq!(entity_manager.table_status::<Book>()?, TableStatus::Empty);
+
+    Ok(())
+}
+
+#[test]
+fn it_dumps_the_document_as_a_tree() -> Result<()> {
+    use automerge::Automerge;
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+        author: String,
+        tags: Vec<String>,
+    }
+
+    let entity_manager = EntityManager::from_doc(Automerge::new());
+
+    assert_eq!(entity_manager.dump()?, "");
+    let book = Book {
+        id: Uuid::new_v4(),
+        author: "Miyazaki Hayao".to_owned(),
+        tags: vec!["fantasy".to_owned()],
+    };
+    entity_manager.transact(|tx| {
+        tx.insert(&book)?;
+        automerge_orm::Result::Ok(())
+    })?;
+    let id = book.id;
+    let lines = [
+        "book:".to_owned(),
+        format!("  {id}:"),
+        "    author: \"Miyazaki Hayao\"".to_owned(),
+        format!("    id: {id}"),
+        "    tags:".to_owned(),
+        "      - \"fantasy\"".to_owned(),
+    ];
+    assert_eq!(entity_manager.dump()?, lines.join("\n") + "\n");
+
+    Ok(())
+}
+
+#[test]
+fn it_returns_history_of_committed_changes() -> Result<()> {
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+    }
+
+    impl Book {
+        pub fn new() -> Self {
+            Self { id: Uuid::new_v4() }
+        }
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle.clone()));
+
+    assert!(entity_manager.history()?.is_empty());
+    for _ in 0..2 {
+        entity_manager.transact(|tx| {
+            tx.insert(&Book::new())?;
+            automerge_orm::Result::Ok(())
+        })?;
+    }
+    let actor = doc_handle.with_doc(|doc| doc.get_actor().clone());
+    let history = entity_manager.history()?;
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[1].hash(), entity_manager.heads()[0]);
+    for commit in &history {
+        assert_eq!(commit.message(), Some("automerge_orm::Transaction::commit"));
+        assert!(commit.time() > 0);
+        assert_eq!(commit.actor(), &actor);
+    }
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_commits_transaction_with_options() -> Result<()> {
+    use automerge_orm::TransactionOptions;
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+    }
+
+    impl Book {
+        pub fn new() -> Self {
+            Self { id: Uuid::new_v4() }
+        }
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle));
+
+    let options = TransactionOptions::new()
+        .with_message("import books")
+        .with_time(1_700_000_000);
+    entity_manager.transact_with_options(options, |tx| {
+        tx.insert(&Book::new())?;
+        automerge_orm::Result::Ok(())
+    })?;
+    entity_manager.transact(|tx| {
+        tx.insert(&Book::new())?;
+        automerge_orm::Result::Ok(())
+    })?;
+    let history = entity_manager.history()?;
+    assert_eq!(history.len(), 2);
+    assert_eq!(history[0].message(), Some("import books"));
+    assert_eq!(history[0].time(), 1_700_000_000);
+    assert_eq!(
+        history[1].message(),
+        Some("automerge_orm::Transaction::commit")
+    );
+    assert!(history[1].time() > 1_700_000_000);
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_skips_commit_of_empty_transaction_when_requested() -> Result<()> {
+    use automerge_orm::TransactionOptions;
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+    }
+
+    impl Book {
+        pub fn new() -> Self {
+            Self { id: Uuid::new_v4() }
+        }
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle));
+
+    let book = Book::new();
+    let options = TransactionOptions::new().with_skip_empty(true);
+    entity_manager.transact_with_options(options.clone(), |tx| {
+        tx.upsert(&book)?;
+        automerge_orm::Result::Ok(())
+    })?;
+    let heads = entity_manager.heads();
+    let found = entity_manager.transact_with_options(options, |tx| {
+        let found = tx.find(book.id())?.is_some();
+        if !found {
+            tx.insert(&book)?;
+        }
+        automerge_orm::Result::Ok(found)
+    })?;
+    assert!(found);
+    assert_eq!(entity_manager.heads(), heads);
+    assert_eq!(entity_manager.history()?.len(), 1);
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_aborts_transaction_exceeding_max_ops() -> Result<()> {
+    use automerge_orm::TransactionOptions;
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    impl Book {
+        pub fn new() -> Self {
+            Self { id: Uuid::new_v4() }
+        }
+    }
+
+    let entity_manager = Arc::new(EntityManager::ephemeral());
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+
+    let books = [Book::new(), Book::new()];
+    let options = TransactionOptions::new().with_max_ops(1);
+    let result = entity_manager.transact_with_options(options, |tx| {
+        for book in &books {
+            tx.insert(book)?;
+        }
+        automerge_orm::Result::Ok(())
+    });
+    let Err(Error::TransactionAborted(err)) = result else {
+        panic!("expected transaction to be aborted");
+    };
+    assert!(matches!(
+        err.downcast_ref::<Error>(),
+        Some(Error::TransactionTooLarge { max_ops: 1, .. })
+    ));
+    assert!(book_repository.list()?.is_empty());
+
+    let options = TransactionOptions::new().with_max_ops(100);
+    entity_manager.transact_with_options(options, |tx| {
+        for book in &books {
+            tx.insert(book)?;
+        }
+        automerge_orm::Result::Ok(())
+    })?;
+    assert_eq!(book_repository.list()?.len(), 2);
+
+    Ok(())
+}
+
+#[test]
+fn it_attributes_changes_to_provided_actor() -> Result<()> {
+    use automerge::ActorId;
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let actor = ActorId::from(b"automerge_orm".as_slice());
+    let entity_manager = Arc::new(EntityManager::with_actor(doc_handle, actor.clone()));
+
+    let book = Book {
+        id: Uuid::from_u128(1),
+    };
+    entity_manager.transact(|tx| {
+        tx.insert(&book)?;
+        automerge_orm::Result::Ok(())
+    })?;
+    let history = entity_manager.history()?;
+    assert_eq!(history.len(), 1);
+    assert_eq!(history[0].actor(), &actor);
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_merges_another_document() -> Result<()> {
+    use autosurgeon::reconcile_prop;
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    impl Book {
+        pub fn new() -> Self {
+            Self { id: Uuid::new_v4() }
+        }
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle.clone()));
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+
+    let books_in = vec![Book::new(), Book::new()];
+    entity_manager.transact(|tx| {
+        tx.insert(&books_in[0])?;
+        automerge_orm::Result::Ok(())
+    })?;
+
+    let mut other = doc_handle.with_doc(|doc| doc.fork());
+    let mut other_tx = other.transaction();
+    let table_id = automerge_orm::get_table::<_, Book>(&other_tx)?.unwrap();
+    reconcile_prop(
+        &mut other_tx,
+        &table_id,
+        &*books_in[1].id().to_string(),
+        &books_in[1],
+    )?;
+    other_tx.commit();
+
+    let hashes = entity_manager.merge(&other)?;
+    assert_eq!(hashes, other.get_heads());
+    assert_eq!(book_repository.find_all()?.len(), 2);
+    assert!(entity_manager.merge(&other)?.is_empty());
+    assert!(entity_manager.merge_bytes(&other.save())?.is_empty());
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_does_not_apply_changes_to_fork_until_merged() -> Result<()> {
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    impl Book {
+        pub fn new() -> Self {
+            Self { id: Uuid::new_v4() }
+        }
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle));
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+
+    let books_in = vec![Book::new(), Book::new()];
+    entity_manager.transact(|tx| {
+        tx.insert(&books_in[0])?;
+        automerge_orm::Result::Ok(())
+    })?;
+
+    let fork = Arc::new(entity_manager.fork()?);
+    assert!(fork.try_doc().is_none());
+    let fork_book_repository = BookRepository::new(Arc::clone(&fork));
+    assert!(fork_book_repository.find(books_in[0].id())?.is_some());
+    fork.transact(|tx| {
+        tx.insert(&books_in[1])?;
+        automerge_orm::Result::Ok(())
+    })?;
+    assert!(fork_book_repository.find(books_in[1].id())?.is_some());
+    assert!(book_repository.find(books_in[1].id())?.is_none());
+
+    entity_manager.merge_bytes(&fork.save())?;
+    assert!(book_repository.find(books_in[1].id())?.is_some());
+    assert_eq!(entity_manager.heads(), fork.heads());
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_runs_pending_migrations_once() -> Result<()> {
+    use automerge_orm::{Migration, Transaction};
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+        author: String,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    struct AddBook(Uuid);
+
+    impl Migration for AddBook {
+        fn version(&self) -> u32 {
+            1
+        }
+
+        fn up(&self, tx: &mut Transaction<'_>) -> automerge_orm::Result<()> {
+            tx.insert(&Book {
+                id: self.0,
+                author: "miyazaki hayao".to_owned(),
+            })
+        }
+    }
+
+    struct CapitalizeAuthors;
+
+    impl Migration for CapitalizeAuthors {
+        fn version(&self) -> u32 {
+            2
+        }
+
+        fn up(&self, tx: &mut Transaction<'_>) -> automerge_orm::Result<()> {
+            for mut book in tx.list::<Book>()? {
+                book.author = book
+                    .author
+                    .split(' ')
+                    .map(|name| name[..1].to_uppercase() + &name[1..])
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                tx.update(&book)?;
+            }
+
+            Ok(())
+        }
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle));
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+
+    let add_book = AddBook(Uuid::new_v4());
+    assert_eq!(entity_manager.migrate(&[&CapitalizeAuthors, &add_book])?, 2);
+    let books = book_repository.list()?;
+    assert_eq!(books.len(), 1);
+    assert_eq!(books[0].author, "Miyazaki Hayao");
+
+    let heads = entity_manager.heads();
+    let add_book = AddBook(Uuid::new_v4());
+    assert_eq!(entity_manager.migrate(&[&add_book, &CapitalizeAuthors])?, 2);
+    assert_eq!(entity_manager.heads(), heads);
+    assert_eq!(book_repository.list()?.len(), 1);
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_stores_schema_version_under_root() -> Result<()> {
+    use automerge::ReadDoc;
+    use automerge_orm::{impls::find_under, Migration, Transaction};
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+    }
+
+    struct AddBook(Uuid);
+
+    impl Migration for AddBook {
+        fn version(&self) -> u32 {
+            1
+        }
+
+        fn up(&self, tx: &mut Transaction<'_>) -> automerge_orm::Result<()> {
+            tx.insert(&Book { id: self.0 })
+        }
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager_a = EntityManager::with_root(doc_handle.clone(), "a");
+    let entity_manager_b = EntityManager::with_root(doc_handle.clone(), "b");
+
+    let add_book_a = AddBook(Uuid::new_v4());
+    assert_eq!(entity_manager_a.migrate(&[&add_book_a])?, 1);
+    let add_book_b = AddBook(Uuid::new_v4());
+    assert_eq!(entity_manager_b.migrate(&[&add_book_b])?, 1);
+
+    doc_handle.with_doc(|doc| {
+        assert!(doc
+            .get(automerge::ROOT, "__automerge_orm_schema_version")?
+            .is_none());
+        assert!(find_under::<_, Book>(doc, Some("a"), add_book_a.0.into())?.is_some());
+        assert!(find_under::<_, Book>(doc, Some("b"), add_book_b.0.into())?.is_some());
+        anyhow::Ok(())
+    })?;
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[cfg(feature = "stream")]
+#[test]
+fn it_streams_changes_to_the_document() -> Result<()> {
+    use automerge_orm::Mapped;
+    use futures::{executor::block_on, StreamExt};
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+    }
+
+    impl Book {
+        pub fn new() -> Self {
+            Self { id: Uuid::new_v4() }
+        }
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle.clone()));
+
+    let mut changes = Box::pin(entity_manager.changes());
+    let book = Book::new();
+    entity_manager.transact(|tx| {
+        tx.insert(&book)?;
+        automerge_orm::Result::Ok(())
+    })?;
+    let event = block_on(changes.next());
+    assert!(event.is_some());
+    let event = event.unwrap();
+    assert_eq!(event.heads(), doc_handle.with_doc(|doc| doc.get_heads()));
+    assert!(event.tables().contains(Book::table_name()));
+    assert!(event.contains_table::<Book>());
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_reads_entities_of_different_types_in_one_scope() -> Result<()> {
+    #[derive(Clone, Debug, Entity, Hydrate, PartialEq, Reconcile)]
+    struct Author {
+        #[key]
+        id: Uuid,
+        name: String,
+    }
+
+    #[derive(Clone, Debug, Entity, Hydrate, PartialEq, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+        author_id: Uuid,
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = EntityManager::with_root(doc_handle, "orm");
+
+    let author = Author {
+        id: Uuid::new_v4(),
+        name: "Miyazaki Hayao".to_owned(),
+    };
+    let book = Book {
+        id: Uuid::new_v4(),
+        author_id: author.id,
+    };
+    entity_manager.transact(|tx| {
+        tx.insert(&author)?;
+        tx.insert(&book)?;
+        automerge_orm::Result::Ok(())
+    })?;
+
+    let (found_author, books, authors) = entity_manager.read(|reader| {
+        let found_author = reader.find(author.id())?;
+        let books = reader.find_all::<Book>()?;
+        let authors = reader.find_all::<Author>()?;
+        automerge_orm::Result::Ok((found_author, books, authors))
+    })?;
+    assert_eq!(found_author, Some(author));
+    assert_eq!(books.into_values().collect::<Vec<_>>(), vec![book]);
+    assert_eq!(authors.len(), 1);
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_records_metrics_of_operations() -> Result<()> {
+    use std::sync::Mutex;
+
+    use automerge_orm::MetricsEvent;
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let entity_manager = Arc::new(EntityManager::ephemeral().with_metrics({
+        let events = Arc::clone(&events);
+        move |event: &MetricsEvent| events.lock().unwrap().push(event.clone())
+    }));
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+
+    entity_manager.transact(|tx| {
+        tx.insert(&Book { id: Uuid::new_v4() })?;
+        tx.insert(&Book { id: Uuid::new_v4() })?;
+        automerge_orm::Result::Ok(())
+    })?;
+    book_repository.find_all()?;
+    entity_manager.read(|reader| reader.find_all::<Book>())?;
+
+    let events = events.lock().unwrap();
+    assert_eq!(events.len(), 4);
+    assert!(matches!(events[0], MetricsEvent::LockWait { .. }));
+    assert!(matches!(events[1], MetricsEvent::Commit { ops, .. } if ops > 0));
+    for event in &events[2..] {
+        assert!(matches!(
+            event,
+            MetricsEvent::FindAll {
+                table: "book",
+                count: 2,
+                ..
+            }
+        ));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn it_recovers_from_panic_in_transaction() -> Result<()> {
+    use std::panic::{self, AssertUnwindSafe};
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+    }
+
+    let entity_manager = EntityManager::ephemeral();
+
+    let book = Book { id: Uuid::new_v4() };
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        entity_manager.transact(|tx| {
+            tx.insert(&book)?;
+            if tx.pending_ops() > 0 {
+                panic!("boom");
+            }
+            automerge_orm::Result::Ok(())
+        })
+    }));
+    assert!(result.is_err());
+    assert!(entity_manager.heads().is_empty());
+    assert!(entity_manager.find_related(book.id())?.is_none());
+
+    entity_manager.transact(|tx| tx.insert(&book))?;
+    assert!(entity_manager.find_related(book.id())?.is_some());
+
+    Ok(())
+}
+
+#[test]
+fn it_reads_latest_state_through_cloned_entity_manager() -> Result<()> {
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = EntityManager::new(doc_handle);
+    let book_repository = DefaultEntityRepository::<Book>::new(Arc::new(entity_manager.clone()));
+    assert!(book_repository.find_all()?.is_empty());
+
+    let book = Book { id: Uuid::new_v4() };
+    entity_manager.transact(|tx| tx.insert(&book))?;
+    assert!(book_repository.find(book.id())?.is_some());
+
+    entity_manager.transact(|tx| tx.remove(book.id()))?;
+    assert!(book_repository.find(book.id())?.is_none());
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_rolls_back_controlled_transaction_on_break() -> Result<()> {
+    use std::ops::ControlFlow;
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+    }
+
+    let entity_manager = EntityManager::ephemeral();
+    let book_repository = DefaultEntityRepository::<Book, _>::new_with(&entity_manager);
+
+    let book = Book { id: Uuid::new_v4() };
+    let flow = entity_manager.transact_controlled(|tx| {
+        tx.insert(&book)?;
+        automerge_orm::Result::Ok(ControlFlow::<&str, _>::Continue(42))
+    })?;
+    assert_eq!(flow, ControlFlow::Continue(42));
+    assert!(book_repository.find(book.id())?.is_some());
+
+    let flow = entity_manager.transact_controlled(|tx| {
+        tx.remove(book.id())?;
+        automerge_orm::Result::Ok(ControlFlow::<_, ()>::Break("nothing to do"))
+    })?;
+    assert_eq!(flow, ControlFlow::Break("nothing to do"));
+    assert!(book_repository.find(book.id())?.is_some());
+
+    let result = entity_manager.transact_controlled(|tx| {
+        tx.remove(book.id())?;
+        Err::<ControlFlow<(), ()>, _>(Error::custom(fmt::Error))
+    });
+    assert!(matches!(result, Err(Error::TransactionAborted(_))));
+    assert!(book_repository.find(book.id())?.is_some());
+
+    Ok(())
+}
@@ -1,7 +1,7 @@
-use std::sync::Arc;
+use std::{rc::Rc, sync::Arc};
 
 use anyhow::Result;
-use automerge_orm::{DefaultEntityRepository, Entity, EntityManager, EntityRepository, Keyed};
+use automerge_orm::{DefaultEntityRepository, Entity, EntityManager, EntityRepository, Key, Keyed};
 use automerge_repo::Repo;
 use autosurgeon::{Hydrate, Reconcile};
 use test_utils::automerge_repo::NoopStorage;
@@ -43,6 +43,83 @@ fn it_finds_entity_by_id() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn it_fails_to_find_entity_which_does_not_exist_or_error() -> Result<()> {
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    impl Book {
+        pub fn new() -> Self {
+            Self { id: Uuid::new_v4() }
+        }
+    }
+
+    let entity_manager = Arc::new(EntityManager::ephemeral());
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+
+    let book = Book::new();
+    let err = book_repository.find_or_error(book.id()).unwrap_err();
+    assert!(err.is_not_found());
+    assert_eq!(err.table_name(), Some("book"));
+    assert_eq!(err.key(), Some(book.id));
+
+    entity_manager.transact(|tx| {
+        tx.insert(&book)?;
+        automerge_orm::Result::Ok(())
+    })?;
+    assert_eq!(book_repository.find_or_error(book.id())?.id, book.id);
+
+    Ok(())
+}
+
+#[test]
+fn it_finds_entity_by_id_using_borrowed_entity_manager() -> Result<()> {
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+    }
+
+    impl Book {
+        pub fn new() -> Self {
+            Self { id: Uuid::new_v4() }
+        }
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = EntityManager::new(doc_handle);
+
+    let book_in = Book::new();
+    entity_manager.transact(|tx| {
+        tx.insert(&book_in)?;
+        automerge_orm::Result::Ok(())
+    })?;
+    let book_repository = DefaultEntityRepository::<Book, _>::new_with(&entity_manager);
+    let book = book_repository.find(book_in.id())?;
+    assert!(book.is_some());
+    assert_eq!(book.unwrap().id(), book_in.id());
+
+    let entity_manager = Rc::new(entity_manager);
+    let book_repository: DefaultEntityRepository<Book, Rc<EntityManager>> =
+        DefaultEntityRepository::new_with(Rc::clone(&entity_manager));
+    let book = book_repository.find(book_in.id())?;
+    assert!(book.is_some());
+
+    let book_repository = DefaultEntityRepository::<Book, _>::new_with(entity_manager);
+    let book = book_repository.find(book_in.id())?;
+    assert!(book.is_some());
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
 #[test]
 fn it_returns_none_when_trying_to_find_entity_using_nonexistent_id() -> Result<()> {
     #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
@@ -160,3 +237,1100 @@ fn it_returns_empty_map_when_trying_to_find_all_entities_in_nonexistent_table()
 
     Ok(())
 }
+
+#[test]
+fn it_lists_all_entities_in_a_table_ordered_by_key() -> Result<()> {
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    impl Book {
+        pub fn new() -> Self {
+            Self { id: Uuid::new_v4() }
+        }
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle));
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+
+    assert!(book_repository.list()?.is_empty());
+
+    let mut books_in = vec![Book::new(), Book::new(), Book::new()];
+    entity_manager.transact(|tx| {
+        for book_in in &books_in {
+            tx.insert(book_in)?;
+        }
+        automerge_orm::Result::Ok(())
+    })?;
+    books_in.sort_by_key(|book| book.id);
+    let books = book_repository.list()?;
+    assert_eq!(
+        books.iter().map(|book| book.id).collect::<Vec<_>>(),
+        books_in.iter().map(|book| book.id).collect::<Vec<_>>()
+    );
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_finds_keys_of_all_entities_in_a_table() -> Result<()> {
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    impl Book {
+        pub fn new() -> Self {
+            Self { id: Uuid::new_v4() }
+        }
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle));
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+
+    assert!(book_repository.keys()?.is_empty());
+
+    let books_in = vec![Book::new(), Book::new()];
+    entity_manager.transact(|tx| {
+        for book_in in &books_in {
+            tx.insert(book_in)?;
+        }
+        automerge_orm::Result::Ok(())
+    })?;
+    let mut keys_in = books_in.iter().map(|book| book.id()).collect::<Vec<_>>();
+    keys_in.sort();
+    assert_eq!(book_repository.keys()?, keys_in);
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_fails_to_find_keys_when_table_contains_invalid_key() -> Result<()> {
+    use automerge::{transaction::Transactable, ObjType, ReadDoc};
+    use automerge_orm::{Error, Mapped};
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    impl Book {
+        pub fn new() -> Self {
+            Self { id: Uuid::new_v4() }
+        }
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle.clone()));
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+
+    entity_manager.transact(|tx| {
+        tx.insert(&Book::new())?;
+        automerge_orm::Result::Ok(())
+    })?;
+    doc_handle.with_doc_mut(|doc| {
+        let mut tx = doc.transaction();
+        let (_, table_id) = tx.get(automerge::ROOT, Book::table_name())?.unwrap();
+        tx.put_object(&table_id, "not a key", ObjType::Map)?;
+        tx.commit();
+        Ok::<_, automerge::AutomergeError>(())
+    })?;
+    let result = book_repository.keys();
+    assert!(matches!(result, Err(Error::InvalidKey { key, .. }) if key == "not a key"));
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_fails_to_find_entity_which_is_not_a_map() -> Result<()> {
+    use automerge::{transaction::Transactable, ObjType};
+    use automerge_orm::{Error, Mapped};
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    impl Book {
+        pub fn new() -> Self {
+            Self { id: Uuid::new_v4() }
+        }
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle.clone()));
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+
+    let book = Book::new();
+    doc_handle.with_doc_mut(|doc| {
+        let mut tx = doc.transaction();
+        let table_id = tx.put_object(automerge::ROOT, Book::table_name(), ObjType::Map)?;
+        tx.put(&table_id, book.id().to_string(), "not a map")?;
+        tx.commit();
+        Ok::<_, automerge::AutomergeError>(())
+    })?;
+    let result = book_repository.find(book.id());
+    assert!(matches!(result, Err(Error::Automerge(_))));
+    assert!(book_repository.find(Key::random())?.is_none());
+    assert!(matches!(
+        book_repository.find_all(),
+        Err(Error::Automerge(_))
+    ));
+    assert!(matches!(book_repository.list(), Err(Error::Automerge(_))));
+    assert!(matches!(book_repository.first(), Err(Error::Automerge(_))));
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_finds_all_entities_sorted_by_comparator() -> Result<()> {
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+        author: String,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    impl Book {
+        pub fn new(author: &str) -> Self {
+            Self {
+                id: Uuid::new_v4(),
+                author: author.to_owned(),
+            }
+        }
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle));
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+
+    let books_in = vec![
+        Book::new("Shinkai Makoto"),
+        Book::new("Miyazaki Hayao"),
+        Book::new("Takahata Isao"),
+    ];
+    entity_manager.transact(|tx| {
+        for book_in in &books_in {
+            tx.insert(book_in)?;
+        }
+        automerge_orm::Result::Ok(())
+    })?;
+    let books = book_repository.find_all_sorted_by(|a, b| a.author.cmp(&b.author))?;
+    let authors: Vec<_> = books.iter().map(|book| book.author.as_str()).collect();
+    assert_eq!(
+        authors,
+        vec!["Miyazaki Hayao", "Shinkai Makoto", "Takahata Isao"]
+    );
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_fails_to_find_entity_written_by_another_type_sharing_the_table() -> Result<()> {
+    use automerge_orm::{Error, Mapped};
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    #[automerge_orm(table_name = "library", type_tag)]
+    struct Book {
+        #[key]
+        id: Uuid,
+    }
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    #[automerge_orm(table_name = "library", type_tag = "magazine")]
+    struct Magazine {
+        #[key]
+        id: Uuid,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle));
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+
+    assert_eq!(Book::type_tag(), Some("Book"));
+    assert_eq!(Magazine::type_tag(), Some("magazine"));
+
+    let book = Book { id: Uuid::new_v4() };
+    let magazine = Magazine { id: Uuid::new_v4() };
+    entity_manager.transact(|tx| {
+        tx.insert(&book)?;
+        tx.insert(&magazine)?;
+        automerge_orm::Result::Ok(())
+    })?;
+    assert!(book_repository.find(book.id())?.is_some());
+    let result = book_repository.find(Key::new(magazine.id));
+    assert!(matches!(
+        result,
+        Err(Error::TypeMismatch { id, expected, actual, .. })
+            if id == magazine.id && expected == "Book" && actual == "magazine"
+    ));
+    assert!(book_repository.list().is_err());
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_finds_first_entity_in_a_table() -> Result<()> {
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    impl Book {
+        pub fn new() -> Self {
+            Self { id: Uuid::new_v4() }
+        }
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle));
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+
+    assert!(book_repository.first()?.is_none());
+    assert!(book_repository.any()?.is_none());
+
+    let mut books_in = vec![Book::new(), Book::new(), Book::new()];
+    entity_manager.transact(|tx| {
+        for book_in in &books_in {
+            tx.insert(book_in)?;
+        }
+        automerge_orm::Result::Ok(())
+    })?;
+    books_in.sort_by_key(|book| book.id);
+    let book = book_repository.first()?;
+    assert!(book.is_some());
+    assert_eq!(book.unwrap().id(), books_in[0].id());
+    let book = book_repository.any()?;
+    assert!(book.is_some());
+    let book = book.unwrap();
+    assert!(books_in.iter().any(|book_in| book_in.id() == book.id()));
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_finds_entities_as_they_were_at_heads() -> Result<()> {
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+        author: String,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    impl Book {
+        pub fn new(author: &str) -> Self {
+            Self {
+                id: Uuid::new_v4(),
+                author: author.to_owned(),
+            }
+        }
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle));
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+
+    let mut book_in = Book::new("Miyazaki Hayao");
+    entity_manager.transact(|tx| {
+        tx.insert(&book_in)?;
+        automerge_orm::Result::Ok(())
+    })?;
+    let heads = entity_manager.heads();
+    book_in.author = "Shinkai Makoto".to_owned();
+    entity_manager.transact(|tx| {
+        tx.update(&book_in)?;
+        tx.insert(&Book::new("Hosoda Mamoru"))?;
+        automerge_orm::Result::Ok(())
+    })?;
+
+    let book = book_repository.find_at(&heads, book_in.id())?;
+    assert!(book.is_some());
+    assert_eq!(book.unwrap().author, "Miyazaki Hayao");
+    let books = book_repository.find_all_at(&heads)?;
+    assert_eq!(books.len(), 1);
+    assert_eq!(books[&book_in.id().to_string()].author, "Miyazaki Hayao");
+    let book = book_repository.find(book_in.id())?;
+    assert_eq!(book.unwrap().author, "Shinkai Makoto");
+    assert!(book_repository.find_all_at(&[])?.is_empty());
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_exports_entities_one_per_line() -> Result<()> {
+    use automerge_orm::Error;
+    use serde::Serialize;
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile, Serialize)]
+    struct Book {
+        #[key]
+        id: Uuid,
+        author: String,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    impl Book {
+        pub fn new(author: &str) -> Self {
+            Self {
+                id: Uuid::new_v4(),
+                author: author.to_owned(),
+            }
+        }
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle));
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+
+    let mut books_in = vec![Book::new("Miyazaki Hayao"), Book::new("Shinkai Makoto")];
+    entity_manager.transact(|tx| {
+        for book in &books_in {
+            tx.insert(book)?;
+        }
+        automerge_orm::Result::Ok(())
+    })?;
+    books_in.sort_by_key(|book| book.id());
+
+    let mut output = Vec::new();
+    book_repository.export_to(&mut output, |writer, book| {
+        serde_json::to_writer(writer, book).map_err(Error::custom)
+    })?;
+    let lines: Vec<serde_json::Value> = String::from_utf8(output)?
+        .lines()
+        .map(serde_json::from_str)
+        .collect::<std::result::Result<_, _>>()?;
+    assert_eq!(lines.len(), 2);
+    for (line, book) in lines.iter().zip(&books_in) {
+        assert_eq!(line["id"], book.id().to_string());
+        assert_eq!(line["author"], book.author);
+    }
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_resolves_cursor_into_text_field_after_edits() -> Result<()> {
+    use autosurgeon::Text;
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+        title: Text,
+        tags: Vec<String>,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle));
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+
+    let book = Book {
+        id: Uuid::new_v4(),
+        title: Text::with_value("Wind"),
+        tags: vec!["manga".to_owned()],
+    };
+    entity_manager.transact(|tx| {
+        tx.insert(&book)?;
+        automerge_orm::Result::Ok(())
+    })?;
+
+    let cursor = book_repository.cursor(book.id(), "title", 0)?.unwrap();
+    let mut book = book_repository.find(book.id())?.unwrap();
+    book.title.splice(0, 0, "The ");
+    entity_manager.transact(|tx| {
+        tx.update(&book)?;
+        automerge_orm::Result::Ok(())
+    })?;
+    assert_eq!(
+        book_repository.resolve_cursor(book.id(), "title", &cursor)?,
+        Some(4)
+    );
+
+    assert!(book_repository.cursor(book.id(), "tags", 0)?.is_some());
+    assert!(book_repository.cursor(book.id(), "subtitle", 0)?.is_none());
+    assert!(book_repository
+        .cursor(Uuid::new_v4().into(), "title", 0)?
+        .is_none());
+    assert!(book_repository.cursor(book.id(), "id", 0).is_err());
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_does_not_track_element_of_list_field_across_update() -> Result<()> {
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+        tags: Vec<String>,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    let entity_manager = Arc::new(EntityManager::ephemeral());
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+
+    let mut book = Book {
+        id: Uuid::new_v4(),
+        tags: vec!["anime".to_owned(), "manga".to_owned()],
+    };
+    entity_manager.transact(|tx| tx.insert(&book))?;
+
+    let cursor = book_repository.cursor(book.id(), "tags", 1)?.unwrap();
+    book.tags.insert(0, "film".to_owned());
+    entity_manager.transact(|tx| tx.update(&book))?;
+
+    // The list is reconciled element by element by index, so the cursor keeps
+    // pointing to the second element rather than to "manga".
+    let index = book_repository.resolve_cursor(book.id(), "tags", &cursor)?;
+    assert_eq!(index, Some(1));
+    let found = book_repository.find(book.id())?.unwrap();
+    assert_eq!(found.tags[1], "anime");
+
+    Ok(())
+}
+
+#[cfg(feature = "serde_json")]
+#[test]
+fn it_converts_entity_into_json() -> Result<()> {
+    use serde_json::json;
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+        author: String,
+        tags: Vec<String>,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    impl Book {
+        pub fn new(author: &str, tags: &[&str]) -> Self {
+            Self {
+                id: Uuid::new_v4(),
+                author: author.to_owned(),
+                tags: tags.iter().map(|&tag| tag.to_owned()).collect(),
+            }
+        }
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle));
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+
+    let book_in = Book::new("Miyazaki Hayao", &["manga"]);
+    entity_manager.transact(|tx| {
+        tx.insert(&book_in)?;
+        automerge_orm::Result::Ok(())
+    })?;
+    let book = book_repository.to_json(book_in.id())?;
+    assert_eq!(
+        book,
+        Some(json!({
+            "id": book_in.id().to_string(),
+            "author": "Miyazaki Hayao",
+            "tags": ["manga"],
+        }))
+    );
+    let book = book_repository.to_json(Uuid::new_v4().into())?;
+    assert!(book.is_none());
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[cfg(feature = "serde_json")]
+#[test]
+fn it_converts_only_key_of_entity_into_uuid_string_in_json() -> Result<()> {
+    use serde_json::json;
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+        author_id: Uuid,
+        #[autosurgeon(with = "automerge_orm::bytes")]
+        checksum: Vec<u8>,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    let entity_manager = Arc::new(EntityManager::ephemeral());
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+
+    let book_in = Book {
+        id: Uuid::new_v4(),
+        author_id: Uuid::new_v4(),
+        checksum: vec![7; 16],
+    };
+    entity_manager.transact(|tx| tx.insert(&book_in))?;
+
+    let expected = json!({
+        "id": book_in.id.to_string(),
+        "author_id": book_in.author_id.as_bytes().to_vec(),
+        "checksum": vec![7; 16],
+    });
+    assert_eq!(
+        book_repository.to_json(book_in.id())?,
+        Some(expected.clone())
+    );
+    assert_eq!(
+        book_repository.export_json()?,
+        json!({ book_in.id.to_string(): expected })
+    );
+
+    Ok(())
+}
+
+#[cfg(feature = "stream")]
+#[test]
+fn it_watches_entities_in_a_table() -> Result<()> {
+    use automerge_orm::EntityEvent;
+    use futures::{executor::block_on, StreamExt};
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+        author: String,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    impl Book {
+        pub fn new(author: &str) -> Self {
+            Self {
+                id: Uuid::new_v4(),
+                author: author.to_owned(),
+            }
+        }
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle));
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+
+    let mut events = Box::pin(book_repository.watch());
+    let mut book_in = Book::new("Miyazaki Hayao");
+    entity_manager.transact(|tx| {
+        tx.insert(&book_in)?;
+        automerge_orm::Result::Ok(())
+    })?;
+    let event = block_on(events.next()).unwrap()?;
+    assert!(matches!(event, EntityEvent::Inserted(book) if book.id() == book_in.id()));
+
+    book_in.author = "Shinkai Makoto".to_owned();
+    entity_manager.transact(|tx| {
+        tx.update(&book_in)?;
+        automerge_orm::Result::Ok(())
+    })?;
+    let event = block_on(events.next()).unwrap()?;
+    assert!(matches!(event, EntityEvent::Updated(book) if book.author == book_in.author));
+
+    entity_manager.transact(|tx| {
+        tx.remove(book_in.id())?;
+        automerge_orm::Result::Ok(())
+    })?;
+    let event = block_on(events.next()).unwrap()?;
+    assert!(matches!(event, EntityEvent::Removed(id) if id == book_in.id()));
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn it_finds_entities_without_blocking_async_runtime() -> Result<()> {
+    use automerge_orm::{AsyncEntityRepository, DefaultAsyncEntityRepository};
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+        author: String,
+    }
+
+    type BookRepository = DefaultAsyncEntityRepository<Book>;
+
+    impl Book {
+        pub fn new(author: &str) -> Self {
+            Self {
+                id: Uuid::new_v4(),
+                author: author.to_owned(),
+            }
+        }
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle));
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+
+    let book_in = Book::new("Miyazaki Hayao");
+    entity_manager.transact(|tx| {
+        tx.insert(&book_in)?;
+        automerge_orm::Result::Ok(())
+    })?;
+
+    let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+    let book_out = runtime
+        .block_on(book_repository.find(book_in.id()))?
+        .unwrap();
+    assert_eq!(book_out.author, book_in.author);
+    let books_out = runtime.block_on(book_repository.list())?;
+    assert_eq!(books_out.len(), 1);
+    let keys = runtime.block_on(book_repository.keys())?;
+    assert_eq!(keys, vec![book_in.id()]);
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn it_finds_entities_asynchronously_using_borrowed_entity_manager() -> Result<()> {
+    use automerge_orm::{AsyncEntityRepository, DefaultAsyncEntityRepository};
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+    }
+
+    let entity_manager = EntityManager::ephemeral();
+    let book_repository = DefaultAsyncEntityRepository::<Book, _>::new_with(&entity_manager);
+
+    let book = Book { id: Uuid::new_v4() };
+    entity_manager.transact(|tx| tx.insert(&book))?;
+
+    let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+    assert!(runtime.block_on(book_repository.find(book.id()))?.is_some());
+    assert_eq!(runtime.block_on(book_repository.keys())?, vec![book.id()]);
+
+    Ok(())
+}
+
+#[test]
+fn it_finds_related_entity_by_key() -> Result<()> {
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Author {
+        #[key]
+        id: Uuid,
+        name: String,
+    }
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+        author_id: Uuid,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    impl Author {
+        pub fn new(name: &str) -> Self {
+            Self {
+                id: Uuid::new_v4(),
+                name: name.to_owned(),
+            }
+        }
+    }
+
+    impl Book {
+        pub fn new(author: &Author) -> Self {
+            Self {
+                id: Uuid::new_v4(),
+                author_id: author.id,
+            }
+        }
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle));
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+
+    let author_in = Author::new("Miyazaki Hayao");
+    let book_in = Book::new(&author_in);
+    entity_manager.transact(|tx| {
+        tx.insert(&author_in)?;
+        tx.insert(&book_in)?;
+        automerge_orm::Result::Ok(())
+    })?;
+    let book = book_repository.find(book_in.id())?.unwrap();
+    let author = book_repository.find_related::<Author>(book.author_id.into())?;
+    assert!(author.is_some());
+    let author = author.unwrap();
+    assert_eq!(author.id(), author_in.id());
+    assert_eq!(author.name, author_in.name);
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_finds_all_entities_referencing_parent_entity() -> Result<()> {
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Author {
+        #[key]
+        id: Uuid,
+    }
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+        author_id: Uuid,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    impl Author {
+        pub fn new() -> Self {
+            Self { id: Uuid::new_v4() }
+        }
+    }
+
+    impl Book {
+        pub fn new(author: &Author) -> Self {
+            Self {
+                id: Uuid::new_v4(),
+                author_id: author.id,
+            }
+        }
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle));
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+
+    let authors_in = vec![Author::new(), Author::new()];
+    let books_in = vec![
+        Book::new(&authors_in[0]),
+        Book::new(&authors_in[0]),
+        Book::new(&authors_in[1]),
+    ];
+    entity_manager.transact(|tx| {
+        for author_in in &authors_in {
+            tx.insert(author_in)?;
+        }
+        for book_in in &books_in {
+            tx.insert(book_in)?;
+        }
+        automerge_orm::Result::Ok(())
+    })?;
+    let books = book_repository.find_all_by("author_id", authors_in[0].id())?;
+    assert_eq!(books.len(), 2);
+    assert!(books.iter().any(|book| book.id() == books_in[0].id()));
+    assert!(books.iter().any(|book| book.id() == books_in[1].id()));
+    let books = book_repository.find_all_by("author_id", Key::<Author>::from(Uuid::new_v4()))?;
+    assert!(books.is_empty());
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_maps_entities_found_in_repository() -> Result<()> {
+    use automerge_orm::RepositoryExt;
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+        title: String,
+        year: u16,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    impl Book {
+        pub fn new(title: &str, year: u16) -> Self {
+            Self {
+                id: Uuid::new_v4(),
+                title: title.to_owned(),
+                year,
+            }
+        }
+    }
+
+    let entity_manager = Arc::new(EntityManager::ephemeral());
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+
+    let books_in = vec![
+        Book::new("Spirited Away", 2001),
+        Book::new("Your Name", 2016),
+    ];
+    entity_manager.transact(|tx| {
+        for book_in in &books_in {
+            tx.insert(book_in)?;
+        }
+        automerge_orm::Result::Ok(())
+    })?;
+
+    let title = book_repository.find_map(books_in[0].id(), |book| book.title)?;
+    assert_eq!(title.as_deref(), Some("Spirited Away"));
+    let title = book_repository.find_map(Key::random(), |book| book.title)?;
+    assert_eq!(title, None);
+
+    let titles =
+        book_repository.find_all_filter_map(|book| (book.year > 2010).then_some(book.title))?;
+    assert_eq!(titles, vec!["Your Name".to_owned()]);
+
+    Ok(())
+}
+
+#[test]
+fn it_reports_conflicting_values_of_entity() -> Result<()> {
+    use automerge::{ScalarValue, Value};
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+        title: String,
+        author: String,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    let entity_manager = Arc::new(EntityManager::ephemeral());
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+
+    let book = Book {
+        id: Uuid::new_v4(),
+        title: "Spirited Away".to_owned(),
+        author: "Miyazaki Hayao".to_owned(),
+    };
+    entity_manager.transact(|tx| {
+        tx.insert(&book)?;
+        automerge_orm::Result::Ok(())
+    })?;
+    assert!(book_repository.conflicts(book.id())?.is_empty());
+
+    let fork = entity_manager.fork()?;
+    entity_manager.transact(|tx| {
+        tx.update_field(book.id(), "author", &"Shinkai Makoto")?;
+        automerge_orm::Result::Ok(())
+    })?;
+    fork.transact(|tx| {
+        tx.update_field(book.id(), "author", &"Hosoda Mamoru")?;
+        automerge_orm::Result::Ok(())
+    })?;
+    entity_manager.merge_bytes(&fork.save())?;
+
+    let conflicts = book_repository.conflicts(book.id())?;
+    assert_eq!(conflicts.len(), 1);
+    let mut authors: Vec<_> = conflicts["author"]
+        .iter()
+        .map(|value| match value {
+            Value::Scalar(value) => match value.as_ref() {
+                ScalarValue::Str(author) => author.to_string(),
+                _ => panic!("expected string, found {value}"),
+            },
+            _ => panic!("expected scalar, found {value}"),
+        })
+        .collect();
+    authors.sort();
+    assert_eq!(authors, vec!["Hosoda Mamoru", "Shinkai Makoto"]);
+    assert!(book_repository.conflicts(Key::random())?.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn it_resolves_conflicts_independently_of_actor_ids() -> Result<()> {
+    use automerge_orm::ConflictPolicy;
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+        author: String,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    // Forks are assigned random actor ids, so the winner picked by Automerge
+    // varies between iterations.
+    for _ in 0..8 {
+        let entity_manager = Arc::new(EntityManager::ephemeral());
+        let book_repository = BookRepository::new(Arc::clone(&entity_manager))
+            .with_conflict_policy(ConflictPolicy::Greatest);
+
+        let book = Book {
+            id: Uuid::new_v4(),
+            author: "Miyazaki Hayao".to_owned(),
+        };
+        entity_manager.transact(|tx| tx.insert(&book))?;
+
+        let fork = entity_manager.fork()?;
+        entity_manager.transact(|tx| tx.update_field(book.id(), "author", &"Hosoda Mamoru"))?;
+        fork.transact(|tx| tx.update_field(book.id(), "author", &"Shinkai Makoto"))?;
+        entity_manager.merge_bytes(&fork.save())?;
+
+        let found = book_repository.find(book.id())?.unwrap();
+        assert_eq!(found.author, "Shinkai Makoto");
+        let books = book_repository.find_all()?;
+        assert_eq!(books[&book.id().to_string()].author, "Shinkai Makoto");
+    }
+
+    Ok(())
+}
+
+#[test]
+fn it_resolves_conflicting_numbers_by_their_textual_representation() -> Result<()> {
+    use automerge_orm::ConflictPolicy;
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+        pages: u64,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    let entity_manager = Arc::new(EntityManager::ephemeral());
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager))
+        .with_conflict_policy(ConflictPolicy::Greatest);
+
+    let book = Book {
+        id: Uuid::new_v4(),
+        pages: 1,
+    };
+    let other_book = Book {
+        id: Uuid::new_v4(),
+        pages: 1,
+    };
+    entity_manager.transact(|tx| {
+        tx.insert(&book)?;
+        tx.insert(&other_book)
+    })?;
+
+    let fork = entity_manager.fork()?;
+    entity_manager.transact(|tx| tx.update_field(book.id(), "pages", &10_u64))?;
+    fork.transact(|tx| tx.update_field(book.id(), "pages", &9_u64))?;
+    entity_manager.merge_bytes(&fork.save())?;
+
+    let found = book_repository.find(book.id())?.unwrap();
+    assert_eq!(found.pages, 9);
+    assert_eq!(book_repository.find(other_book.id())?.unwrap().pages, 1);
+    assert!(book_repository.find(Key::random())?.is_none());
+
+    Ok(())
+}
+
+#[test]
+fn it_provides_default_implementations_based_on_find_all() -> Result<()> {
+    use std::collections::BTreeMap;
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+    }
+
+    struct BookRepository(DefaultEntityRepository<Book>);
+
+    impl EntityRepository<Book> for BookRepository {
+        fn find(&self, id: Key<Book>) -> automerge_orm::Result<Option<Book>> {
+            self.0.find(id)
+        }
+
+        fn find_all(&self) -> automerge_orm::Result<BTreeMap<String, Book>> {
+            self.0.find_all()
+        }
+    }
+
+    let entity_manager = Arc::new(EntityManager::ephemeral());
+    let book_repository = BookRepository(DefaultEntityRepository::new(Arc::clone(&entity_manager)));
+    assert!(book_repository.first()?.is_none());
+
+    let mut books_in = vec![Book { id: Uuid::new_v4() }, Book { id: Uuid::new_v4() }];
+    entity_manager.transact(|tx| {
+        for book_in in &books_in {
+            tx.insert(book_in)?;
+        }
+        automerge_orm::Result::Ok(())
+    })?;
+    books_in.sort_by_key(|book| book.id);
+
+    let books = book_repository.list()?;
+    assert_eq!(books.len(), 2);
+    assert_eq!(books[0].id, books_in[0].id);
+    assert_eq!(
+        book_repository.keys()?,
+        vec![books_in[0].id(), books_in[1].id()]
+    );
+    assert_eq!(book_repository.first()?.unwrap().id, books_in[0].id);
+
+    Ok(())
+}
@@ -0,0 +1,90 @@
+use automerge_orm::Error;
+
+#[test]
+fn it_includes_type_name_in_unsupported_type_error() {
+    struct Weird;
+
+    let err = Error::unsupported_type::<Weird>("cannot be stored");
+    assert!(err.type_name().unwrap().ends_with("::Weird"));
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "unsupported type `{}`: cannot be stored",
+            std::any::type_name::<Weird>()
+        )
+    );
+    assert!(Error::custom(std::fmt::Error).type_name().is_none());
+}
+
+#[test]
+fn it_includes_table_and_key_in_reconcile_error() {
+    use std::{error::Error as _, sync::Arc};
+
+    use automerge::AutomergeError;
+    use autosurgeon::ReconcileError;
+    use uuid::Uuid;
+
+    let id = Uuid::new_v4();
+    let err = Error::Reconcile {
+        table_name: "books".to_owned(),
+        id,
+        source: Arc::new(ReconcileError::Automerge(AutomergeError::InvalidIndex(3))),
+    };
+    assert_eq!(err.table_name(), Some("books"));
+    assert_eq!(err.key(), Some(id));
+    assert!(err.source().is_some());
+    assert!(err
+        .to_string()
+        .starts_with(&format!("failed to reconcile books[{id}]: ")));
+}
+
+#[test]
+fn it_classifies_object_errors_and_reports_their_table_and_key() {
+    use uuid::Uuid;
+
+    let id = Uuid::new_v4();
+    let not_found = Error::ObjectDoesNotExist {
+        table_name: "books".to_owned(),
+        id,
+    };
+    assert!(not_found.is_not_found());
+    assert!(!not_found.is_already_exists());
+    assert_eq!(not_found.table_name(), Some("books"));
+    assert_eq!(not_found.key(), Some(id));
+    assert!(not_found.type_name().is_none());
+
+    let already_exists = Error::ObjectAlreadyExists {
+        table_name: "books".to_owned(),
+        id,
+    };
+    assert!(already_exists.is_already_exists());
+    assert!(!already_exists.is_not_found());
+    assert_eq!(already_exists.table_name(), Some("books"));
+    assert_eq!(already_exists.key(), Some(id));
+
+    let other = Error::custom(std::fmt::Error);
+    assert!(!other.is_not_found());
+    assert!(!other.is_already_exists());
+    assert!(other.table_name().is_none());
+    assert!(other.key().is_none());
+}
+
+#[test]
+fn it_looks_through_aborted_transaction_in_accessors() {
+    use std::sync::Arc;
+
+    use uuid::Uuid;
+
+    let id = Uuid::new_v4();
+    let err = Error::TransactionAborted(Arc::new(Error::ObjectDoesNotExist {
+        table_name: "books".to_owned(),
+        id,
+    }));
+    assert!(err.is_not_found());
+    assert_eq!(err.table_name(), Some("books"));
+    assert_eq!(err.key(), Some(id));
+
+    let err = Error::TransactionAborted(Arc::new(std::fmt::Error));
+    assert!(!err.is_not_found());
+    assert!(err.table_name().is_none());
+}
@@ -0,0 +1,68 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use automerge_orm::{Entity, EntityManager, FederatedRepository, Keyed};
+use automerge_repo::Repo;
+use autosurgeon::{Hydrate, Reconcile};
+use test_utils::automerge_repo::NoopStorage;
+use uuid::Uuid;
+
+#[test]
+fn it_finds_entities_across_documents() -> Result<()> {
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+        author: String,
+    }
+
+    type BookRepository = FederatedRepository<Book>;
+
+    impl Book {
+        pub fn new(author: &str) -> Self {
+            Self {
+                id: Uuid::new_v4(),
+                author: author.to_owned(),
+            }
+        }
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle_a = repo_handle.new_document();
+    let doc_handle_b = repo_handle.new_document();
+    let entity_manager_a = Arc::new(EntityManager::new(doc_handle_a.clone()));
+    let entity_manager_b = Arc::new(EntityManager::new(doc_handle_b.clone()));
+    let book_repository = BookRepository::new(vec![
+        (doc_handle_a.document_id(), Arc::clone(&entity_manager_a)),
+        (doc_handle_b.document_id(), Arc::clone(&entity_manager_b)),
+    ]);
+
+    let book_a = Book::new("Miyazaki Hayao");
+    let book_b = Book::new("Shinkai Makoto");
+    entity_manager_a.transact(|tx| {
+        tx.insert(&book_a)?;
+        automerge_orm::Result::Ok(())
+    })?;
+    entity_manager_b.transact(|tx| {
+        tx.insert(&book_b)?;
+        automerge_orm::Result::Ok(())
+    })?;
+
+    let (document_id, book) = book_repository.find(book_b.id())?.unwrap();
+    assert_eq!(document_id, doc_handle_b.document_id());
+    assert_eq!(book.author, "Shinkai Makoto");
+    assert!(book_repository.find(Book::new("").id())?.is_none());
+
+    let books = book_repository.find_all()?;
+    assert_eq!(books.len(), 2);
+    let (document_id, book) = &books[&book_a.id().to_string()];
+    assert_eq!(*document_id, doc_handle_a.document_id());
+    assert_eq!(book.author, "Miyazaki Hayao");
+    let (document_id, book) = &books[&book_b.id().to_string()];
+    assert_eq!(*document_id, doc_handle_b.document_id());
+    assert_eq!(book.author, "Shinkai Makoto");
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
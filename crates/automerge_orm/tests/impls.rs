@@ -0,0 +1,124 @@
+use anyhow::Result;
+use automerge::AutoCommit;
+use automerge_orm::{create_table, find, Entity, Key};
+use autosurgeon::{reconcile_prop, Hydrate, Reconcile};
+use uuid::Uuid;
+
+#[test]
+fn it_returns_existing_table_when_creating_table() -> Result<()> {
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+    }
+
+    let mut doc = AutoCommit::new();
+
+    let book = Book { id: Uuid::new_v4() };
+    let table_id = create_table::<_, Book>(&mut doc)?;
+    reconcile_prop(&mut doc, &table_id, &*book.id.to_string(), &book)?;
+
+    assert_eq!(create_table::<_, Book>(&mut doc)?, table_id);
+    assert!(find::<_, Book>(&doc, Key::from(book.id))?.is_some());
+
+    Ok(())
+}
+
+#[test]
+fn it_fails_to_create_table_with_reserved_name() {
+    use automerge_orm::{Error, Mapped};
+
+    struct Metadata;
+
+    impl Mapped for Metadata {
+        fn table_name() -> &'static str {
+            "__automerge_orm_metadata"
+        }
+    }
+
+    let mut doc = AutoCommit::new();
+
+    let result = create_table::<_, Metadata>(&mut doc);
+    assert!(matches!(
+        result,
+        Err(Error::ReservedTableName { table_name }) if table_name == "__automerge_orm_metadata"
+    ));
+}
+
+#[test]
+fn it_reports_table_name_and_key_when_entity_cannot_be_hydrated() -> Result<()> {
+    use automerge_orm::{find_all, Error};
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    #[automerge_orm(table_name = "books")]
+    struct Book {
+        #[key]
+        id: Uuid,
+        title: String,
+    }
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    #[automerge_orm(table_name = "books")]
+    struct RenamedBook {
+        #[key]
+        id: Uuid,
+        name: String,
+    }
+
+    let mut doc = AutoCommit::new();
+
+    let book = Book {
+        id: Uuid::new_v4(),
+        title: "Spirited Away".to_owned(),
+    };
+    let table_id = create_table::<_, Book>(&mut doc)?;
+    reconcile_prop(&mut doc, &table_id, &*book.id.to_string(), &book)?;
+
+    let err = find::<_, RenamedBook>(&doc, Key::from(book.id)).unwrap_err();
+    assert!(matches!(
+        &err,
+        Error::Hydrate { table_name, id, .. } if table_name == "books" && *id == Some(book.id)
+    ));
+    assert!(err
+        .to_string()
+        .starts_with(&format!("failed to hydrate books[{}]: ", book.id)));
+
+    let err = find_all::<_, RenamedBook>(&doc).unwrap_err();
+    assert_eq!(err.table_name(), Some("books"));
+    assert_eq!(err.key(), Some(book.id));
+
+    Ok(())
+}
+
+#[test]
+fn it_reports_property_when_entity_with_invalid_key_cannot_be_hydrated() -> Result<()> {
+    use automerge::{transaction::Transactable, ObjType};
+    use automerge_orm::{find_all, Error};
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    #[automerge_orm(table_name = "books")]
+    struct Book {
+        #[key]
+        id: Uuid,
+        title: String,
+    }
+
+    let mut doc = AutoCommit::new();
+
+    let table_id = create_table::<_, Book>(&mut doc)?;
+    let entity_id = doc.put_object(&table_id, "not-a-key", ObjType::Map)?;
+    doc.put(&entity_id, "name", "Spirited Away")?;
+
+    let err = find_all::<_, Book>(&doc).unwrap_err();
+    assert!(matches!(
+        &err,
+        Error::Hydrate { table_name, id: None, key, .. }
+            if table_name == "books" && key == "not-a-key"
+    ));
+    assert!(err
+        .to_string()
+        .starts_with("failed to hydrate books[not-a-key]: "));
+    assert!(err.key().is_none());
+
+    Ok(())
+}
@@ -0,0 +1,200 @@
+use anyhow::Result;
+use automerge::ScalarValue;
+use automerge_orm::{Entity, Error, Key};
+use autosurgeon::{Hydrate, Reconcile};
+use uuid::Uuid;
+
+#[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+struct Book {
+    #[key]
+    id: Uuid,
+}
+
+#[test]
+fn it_converts_key_from_and_into_scalar_value() -> Result<()> {
+    let key = Key::<Book>::random();
+
+    let value = ScalarValue::from(key);
+    assert_eq!(Key::<Book>::try_from(value)?, key);
+
+    let result = Key::<Book>::try_from(&[0u8; 4][..]);
+    assert!(matches!(result, Err(Error::InvalidKey { .. })));
+    let value = ScalarValue::Str(key.to_string().into());
+    assert_eq!(Key::<Book>::try_from(value)?, key);
+    let result = Key::<Book>::try_from(ScalarValue::Int(1));
+    assert!(matches!(result, Err(Error::Automerge(_))));
+
+    Ok(())
+}
+
+#[test]
+fn it_assumes_key_from_uuid() {
+    use automerge_orm::Keyed;
+
+    let book = Book { id: Uuid::new_v4() };
+    let key = Key::<Book>::assume(book.id);
+    assert_eq!(key, book.id());
+    assert_eq!(Uuid::from(key), book.id);
+}
+
+#[test]
+fn it_round_trips_key_through_short_form() -> Result<()> {
+    let keys = (0..1000)
+        .map(|_| Key::<Book>::random())
+        .chain([Key::nil(), Key::assume(Uuid::from_u128(u128::MAX))]);
+    for key in keys {
+        let short = key.to_short();
+        assert_eq!(short.len(), 22);
+        assert!(short.chars().all(|c| c.is_ascii_alphanumeric()));
+        assert_eq!(Key::<Book>::from_short(&short)?, key);
+    }
+    assert_eq!(Key::<Book>::nil().to_short(), "0".repeat(22));
+
+    let (a, b) = (Key::<Book>::random(), Key::<Book>::random());
+    assert_eq!(a.cmp(&b), a.to_short().cmp(&b.to_short()));
+
+    for short in ["", "0", "-".repeat(22).as_str(), "z".repeat(22).as_str()] {
+        let result = Key::<Book>::from_short(short);
+        assert!(matches!(result, Err(Error::InvalidShortKey { key }) if key == short));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn it_creates_random_and_nil_keys() {
+    let key = Key::<Book>::random();
+    assert_ne!(key, Key::random());
+    assert_eq!(Uuid::from(key).get_version_num(), 4);
+
+    assert_eq!(Uuid::from(Key::<Book>::nil()), Uuid::nil());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn it_serializes_and_deserializes_key() -> Result<()> {
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct BookDto {
+        id: Key<Book>,
+    }
+
+    let book = BookDto { id: Key::random() };
+    let json = serde_json::to_value(&book)?;
+    assert_eq!(json, serde_json::json!({ "id": book.id.to_string() }));
+    let book_out: BookDto = serde_json::from_value(json)?;
+    assert_eq!(book_out, book);
+
+    Ok(())
+}
+
+#[test]
+fn it_stores_key_field_as_bytes_or_string() -> Result<()> {
+    use std::sync::Arc;
+
+    use automerge::{Automerge, ReadDoc, Value};
+    use automerge_orm::{DefaultEntityRepository, EntityManager, EntityRepository, Keyed, Mapped};
+
+    #[derive(Clone, Debug, Entity, Hydrate, PartialEq, Reconcile)]
+    struct Author {
+        #[key]
+        #[autosurgeon(with = "automerge_orm::key_string")]
+        id: Uuid,
+    }
+
+    fn stored_id<T: Mapped>(entity_manager: &EntityManager, key: Key<T>) -> Result<ScalarValue> {
+        let doc = Automerge::load(&entity_manager.save())?;
+        let table_id = entity_manager.table_id::<T>()?.unwrap();
+        let (_, entity_id) = doc.get(&table_id, key.to_string())?.unwrap();
+        let Some((Value::Scalar(id), _)) = doc.get(&entity_id, "id")? else {
+            anyhow::bail!("id is not a scalar");
+        };
+
+        Ok(id.into_owned())
+    }
+
+    let entity_manager = Arc::new(EntityManager::ephemeral());
+    let book_repository = DefaultEntityRepository::<Book>::new(Arc::clone(&entity_manager));
+    let author_repository = DefaultEntityRepository::<Author>::new(Arc::clone(&entity_manager));
+
+    let book = Book { id: Uuid::new_v4() };
+    let author = Author { id: Uuid::new_v4() };
+    entity_manager.transact(|tx| {
+        tx.insert(&book)?;
+        tx.insert(&author)?;
+        automerge_orm::Result::Ok(())
+    })?;
+
+    assert_eq!(
+        stored_id(&entity_manager, book.id())?,
+        ScalarValue::from(book.id())
+    );
+    assert_eq!(
+        stored_id(&entity_manager, author.id())?,
+        ScalarValue::Str(author.id.hyphenated().to_string().into())
+    );
+    assert_eq!(book_repository.find(book.id())?.unwrap().id, book.id);
+    assert_eq!(author_repository.find(author.id())?, Some(author));
+
+    Ok(())
+}
+
+#[test]
+fn it_round_trips_key_fields() -> Result<()> {
+    use automerge::{Automerge, ReadDoc, Value};
+    use automerge_orm::{EntityManager, Keyed};
+
+    #[derive(Clone, Debug, Entity, Hydrate, PartialEq, Reconcile)]
+    struct Author {
+        #[key]
+        id: Key<Author>,
+    }
+
+    #[derive(Clone, Debug, Entity, Hydrate, PartialEq, Reconcile)]
+    struct Novel {
+        #[key]
+        id: Key<Novel>,
+        author: Key<Author>,
+        translator: Option<Key<Author>>,
+    }
+
+    let entity_manager = EntityManager::ephemeral();
+
+    let author = Author { id: Key::random() };
+    let novel = Novel {
+        id: Key::random(),
+        author: author.id(),
+        translator: None,
+    };
+    let translated = Novel {
+        id: Key::random(),
+        author: author.id(),
+        translator: Some(author.id()),
+    };
+    entity_manager.transact(|tx| {
+        tx.insert(&author)?;
+        tx.insert(&novel)?;
+        tx.insert(&translated)
+    })?;
+
+    let doc = Automerge::load(&entity_manager.save())?;
+    let table_id = entity_manager.table_id::<Novel>()?.unwrap();
+    let (_, novel_id) = doc.get(&table_id, novel.id.to_string())?.unwrap();
+    let Some((Value::Scalar(stored), _)) = doc.get(&novel_id, "author")? else {
+        anyhow::bail!("author is not a scalar");
+    };
+    assert_eq!(stored.into_owned(), ScalarValue::from(author.id()));
+
+    assert_eq!(
+        entity_manager.find_related(novel.id())?,
+        Some(novel.clone())
+    );
+    assert_eq!(
+        entity_manager.find_related(translated.id())?,
+        Some(translated)
+    );
+    assert_eq!(entity_manager.find_related(novel.author)?, Some(author));
+
+    Ok(())
+}
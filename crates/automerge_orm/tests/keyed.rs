@@ -0,0 +1,25 @@
+use automerge_orm::{Entity, Key, Keyed};
+use autosurgeon::{Hydrate, Reconcile};
+use uuid::Uuid;
+
+#[test]
+fn it_forwards_key_of_references_and_boxes() {
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+    }
+
+    fn key_of<K: Keyed>(entity: K) -> Key<K::Entity> {
+        entity.id()
+    }
+
+    let book = Book { id: Uuid::new_v4() };
+    let key: Key<Book> = book.id();
+
+    assert_eq!(key_of(&book), key);
+    assert_eq!(key_of(&&book), key);
+    assert_eq!(key_of(Box::new(book.clone())), key);
+    assert_eq!(key_of(&Box::new(book.clone())), key);
+    assert_eq!(Box::new(book).id(), key);
+}
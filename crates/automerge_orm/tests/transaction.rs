@@ -1,9 +1,9 @@
-use std::sync::Arc;
+use std::{collections::BTreeMap, sync::Arc};
 
 use anyhow::Result;
 use automerge::ScalarValue;
 use automerge_orm::{
-    DefaultEntityRepository, Entity, EntityManager, EntityRepository, Keyed, Mapped,
+    DefaultEntityRepository, Entity, EntityManager, EntityRepository, Key, Keyed, Mapped,
 };
 use automerge_repo::Repo;
 use automerge_test::{assert_doc, map};
@@ -91,6 +91,136 @@ fn it_fails_to_insert_new_entity_when_entity_with_same_id_already_exists() -> Re
     Ok(())
 }
 
+#[test]
+fn it_inserts_new_owned_entity_and_returns_it() -> Result<()> {
+    #[derive(Clone, Debug, Entity, Hydrate, PartialEq, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    impl Book {
+        pub fn new() -> Self {
+            Self { id: Uuid::new_v4() }
+        }
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle));
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+
+    let book = entity_manager.transact(|tx| tx.insert_owned(Book::new()))?;
+    assert_eq!(book_repository.find(book.id())?, Some(book.clone()));
+
+    let result = entity_manager.transact(|tx| tx.insert_owned(book.clone()));
+    assert!(result.unwrap_err().is_already_exists());
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_inserts_entities_of_different_types_as_trait_objects() -> Result<()> {
+    use automerge_orm::DynEntity;
+
+    #[derive(Clone, Debug, Entity, Hydrate, PartialEq, Reconcile)]
+    #[automerge_orm(pluralize)]
+    struct Book {
+        #[key]
+        id: Uuid,
+    }
+
+    #[derive(Clone, Debug, Entity, Hydrate, PartialEq, Reconcile)]
+    #[automerge_orm(pluralize)]
+    struct Author {
+        #[key]
+        id: Uuid,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+    type AuthorRepository = DefaultEntityRepository<Author>;
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle));
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+    let author_repository = AuthorRepository::new(Arc::clone(&entity_manager));
+
+    let book = Book { id: Uuid::new_v4() };
+    let author = Author { id: Uuid::new_v4() };
+    let entities: Vec<Box<dyn DynEntity>> = vec![Box::new(book.clone()), Box::new(author.clone())];
+    assert_eq!(entities[0].table_name(), "books");
+    assert_eq!(entities[0].key_string(), book.id.to_string());
+    assert_eq!(entities[1].table_name(), "authors");
+
+    entity_manager.transact(|tx| {
+        for entity in &entities {
+            tx.insert_dyn(entity.as_ref())?;
+        }
+        automerge_orm::Result::Ok(())
+    })?;
+    assert_eq!(book_repository.find(book.id())?, Some(book));
+    assert_eq!(author_repository.find(author.id())?, Some(author));
+
+    let result = entity_manager.transact(|tx| tx.insert_dyn(entities[0].as_ref()));
+    assert!(result.unwrap_err().is_already_exists());
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_gets_raw_value_of_property_not_modelled_by_entity() -> Result<()> {
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    #[automerge_orm(table_name = "book")]
+    struct LegacyBook {
+        #[key]
+        id: Uuid,
+        isbn: String,
+    }
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle));
+
+    let legacy_book = LegacyBook {
+        id: Uuid::new_v4(),
+        isbn: "978-4-19-720000-0".to_owned(),
+    };
+    entity_manager.transact(|tx| {
+        tx.insert(&legacy_book)?;
+        automerge_orm::Result::Ok(())
+    })?;
+
+    let id = Key::<Book>::new(legacy_book.id);
+    let (isbn, missing, unknown) = entity_manager.transact(|tx| {
+        let isbn = tx
+            .get_raw(id, "isbn")?
+            .and_then(|(value, _)| value.to_str().map(str::to_owned));
+        let missing = tx.get_raw(id, "title")?.is_none();
+        let unknown = tx.get_raw(Key::<Book>::random(), "isbn")?.is_none();
+        automerge_orm::Result::Ok((isbn, missing, unknown))
+    })?;
+    assert_eq!(isbn.as_deref(), Some("978-4-19-720000-0"));
+    assert!(missing);
+    assert!(unknown);
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
 #[test]
 fn it_gets_or_inserts_existing_entity() -> Result<()> {
     #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
@@ -295,56 +425,39 @@ fn it_updates_existing_entity() -> Result<()> {
 }
 
 #[test]
-fn it_fails_to_update_entity_which_does_not_exist() -> Result<()> {
+fn it_deletes_property_of_optional_field_set_to_none_on_update() -> Result<()> {
     #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
     struct Book {
         #[key]
         id: Uuid,
+        author: Option<String>,
     }
 
-    impl Book {
-        pub fn new() -> Self {
-            Self { id: Uuid::new_v4() }
-        }
-    }
-
-    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
-    let doc_handle = repo_handle.new_document();
-    let entity_manager = Arc::new(EntityManager::new(doc_handle));
-
-    let book = Book::new();
-    let result = entity_manager.transact(|tx| {
-        tx.update(&book)?;
-        automerge_orm::Result::Ok(())
-    });
-    assert!(result.is_err());
-
-    repo_handle.stop().unwrap();
-
-    Ok(())
-}
-
-#[test]
-fn it_upserts_new_entity() -> Result<()> {
-    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
-    struct Book {
-        #[key]
-        id: Uuid,
-    }
+    type BookRepository = DefaultEntityRepository<Book>;
 
     impl Book {
-        pub fn new() -> Self {
-            Self { id: Uuid::new_v4() }
+        pub fn new(author: &str) -> Self {
+            Self {
+                id: Uuid::new_v4(),
+                author: Some(author.to_owned()),
+            }
         }
     }
 
     let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
     let doc_handle = repo_handle.new_document();
     let entity_manager = Arc::new(EntityManager::new(doc_handle.clone()));
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
 
-    let book = Book::new();
+    let mut book = Book::new("Miyazaki Hayao");
     entity_manager.transact(|tx| {
-        tx.upsert(&book)?;
+        tx.insert(&book)?;
+        automerge_orm::Result::Ok(())
+    })?;
+
+    book.author = None;
+    entity_manager.transact(|tx| {
+        tx.update(&book)?;
         automerge_orm::Result::Ok(())
     })?;
 
@@ -364,6 +477,24 @@ fn it_upserts_new_entity() -> Result<()> {
             }
         );
     });
+    assert_eq!(book_repository.find(book.id())?.unwrap().author, None);
+
+    book.author = Some("Shinkai Makoto".to_owned());
+    entity_manager.transact(|tx| {
+        tx.upsert(&book)?;
+        automerge_orm::Result::Ok(())
+    })?;
+    assert_eq!(
+        book_repository.find(book.id())?.unwrap().author.as_deref(),
+        Some("Shinkai Makoto")
+    );
+
+    book.author = None;
+    entity_manager.transact(|tx| {
+        tx.upsert(&book)?;
+        automerge_orm::Result::Ok(())
+    })?;
+    assert_eq!(book_repository.find(book.id())?.unwrap().author, None);
 
     repo_handle.stop().unwrap();
 
@@ -371,43 +502,45 @@ fn it_upserts_new_entity() -> Result<()> {
 }
 
 #[test]
-fn it_upserts_existing_entity() -> Result<()> {
+fn it_deletes_only_properties_which_are_set_to_none_by_write() -> Result<()> {
+    #[derive(Clone, Debug, Hydrate, Reconcile)]
+    struct Details {
+        subtitle: Option<String>,
+    }
+
     #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
     struct Book {
         #[key]
         id: Uuid,
-        author: String,
-    }
-
-    impl Book {
-        pub fn new(author: &str) -> Self {
-            Self {
-                id: Uuid::new_v4(),
-                author: author.to_owned(),
-            }
-        }
-
-        pub fn author(&self) -> &str {
-            &self.author
-        }
-
-        pub fn set_author(&mut self, author: &str) {
-            self.author = author.to_owned();
-        }
+        author: Option<String>,
+        details: Details,
     }
 
     let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
     let doc_handle = repo_handle.new_document();
-    let entity_manager = Arc::new(EntityManager::new(doc_handle.clone()));
-
-    let mut book = Book::new("Miyazaki Hayao");
+    let entity_manager = EntityManager::new(doc_handle.clone());
+
+    let mut book = Book {
+        id: Uuid::new_v4(),
+        author: None,
+        details: Details {
+            subtitle: Some("The Journey".to_owned()),
+        },
+    };
     entity_manager.transact(|tx| {
-        tx.upsert(&book)?;
+        tx.insert(&book)?;
         automerge_orm::Result::Ok(())
     })?;
-    book.set_author("Shinkai Makoto");
+
+    let is_empty = entity_manager.transact(|tx| {
+        tx.update(&book)?;
+        automerge_orm::Result::Ok(tx.is_empty())
+    })?;
+    assert!(is_empty);
+
+    book.details.subtitle = None;
     entity_manager.transact(|tx| {
-        tx.upsert(&book)?;
+        tx.update(&book)?;
         automerge_orm::Result::Ok(())
     })?;
 
@@ -420,7 +553,12 @@ fn it_upserts_existing_entity() -> Result<()> {
                         book.id() => {
                             map!{
                                 "id" => { ScalarValue::from(book.id()) },
-                                "author" => { book.author() },
+                                "author" => { ScalarValue::Null },
+                                "details" => {
+                                    map!{
+                                        "subtitle" => { ScalarValue::Null },
+                                    },
+                                },
                             },
                         },
                     },
@@ -435,51 +573,40 @@ fn it_upserts_existing_entity() -> Result<()> {
 }
 
 #[test]
-fn it_removes_entity_by_id() -> Result<()> {
+fn it_keeps_null_values_of_map_field_on_update() -> Result<()> {
     #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
     struct Book {
         #[key]
         id: Uuid,
+        subtitles: BTreeMap<String, Option<String>>,
     }
 
     type BookRepository = DefaultEntityRepository<Book>;
 
-    impl Book {
-        pub fn new() -> Self {
-            Self { id: Uuid::new_v4() }
-        }
-    }
-
     let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
     let doc_handle = repo_handle.new_document();
-    let entity_manager = Arc::new(EntityManager::new(doc_handle.clone()));
+    let entity_manager = Arc::new(EntityManager::new(doc_handle));
     let book_repository = BookRepository::new(Arc::clone(&entity_manager));
 
-    let book = Book::new();
+    let mut book = Book {
+        id: Uuid::new_v4(),
+        subtitles: BTreeMap::from([
+            ("en".to_owned(), Some("The Journey".to_owned())),
+            ("ja".to_owned(), None),
+        ]),
+    };
     entity_manager.transact(|tx| {
         tx.insert(&book)?;
         automerge_orm::Result::Ok(())
     })?;
-    let book = book_repository.find(book.id())?;
-    assert!(book.is_some());
-    let book = book.unwrap();
+
+    book.subtitles.insert("en".to_owned(), None);
     entity_manager.transact(|tx| {
-        tx.remove(book.id())?;
+        tx.update(&book)?;
         automerge_orm::Result::Ok(())
     })?;
-    let book = book_repository.find(book.id())?;
-    assert!(book.is_none());
-
-    doc_handle.with_doc(|doc| {
-        assert_doc!(
-            doc,
-            map! {
-                Book::table_name() => {
-                    map!{},
-                },
-            }
-        );
-    });
+    let found = book_repository.find(book.id())?.unwrap();
+    assert_eq!(found.subtitles, book.subtitles);
 
     repo_handle.stop().unwrap();
 
@@ -487,7 +614,7 @@ fn it_removes_entity_by_id() -> Result<()> {
 }
 
 #[test]
-fn it_does_not_fail_when_trying_to_remove_entity_using_nonexistent_id() -> Result<()> {
+fn it_fails_to_update_entity_which_does_not_exist() -> Result<()> {
     #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
     struct Book {
         #[key]
@@ -504,15 +631,12 @@ fn it_does_not_fail_when_trying_to_remove_entity_using_nonexistent_id() -> Resul
     let doc_handle = repo_handle.new_document();
     let entity_manager = Arc::new(EntityManager::new(doc_handle));
 
-    let book_in = Book::new();
-    entity_manager.transact(|tx| {
-        tx.insert(&book_in)?;
-        automerge_orm::Result::Ok(())
-    })?;
-    entity_manager.transact(|tx| {
-        tx.remove::<Book>(Uuid::new_v4().into())?;
+    let book = Book::new();
+    let result = entity_manager.transact(|tx| {
+        tx.update(&book)?;
         automerge_orm::Result::Ok(())
-    })?;
+    });
+    assert!(result.is_err());
 
     repo_handle.stop().unwrap();
 
@@ -520,23 +644,1769 @@ fn it_does_not_fail_when_trying_to_remove_entity_using_nonexistent_id() -> Resul
 }
 
 #[test]
-fn it_does_not_fail_when_trying_to_remove_entity_in_nonexistent_table() -> Result<()> {
+fn it_fails_to_update_entity_with_data_of_another_entity() -> Result<()> {
+    use automerge_orm::Error;
+
     #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
     struct Book {
         #[key]
         id: Uuid,
+        author: String,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    impl Book {
+        pub fn new(author: &str) -> Self {
+            Self {
+                id: Uuid::new_v4(),
+                author: author.to_owned(),
+            }
+        }
     }
 
     let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
     let doc_handle = repo_handle.new_document();
     let entity_manager = Arc::new(EntityManager::new(doc_handle));
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
 
+    let book_a = Book::new("Miyazaki Hayao");
+    let book_b = Book::new("Shinkai Makoto");
     entity_manager.transact(|tx| {
-        tx.remove::<Book>(Uuid::new_v4().into())?;
+        tx.insert(&book_a)?;
+        tx.insert(&book_b)?;
         automerge_orm::Result::Ok(())
     })?;
+    let result = entity_manager.transact(|tx| {
+        tx.update_at(book_a.id(), &book_b)?;
+        automerge_orm::Result::Ok(())
+    });
+    let err = result.unwrap_err();
+    let Error::TransactionAborted(err) = err else {
+        panic!("expected transaction to be aborted");
+    };
+    assert!(matches!(
+        err.downcast_ref::<Error>(),
+        Some(Error::KeyMismatch { actual, expected, .. })
+            if *actual == book_b.id && *expected == book_a.id
+    ));
+    assert_eq!(
+        book_repository.find(book_a.id())?.unwrap().author,
+        "Miyazaki Hayao"
+    );
 
     repo_handle.stop().unwrap();
 
     Ok(())
 }
+
+#[test]
+fn it_does_not_add_operations_when_updating_entity_with_identical_data() -> Result<()> {
+    #[derive(Clone, Debug, Entity, Hydrate, PartialEq, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+        author: String,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    impl Book {
+        pub fn new(author: &str) -> Self {
+            Self {
+                id: Uuid::new_v4(),
+                author: author.to_owned(),
+            }
+        }
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle));
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+
+    let mut book = Book::new("Miyazaki Hayao");
+    entity_manager.transact(|tx| {
+        tx.insert(&book)?;
+        automerge_orm::Result::Ok(())
+    })?;
+
+    let heads = entity_manager.heads();
+    let updated = entity_manager.transact(|tx| tx.update_diffed(&book.clone()))?;
+    assert!(!updated);
+    assert_eq!(entity_manager.heads(), heads);
+
+    book.author = "Shinkai Makoto".to_owned();
+    let updated = entity_manager.transact(|tx| tx.update_diffed(&book))?;
+    assert!(updated);
+    assert_ne!(entity_manager.heads(), heads);
+    assert_eq!(book_repository.find(book.id())?.unwrap(), book);
+
+    let result = entity_manager.transact(|tx| tx.update_diffed(&Book::new("Shinkai Makoto")));
+    assert!(result.unwrap_err().is_not_found());
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_replaces_existing_entity_removing_absent_properties() -> Result<()> {
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    #[automerge_orm(table_name = "book")]
+    struct LegacyBook {
+        #[key]
+        id: Uuid,
+        author: String,
+        isbn: String,
+    }
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+        author: String,
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle.clone()));
+
+    let legacy_book = LegacyBook {
+        id: Uuid::new_v4(),
+        author: "Miyazaki Hayao".to_owned(),
+        isbn: "978-4-19-720000-0".to_owned(),
+    };
+    entity_manager.transact(|tx| {
+        tx.insert(&legacy_book)?;
+        automerge_orm::Result::Ok(())
+    })?;
+
+    let book = Book {
+        id: legacy_book.id,
+        author: "Shinkai Makoto".to_owned(),
+    };
+    entity_manager.transact(|tx| {
+        tx.replace(&book)?;
+        automerge_orm::Result::Ok(())
+    })?;
+
+    doc_handle.with_doc(|doc| {
+        assert_doc!(
+            doc,
+            map! {
+                Book::table_name() => {
+                    map!{
+                        book.id() => {
+                            map!{
+                                "id" => { ScalarValue::from(book.id()) },
+                                "author" => { "Shinkai Makoto" },
+                            },
+                        },
+                    },
+                },
+            }
+        );
+    });
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_fails_to_replace_entity_which_does_not_exist() -> Result<()> {
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+    }
+
+    impl Book {
+        pub fn new() -> Self {
+            Self { id: Uuid::new_v4() }
+        }
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle));
+
+    let book = Book::new();
+    let result = entity_manager.transact(|tx| {
+        tx.replace(&book)?;
+        automerge_orm::Result::Ok(())
+    });
+    assert!(result.unwrap_err().is_not_found());
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_upserts_new_entity() -> Result<()> {
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+    }
+
+    impl Book {
+        pub fn new() -> Self {
+            Self { id: Uuid::new_v4() }
+        }
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle.clone()));
+
+    let book = Book::new();
+    entity_manager.transact(|tx| {
+        tx.upsert(&book)?;
+        automerge_orm::Result::Ok(())
+    })?;
+
+    doc_handle.with_doc(|doc| {
+        assert_doc!(
+            doc,
+            map! {
+                Book::table_name() => {
+                    map!{
+                        book.id() => {
+                            map!{
+                                "id" => { ScalarValue::from(book.id()) },
+                            },
+                        },
+                    },
+                },
+            }
+        );
+    });
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_upserts_existing_entity() -> Result<()> {
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+        author: String,
+    }
+
+    impl Book {
+        pub fn new(author: &str) -> Self {
+            Self {
+                id: Uuid::new_v4(),
+                author: author.to_owned(),
+            }
+        }
+
+        pub fn author(&self) -> &str {
+            &self.author
+        }
+
+        pub fn set_author(&mut self, author: &str) {
+            self.author = author.to_owned();
+        }
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle.clone()));
+
+    let mut book = Book::new("Miyazaki Hayao");
+    entity_manager.transact(|tx| {
+        tx.upsert(&book)?;
+        automerge_orm::Result::Ok(())
+    })?;
+    book.set_author("Shinkai Makoto");
+    entity_manager.transact(|tx| {
+        tx.upsert(&book)?;
+        automerge_orm::Result::Ok(())
+    })?;
+
+    doc_handle.with_doc(|doc| {
+        assert_doc!(
+            doc,
+            map! {
+                Book::table_name() => {
+                    map!{
+                        book.id() => {
+                            map!{
+                                "id" => { ScalarValue::from(book.id()) },
+                                "author" => { book.author() },
+                            },
+                        },
+                    },
+                },
+            }
+        );
+    });
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_imports_entities_according_to_conflict_strategy() -> Result<()> {
+    use automerge_orm::OnConflict;
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+        author: String,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    impl Book {
+        pub fn new(author: &str) -> Self {
+            Self {
+                id: Uuid::new_v4(),
+                author: author.to_owned(),
+            }
+        }
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle));
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+
+    let mut book_a = Book::new("Miyazaki Hayao");
+    let report = entity_manager.transact(|tx| tx.import([book_a.clone()], OnConflict::Error))?;
+    assert_eq!(report.inserted(), 1);
+
+    book_a.author = "Shinkai Makoto".to_owned();
+    let book_b = Book::new("Hosoda Mamoru");
+    let report = entity_manager
+        .transact(|tx| tx.import([book_a.clone(), book_b.clone()], OnConflict::Skip))?;
+    assert_eq!(
+        (report.inserted(), report.updated(), report.skipped()),
+        (1, 0, 1)
+    );
+    assert_eq!(
+        book_repository.find(book_a.id())?.unwrap().author,
+        "Miyazaki Hayao"
+    );
+
+    let report =
+        entity_manager.transact(|tx| tx.import([book_a.clone()], OnConflict::Overwrite))?;
+    assert_eq!(
+        (report.inserted(), report.updated(), report.skipped()),
+        (0, 1, 0)
+    );
+    assert_eq!(
+        book_repository.find(book_a.id())?.unwrap().author,
+        "Shinkai Makoto"
+    );
+
+    let book_c = Book::new("Kon Satoshi");
+    let result =
+        entity_manager.transact(|tx| tx.import([book_c.clone(), book_b], OnConflict::Error));
+    assert!(result.unwrap_err().is_already_exists());
+    assert!(book_repository.find(book_c.id())?.is_none());
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_removes_entity_by_id() -> Result<()> {
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    impl Book {
+        pub fn new() -> Self {
+            Self { id: Uuid::new_v4() }
+        }
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle.clone()));
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+
+    let book = Book::new();
+    entity_manager.transact(|tx| {
+        tx.insert(&book)?;
+        automerge_orm::Result::Ok(())
+    })?;
+    let book = book_repository.find(book.id())?;
+    assert!(book.is_some());
+    let book = book.unwrap();
+    entity_manager.transact(|tx| {
+        tx.remove(book.id())?;
+        automerge_orm::Result::Ok(())
+    })?;
+    let book = book_repository.find(book.id())?;
+    assert!(book.is_none());
+
+    doc_handle.with_doc(|doc| {
+        assert_doc!(
+            doc,
+            map! {
+                Book::table_name() => {
+                    map!{},
+                },
+            }
+        );
+    });
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_does_not_fail_when_trying_to_remove_entity_using_nonexistent_id() -> Result<()> {
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+    }
+
+    impl Book {
+        pub fn new() -> Self {
+            Self { id: Uuid::new_v4() }
+        }
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle));
+
+    let book_in = Book::new();
+    entity_manager.transact(|tx| {
+        tx.insert(&book_in)?;
+        automerge_orm::Result::Ok(())
+    })?;
+    entity_manager.transact(|tx| {
+        tx.remove::<Book>(Uuid::new_v4().into())?;
+        automerge_orm::Result::Ok(())
+    })?;
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_does_not_fail_when_trying_to_remove_entity_in_nonexistent_table() -> Result<()> {
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle));
+
+    entity_manager.transact(|tx| {
+        tx.remove::<Book>(Uuid::new_v4().into())?;
+        automerge_orm::Result::Ok(())
+    })?;
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_fails_when_trying_to_remove_nonexistent_entity_strictly() -> Result<()> {
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+    }
+
+    let entity_manager = EntityManager::ephemeral();
+    let book_repository = DefaultEntityRepository::<Book, _>::new_with(&entity_manager);
+
+    let err = entity_manager
+        .transact(|tx| tx.remove_existing::<Book>(Key::random()))
+        .unwrap_err();
+    assert!(err.is_not_found());
+    assert_eq!(err.table_name(), Some(Book::table_name()));
+
+    let book = Book { id: Uuid::new_v4() };
+    entity_manager.transact(|tx| tx.insert(&book))?;
+    let err = entity_manager
+        .transact(|tx| tx.remove_existing::<Book>(Key::random()))
+        .unwrap_err();
+    assert!(err.is_not_found());
+    entity_manager.transact(|tx| tx.remove_existing(book.id()))?;
+    assert!(book_repository.find(book.id())?.is_none());
+
+    Ok(())
+}
+
+#[cfg(feature = "serde_json")]
+#[test]
+fn it_imports_entities_from_json() -> Result<()> {
+    use serde::Deserialize;
+
+    #[derive(Clone, Debug, Deserialize, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+        author: String,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    impl Book {
+        pub fn new(author: &str) -> Self {
+            Self {
+                id: Uuid::new_v4(),
+                author: author.to_owned(),
+            }
+        }
+
+        pub fn author(&self) -> &str {
+            &self.author
+        }
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let entity_manager = Arc::new(EntityManager::new(repo_handle.new_document()));
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+    let other_entity_manager = Arc::new(EntityManager::new(repo_handle.new_document()));
+    let other_book_repository = BookRepository::new(Arc::clone(&other_entity_manager));
+
+    let books_in = vec![Book::new("Miyazaki Hayao"), Book::new("Shinkai Makoto")];
+    entity_manager.transact(|tx| {
+        for book_in in &books_in {
+            tx.insert(book_in)?;
+        }
+        automerge_orm::Result::Ok(())
+    })?;
+    let books_json = book_repository.export_json()?;
+    other_entity_manager.transact(|tx| {
+        tx.import_json::<Book>(books_json)?;
+        automerge_orm::Result::Ok(())
+    })?;
+    let books = other_book_repository.find_all()?;
+    assert_eq!(books.len(), 2);
+    for book_in in &books_in {
+        let book = books.get(&book_in.id().to_string());
+        assert!(book.is_some());
+        assert_eq!(book.unwrap().author(), book_in.author());
+    }
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[cfg(feature = "serde_json")]
+#[test]
+fn it_fails_to_import_entity_from_json_with_mismatched_id() -> Result<()> {
+    use serde::Deserialize;
+    use serde_json::json;
+
+    #[derive(Clone, Debug, Deserialize, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle));
+
+    let mut books_json = serde_json::Map::new();
+    books_json.insert(
+        Uuid::new_v4().to_string(),
+        json!({ "id": Uuid::new_v4().to_string() }),
+    );
+    let books_json = books_json.into();
+    let result = entity_manager.transact(|tx| {
+        tx.import_json::<Book>(books_json)?;
+        automerge_orm::Result::Ok(())
+    });
+    assert!(result.is_err());
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_merges_concurrent_edits_to_text_field() -> Result<()> {
+    use autosurgeon::{reconcile_prop, Text};
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+        title: Text,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    impl Book {
+        pub fn new(title: &str) -> Self {
+            Self {
+                id: Uuid::new_v4(),
+                title: Text::with_value(title),
+            }
+        }
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle.clone()));
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+
+    let book = Book::new("Spirited Away");
+    entity_manager.transact(|tx| {
+        tx.insert(&book)?;
+        automerge_orm::Result::Ok(())
+    })?;
+
+    let mut fork = doc_handle.with_doc(|doc| doc.fork());
+    let mut fork_book = automerge_orm::find(&fork, book.id())?.unwrap();
+    fork_book.title.splice(0, 0, "The ");
+    let mut fork_tx = fork.transaction();
+    let table_id = automerge_orm::get_table::<_, Book>(&fork_tx)?.unwrap();
+    reconcile_prop(&mut fork_tx, &table_id, &*book.id().to_string(), &fork_book)?;
+    fork_tx.commit();
+
+    let mut book = book_repository.find(book.id())?.unwrap();
+    book.title.splice(13, 0, " (2001)");
+    entity_manager.transact(|tx| {
+        tx.update(&book)?;
+        automerge_orm::Result::Ok(())
+    })?;
+    doc_handle.with_doc_mut(|doc| doc.merge(&mut fork))?;
+
+    let book = book_repository.find(book.id())?.unwrap();
+    assert_eq!(book.title.as_str(), "The Spirited Away (2001)");
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_sums_concurrent_increments_to_counter_field() -> Result<()> {
+    use automerge::transaction::Transactable;
+    use autosurgeon::Counter;
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+        likes: Counter,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    impl Book {
+        pub fn new() -> Self {
+            Self {
+                id: Uuid::new_v4(),
+                likes: Counter::default(),
+            }
+        }
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle.clone()));
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+
+    let book = Book::new();
+    entity_manager.transact(|tx| {
+        tx.insert(&book)?;
+        automerge_orm::Result::Ok(())
+    })?;
+
+    let mut fork = doc_handle.with_doc(|doc| doc.fork());
+    let mut fork_tx = fork.transaction();
+    let entity_id = automerge_orm::get_entity(&fork_tx, book.id())?.unwrap();
+    fork_tx.increment(&entity_id, "likes", 1)?;
+    fork_tx.commit();
+
+    entity_manager.transact(|tx| {
+        tx.increment(book.id(), "likes", 2)?;
+        automerge_orm::Result::Ok(())
+    })?;
+    doc_handle.with_doc_mut(|doc| doc.merge(&mut fork))?;
+
+    let book = book_repository.find(book.id())?.unwrap();
+    assert_eq!(book.likes.value(), 3);
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_sums_concurrent_increments_to_integer_field_stored_as_counter() -> Result<()> {
+    use automerge::transaction::Transactable;
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+        #[automerge_orm(counter)]
+        #[autosurgeon(with = "automerge_orm::counter")]
+        likes: u32,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle.clone()));
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+
+    let book = Book {
+        id: Uuid::new_v4(),
+        likes: 5,
+    };
+    entity_manager.transact(|tx| {
+        tx.insert(&book)?;
+        automerge_orm::Result::Ok(())
+    })?;
+
+    let mut fork = doc_handle.with_doc(|doc| doc.fork());
+    let mut fork_tx = fork.transaction();
+    let entity_id = automerge_orm::get_entity(&fork_tx, book.id())?.unwrap();
+    fork_tx.increment(&entity_id, "likes", 1)?;
+    fork_tx.commit();
+
+    entity_manager.transact(|tx| {
+        tx.increment(book.id(), "likes", 2)?;
+        automerge_orm::Result::Ok(())
+    })?;
+    doc_handle.with_doc_mut(|doc| doc.merge(&mut fork))?;
+
+    let book = book_repository.find(book.id())?.unwrap();
+    assert_eq!(book.likes, 8);
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_keeps_concurrent_increment_when_updating_other_field_of_entity() -> Result<()> {
+    use automerge::transaction::Transactable;
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+        title: String,
+        #[automerge_orm(counter)]
+        #[autosurgeon(with = "automerge_orm::counter")]
+        likes: i64,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle.clone()));
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+
+    let book = Book {
+        id: Uuid::new_v4(),
+        title: "Spirited Away".to_owned(),
+        likes: 5,
+    };
+    entity_manager.transact(|tx| {
+        tx.insert(&book)?;
+        automerge_orm::Result::Ok(())
+    })?;
+
+    let mut fork = doc_handle.with_doc(|doc| doc.fork());
+    let mut fork_tx = fork.transaction();
+    let entity_id = automerge_orm::get_entity(&fork_tx, book.id())?.unwrap();
+    fork_tx.increment(&entity_id, "likes", 1)?;
+    fork_tx.commit();
+
+    entity_manager.transact(|tx| {
+        let mut book = tx.find(book.id())?.unwrap();
+        book.title = "Spirited Away (2001)".to_owned();
+        tx.update(&book)?;
+        automerge_orm::Result::Ok(())
+    })?;
+    doc_handle.with_doc_mut(|doc| doc.merge(&mut fork))?;
+
+    let book = book_repository.find(book.id())?.unwrap();
+    assert_eq!(book.title, "Spirited Away (2001)");
+    assert_eq!(book.likes, 6);
+
+    entity_manager.transact(|tx| {
+        let mut book = tx.find(book.id())?.unwrap();
+        book.likes += 2;
+        tx.update(&book)?;
+        automerge_orm::Result::Ok(())
+    })?;
+    let book = book_repository.find(book.id())?.unwrap();
+    assert_eq!(book.likes, 8);
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_fails_to_increment_counter_field_of_entity_which_does_not_exist() -> Result<()> {
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle));
+
+    let result = entity_manager.transact(|tx| {
+        tx.increment::<Book>(Uuid::new_v4().into(), "likes", 1)?;
+        automerge_orm::Result::Ok(())
+    });
+    assert!(result.unwrap_err().is_not_found());
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_finds_entities_queued_up_in_the_same_transaction() -> Result<()> {
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+    }
+
+    impl Book {
+        pub fn new() -> Self {
+            Self { id: Uuid::new_v4() }
+        }
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle));
+
+    let books_in = vec![Book::new(), Book::new()];
+    entity_manager.transact(|tx| {
+        assert!(tx.find(books_in[0].id())?.is_none());
+        assert!(tx.find_all::<Book>()?.is_empty());
+        for book_in in &books_in {
+            tx.insert(book_in)?;
+        }
+        let book = tx.find(books_in[0].id())?;
+        assert!(book.is_some());
+        assert_eq!(book.unwrap().id(), books_in[0].id());
+        let books = tx.find_all::<Book>()?;
+        assert_eq!(books.len(), 2);
+        automerge_orm::Result::Ok(())
+    })?;
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_preserves_concurrent_edits_to_other_fields_when_updating_field() -> Result<()> {
+    use autosurgeon::reconcile_prop;
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+        title: String,
+        author: String,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    impl Book {
+        pub fn new(title: &str, author: &str) -> Self {
+            Self {
+                id: Uuid::new_v4(),
+                title: title.to_owned(),
+                author: author.to_owned(),
+            }
+        }
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle.clone()));
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+
+    let book = Book::new("Spirited Away", "Miyazaki Hayao");
+    entity_manager.transact(|tx| {
+        tx.insert(&book)?;
+        automerge_orm::Result::Ok(())
+    })?;
+
+    let mut fork = doc_handle.with_doc(|doc| doc.fork());
+    let mut fork_tx = fork.transaction();
+    let entity_id = automerge_orm::get_entity(&fork_tx, book.id())?.unwrap();
+    reconcile_prop(&mut fork_tx, &entity_id, "title", "Your Name")?;
+    fork_tx.commit();
+
+    entity_manager.transact(|tx| {
+        tx.update_field(book.id(), "author", &"Shinkai Makoto")?;
+        automerge_orm::Result::Ok(())
+    })?;
+    doc_handle.with_doc_mut(|doc| doc.merge(&mut fork))?;
+
+    let book = book_repository.find(book.id())?.unwrap();
+    assert_eq!(book.title, "Your Name");
+    assert_eq!(book.author, "Shinkai Makoto");
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_fails_to_update_field_of_entity_which_does_not_exist() -> Result<()> {
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+        author: String,
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle));
+
+    let result = entity_manager.transact(|tx| {
+        tx.update_field::<Book, _>(Uuid::new_v4().into(), "author", &"Miyazaki Hayao")?;
+        automerge_orm::Result::Ok(())
+    });
+    assert!(result.unwrap_err().is_not_found());
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_applies_patch_to_existing_entity() -> Result<()> {
+    use automerge_orm::Patch;
+
+    #[derive(Clone, Debug, Entity, Hydrate, Patch, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+        title: String,
+        #[automerge_orm(rename = "written_by")]
+        #[autosurgeon(rename = "written_by")]
+        author: String,
+        year: u16,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    impl Book {
+        pub fn new(title: &str, author: &str, year: u16) -> Self {
+            Self {
+                id: Uuid::new_v4(),
+                title: title.to_owned(),
+                author: author.to_owned(),
+                year,
+            }
+        }
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle));
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+
+    let book = Book::new("Spirited Away", "Miyazaki Hayao", 2001);
+    entity_manager.transact(|tx| {
+        tx.insert(&book)?;
+        automerge_orm::Result::Ok(())
+    })?;
+
+    let patch = BookPatch {
+        author: Some("Shinkai Makoto".to_owned()),
+        year: Some(2016),
+        ..BookPatch::default()
+    };
+    entity_manager.transact(|tx| {
+        tx.apply_patch(book.id(), &patch)?;
+        automerge_orm::Result::Ok(())
+    })?;
+
+    let book = book_repository.find(book.id())?.unwrap();
+    assert_eq!(book.title, "Spirited Away");
+    assert_eq!(book.author, "Shinkai Makoto");
+    assert_eq!(book.year, 2016);
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_fails_to_apply_patch_to_entity_which_does_not_exist() -> Result<()> {
+    use automerge_orm::Patch;
+
+    #[derive(Clone, Debug, Entity, Hydrate, Patch, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+        author: String,
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle));
+
+    let result = entity_manager.transact(|tx| {
+        tx.apply_patch::<BookPatch>(Uuid::new_v4().into(), &BookPatch::default())?;
+        automerge_orm::Result::Ok(())
+    });
+    assert!(result.unwrap_err().is_not_found());
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_applies_patch_to_field_with_custom_reconcile() -> Result<()> {
+    use automerge::Value;
+    use automerge_orm::Patch;
+
+    #[derive(Clone, Debug, Entity, Hydrate, Patch, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+        #[autosurgeon(with = "automerge_orm::bytes")]
+        thumbnail: Vec<u8>,
+    }
+
+    let entity_manager = EntityManager::ephemeral();
+
+    let book = Book {
+        id: Uuid::new_v4(),
+        thumbnail: vec![1, 2, 3],
+    };
+    entity_manager.transact(|tx| tx.insert(&book))?;
+    let patch = BookPatch {
+        thumbnail: Some(vec![4, 5]),
+    };
+    entity_manager.transact(|tx| tx.apply_patch(book.id(), &patch))?;
+
+    entity_manager.transact(|tx| {
+        let (value, _) = tx.get_raw(book.id(), "thumbnail")?.unwrap();
+        assert!(matches!(value, Value::Scalar(s) if *s == ScalarValue::Bytes(vec![4, 5])));
+        automerge_orm::Result::Ok(())
+    })?;
+    let found = entity_manager.find_related(book.id())?.unwrap();
+    assert_eq!(found.thumbnail, vec![4, 5]);
+
+    Ok(())
+}
+
+#[test]
+fn it_fails_to_write_entity_which_is_invalid() -> Result<()> {
+    use std::error::Error as _;
+
+    use automerge_orm::{Error, Validate, ValidationError};
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    #[automerge_orm(validate)]
+    struct Book {
+        #[key]
+        id: Uuid,
+        title: String,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    impl Book {
+        pub fn new(title: &str) -> Self {
+            Self {
+                id: Uuid::new_v4(),
+                title: title.to_owned(),
+            }
+        }
+    }
+
+    impl Validate for Book {
+        fn validate(&self) -> std::result::Result<(), ValidationError> {
+            if self.title.is_empty() {
+                return Err(ValidationError::for_field("title", "must not be empty"));
+            }
+            Ok(())
+        }
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle));
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+
+    let mut book = Book::new("");
+    let result = entity_manager.transact(|tx| {
+        tx.insert(&book)?;
+        automerge_orm::Result::Ok(())
+    });
+    let err = result.unwrap_err();
+    assert!(matches!(
+        err.source().and_then(|e| e.downcast_ref::<Error>()),
+        Some(Error::Validation { source, .. }) if source.field() == Some("title")
+    ));
+    assert_eq!(err.table_name(), Some(Book::table_name()));
+    assert_eq!(err.key(), Some(book.id));
+    assert!(book_repository.find(book.id())?.is_none());
+
+    book.title = "Spirited Away".to_owned();
+    entity_manager.transact(|tx| {
+        tx.insert(&book)?;
+        automerge_orm::Result::Ok(())
+    })?;
+    book.title = String::new();
+    let result = entity_manager.transact(|tx| {
+        tx.update(&book)?;
+        automerge_orm::Result::Ok(())
+    });
+    assert!(result.is_err());
+    let result = entity_manager.transact(|tx| {
+        tx.upsert(&book)?;
+        automerge_orm::Result::Ok(())
+    });
+    assert!(result.is_err());
+    assert_eq!(
+        book_repository.find(book.id())?.unwrap().title,
+        "Spirited Away"
+    );
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_writes_entity_which_implements_neither_validate_nor_hooks() -> Result<()> {
+    #[derive(Clone, Debug, Hydrate, Reconcile)]
+    struct Book {
+        id: Uuid,
+        title: String,
+    }
+
+    impl Mapped for Book {
+        fn table_name() -> &'static str {
+            "books"
+        }
+    }
+
+    impl Keyed for Book {
+        type Entity = Self;
+
+        fn id(&self) -> Key<Self> {
+            Key::from(self.id)
+        }
+    }
+
+    let entity_manager = EntityManager::ephemeral();
+
+    let mut book = Book {
+        id: Uuid::new_v4(),
+        title: String::new(),
+    };
+    entity_manager.transact(|tx| {
+        tx.insert(&book)?;
+        automerge_orm::Result::Ok(())
+    })?;
+    book.title = "Spirited Away".to_owned();
+    entity_manager.transact(|tx| {
+        tx.update(&book)?;
+        automerge_orm::Result::Ok(())
+    })?;
+
+    let found = entity_manager.read(|reader| reader.find(book.id()))?;
+    assert_eq!(found.unwrap().title, "Spirited Away");
+
+    Ok(())
+}
+
+#[test]
+fn it_invokes_hooks_around_writes() -> Result<()> {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use automerge_orm::{Error, Hooks};
+
+    static UPDATES: AtomicUsize = AtomicUsize::new(0);
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    #[automerge_orm(hooks)]
+    struct Book {
+        #[key]
+        id: Uuid,
+        title: String,
+        slug: String,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    impl Book {
+        pub fn new(title: &str) -> Self {
+            Self {
+                id: Uuid::new_v4(),
+                title: title.to_owned(),
+                slug: String::new(),
+            }
+        }
+    }
+
+    impl Hooks for Book {
+        fn before_insert(&self) -> automerge_orm::Result<Option<Self>> {
+            let mut book = self.clone();
+            book.slug = self.title.to_lowercase().replace(' ', "-");
+            Ok(Some(book))
+        }
+
+        fn after_update_write(&self) -> automerge_orm::Result<()> {
+            if self.title.is_empty() {
+                return Err(Error::custom(std::fmt::Error));
+            }
+            UPDATES.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle));
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+
+    let mut book = Book::new("Spirited Away");
+    entity_manager.transact(|tx| {
+        tx.insert(&book)?;
+        automerge_orm::Result::Ok(())
+    })?;
+    assert_eq!(
+        book_repository.find(book.id())?.unwrap().slug,
+        "spirited-away"
+    );
+
+    book.title = "Howl's Moving Castle".to_owned();
+    entity_manager.transact(|tx| {
+        tx.upsert(&book)?;
+        automerge_orm::Result::Ok(())
+    })?;
+    assert_eq!(UPDATES.load(Ordering::SeqCst), 1);
+
+    book.title = String::new();
+    let result = entity_manager.transact(|tx| {
+        tx.update(&book)?;
+        automerge_orm::Result::Ok(())
+    });
+    assert!(matches!(result, Err(Error::TransactionAborted(_))));
+    assert_eq!(
+        book_repository.find(book.id())?.unwrap().title,
+        "Howl's Moving Castle"
+    );
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_removes_many_entities_by_id() -> Result<()> {
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    impl Book {
+        pub fn new() -> Self {
+            Self { id: Uuid::new_v4() }
+        }
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle));
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+
+    let removed =
+        entity_manager.transact(|tx| tx.remove_many([Key::<Book>::from(Uuid::new_v4())]))?;
+    assert_eq!(removed, 0);
+
+    let books = vec![Book::new(), Book::new(), Book::new()];
+    entity_manager.transact(|tx| {
+        for book in &books {
+            tx.insert(book)?;
+        }
+        automerge_orm::Result::Ok(())
+    })?;
+    let removed = entity_manager.transact(|tx| {
+        tx.remove_many(
+            books[..2]
+                .iter()
+                .map(|book| book.id())
+                .chain([Uuid::new_v4().into()]),
+        )
+    })?;
+    assert_eq!(removed, 2);
+    let books_out = book_repository.find_all()?;
+    assert_eq!(books_out.len(), 1);
+    assert!(books_out.contains_key(&books[2].id().to_string()));
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_modifies_existing_entity_or_inserts_new_entity() -> Result<()> {
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+        copies: u64,
+    }
+
+    type BookRepository = DefaultEntityRepository<Book>;
+
+    impl Book {
+        pub fn new(id: Uuid) -> Self {
+            Self { id, copies: 1 }
+        }
+    }
+
+    let repo_handle = Repo::new(None, Box::new(NoopStorage)).run();
+    let doc_handle = repo_handle.new_document();
+    let entity_manager = Arc::new(EntityManager::new(doc_handle));
+    let book_repository = BookRepository::new(Arc::clone(&entity_manager));
+
+    let book_id = Uuid::new_v4();
+    for copies in 1..=3 {
+        let book = entity_manager.transact(|tx| {
+            tx.modify_or_insert(
+                book_id.into(),
+                |book: &mut Book| book.copies += 1,
+                || Book::new(book_id),
+            )
+        })?;
+        assert_eq!(book.copies, copies);
+        assert_eq!(
+            book_repository.find(book_id.into())?.unwrap().copies,
+            copies
+        );
+    }
+
+    let result = entity_manager.transact(|tx| {
+        tx.modify_or_insert(
+            Uuid::new_v4().into(),
+            |book: &mut Book| book.copies += 1,
+            || Book::new(book_id),
+        )
+    });
+    assert!(result.is_err());
+
+    repo_handle.stop().unwrap();
+
+    Ok(())
+}
+
+#[test]
+fn it_adopts_externally_created_tables() -> Result<()> {
+    use automerge::{transaction::Transactable, Automerge, ObjType};
+    use automerge_orm::Error;
+
+    #[derive(Clone, Debug, Entity, Hydrate, PartialEq, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+        title: String,
+    }
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Author {
+        #[key]
+        id: Uuid,
+    }
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Magazine {
+        #[key]
+        id: Uuid,
+    }
+
+    let books = [
+        Book {
+            id: Uuid::new_v4(),
+            title: "Spirited Away".to_owned(),
+        },
+        Book {
+            id: Uuid::new_v4(),
+            title: "Your Name".to_owned(),
+        },
+    ];
+    let mut doc = Automerge::new();
+    let mut tx = doc.transaction();
+    let table_id = tx.put_object(automerge::ROOT, "book", ObjType::List)?;
+    for (index, book) in books.iter().enumerate() {
+        let book_id = tx.insert_object(&table_id, index, ObjType::Map)?;
+        tx.put(
+            &book_id,
+            "id",
+            ScalarValue::Bytes(book.id.as_bytes().to_vec()),
+        )?;
+        tx.put(&book_id, "title", book.title.as_str())?;
+    }
+    let table_id = tx.put_object(automerge::ROOT, "author", ObjType::Map)?;
+    let author_id = Uuid::new_v4();
+    let author = tx.put_object(&table_id, author_id.to_string(), ObjType::Map)?;
+    tx.put(
+        &author,
+        "id",
+        ScalarValue::Bytes(author_id.as_bytes().to_vec()),
+    )?;
+    let table_id = tx.put_object(automerge::ROOT, "magazine", ObjType::Map)?;
+    tx.put(&table_id, "not-a-key", "Monthly")?;
+    tx.commit();
+    let entity_manager = EntityManager::from_doc(doc);
+
+    assert!(entity_manager
+        .read(|reader| reader.find_all::<Book>())
+        .is_err());
+    assert_eq!(entity_manager.transact(|tx| tx.adopt_table::<Book>())?, 2);
+    let found = entity_manager.read(|reader| reader.find_all::<Book>())?;
+    let mut expected = books.to_vec();
+    expected.sort_by_key(|book| book.id);
+    assert_eq!(found.into_values().collect::<Vec<_>>(), expected);
+
+    assert_eq!(entity_manager.transact(|tx| tx.adopt_table::<Author>())?, 1);
+    let err = entity_manager
+        .transact(|tx| tx.adopt_table::<Magazine>())
+        .unwrap_err();
+    let Error::TransactionAborted(err) = err else {
+        panic!("expected transaction to be aborted");
+    };
+    assert!(matches!(
+        err.downcast_ref::<Error>(),
+        Some(Error::InvalidKey { key, .. }) if key == "not-a-key"
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn it_enforces_unique_fields() -> Result<()> {
+    use automerge_orm::Error;
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct User {
+        #[key]
+        id: Uuid,
+        #[automerge_orm(unique)]
+        email: String,
+        name: String,
+    }
+
+    impl User {
+        pub fn new(email: &str, name: &str) -> Self {
+            Self {
+                id: Uuid::new_v4(),
+                email: email.to_owned(),
+                name: name.to_owned(),
+            }
+        }
+    }
+
+    assert_eq!(User::unique_fields(), ["email"]);
+
+    let entity_manager = EntityManager::ephemeral();
+
+    let mut alice = User::new("alice@example.com", "Alice");
+    let bob = User::new("bob@example.com", "Bob");
+    entity_manager.transact(|tx| {
+        tx.insert(&alice)?;
+        tx.insert(&bob)?;
+        automerge_orm::Result::Ok(())
+    })?;
+
+    let err = entity_manager
+        .transact(|tx| tx.insert(&User::new("alice@example.com", "Mallory")))
+        .unwrap_err();
+    let Error::TransactionAborted(err) = err else {
+        panic!("expected transaction to be aborted");
+    };
+    assert!(matches!(
+        err.downcast_ref::<Error>(),
+        Some(Error::UniqueViolation { table_name, field, value })
+            if table_name == "user"
+                && field == "email"
+                && value.to_str() == Some("alice@example.com")
+    ));
+
+    // An object does not violate uniqueness with itself.
+    alice.name = "Alice Liddell".to_owned();
+    entity_manager.transact(|tx| tx.update(&alice))?;
+    alice.email = "bob@example.com".to_owned();
+    let err = entity_manager.transact(|tx| tx.update(&alice)).unwrap_err();
+    assert_eq!(err.table_name(), Some("user"));
+
+    Ok(())
+}
+
+#[test]
+fn it_rejects_update_of_key_field() -> Result<()> {
+    use automerge_orm::Error;
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+        author: String,
+    }
+
+    let entity_manager = EntityManager::ephemeral();
+
+    let book = Book {
+        id: Uuid::new_v4(),
+        author: "Miyazaki Hayao".to_owned(),
+    };
+    entity_manager.transact(|tx| tx.insert(&book))?;
+
+    let result = entity_manager.transact(|tx| tx.update_field(book.id(), "id", &Uuid::new_v4()));
+    assert!(matches!(
+        result,
+        Err(Error::KeyFieldUpdate { table_name, field }) if table_name == "book" && field == "id"
+    ));
+    let found = entity_manager.find_related(book.id())?.unwrap();
+    assert_eq!(found.id, book.id);
+
+    Ok(())
+}
+
+#[test]
+fn it_enforces_unique_fields_when_updating_single_field() -> Result<()> {
+    use automerge_orm::Patch;
+
+    #[derive(Clone, Debug, Entity, Hydrate, Patch, Reconcile)]
+    struct User {
+        #[key]
+        id: Uuid,
+        #[automerge_orm(unique)]
+        email: String,
+    }
+
+    impl User {
+        pub fn new(email: &str) -> Self {
+            Self {
+                id: Uuid::new_v4(),
+                email: email.to_owned(),
+            }
+        }
+    }
+
+    let entity_manager = EntityManager::ephemeral();
+
+    let alice = User::new("alice@example.com");
+    let bob = User::new("bob@example.com");
+    entity_manager.transact(|tx| {
+        tx.insert(&alice)?;
+        tx.insert(&bob)?;
+        automerge_orm::Result::Ok(())
+    })?;
+
+    entity_manager.transact(|tx| tx.update_field(alice.id(), "email", &"alice@example.com"))?;
+    let err = entity_manager
+        .transact(|tx| tx.update_field(alice.id(), "email", &"bob@example.com"))
+        .unwrap_err();
+    assert_eq!(err.table_name(), Some("user"));
+
+    let patch = UserPatch {
+        email: Some("bob@example.com".to_owned()),
+    };
+    let err = entity_manager
+        .transact(|tx| tx.apply_patch(alice.id(), &patch))
+        .unwrap_err();
+    assert_eq!(err.table_name(), Some("user"));
+
+    Ok(())
+}
+
+#[test]
+fn it_returns_base_heads_of_transaction() -> Result<()> {
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+    }
+
+    let entity_manager = EntityManager::ephemeral();
+
+    let base_heads = entity_manager.transact(|tx| {
+        tx.insert(&Book { id: Uuid::new_v4() })?;
+        automerge_orm::Result::Ok(tx.base_heads())
+    })?;
+    assert!(base_heads.is_empty());
+
+    let fork = entity_manager.fork()?;
+    fork.transact(|tx| tx.insert(&Book { id: Uuid::new_v4() }))?;
+    entity_manager.transact(|tx| tx.insert(&Book { id: Uuid::new_v4() }))?;
+    entity_manager.merge_bytes(&fork.save())?;
+    let mut heads = entity_manager.heads();
+    assert_eq!(heads.len(), 2);
+
+    let mut base_heads = entity_manager.transact(|tx| {
+        tx.insert(&Book { id: Uuid::new_v4() })?;
+        automerge_orm::Result::Ok(tx.base_heads())
+    })?;
+    base_heads.sort();
+    heads.sort();
+    assert_eq!(base_heads, heads);
+
+    Ok(())
+}
+
+#[test]
+fn it_keeps_concurrent_pushes_to_list_field() -> Result<()> {
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+        tags: Vec<String>,
+    }
+
+    let entity_manager = EntityManager::ephemeral();
+
+    let book = Book {
+        id: Uuid::new_v4(),
+        tags: vec!["classic".to_owned()],
+    };
+    entity_manager.transact(|tx| tx.insert(&book))?;
+
+    let fork = entity_manager.fork()?;
+    fork.transact(|tx| tx.list_push(book.id(), "tags", &"fantasy"))?;
+    entity_manager.transact(|tx| tx.list_push(book.id(), "tags", &"anime"))?;
+    entity_manager.merge_bytes(&fork.save())?;
+    let mut tags = entity_manager.find_related(book.id())?.unwrap().tags;
+    tags.sort();
+    assert_eq!(tags, ["anime", "classic", "fantasy"]);
+
+    entity_manager.transact(|tx| tx.list_remove(book.id(), "tags", 0))?;
+    let found = entity_manager.find_related(book.id())?.unwrap();
+    assert_eq!(found.tags.len(), 2);
+    assert!(!found.tags.contains(&"classic".to_owned()));
+
+    let result = entity_manager.transact(|tx| tx.list_remove(book.id(), "tags", 2));
+    assert!(result.is_err());
+
+    Ok(())
+}
+
+#[test]
+fn it_keeps_concurrent_entries_of_map_field() -> Result<()> {
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+        titles: BTreeMap<String, String>,
+    }
+
+    let entity_manager = EntityManager::ephemeral();
+
+    let book = Book {
+        id: Uuid::new_v4(),
+        titles: BTreeMap::from([("ja".to_owned(), "Sen to Chihiro".to_owned())]),
+    };
+    entity_manager.transact(|tx| tx.insert(&book))?;
+
+    let fork = entity_manager.fork()?;
+    fork.transact(|tx| tx.map_set(book.id(), "titles", "en", &"Spirited Away"))?;
+    entity_manager.transact(|tx| tx.map_set(book.id(), "titles", "de", &"Chihiros Reise"))?;
+    entity_manager.merge_bytes(&fork.save())?;
+    let found = entity_manager.find_related(book.id())?.unwrap();
+    assert_eq!(found.titles.len(), 3);
+    assert_eq!(found.titles["en"], "Spirited Away");
+    assert_eq!(found.titles["de"], "Chihiros Reise");
+
+    entity_manager.transact(|tx| tx.map_remove(book.id(), "titles", "ja"))?;
+    let found = entity_manager.find_related(book.id())?.unwrap();
+    assert!(!found.titles.contains_key("ja"));
+
+    let result = entity_manager
+        .transact(|tx| tx.map_set(Key::<Book>::random(), "titles", "en", &"Spirited Away"));
+    assert!(result.unwrap_err().is_not_found());
+
+    Ok(())
+}
+
+#[test]
+fn it_reports_reconcile_error_with_table_and_key_of_entity() -> Result<()> {
+    use automerge_orm::Error;
+
+    mod unreconcilable {
+        use automerge::{AutomergeError, ObjId};
+        use autosurgeon::{Hydrate, HydrateError, Prop, ReadDoc, Reconciler};
+
+        pub fn hydrate<D: ReadDoc>(
+            doc: &D,
+            obj: &ObjId,
+            prop: Prop<'_>,
+        ) -> Result<String, HydrateError> {
+            String::hydrate(doc, obj, prop)
+        }
+
+        pub fn reconcile<R: Reconciler>(_value: &str, _reconciler: R) -> Result<(), R::Error> {
+            Err(AutomergeError::InvalidIndex(0).into())
+        }
+    }
+
+    #[derive(Clone, Debug, Entity, Hydrate, Reconcile)]
+    struct Book {
+        #[key]
+        id: Uuid,
+        #[autosurgeon(with = "unreconcilable")]
+        title: String,
+    }
+
+    let entity_manager = EntityManager::ephemeral();
+
+    let book = Book {
+        id: Uuid::new_v4(),
+        title: "Spirited Away".to_owned(),
+    };
+    let err = entity_manager.transact(|tx| tx.insert(&book)).unwrap_err();
+    assert!(matches!(err, Error::Reconcile { .. }));
+    assert_eq!(err.table_name(), Some("book"));
+    assert_eq!(err.key(), Some(book.id));
+    assert!(entity_manager.find_related(book.id())?.is_none());
+
+    Ok(())
+}
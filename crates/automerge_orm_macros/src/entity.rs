@@ -1,12 +1,24 @@
 use heck::ToSnakeCase;
 use proc_macro2::TokenStream;
-use quote::{quote, ToTokens};
-use syn::{parse_quote, DeriveInput, Error, Expr, Lit, Meta, NestedMeta};
+use quote::{quote, quote_spanned, ToTokens};
+use syn::{
+    parse_quote, spanned::Spanned, Data, DeriveInput, Error, Expr, Field, Fields, Lit, Meta,
+    NestedMeta, Path,
+};
+
+/// The prefix of keys in the root of the Automerge document which are reserved
+/// for metadata of the Automerge ORM.
+const RESERVED_PREFIX: &str = "__automerge_orm";
 
 pub fn derive(input: DeriveInput) -> syn::Result<TokenStream> {
     let entity = input.ident;
-    let mut table_name = entity.to_string().to_snake_case();
-    let mut id_expr: Expr = parse_quote!(self.id);
+    let mut table_name = None;
+    let mut pluralize = false;
+    let mut hooks = false;
+    let mut validate = false;
+    let mut type_tag = None;
+    let mut key_prop: Option<Path> = None;
+    let mut id_expr = None;
     for attr in input.attrs {
         if attr.path.is_ident("automerge_orm") {
             let meta = attr.parse_meta()?;
@@ -19,13 +31,46 @@ pub fn derive(input: DeriveInput) -> syn::Result<TokenStream> {
                         let Lit::Str(s) = &m.lit else {
                             return Err(Error::new_spanned(&m.lit, "expected string literal"));
                         };
-                        id_expr = syn::parse_str(&s.value())?;
+                        id_expr = Some(syn::parse_str::<Expr>(&s.value())?);
                     },
                     NestedMeta::Meta(Meta::NameValue(m)) if m.path.is_ident("table_name") => {
                         let Lit::Str(s) = &m.lit else {
                             return Err(Error::new_spanned(&m.lit, "expected string literal"));
                         };
-                        table_name = s.value();
+                        if s.value().starts_with(RESERVED_PREFIX) {
+                            return Err(Error::new_spanned(
+                                s,
+                                format!(
+                                    "table names starting with `{RESERVED_PREFIX}` are reserved \
+                                    for metadata of automerge_orm"
+                                ),
+                            ));
+                        }
+                        table_name = Some(s.value());
+                    },
+                    NestedMeta::Meta(Meta::NameValue(m)) if m.path.is_ident("type_tag") => {
+                        let Lit::Str(s) = &m.lit else {
+                            return Err(Error::new_spanned(&m.lit, "expected string literal"));
+                        };
+                        type_tag = Some(s.value());
+                    },
+                    NestedMeta::Meta(Meta::NameValue(m)) if m.path.is_ident("key_prop") => {
+                        let Lit::Str(s) = &m.lit else {
+                            return Err(Error::new_spanned(&m.lit, "expected string literal"));
+                        };
+                        key_prop = Some(s.parse()?);
+                    },
+                    NestedMeta::Meta(Meta::Path(p)) if p.is_ident("type_tag") => {
+                        type_tag = Some(entity.to_string());
+                    },
+                    NestedMeta::Meta(Meta::Path(p)) if p.is_ident("pluralize") => {
+                        pluralize = true;
+                    },
+                    NestedMeta::Meta(Meta::Path(p)) if p.is_ident("hooks") => {
+                        hooks = true;
+                    },
+                    NestedMeta::Meta(Meta::Path(p)) if p.is_ident("validate") => {
+                        validate = true;
                     },
                     NestedMeta::Meta(meta_item) => {
                         let path = meta_item
@@ -49,14 +94,196 @@ pub fn derive(input: DeriveInput) -> syn::Result<TokenStream> {
         }
     }
 
+    let mut renames = Vec::new();
+    let mut unique_fields = Vec::new();
+    let mut counter_fields = Vec::new();
+    let mut encrypted_fields = Vec::new();
+    let mut key_assertion = None;
+    let mut key_field = None;
+    if let Data::Struct(data) = &input.data {
+        for field in &data.fields {
+            let is_key = field.attrs.iter().any(|attr| attr.path.is_ident("key"));
+            if is_key {
+                key_field = field.ident.as_ref().map(ToString::to_string);
+            }
+            // Without an `id` expression, the key field is converted into a
+            // key as is, which fails deep in the generated code for types
+            // other than `Uuid` and `Key<Self>`, so the conversion is asserted
+            // with the span of the type of the field instead.
+            if id_expr.is_none() && is_key {
+                let ty = &field.ty;
+                key_assertion = Some(quote_spanned! {ty.span()=>
+                    const _: fn() = || {
+                        fn assert_into_key<K>()
+                        where
+                            K: ::automerge_orm::__macro_support::IntoKey<#entity>,
+                        {
+                        }
+                        assert_into_key::<#ty>();
+                    };
+                });
+            }
+            let attrs = parse_field_attrs(field)?;
+            // The ORM relies on the attribute to handle such fields, while
+            // autosurgeon relies on the adapter to store them, so neither is
+            // accepted without the other.
+            check_adapter(field, "counter", attrs.counter, "automerge_orm::counter")?;
+            check_adapter(field, "encrypt", attrs.encrypt, "automerge_orm::encrypted")?;
+            if attrs.rename.is_none() && !attrs.unique && !attrs.counter && !attrs.encrypt {
+                continue;
+            }
+            let Fields::Named(_) = &data.fields else {
+                return Err(Error::new_spanned(field, "expected named field"));
+            };
+            let ident = field.ident.as_ref().unwrap().to_string();
+            if let Some(rename) = attrs.rename {
+                renames.push(quote!(#ident => #rename));
+            }
+            if attrs.unique {
+                unique_fields.push(ident.clone());
+            }
+            if attrs.counter {
+                counter_fields.push(ident.clone());
+            }
+            if attrs.encrypt {
+                encrypted_fields.push(ident);
+            }
+        }
+    }
+
+    let id_expr = id_expr.unwrap_or_else(|| parse_quote!(self.id));
+    let table_name = table_name.unwrap_or_else(|| {
+        let table_name = entity.to_string().to_snake_case();
+        if pluralize {
+            pluralize_snake_case(&table_name)
+        } else {
+            table_name
+        }
+    });
+    let hooks_impl = if hooks {
+        None
+    } else {
+        Some(quote! {
+            #[automatically_derived]
+            impl ::automerge_orm::Hooks for #entity {}
+        })
+    };
+    let prop_name_fn = if renames.is_empty() {
+        None
+    } else {
+        Some(quote! {
+            fn prop_name(field: &str) -> &str {
+                match field {
+                    #(#renames,)*
+                    _ => field,
+                }
+            }
+        })
+    };
+    let type_tag_fn = type_tag.map(|type_tag| {
+        quote! {
+            fn type_tag() -> ::automerge_orm::__macro_support::Option<&'static str> {
+                ::automerge_orm::__macro_support::Option::Some(#type_tag)
+            }
+        }
+    });
+    let unique_fields_fn = if unique_fields.is_empty() {
+        None
+    } else {
+        Some(quote! {
+            fn unique_fields() -> &'static [&'static str] {
+                &[#(#unique_fields),*]
+            }
+        })
+    };
+    let counter_fields_fn = if counter_fields.is_empty() {
+        None
+    } else {
+        Some(quote! {
+            fn counter_fields() -> &'static [&'static str] {
+                &[#(#counter_fields),*]
+            }
+        })
+    };
+    let encrypted_fields_fn = if encrypted_fields.is_empty() {
+        None
+    } else {
+        Some(quote! {
+            fn encrypted_fields() -> &'static [&'static str] {
+                &[#(#encrypted_fields),*]
+            }
+        })
+    };
+    let key_field_fn = key_field.map(|key_field| {
+        quote! {
+            fn key_field() -> ::automerge_orm::__macro_support::Option<&'static str> {
+                ::automerge_orm::__macro_support::Option::Some(#key_field)
+            }
+        }
+    });
+    let key_prop_fns = key_prop.map(|key_prop| {
+        quote! {
+            fn key_prop(
+                id: ::automerge_orm::__macro_support::Uuid,
+            ) -> ::automerge_orm::__macro_support::String {
+                #key_prop::to_prop(id)
+            }
+
+            fn key_from_prop(
+                prop: &str,
+            ) -> ::automerge_orm::Result<::automerge_orm::__macro_support::Uuid> {
+                #key_prop::from_prop(prop)
+            }
+        }
+    });
+    let validate_impl = if validate {
+        None
+    } else {
+        Some(quote! {
+            #[automatically_derived]
+            impl ::automerge_orm::Validate for #entity {}
+        })
+    };
+
     Ok(quote! {
         #[automatically_derived]
         impl ::automerge_orm::Entity for #entity {}
 
+        #[automatically_derived]
+        impl #entity {
+            /// Returns the name of the table which the entity is mapped to.
+            ///
+            /// This is the same as
+            /// [`Mapped::table_name`](::automerge_orm::Mapped::table_name), but
+            /// does not require the trait to be in scope, and can be used in
+            /// constant expressions.
+            pub const fn table_name() -> &'static str {
+                #table_name
+            }
+        }
+
         #[automatically_derived]
         impl ::automerge_orm::Mapped for #entity {
-            fn table_name() -> ::automerge_orm::__macro_support::String {
-                ::automerge_orm::__macro_support::ToOwned::to_owned(#table_name)
+            fn table_name() -> &'static str {
+                #table_name
+            }
+
+            #prop_name_fn
+
+            #type_tag_fn
+
+            #unique_fields_fn
+
+            #counter_fields_fn
+
+            #encrypted_fields_fn
+
+            #key_field_fn
+
+            #key_prop_fns
+
+            fn lifecycle() -> ::automerge_orm::Lifecycle<Self> {
+                ::automerge_orm::Lifecycle::new().validated().hooked()
             }
         }
 
@@ -65,8 +292,146 @@ pub fn derive(input: DeriveInput) -> syn::Result<TokenStream> {
             type Entity = #entity;
 
             fn id(&self) -> ::automerge_orm::Key<Self::Entity> {
-                ::automerge_orm::__macro_support::Into::into(#id_expr)
+                ::automerge_orm::__macro_support::IntoKey::into_key(#id_expr)
             }
         }
+
+        #key_assertion
+
+        #hooks_impl
+
+        #validate_impl
     })
 }
+
+/// The `#[automerge_orm(...)]` attributes on a field.
+#[derive(Default)]
+struct FieldAttrs {
+    /// The name of the property which the field is mapped to, if renamed.
+    rename: Option<String>,
+    /// Whether the values of the field must be unique within the table.
+    unique: bool,
+    /// Whether the field is stored as an Automerge counter.
+    counter: bool,
+    /// Whether the field is encrypted with the encryption key of the entity
+    /// manager.
+    encrypt: bool,
+}
+
+/// Parses the `#[automerge_orm(...)]` attributes on a field.
+fn parse_field_attrs(field: &Field) -> syn::Result<FieldAttrs> {
+    let mut attrs = FieldAttrs::default();
+    for attr in &field.attrs {
+        if attr.path.is_ident("automerge_orm") {
+            let meta = attr.parse_meta()?;
+            let Meta::List(meta) = meta else {
+                return Err(Error::new_spanned(meta, "expected #[automerge_orm(...)]"));
+            };
+            for meta_item in meta.nested {
+                match &meta_item {
+                    NestedMeta::Meta(Meta::NameValue(m)) if m.path.is_ident("rename") => {
+                        let Lit::Str(s) = &m.lit else {
+                            return Err(Error::new_spanned(&m.lit, "expected string literal"));
+                        };
+                        attrs.rename = Some(s.value());
+                    },
+                    NestedMeta::Meta(Meta::Path(p)) if p.is_ident("unique") => {
+                        attrs.unique = true;
+                    },
+                    NestedMeta::Meta(Meta::Path(p)) if p.is_ident("counter") => {
+                        attrs.counter = true;
+                    },
+                    NestedMeta::Meta(Meta::Path(p)) if p.is_ident("encrypt") => {
+                        attrs.encrypt = true;
+                    },
+                    NestedMeta::Meta(meta_item) => {
+                        let path = meta_item
+                            .path()
+                            .into_token_stream()
+                            .to_string()
+                            .replace(' ', "");
+                        return Err(Error::new_spanned(
+                            meta_item.path(),
+                            format!("unknown automerge_orm field attribute `{path}`"),
+                        ));
+                    },
+                    NestedMeta::Lit(lit) => {
+                        return Err(Error::new_spanned(
+                            lit,
+                            "unexpected literal in automerge_orm field attribute",
+                        ));
+                    },
+                }
+            }
+        }
+    }
+
+    Ok(attrs)
+}
+
+/// Returns an error unless a field which is annotated with
+/// `#[automerge_orm(<attr>)]`, as given by `annotated`, is annotated with
+/// `#[autosurgeon(with = "<adapter>")]` as well, and vice versa.
+fn check_adapter(field: &Field, attr: &str, annotated: bool, adapter: &str) -> syn::Result<()> {
+    let msg = match (annotated, has_adapter(field, adapter)?) {
+        (true, false) => {
+            format!("field must be annotated with #[autosurgeon(with = \"{adapter}\")]")
+        },
+        (false, true) => format!("field must be annotated with #[automerge_orm({attr})]"),
+        _ => return Ok(()),
+    };
+
+    Err(Error::new_spanned(field, msg))
+}
+
+/// Returns whether a field is annotated with
+/// `#[autosurgeon(with = "<adapter>")]`.
+fn has_adapter(field: &Field, adapter: &str) -> syn::Result<bool> {
+    for attr in &field.attrs {
+        if !attr.path.is_ident("autosurgeon") {
+            continue;
+        }
+        let Meta::List(meta) = attr.parse_meta()? else {
+            continue;
+        };
+        for meta_item in meta.nested {
+            let NestedMeta::Meta(Meta::NameValue(m)) = meta_item else {
+                continue;
+            };
+            let Lit::Str(s) = &m.lit else {
+                continue;
+            };
+            let path = s.value().replace(' ', "");
+            if m.path.is_ident("with") && path.trim_start_matches("::") == adapter {
+                return Ok(true);
+            }
+        }
+    }
+
+    Ok(false)
+}
+
+/// Pluralizes the last word of a snake-cased name using simple English rules,
+/// e.g. `book` becomes `books`, `library_branch` becomes `library_branches`,
+/// and `category` becomes `categories`.
+fn pluralize_snake_case(name: &str) -> String {
+    let (prefix, word) = match name.rsplit_once('_') {
+        Some((prefix, word)) => (&name[..=prefix.len()], word),
+        None => ("", name),
+    };
+    let plural = if let Some(stem) = word
+        .strip_suffix('y')
+        .filter(|stem| stem.ends_with(|c: char| !"aeiou".contains(c)))
+    {
+        format!("{stem}ies")
+    } else if ["s", "x", "z", "ch", "sh"]
+        .iter()
+        .any(|suffix| word.ends_with(suffix))
+    {
+        format!("{word}es")
+    } else {
+        format!("{word}s")
+    };
+
+    format!("{prefix}{plural}")
+}
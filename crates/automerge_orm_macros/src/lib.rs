@@ -2,6 +2,7 @@ use proc_macro::TokenStream;
 use syn::{parse_macro_input, DeriveInput};
 
 mod entity;
+mod patch;
 
 #[proc_macro_derive(Entity, attributes(automerge_orm))]
 pub fn derive_entity(input: TokenStream) -> TokenStream {
@@ -11,3 +12,12 @@ pub fn derive_entity(input: TokenStream) -> TokenStream {
         .unwrap_or_else(syn::Error::into_compile_error)
         .into()
 }
+
+#[proc_macro_derive(Patch, attributes(automerge_orm))]
+pub fn derive_patch(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    patch::derive(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
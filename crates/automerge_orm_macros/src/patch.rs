@@ -0,0 +1,119 @@
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{Data, DeriveInput, Error, Field, Fields, Lit, Meta, NestedMeta, Path};
+
+pub fn derive(input: DeriveInput) -> syn::Result<TokenStream> {
+    let entity = input.ident;
+    let vis = input.vis;
+    let Data::Struct(data) = &input.data else {
+        return Err(Error::new_spanned(&entity, "expected struct"));
+    };
+    let Fields::Named(fields) = &data.fields else {
+        return Err(Error::new_spanned(&data.fields, "expected named fields"));
+    };
+
+    let mut patch_fields = Vec::new();
+    let mut apply_fields = Vec::new();
+    for field in &fields.named {
+        // The key of an entity cannot be changed by a patch.
+        if field.attrs.iter().any(|attr| attr.path.is_ident("key")) {
+            continue;
+        }
+        let field_vis = &field.vis;
+        let ident = field.ident.as_ref().unwrap();
+        let ty = &field.ty;
+        let name = ident.to_string();
+        patch_fields.push(quote! {
+            #field_vis #ident: ::automerge_orm::__macro_support::Option<#ty>
+        });
+        // A field which is reconciled by a custom function is wrapped, so that
+        // `update_field` reconciles it in the same way as the entity does.
+        let apply_field = match reconcile_fn(field)? {
+            Some(reconcile_fn) => quote! {
+                struct Reconciled<'a>(&'a #ty);
+
+                impl ::automerge_orm::__macro_support::Reconcile for Reconciled<'_> {
+                    type Key<'k> = ::automerge_orm::__macro_support::NoKey;
+
+                    fn reconcile<R>(
+                        &self,
+                        reconciler: R,
+                    ) -> ::automerge_orm::__macro_support::Result<(), R::Error>
+                    where
+                        R: ::automerge_orm::__macro_support::Reconciler,
+                    {
+                        #reconcile_fn(self.0, reconciler)
+                    }
+                }
+
+                tx.update_field(id, #name, &Reconciled(value))?;
+            },
+            None => quote! {
+                tx.update_field(id, #name, value)?;
+            },
+        };
+        apply_fields.push(quote! {
+            if let ::automerge_orm::__macro_support::Option::Some(value) = &self.#ident {
+                #apply_field
+            }
+        });
+    }
+
+    let patch = format_ident!("{entity}Patch");
+    let doc = format!("A patch which updates some of the fields of [`{entity}`].");
+
+    Ok(quote! {
+        #[doc = #doc]
+        #[derive(Default)]
+        #vis struct #patch {
+            #(#patch_fields,)*
+        }
+
+        #[automatically_derived]
+        impl ::automerge_orm::Patch for #patch {
+            type Entity = #entity;
+
+            fn apply(
+                &self,
+                tx: &mut ::automerge_orm::Transaction<'_>,
+                id: ::automerge_orm::Key<Self::Entity>,
+            ) -> ::automerge_orm::Result<()> {
+                #(#apply_fields)*
+
+                ::automerge_orm::Result::Ok(())
+            }
+        }
+    })
+}
+
+/// Returns the function which reconciles a field, as configured by
+/// `#[autosurgeon(with = "...")]` or `#[autosurgeon(reconcile = "...")]`, if
+/// any.
+fn reconcile_fn(field: &Field) -> syn::Result<Option<TokenStream>> {
+    let mut reconcile_fn = None;
+    for attr in &field.attrs {
+        if !attr.path.is_ident("autosurgeon") {
+            continue;
+        }
+        let Meta::List(meta) = attr.parse_meta()? else {
+            continue;
+        };
+        for meta_item in meta.nested {
+            let NestedMeta::Meta(Meta::NameValue(m)) = meta_item else {
+                continue;
+            };
+            let Lit::Str(s) = &m.lit else {
+                continue;
+            };
+            if m.path.is_ident("with") {
+                let module = s.parse::<Path>()?;
+                reconcile_fn = Some(quote!(#module::reconcile));
+            } else if m.path.is_ident("reconcile") {
+                let function = s.parse::<Path>()?;
+                reconcile_fn = Some(quote!(#function));
+            }
+        }
+    }
+
+    Ok(reconcile_fn)
+}